@@ -0,0 +1,13 @@
+//! Generates the gRPC messages, server and client from `docs/api/cds_index.proto`.
+//!
+//! Uses the vendored `protoc` unless `PROTOC` points at another one.
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let proto = "../../docs/api/cds_index.proto";
+    println!("cargo:rerun-if-changed={proto}");
+    if std::env::var_os("PROTOC").is_none() {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
+    tonic_prost_build::configure().compile_protos(&[proto], &["../../docs/api"])?;
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! CDS audit replay - re-runs an audit log against another index
+//!
+//! Reads the JSONL audit log(s) written with `INDEX_AUDIT_LOG` (oldest file
+//! first, e.g. `audit.jsonl.2 audit.jsonl.1 audit.jsonl`), re-runs every
+//! `search_entities`, `traverse_graph` and `retrieve_entity` call in-process
+//! against the index in `GRAPH_INDEX_DIR`/`BM25_INDEX_DIR`, and prints one
+//! JSON diff per call whose results changed. A summary goes to stderr.
+
+use anyhow::{Context, Result};
+use cds_index::service::audit::{read_log, replay};
+use cds_index::service::ServiceState;
+use cds_index::IndexServiceConfig;
+use clap::Parser;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+#[derive(Parser)]
+#[command(name = "cds-audit-replay")]
+#[command(about = "Replay a CDS-Index audit log against another index and diff the results")]
+struct Args {
+    /// Audit log files, oldest first
+    #[arg(required = true)]
+    logs: Vec<PathBuf>,
+    /// Graph index to replay against
+    #[arg(long, env = "GRAPH_INDEX_DIR")]
+    graph_dir: PathBuf,
+    /// BM25 index to replay against
+    #[arg(long, env = "BM25_INDEX_DIR")]
+    bm25_dir: PathBuf,
+    /// Indices of named repositories (default: `repos/` next to the graph index)
+    #[arg(long, env = "INDEX_REPOS_DIR")]
+    repos_dir: Option<PathBuf>,
+    /// Only replay calls of this session
+    #[arg(long)]
+    session: Option<String>,
+    /// Also print calls whose results are unchanged
+    #[arg(long)]
+    all: bool,
+    /// Exit with status 1 when any result changed
+    #[arg(long)]
+    fail_on_diff: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<ExitCode> {
+    let args = Args::parse();
+
+    let mut config = IndexServiceConfig::new(&args.graph_dir, &args.bm25_dir);
+    if let Some(repos_dir) = args.repos_dir {
+        config.repos_dir = repos_dir;
+    }
+    config.query_cache_size = 0;
+    let state = Arc::new(ServiceState::new(config));
+    if !state.load_persisted().context("Failed to load the index")? {
+        eprintln!(
+            "warning: no index in {}; default-repository calls will fail with -32001",
+            args.graph_dir.display()
+        );
+    }
+
+    let (mut replayed, mut skipped, mut changed) = (0usize, 0usize, 0usize);
+    let mut stdout = std::io::stdout().lock();
+    for path in &args.logs {
+        let records =
+            read_log(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        for record in records {
+            if args.session.is_some() && record.session != args.session {
+                continue;
+            }
+            let Some(diff) = replay(&state, &record).await else {
+                skipped += 1;
+                continue;
+            };
+            replayed += 1;
+            if !diff.is_unchanged() {
+                changed += 1;
+            } else if !args.all {
+                continue;
+            }
+            writeln!(stdout, "{}", serde_json::to_string(&diff)?)?;
+        }
+    }
+    eprintln!("replayed {replayed} calls ({skipped} skipped): {changed} changed");
+
+    Ok(if args.fail_on_diff && changed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
@@ -3,41 +3,186 @@
 //! Runs the Index Service as a long-running daemon, exposing:
 //! - /rpc - JSON-RPC 2.0 endpoint
 //! - /health - Health check endpoint
-//! - /metrics - Prometheus metrics
+//! - /events - Server-sent index change notifications
+//!
+//! `INDEX_SERVICE_TRANSPORT` selects where requests arrive: `tcp` (default,
+//! `INDEX_SERVICE_HOST:INDEX_SERVICE_PORT`), `unix` (`INDEX_SERVICE_SOCKET`)
+//! or `stdio` (line-delimited JSON-RPC, for agents that spawn the service as
+//! a child process). `--mcp` (or `INDEX_SERVICE_TRANSPORT=mcp`) runs a Model
+//! Context Protocol server on stdin/stdout instead. With `INDEX_GRPC_PORT`
+//! set, a gRPC server runs alongside on `INDEX_SERVICE_HOST:INDEX_GRPC_PORT`.
+//! Logs go to stderr, or straight to the journal when systemd connected
+//! stderr to it.
+//!
+//! `SIGTERM`/`SIGINT` shut down gracefully and `SIGHUP` reloads the
+//! configuration and indices (see `cds_index::service::lifecycle`); readiness
+//! is reported to systemd for `Type=notify` units.
+//!
+//! Settings come from the defaults, a TOML file (`--config` or
+//! `INDEX_CONFIG_FILE`), the environment and `--set key=value`, in increasing
+//! precedence (see `cds_index::config`); `--print-config` shows the result.
 
-use anyhow::Result;
-use cds_index::IndexServiceConfig;
+use anyhow::{Context, Result};
+use cds_index::config::ConfigLoader;
+use cds_index::service::{grpc, lifecycle, mcp, server, stdio, systemd, ServiceState};
+use cds_index::ServiceTransport;
+use clap::Parser;
 use std::net::SocketAddr;
-use tracing::info;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+#[derive(Parser)]
+#[command(name = "cds-index-service")]
+#[command(about = "CDS-Index Service: JSON-RPC, MCP and gRPC access to code indices")]
+struct Args {
+    /// TOML configuration file (default: `INDEX_CONFIG_FILE`)
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Override a setting, e.g. `--set port=8080` or `--set builder.skip_dirs=vendor,gen`
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_setting)]
+    set: Vec<(String, String)>,
+    /// Serve the Model Context Protocol on stdin/stdout (`--set transport=mcp`)
+    #[arg(long)]
+    mcp: bool,
+    /// Print the effective configuration and where each value came from, then exit
+    #[arg(long)]
+    print_config: bool,
+}
+
+fn parse_setting(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{value}'"))
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive("cds_index=info".parse()?),
-        )
-        .init();
+    let args = Args::parse();
+    let mut loader = ConfigLoader::new();
+    if let Some(path) = &args.config {
+        loader = loader.file(path);
+    }
+    for (key, value) in &args.set {
+        loader = loader.set(key, value);
+    }
+    if args.mcp {
+        loader = loader.flag("--mcp", "transport", "mcp");
+    }
+    if args.print_config {
+        print!("{}", loader.load()?);
+        return Ok(());
+    }
+
+    init_logging()?;
 
     info!("Starting CDS-Index Service...");
 
-    // Load configuration
-    let config = IndexServiceConfig::from_env()?;
+    // Load configuration; SIGHUP re-reads every layer.
+    let load_config = move || Ok(loader.load()?.config);
+    let config = load_config()?;
     config.validate()?;
 
     info!("Configuration loaded: {:?}", config);
 
-    // TODO: Build or load graph index
-    // TODO: Build or load BM25 index
-    // TODO: Set up JSON-RPC server with axum
-    // TODO: Notify systemd (if running under systemd)
+    let transport = config.transport;
+    let addr: SocketAddr = format!("{}:{}", config.host, config.port)
+        .parse()
+        .context("Invalid INDEX_SERVICE_HOST/INDEX_SERVICE_PORT")?;
+    let grpc_addr = config
+        .grpc_port
+        .map(|port| SocketAddr::new(addr.ip(), port));
+    let (socket_path, socket_mode) = (config.socket_path.clone(), config.socket_mode);
+    let exposed = (transport == ServiceTransport::Tcp).then_some(addr);
+    for addr in exposed.into_iter().chain(grpc_addr) {
+        if config.auth_tokens.is_empty() && !addr.ip().is_loopback() {
+            warn!("Listening on {addr} without authentication; set INDEX_SERVICE_TOKENS");
+        }
+    }
+
+    // Load the persisted graph + BM25 index; without one the service still
+    // starts so clients can call rebuild_index.
+    let state = Arc::new(ServiceState::new(config));
+    match state.load_persisted() {
+        Ok(true) => {}
+        Ok(false) => warn!("No persisted index found; call rebuild_index to build one"),
+        Err(err) => warn!("Failed to load persisted index: {err}"),
+    }
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], config.port));
-    info!("CDS-Index Service will listen on {}", addr);
+    tokio::spawn(lifecycle::handle_signals(Arc::clone(&state), load_config));
+    lifecycle::spawn_watchdog();
 
-    // Placeholder - will be replaced with actual server
-    info!("Service bootstrap complete (placeholder)");
+    // Whichever server stops first (stdin closed, or an error) shuts the
+    // other one down, so both drain before the process exits.
+    let grpc = async {
+        let Some(grpc_addr) = grpc_addr else {
+            return Ok(());
+        };
+        let result = grpc::serve_grpc(Arc::clone(&state), grpc_addr).await;
+        state.begin_shutdown();
+        result
+    };
+    let serve = async {
+        let result = serve_transport(
+            Arc::clone(&state),
+            transport,
+            addr,
+            socket_path,
+            socket_mode,
+        )
+        .await;
+        state.begin_shutdown();
+        result
+    };
+    let serve = async {
+        let (serve, grpc) = tokio::join!(serve, grpc);
+        serve.and(grpc)
+    };
+    systemd::notify_ready(&lifecycle::status(&state));
+    lifecycle::run(&state, serve).await
+}
 
+/// Logs to the journal when systemd connected stderr to it, else to stderr.
+fn init_logging() -> Result<()> {
+    let filter =
+        tracing_subscriber::EnvFilter::from_default_env().add_directive("cds_index=info".parse()?);
+    #[cfg(target_os = "linux")]
+    if systemd::journal_connected() {
+        tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_writer(systemd::JournalWriter)
+            .with_ansi(false)
+            .without_time()
+            .with_level(false)
+            .with_target(false)
+            .init();
+        return Ok(());
+    }
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
     Ok(())
 }
+
+async fn serve_transport(
+    state: Arc<ServiceState>,
+    transport: ServiceTransport,
+    addr: SocketAddr,
+    socket_path: std::path::PathBuf,
+    socket_mode: u32,
+) -> Result<()> {
+    match transport {
+        ServiceTransport::Tcp => server::serve(state, addr).await,
+        #[cfg(unix)]
+        ServiceTransport::Unix => server::serve_unix(state, &socket_path, socket_mode).await,
+        #[cfg(not(unix))]
+        ServiceTransport::Unix => {
+            let _ = (socket_path, socket_mode);
+            anyhow::bail!("the unix transport is not supported on this platform")
+        }
+        ServiceTransport::Stdio => Ok(stdio::serve_stdio(state).await?),
+        ServiceTransport::Mcp => Ok(mcp::serve_mcp(state).await?),
+    }
+}
@@ -1,54 +1,261 @@
 //! Configuration management for CDS-Index Service
+//!
+//! Settings are layered: built-in defaults, then an optional TOML file
+//! (`--config` or `INDEX_CONFIG_FILE`), then environment variables, then
+//! command-line flags. See [`ConfigLoader`] for the file format.
 
+mod layers;
+
+pub use layers::{ConfigLoader, ConfigSource, LoadedConfig};
+
+use crate::graph::GraphBuilderConfig;
+use crate::index::bm25::{Bm25Params, MIN_WRITER_HEAP_BYTES};
+use crate::service::auth::{self, ApiToken};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const DEFAULT_MAX_LOADED_REPOS: usize = 4;
+const DEFAULT_SOCKET_MODE: u32 = 0o600;
+const DEFAULT_RATE_LIMIT_BURST: u32 = 20;
+const DEFAULT_QUERY_CACHE_SIZE: usize = 1024;
+const DEFAULT_AUDIT_LOG_MAX_BYTES: u64 = 64 * 1024 * 1024;
+const DEFAULT_AUDIT_LOG_MAX_FILES: usize = 5;
+const DEFAULT_SESSION_IDLE_TIMEOUT_SECS: u64 = 3600;
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+
+/// How the service accepts JSON-RPC requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceTransport {
+    /// HTTP on `host:port`.
+    #[default]
+    Tcp,
+    /// HTTP on the Unix domain socket `socket_path`.
+    Unix,
+    /// Line-delimited JSON-RPC on stdin/stdout.
+    Stdio,
+    /// Model Context Protocol server on stdin/stdout.
+    Mcp,
+}
+
+impl FromStr for ServiceTransport {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "tcp" | "http" => Ok(Self::Tcp),
+            "unix" | "uds" => Ok(Self::Unix),
+            "stdio" => Ok(Self::Stdio),
+            "mcp" => Ok(Self::Mcp),
+            other => {
+                anyhow::bail!("unknown transport '{other}' (expected tcp, unix, stdio or mcp)")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ServiceTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Tcp => "tcp",
+            Self::Unix => "unix",
+            Self::Stdio => "stdio",
+            Self::Mcp => "mcp",
+        })
+    }
+}
+
+/// Graph builder options (`[builder]`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuilderSettings {
+    pub follow_symlinks: bool,
+    /// Stop after this many Python files; unset indexes all of them.
+    pub max_python_files: Option<usize>,
+    /// Directory names skipped in addition to the built-in list.
+    pub skip_dirs: Vec<String>,
+}
+
+/// Overrides for one registered repository (`[repos.<name>]`); unset values
+/// fall back to `[builder]` and the top-level settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepoSettings {
+    pub follow_symlinks: Option<bool>,
+    pub max_python_files: Option<usize>,
+    /// Skipped in addition to `[builder]`'s `skip_dirs`.
+    pub skip_dirs: Vec<String>,
+    pub cross_repo_links: Option<bool>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexServiceConfig {
     pub graph_index_dir: PathBuf,
     pub bm25_index_dir: PathBuf,
+    /// Root for indices of repositories registered with `add_repo`
+    /// (`<repos_dir>/<name>/graph`, `<repos_dir>/<name>/bm25`, `repos.json`).
+    pub repos_dir: PathBuf,
+    /// Upper bound on repositories held in memory at once; the least recently
+    /// queried ones are unloaded and reloaded lazily on their next query.
+    pub max_loaded_repos: usize,
+    /// Link unresolved imports to other registered repositories on rebuild
+    /// unless a request overrides it with `link_repos`.
+    pub cross_repo_links: bool,
+    /// Cached `search_entities`/`traverse_graph` results (LRU); 0 disables caching.
+    pub query_cache_size: usize,
+    pub transport: ServiceTransport,
+    /// Socket path for the `unix` transport.
+    pub socket_path: PathBuf,
+    /// Permission bits applied to `socket_path` (owner-only by default).
+    pub socket_mode: u32,
+    /// Bearer tokens accepted by the HTTP transports; empty disables
+    /// authentication (see [`crate::service::auth`]).
+    pub auth_tokens: Vec<ApiToken>,
+    /// Sustained requests per second allowed per client on `/rpc`; 0 disables
+    /// rate limiting (see [`crate::service::limits`]).
+    pub rate_limit_per_second: f64,
+    /// Requests a client may send at once before the rate limit applies.
+    pub rate_limit_burst: u32,
+    /// Requests handled concurrently across all clients; 0 means unlimited.
+    pub max_concurrent_requests: usize,
+    /// Also serve gRPC (see [`crate::service::grpc`]) on `host:grpc_port`.
+    pub grpc_port: Option<u16>,
+    /// JSONL audit log of every call (see [`crate::service::audit`]); `None` disables it.
+    pub audit_log: Option<PathBuf>,
+    /// Size at which the audit log is rotated; 0 never rotates.
+    pub audit_log_max_bytes: u64,
+    /// Rotated audit logs kept (`<audit_log>.1` is the newest).
+    pub audit_log_max_files: usize,
+    /// Seconds without a call after which a session's seen entities are
+    /// forgotten (see [`crate::service::sessions`]).
+    pub session_idle_timeout_secs: u64,
+    /// Seconds a graceful shutdown waits for in-flight requests and rebuild
+    /// jobs before cancelling the jobs (see [`crate::service::lifecycle`]).
+    pub shutdown_timeout_secs: u64,
     pub port: u16,
     pub host: String,
     pub log_level: String,
+    /// Options for every rebuild, unless `repos` overrides them.
+    pub builder: BuilderSettings,
+    pub bm25: Bm25Params,
+    /// Per-repository overrides, by registered name (`default` for the
+    /// default repository).
+    pub repos: BTreeMap<String, RepoSettings>,
 }
 
 impl IndexServiceConfig {
-    /// Load configuration from environment variables
-    pub fn from_env() -> Result<Self> {
-        let graph_index_dir = std::env::var("GRAPH_INDEX_DIR")
-            .context("GRAPH_INDEX_DIR not set")?
-            .into();
-
-        let bm25_index_dir = std::env::var("BM25_INDEX_DIR")
-            .context("BM25_INDEX_DIR not set")?
-            .into();
-
-        let port = std::env::var("INDEX_SERVICE_PORT")
-            .unwrap_or_else(|_| "3030".to_string())
-            .parse()
-            .context("Invalid INDEX_SERVICE_PORT")?;
+    /// Configuration with the given index directories and default network settings.
+    pub fn new(graph_index_dir: impl Into<PathBuf>, bm25_index_dir: impl Into<PathBuf>) -> Self {
+        let graph_index_dir = graph_index_dir.into();
+        Self {
+            repos_dir: default_repos_dir(&graph_index_dir),
+            max_loaded_repos: DEFAULT_MAX_LOADED_REPOS,
+            cross_repo_links: false,
+            query_cache_size: DEFAULT_QUERY_CACHE_SIZE,
+            transport: ServiceTransport::Tcp,
+            socket_path: default_socket_path(&graph_index_dir),
+            socket_mode: DEFAULT_SOCKET_MODE,
+            auth_tokens: Vec::new(),
+            rate_limit_per_second: 0.0,
+            rate_limit_burst: DEFAULT_RATE_LIMIT_BURST,
+            max_concurrent_requests: 0,
+            grpc_port: None,
+            audit_log: None,
+            audit_log_max_bytes: DEFAULT_AUDIT_LOG_MAX_BYTES,
+            audit_log_max_files: DEFAULT_AUDIT_LOG_MAX_FILES,
+            session_idle_timeout_secs: DEFAULT_SESSION_IDLE_TIMEOUT_SECS,
+            shutdown_timeout_secs: DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+            graph_index_dir,
+            bm25_index_dir: bm25_index_dir.into(),
+            port: 3030,
+            host: "127.0.0.1".to_string(),
+            log_level: "info".to_string(),
+            builder: BuilderSettings::default(),
+            bm25: Bm25Params::default(),
+            repos: BTreeMap::new(),
+        }
+    }
 
-        let host = std::env::var("INDEX_SERVICE_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
+    /// Load configuration from `INDEX_CONFIG_FILE` (if set) and environment
+    /// variables; use [`ConfigLoader`] to add a file or flags.
+    pub fn from_env() -> Result<Self> {
+        Ok(ConfigLoader::new().load()?.config)
+    }
 
-        let log_level = std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string());
+    /// Graph builder options for a rebuild of `repo`.
+    pub fn builder_config(&self, repo: &str) -> GraphBuilderConfig {
+        let overrides = self.repos.get(repo);
+        let mut skip_dirs = self.builder.skip_dirs.clone();
+        skip_dirs.extend(
+            overrides
+                .into_iter()
+                .flat_map(|repo| repo.skip_dirs.clone()),
+        );
+        GraphBuilderConfig {
+            follow_symlinks: overrides
+                .and_then(|repo| repo.follow_symlinks)
+                .unwrap_or(self.builder.follow_symlinks),
+            max_python_files: overrides
+                .and_then(|repo| repo.max_python_files)
+                .or(self.builder.max_python_files),
+            skip_dirs,
+            ..GraphBuilderConfig::default()
+        }
+    }
 
-        Ok(Self {
-            graph_index_dir,
-            bm25_index_dir,
-            port,
-            host,
-            log_level,
-        })
+    /// Whether rebuilds of `repo` link imports to the other registered repositories.
+    pub fn cross_repo_links_for(&self, repo: &str) -> bool {
+        self.repos
+            .get(repo)
+            .and_then(|repo| repo.cross_repo_links)
+            .unwrap_or(self.cross_repo_links)
     }
 
     /// Validate configuration
     pub fn validate(&self) -> Result<()> {
         // Validate port range
-        if self.port < 1024 {
+        if self.transport == ServiceTransport::Tcp && self.port < 1024 {
             anyhow::bail!("INDEX_SERVICE_PORT must be >= 1024");
         }
 
+        if self.grpc_port.is_some_and(|port| port < 1024) {
+            anyhow::bail!("INDEX_GRPC_PORT must be >= 1024");
+        }
+
+        if self.transport == ServiceTransport::Unix && !cfg!(unix) {
+            anyhow::bail!("INDEX_SERVICE_TRANSPORT=unix is only supported on Unix platforms");
+        }
+
+        if self.socket_mode > 0o777 {
+            anyhow::bail!("INDEX_SERVICE_SOCKET_MODE must be an octal mode such as 600");
+        }
+
+        auth::validate_tokens(&self.auth_tokens).context("Invalid service tokens")?;
+
+        if !self.rate_limit_per_second.is_finite() || self.rate_limit_per_second < 0.0 {
+            anyhow::bail!("INDEX_RATE_LIMIT must be a non-negative number of requests per second");
+        }
+
+        if self.rate_limit_burst == 0 {
+            anyhow::bail!("INDEX_RATE_LIMIT_BURST must be >= 1");
+        }
+
+        if self.max_loaded_repos == 0 {
+            anyhow::bail!("INDEX_MAX_LOADED_REPOS must be >= 1");
+        }
+
+        if !self.bm25.name_boost.is_finite() || self.bm25.name_boost <= 0.0 {
+            anyhow::bail!("bm25.name_boost must be a positive number");
+        }
+
+        if self.bm25.writer_heap_bytes < MIN_WRITER_HEAP_BYTES {
+            anyhow::bail!("bm25.writer_heap_bytes must be >= {MIN_WRITER_HEAP_BYTES}");
+        }
+
         // Create directories if they don't exist
         if !self.graph_index_dir.exists() {
             std::fs::create_dir_all(&self.graph_index_dir)
@@ -62,4 +269,99 @@ impl IndexServiceConfig {
 
         Ok(())
     }
+
+    /// Settings of `other` that differ from this configuration, by variable
+    /// name (or file section), except the tokens (which a reload applies in
+    /// place).
+    pub fn changed_settings(&self, other: &Self) -> Vec<&'static str> {
+        let checks = [
+            (
+                "GRAPH_INDEX_DIR",
+                self.graph_index_dir == other.graph_index_dir,
+            ),
+            (
+                "BM25_INDEX_DIR",
+                self.bm25_index_dir == other.bm25_index_dir,
+            ),
+            ("INDEX_REPOS_DIR", self.repos_dir == other.repos_dir),
+            (
+                "INDEX_MAX_LOADED_REPOS",
+                self.max_loaded_repos == other.max_loaded_repos,
+            ),
+            (
+                "INDEX_CROSS_REPO_LINKS",
+                self.cross_repo_links == other.cross_repo_links,
+            ),
+            (
+                "INDEX_QUERY_CACHE_SIZE",
+                self.query_cache_size == other.query_cache_size,
+            ),
+            ("INDEX_SERVICE_TRANSPORT", self.transport == other.transport),
+            (
+                "INDEX_SERVICE_SOCKET",
+                self.socket_path == other.socket_path,
+            ),
+            (
+                "INDEX_SERVICE_SOCKET_MODE",
+                self.socket_mode == other.socket_mode,
+            ),
+            (
+                "INDEX_RATE_LIMIT",
+                self.rate_limit_per_second == other.rate_limit_per_second,
+            ),
+            (
+                "INDEX_RATE_LIMIT_BURST",
+                self.rate_limit_burst == other.rate_limit_burst,
+            ),
+            (
+                "INDEX_MAX_CONCURRENT_REQUESTS",
+                self.max_concurrent_requests == other.max_concurrent_requests,
+            ),
+            ("INDEX_GRPC_PORT", self.grpc_port == other.grpc_port),
+            ("INDEX_AUDIT_LOG", self.audit_log == other.audit_log),
+            (
+                "INDEX_AUDIT_LOG_MAX_BYTES",
+                self.audit_log_max_bytes == other.audit_log_max_bytes,
+            ),
+            (
+                "INDEX_AUDIT_LOG_MAX_FILES",
+                self.audit_log_max_files == other.audit_log_max_files,
+            ),
+            (
+                "INDEX_SESSION_IDLE_TIMEOUT",
+                self.session_idle_timeout_secs == other.session_idle_timeout_secs,
+            ),
+            (
+                "INDEX_SHUTDOWN_TIMEOUT",
+                self.shutdown_timeout_secs == other.shutdown_timeout_secs,
+            ),
+            ("INDEX_SERVICE_PORT", self.port == other.port),
+            ("INDEX_SERVICE_HOST", self.host == other.host),
+            ("RUST_LOG", self.log_level == other.log_level),
+            ("[builder]", self.builder == other.builder),
+            ("[bm25]", self.bm25 == other.bm25),
+            ("[repos]", self.repos == other.repos),
+        ];
+        checks
+            .into_iter()
+            .filter(|(_, same)| !same)
+            .map(|(name, _)| name)
+            .collect()
+    }
+}
+
+/// `cds-index.sock` next to the default graph index directory.
+fn default_socket_path(graph_index_dir: &Path) -> PathBuf {
+    graph_index_dir
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("cds-index.sock")
+}
+
+/// `repos/` next to the default graph index directory.
+fn default_repos_dir(graph_index_dir: &Path) -> PathBuf {
+    graph_index_dir
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("repos")
 }
@@ -0,0 +1,562 @@
+//! Layered loading: defaults → TOML file → environment → flags
+//!
+//! The file uses the field names of [`IndexServiceConfig`]; every key is
+//! optional and unknown keys are rejected:
+//!
+//! ```toml
+//! graph_index_dir = "/var/lib/cds/graph"
+//! bm25_index_dir = "/var/lib/cds/bm25"
+//! port = 3030
+//! tokens_file = "/etc/cds/tokens.toml"
+//! session_idle_timeout_secs = 3600
+//! shutdown_timeout_secs = 30
+//!
+//! [builder]
+//! follow_symlinks = false
+//! max_python_files = 20000
+//! skip_dirs = ["vendor", "third_party"]
+//!
+//! [bm25]
+//! name_boost = 2.0
+//! writer_heap_bytes = 50000000
+//!
+//! [repos.legacy]
+//! skip_dirs = ["generated"]
+//! cross_repo_links = true
+//! ```
+//!
+//! Each environment variable overrides one key (`INDEX_SERVICE_PORT` sets
+//! `port`, see `.env.example`), and `--set key=value` overrides any key,
+//! including `repos.<name>.<key>`. [`LoadedConfig`] remembers where each value
+//! came from; its `Display` output is what `--print-config` shows.
+
+use super::{
+    default_repos_dir, default_socket_path, BuilderSettings, IndexServiceConfig, RepoSettings,
+    ServiceTransport, DEFAULT_AUDIT_LOG_MAX_BYTES, DEFAULT_AUDIT_LOG_MAX_FILES,
+    DEFAULT_MAX_LOADED_REPOS, DEFAULT_QUERY_CACHE_SIZE, DEFAULT_RATE_LIMIT_BURST,
+    DEFAULT_SESSION_IDLE_TIMEOUT_SECS, DEFAULT_SHUTDOWN_TIMEOUT_SECS, DEFAULT_SOCKET_MODE,
+};
+use crate::index::Bm25Params;
+use crate::service::auth;
+use anyhow::{Context, Result};
+use config::{Config, File, FileFormat, ValueKind};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Names the configuration file when no `--config` flag is given.
+const CONFIG_FILE_VAR: &str = "INDEX_CONFIG_FILE";
+
+/// Column at which `--print-config` aligns the source comments.
+const SOURCE_COLUMN: usize = 48;
+
+/// How an environment variable or `--set` value is parsed.
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Text,
+    Bool,
+    Int,
+    Float,
+    /// Permission bits written in octal (`600`).
+    Octal,
+    Transport,
+    /// An empty value unsets the key.
+    OptionalPath,
+    /// Comma-separated.
+    List,
+}
+
+/// Keys that can be overridden, with their environment variable.
+const KEYS: &[(&str, Option<&str>, Kind)] = &[
+    ("graph_index_dir", Some("GRAPH_INDEX_DIR"), Kind::Text),
+    ("bm25_index_dir", Some("BM25_INDEX_DIR"), Kind::Text),
+    ("repos_dir", Some("INDEX_REPOS_DIR"), Kind::Text),
+    (
+        "max_loaded_repos",
+        Some("INDEX_MAX_LOADED_REPOS"),
+        Kind::Int,
+    ),
+    (
+        "cross_repo_links",
+        Some("INDEX_CROSS_REPO_LINKS"),
+        Kind::Bool,
+    ),
+    (
+        "query_cache_size",
+        Some("INDEX_QUERY_CACHE_SIZE"),
+        Kind::Int,
+    ),
+    (
+        "transport",
+        Some("INDEX_SERVICE_TRANSPORT"),
+        Kind::Transport,
+    ),
+    ("socket_path", Some("INDEX_SERVICE_SOCKET"), Kind::Text),
+    (
+        "socket_mode",
+        Some("INDEX_SERVICE_SOCKET_MODE"),
+        Kind::Octal,
+    ),
+    (
+        "tokens_file",
+        Some("INDEX_SERVICE_TOKENS_FILE"),
+        Kind::OptionalPath,
+    ),
+    ("tokens", Some("INDEX_SERVICE_TOKENS"), Kind::Text),
+    (
+        "rate_limit_per_second",
+        Some("INDEX_RATE_LIMIT"),
+        Kind::Float,
+    ),
+    (
+        "rate_limit_burst",
+        Some("INDEX_RATE_LIMIT_BURST"),
+        Kind::Int,
+    ),
+    (
+        "max_concurrent_requests",
+        Some("INDEX_MAX_CONCURRENT_REQUESTS"),
+        Kind::Int,
+    ),
+    ("grpc_port", Some("INDEX_GRPC_PORT"), Kind::Int),
+    ("audit_log", Some("INDEX_AUDIT_LOG"), Kind::OptionalPath),
+    (
+        "audit_log_max_bytes",
+        Some("INDEX_AUDIT_LOG_MAX_BYTES"),
+        Kind::Int,
+    ),
+    (
+        "audit_log_max_files",
+        Some("INDEX_AUDIT_LOG_MAX_FILES"),
+        Kind::Int,
+    ),
+    (
+        "session_idle_timeout_secs",
+        Some("INDEX_SESSION_IDLE_TIMEOUT"),
+        Kind::Int,
+    ),
+    (
+        "shutdown_timeout_secs",
+        Some("INDEX_SHUTDOWN_TIMEOUT"),
+        Kind::Int,
+    ),
+    ("port", Some("INDEX_SERVICE_PORT"), Kind::Int),
+    ("host", Some("INDEX_SERVICE_HOST"), Kind::Text),
+    ("log_level", Some("RUST_LOG"), Kind::Text),
+    (
+        "builder.follow_symlinks",
+        Some("INDEX_FOLLOW_SYMLINKS"),
+        Kind::Bool,
+    ),
+    (
+        "builder.max_python_files",
+        Some("INDEX_MAX_PYTHON_FILES"),
+        Kind::Int,
+    ),
+    ("builder.skip_dirs", Some("INDEX_SKIP_DIRS"), Kind::List),
+    (
+        "bm25.name_boost",
+        Some("INDEX_BM25_NAME_BOOST"),
+        Kind::Float,
+    ),
+    (
+        "bm25.writer_heap_bytes",
+        Some("INDEX_BM25_WRITER_HEAP_BYTES"),
+        Kind::Int,
+    ),
+];
+
+/// Keys of a `[repos.<name>]` section.
+const REPO_KEYS: &[(&str, Kind)] = &[
+    ("follow_symlinks", Kind::Bool),
+    ("max_python_files", Kind::Int),
+    ("skip_dirs", Kind::List),
+    ("cross_repo_links", Kind::Bool),
+];
+
+/// Where a configuration value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    File(PathBuf),
+    Env(&'static str),
+    /// A command-line flag, e.g. `--set` or `--mcp`.
+    Flag(String),
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::File(path) => write!(f, "file {}", path.display()),
+            Self::Env(var) => write!(f, "env {var}"),
+            Self::Flag(flag) => write!(f, "flag {flag}"),
+        }
+    }
+}
+
+/// Builds an [`IndexServiceConfig`] from the defaults, a TOML file, the
+/// environment and flags, in increasing precedence.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLoader {
+    file: Option<PathBuf>,
+    env: Option<BTreeMap<String, String>>,
+    flags: Vec<(String, String, String)>,
+}
+
+impl ConfigLoader {
+    /// Loader for `INDEX_CONFIG_FILE` (if set) and the process environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads `path` instead of `INDEX_CONFIG_FILE`.
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    /// Reads environment overrides from `vars` instead of the process environment.
+    pub fn env_vars<K, V>(mut self, vars: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let vars = vars.into_iter().map(|(k, v)| (k.into(), v.into()));
+        self.env = Some(vars.collect());
+        self
+    }
+
+    /// Overrides `key` with `value` as given by `--set key=value`.
+    pub fn set(self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.flag("--set", key, value)
+    }
+
+    /// Overrides `key` with `value` on behalf of the command-line flag `flag`.
+    pub fn flag(
+        mut self,
+        flag: impl Into<String>,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.flags.push((flag.into(), key.into(), value.into()));
+        self
+    }
+
+    /// Merges all layers; fails on unreadable files, unknown keys and values
+    /// of the wrong type.
+    pub fn load(&self) -> Result<LoadedConfig> {
+        let mut sources = BTreeMap::new();
+        let mut builder = Config::builder().add_source(
+            Config::try_from(&Settings::default()).context("Invalid default configuration")?,
+        );
+
+        let file = self
+            .file
+            .clone()
+            .or_else(|| self.var(CONFIG_FILE_VAR).map(PathBuf::from));
+        if let Some(path) = &file {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config file {}", path.display()))?;
+            let table: toml::Table = toml::from_str(&text)
+                .with_context(|| format!("Invalid config file {}", path.display()))?;
+            for key in leaf_keys(&table, "") {
+                if kind_of(&key).is_none() {
+                    anyhow::bail!(
+                        "Invalid config file {}: unknown setting '{key}'",
+                        path.display()
+                    );
+                }
+                sources.insert(key, ConfigSource::File(path.clone()));
+            }
+            builder = builder.add_source(File::from_str(&text, FileFormat::Toml));
+        }
+
+        for &(key, var, kind) in KEYS {
+            let Some(var) = var else {
+                continue;
+            };
+            if let Some(raw) = self.var(var) {
+                let value = parse(kind, &raw).with_context(|| format!("Invalid {var}"))?;
+                builder = builder.set_override(key, value)?;
+                sources.insert(key.to_string(), ConfigSource::Env(var));
+            }
+        }
+
+        for (flag, key, raw) in &self.flags {
+            let kind = kind_of(key).with_context(|| format!("{flag}: unknown setting '{key}'"))?;
+            let value = parse(kind, raw).with_context(|| format!("{flag}: invalid {key}"))?;
+            builder = builder.set_override(key.as_str(), value)?;
+            sources.insert(key.clone(), ConfigSource::Flag(flag.clone()));
+        }
+
+        let mut settings: Settings = builder
+            .build()
+            .and_then(Config::try_deserialize)
+            .context("Invalid configuration")?;
+        let config = settings.resolve()?;
+        Ok(LoadedConfig {
+            config,
+            file,
+            settings,
+            sources,
+        })
+    }
+
+    fn var(&self, name: &str) -> Option<String> {
+        match &self.env {
+            Some(vars) => vars.get(name).cloned(),
+            None => std::env::var(name).ok(),
+        }
+    }
+}
+
+/// The merged configuration and the source of each value.
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    pub config: IndexServiceConfig,
+    /// The configuration file that was read, if any.
+    pub file: Option<PathBuf>,
+    settings: Settings,
+    sources: BTreeMap<String, ConfigSource>,
+}
+
+impl LoadedConfig {
+    /// Where the value of `key` (e.g. `port`, `builder.skip_dirs` or
+    /// `repos.legacy.cross_repo_links`) came from.
+    pub fn source(&self, key: &str) -> &ConfigSource {
+        self.sources.get(key).unwrap_or(&ConfigSource::Default)
+    }
+
+    fn line(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        key: &str,
+        value: Option<&toml::Value>,
+    ) -> fmt::Result {
+        let name = key.rsplit('.').next().unwrap_or(key);
+        let text = match value {
+            Some(_) if name == "tokens" => format!("{name} = \"<redacted>\""),
+            Some(toml::Value::Integer(mode)) if name == "socket_mode" => {
+                format!("{name} = 0o{mode:o}")
+            }
+            Some(value) => format!("{name} = {value}"),
+            None => format!("# {name} is unset"),
+        };
+        let width = SOURCE_COLUMN.max(text.len() + 1);
+        writeln!(f, "{text:<width$}# {}", self.source(key))
+    }
+}
+
+/// TOML of the effective configuration, each value commented with its
+/// source; tokens are redacted.
+impl fmt::Display for LoadedConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table = toml::Table::try_from(&self.settings).map_err(|_| fmt::Error)?;
+        let lookup = |key: &str| {
+            key.split('.')
+                .try_fold(&table, |table, part| match table.get(part) {
+                    Some(toml::Value::Table(inner)) => Ok(inner),
+                    other => Err(other),
+                })
+                .err()
+                .flatten()
+        };
+
+        writeln!(
+            f,
+            "# Effective configuration (defaults < file < environment < flags)"
+        )?;
+        if let Some(file) = &self.file {
+            writeln!(f, "# Config file: {}", file.display())?;
+        }
+        let mut section = "";
+        for &(key, _, _) in KEYS {
+            let current = key.split_once('.').map_or("", |(section, _)| section);
+            if current != section {
+                section = current;
+                writeln!(f, "\n[{section}]")?;
+            }
+            self.line(f, key, lookup(key))?;
+        }
+        for (name, repo) in &self.settings.repos {
+            writeln!(f, "\n[repos.{name}]")?;
+            let repo = toml::Table::try_from(repo).map_err(|_| fmt::Error)?;
+            for &(key, _) in REPO_KEYS {
+                self.line(f, &format!("repos.{name}.{key}"), repo.get(key))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The layered settings as they appear in the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Settings {
+    graph_index_dir: Option<PathBuf>,
+    bm25_index_dir: Option<PathBuf>,
+    repos_dir: Option<PathBuf>,
+    max_loaded_repos: usize,
+    cross_repo_links: bool,
+    query_cache_size: usize,
+    transport: ServiceTransport,
+    socket_path: Option<PathBuf>,
+    socket_mode: u32,
+    tokens_file: Option<PathBuf>,
+    tokens: Option<String>,
+    rate_limit_per_second: f64,
+    rate_limit_burst: u32,
+    max_concurrent_requests: usize,
+    grpc_port: Option<u16>,
+    audit_log: Option<PathBuf>,
+    audit_log_max_bytes: u64,
+    audit_log_max_files: usize,
+    session_idle_timeout_secs: u64,
+    shutdown_timeout_secs: u64,
+    port: u16,
+    host: String,
+    log_level: String,
+    builder: BuilderSettings,
+    bm25: Bm25Params,
+    repos: BTreeMap<String, RepoSettings>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            graph_index_dir: None,
+            bm25_index_dir: None,
+            repos_dir: None,
+            max_loaded_repos: DEFAULT_MAX_LOADED_REPOS,
+            cross_repo_links: false,
+            query_cache_size: DEFAULT_QUERY_CACHE_SIZE,
+            transport: ServiceTransport::Tcp,
+            socket_path: None,
+            socket_mode: DEFAULT_SOCKET_MODE,
+            tokens_file: None,
+            tokens: None,
+            rate_limit_per_second: 0.0,
+            rate_limit_burst: DEFAULT_RATE_LIMIT_BURST,
+            max_concurrent_requests: 0,
+            grpc_port: None,
+            audit_log: None,
+            audit_log_max_bytes: DEFAULT_AUDIT_LOG_MAX_BYTES,
+            audit_log_max_files: DEFAULT_AUDIT_LOG_MAX_FILES,
+            session_idle_timeout_secs: DEFAULT_SESSION_IDLE_TIMEOUT_SECS,
+            shutdown_timeout_secs: DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+            port: 3030,
+            host: "127.0.0.1".to_string(),
+            log_level: "info".to_string(),
+            builder: BuilderSettings::default(),
+            bm25: Bm25Params::default(),
+            repos: BTreeMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Fills in the paths derived from `graph_index_dir` and loads the tokens.
+    fn resolve(&mut self) -> Result<IndexServiceConfig> {
+        let graph_index_dir = self
+            .graph_index_dir
+            .clone()
+            .context("GRAPH_INDEX_DIR not set (or graph_index_dir in the config file)")?;
+        let bm25_index_dir = self
+            .bm25_index_dir
+            .clone()
+            .context("BM25_INDEX_DIR not set (or bm25_index_dir in the config file)")?;
+        let repos_dir = self
+            .repos_dir
+            .get_or_insert_with(|| default_repos_dir(&graph_index_dir))
+            .clone();
+        let socket_path = self
+            .socket_path
+            .get_or_insert_with(|| default_socket_path(&graph_index_dir))
+            .clone();
+
+        let mut auth_tokens = match &self.tokens_file {
+            Some(path) => auth::load_token_file(path)?,
+            None => Vec::new(),
+        };
+        if let Some(list) = &self.tokens {
+            auth_tokens.extend(auth::parse_token_list(list).context("Invalid tokens")?);
+        }
+
+        Ok(IndexServiceConfig {
+            graph_index_dir,
+            bm25_index_dir,
+            repos_dir,
+            max_loaded_repos: self.max_loaded_repos,
+            cross_repo_links: self.cross_repo_links,
+            query_cache_size: self.query_cache_size,
+            transport: self.transport,
+            socket_path,
+            socket_mode: self.socket_mode,
+            auth_tokens,
+            rate_limit_per_second: self.rate_limit_per_second,
+            rate_limit_burst: self.rate_limit_burst,
+            max_concurrent_requests: self.max_concurrent_requests,
+            grpc_port: self.grpc_port,
+            audit_log: self.audit_log.clone(),
+            audit_log_max_bytes: self.audit_log_max_bytes,
+            audit_log_max_files: self.audit_log_max_files,
+            session_idle_timeout_secs: self.session_idle_timeout_secs,
+            shutdown_timeout_secs: self.shutdown_timeout_secs,
+            port: self.port,
+            host: self.host.clone(),
+            log_level: self.log_level.clone(),
+            builder: self.builder.clone(),
+            bm25: self.bm25,
+            repos: self.repos.clone(),
+        })
+    }
+}
+
+fn kind_of(key: &str) -> Option<Kind> {
+    if let Some(&(_, _, kind)) = KEYS.iter().find(|(name, _, _)| *name == key) {
+        return Some(kind);
+    }
+    let rest = key.strip_prefix("repos.")?;
+    let (name, key) = rest.split_once('.')?;
+    if name.is_empty() {
+        return None;
+    }
+    REPO_KEYS
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|&(_, kind)| kind)
+}
+
+fn parse(kind: Kind, raw: &str) -> Result<ValueKind> {
+    Ok(match kind {
+        Kind::Text => raw.into(),
+        Kind::Bool => raw.parse::<bool>()?.into(),
+        Kind::Int => raw.parse::<u64>()?.into(),
+        Kind::Float => raw.parse::<f64>()?.into(),
+        Kind::Octal => u64::from(u32::from_str_radix(raw, 8)?).into(),
+        Kind::Transport => raw.parse::<ServiceTransport>()?.to_string().into(),
+        Kind::OptionalPath => Some(raw).filter(|path| !path.is_empty()).into(),
+        Kind::List => raw
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+            .into(),
+    })
+}
+
+/// Dotted paths of the values in `table`; arrays count as one value.
+fn leaf_keys(table: &toml::Table, prefix: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    for (key, value) in table {
+        let path = format!("{prefix}{key}");
+        match value {
+            toml::Value::Table(inner) => keys.extend(leaf_keys(inner, &format!("{path}."))),
+            _ => keys.push(path),
+        }
+    }
+    keys
+}
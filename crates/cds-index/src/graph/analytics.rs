@@ -0,0 +1,245 @@
+//! Hotspot analytics over a dependency graph.
+//!
+//! Backs `get_index_stats`: the files holding the most entities, the most
+//! invoked entities, the deepest inheritance chains and the import cycles
+//! between files. Entities of other repositories are left out throughout.
+
+use crate::graph::{DependencyGraph, EdgeKind, GraphNodeIndex, NodeKind};
+use petgraph::algo::tarjan_scc;
+use petgraph::graph::DiGraph;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use petgraph::Direction;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A file and the classes and functions it defines, nested ones included.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEntityCount {
+    pub file_path: String,
+    pub entities: usize,
+}
+
+/// An entity and the number of entities invoking it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvokedEntity {
+    pub id: String,
+    pub entity_type: NodeKind,
+    pub callers: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphAnalytics {
+    /// Most entities first.
+    pub largest_files: Vec<FileEntityCount>,
+    /// Most callers first.
+    pub most_invoked: Vec<InvokedEntity>,
+    /// Class ids from a subclass to its farthest base, longest first.
+    pub inheritance_chains: Vec<Vec<String>>,
+    /// Files importing each other, largest cycle first.
+    pub import_cycles: Vec<Vec<String>>,
+    /// Import cycles before the `top` limit was applied.
+    pub import_cycles_total: usize,
+}
+
+/// Computes every list of [`GraphAnalytics`], keeping the first `top` entries
+/// of each. Ties are broken by id so the result is stable across builds.
+pub fn analyze(graph: &DependencyGraph, top: usize) -> GraphAnalytics {
+    let mut import_cycles = import_cycles(graph);
+    let import_cycles_total = import_cycles.len();
+    import_cycles.truncate(top);
+    GraphAnalytics {
+        largest_files: largest_files(graph, top),
+        most_invoked: most_invoked(graph, top),
+        inheritance_chains: inheritance_chains(graph, top),
+        import_cycles,
+        import_cycles_total,
+    }
+}
+
+fn is_local(graph: &DependencyGraph, idx: GraphNodeIndex) -> bool {
+    graph.node(idx).is_some_and(|node| !node.is_external())
+}
+
+fn largest_files(graph: &DependencyGraph, top: usize) -> Vec<FileEntityCount> {
+    let storage = graph.graph();
+    let mut files: Vec<FileEntityCount> = storage
+        .node_indices()
+        .filter_map(|idx| {
+            let node = graph.node(idx)?;
+            if node.kind != NodeKind::File || node.is_external() {
+                return None;
+            }
+            let mut entities = 0;
+            let mut stack = vec![idx];
+            while let Some(parent) = stack.pop() {
+                for edge in storage.edges_directed(parent, Direction::Outgoing) {
+                    if edge.weight().kind == EdgeKind::Contain {
+                        entities += 1;
+                        stack.push(edge.target());
+                    }
+                }
+            }
+            (entities > 0).then(|| FileEntityCount {
+                file_path: node.id.clone(),
+                entities,
+            })
+        })
+        .collect();
+    files.sort_by(|a, b| {
+        b.entities
+            .cmp(&a.entities)
+            .then_with(|| a.file_path.cmp(&b.file_path))
+    });
+    files.truncate(top);
+    files
+}
+
+fn most_invoked(graph: &DependencyGraph, top: usize) -> Vec<InvokedEntity> {
+    let storage = graph.graph();
+    let mut invoked: Vec<InvokedEntity> = storage
+        .node_indices()
+        .filter_map(|idx| {
+            let node = graph.node(idx)?;
+            if node.is_external() {
+                return None;
+            }
+            let callers = storage
+                .edges_directed(idx, Direction::Incoming)
+                .filter(|edge| edge.weight().kind == EdgeKind::Invoke)
+                .count();
+            (callers > 0).then(|| InvokedEntity {
+                id: node.id.clone(),
+                entity_type: node.kind,
+                callers,
+            })
+        })
+        .collect();
+    invoked.sort_by(|a, b| b.callers.cmp(&a.callers).then_with(|| a.id.cmp(&b.id)));
+    invoked.truncate(top);
+    invoked
+}
+
+/// The longest base-class chain starting at each class nothing inherits from.
+fn inheritance_chains(graph: &DependencyGraph, top: usize) -> Vec<Vec<String>> {
+    let storage = graph.graph();
+    let bases = |idx: GraphNodeIndex| {
+        storage
+            .edges_directed(idx, Direction::Outgoing)
+            .filter(|edge| edge.weight().kind == EdgeKind::Inherit)
+            .map(|edge| edge.target())
+            .filter(|&base| is_local(graph, base))
+    };
+    let mut longest: HashMap<GraphNodeIndex, Vec<GraphNodeIndex>> = HashMap::new();
+    let mut chains: Vec<Vec<String>> = storage
+        .node_indices()
+        .filter(|&idx| {
+            graph
+                .node(idx)
+                .is_some_and(|node| node.kind == NodeKind::Class && !node.is_external())
+        })
+        .filter(|&idx| {
+            !storage
+                .edges_directed(idx, Direction::Incoming)
+                .any(|edge| edge.weight().kind == EdgeKind::Inherit)
+        })
+        .filter_map(|idx| {
+            let chain = longest_chain(idx, &bases, &mut longest, &mut Vec::new());
+            (chain.len() > 1).then(|| {
+                chain
+                    .iter()
+                    .filter_map(|&idx| graph.node(idx).map(|node| node.id.clone()))
+                    .collect()
+            })
+        })
+        .collect();
+    chains.sort_by(|a: &Vec<String>, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    chains.truncate(top);
+    chains
+}
+
+/// Memoized depth-first search; a base already on `path` (an inheritance
+/// cycle) ends the chain.
+fn longest_chain<I>(
+    idx: GraphNodeIndex,
+    bases: &impl Fn(GraphNodeIndex) -> I,
+    memo: &mut HashMap<GraphNodeIndex, Vec<GraphNodeIndex>>,
+    path: &mut Vec<GraphNodeIndex>,
+) -> Vec<GraphNodeIndex>
+where
+    I: Iterator<Item = GraphNodeIndex>,
+{
+    if let Some(chain) = memo.get(&idx) {
+        return chain.clone();
+    }
+    path.push(idx);
+    let mut deepest: Vec<GraphNodeIndex> = Vec::new();
+    for base in bases(idx) {
+        if path.contains(&base) {
+            continue;
+        }
+        let chain = longest_chain(base, bases, memo, path);
+        if chain.len() > deepest.len() {
+            deepest = chain;
+        }
+    }
+    path.pop();
+    let mut chain = Vec::with_capacity(deepest.len() + 1);
+    chain.push(idx);
+    chain.extend(deepest);
+    memo.insert(idx, chain.clone());
+    chain
+}
+
+/// Strongly connected components of the file-level import graph: an import
+/// by or of a class or function counts as one between their files.
+fn import_cycles(graph: &DependencyGraph) -> Vec<Vec<String>> {
+    let storage = graph.graph();
+    let file_of = |idx: GraphNodeIndex| -> Option<String> {
+        let node = graph.node(idx)?;
+        match node.kind {
+            _ if node.is_external() => None,
+            NodeKind::Directory => None,
+            NodeKind::File => Some(node.id.clone()),
+            NodeKind::Class | NodeKind::Function => {
+                node.id.split_once("::").map(|(file, _)| file.to_string())
+            }
+        }
+    };
+
+    let mut files: DiGraph<String, ()> = DiGraph::new();
+    let mut positions = HashMap::new();
+    let mut position = |files: &mut DiGraph<String, ()>, file: String| {
+        *positions
+            .entry(file.clone())
+            .or_insert_with(|| files.add_node(file))
+    };
+    for edge in storage.edge_references() {
+        if edge.weight().kind != EdgeKind::Import {
+            continue;
+        }
+        let (Some(source), Some(target)) = (file_of(edge.source()), file_of(edge.target())) else {
+            continue;
+        };
+        if source == target {
+            continue;
+        }
+        let source = position(&mut files, source);
+        let target = position(&mut files, target);
+        files.update_edge(source, target, ());
+    }
+
+    let mut cycles: Vec<Vec<String>> = tarjan_scc(&files)
+        .into_iter()
+        .filter(|component| component.len() > 1)
+        .map(|component| {
+            let mut members: Vec<String> = component
+                .into_iter()
+                .map(|idx| files[idx].clone())
+                .collect();
+            members.sort();
+            members
+        })
+        .collect();
+    cycles.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    cycles
+}
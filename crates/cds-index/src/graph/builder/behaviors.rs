@@ -19,6 +19,7 @@ use petgraph::visit::EdgeRef;
 use petgraph::Direction;
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use tracing::debug;
 
 /// Process behavior edges for all entities in all files
 ///
@@ -50,7 +51,7 @@ pub(in crate::graph::builder) fn process_behavior_edges(state: &mut BuilderState
             && (normalized.contains("gen_oracle_locations")
                 || normalized.contains("evaluation/eval_metric.py"))
         {
-            println!(
+            debug!(
                 "[PARITY DEBUG] Behavior file pre-check {} (has_entities={})",
                 normalized,
                 state
@@ -61,7 +62,7 @@ pub(in crate::graph::builder) fn process_behavior_edges(state: &mut BuilderState
             );
         }
         if debug && normalized == "util/benchmark/gen_oracle_locations.py" {
-            println!(
+            debug!(
                 "[PARITY DEBUG] normalized check {} eq? {}",
                 normalized,
                 normalized == "util/benchmark/gen_oracle_locations.py"
@@ -71,7 +72,7 @@ pub(in crate::graph::builder) fn process_behavior_edges(state: &mut BuilderState
             && (normalized.contains("gen_oracle_locations")
                 || normalized.contains("evaluation/eval_metric.py"))
         {
-            println!(
+            debug!(
                 "[PARITY DEBUG] Processing behavior file {} (target match={})",
                 normalized,
                 normalized.contains("gen_oracle_locations")
@@ -91,7 +92,7 @@ pub(in crate::graph::builder) fn process_behavior_edges(state: &mut BuilderState
             && (normalized.contains("gen_oracle_locations")
                 || normalized.contains("evaluation/eval_metric.py"))
         {
-            println!(
+            debug!(
                 "[PARITY DEBUG] Entity count for {} = {} (alias_map keys: {})",
                 normalized,
                 state
@@ -109,7 +110,7 @@ pub(in crate::graph::builder) fn process_behavior_edges(state: &mut BuilderState
             None => continue,
         };
         if debug {
-            println!(
+            debug!(
                 "[PARITY DEBUG] entity_indices len {} for {}",
                 entity_indices.len(),
                 normalized
@@ -122,12 +123,12 @@ pub(in crate::graph::builder) fn process_behavior_edges(state: &mut BuilderState
                 && (normalized == "util/benchmark/gen_oracle_locations.py"
                     || normalized == "evaluation/eval_metric.py")
             {
-                println!(
+                debug!(
                     "[PARITY DEBUG] contains_key? {}",
                     state.file_entities.contains_key(&rel_path)
                 );
                 if let Some(node) = state.graph.node(entity_idx) {
-                    println!(
+                    debug!(
                         "[PARITY DEBUG] Iterating entity {} (kind={:?}) in {}",
                         node.id, node.kind, normalized
                     );
@@ -381,22 +382,22 @@ fn connect_behavior_edges(
             crate::graph::builder::imports::resolve_targets(state, rel_path, alias_map, name);
 
         if debug {
-            if let Some(display) = rel_display {
+            if let Some(file) = rel_display {
                 if targets.is_empty() {
-                    println!(
+                    debug!(
                         "[PARITY DEBUG] Behavior target unresolved {} -> {}",
-                        display, name
+                        file, name
                     );
-                } else if display.contains("evaluation/eval_metric.py") && name == "load_jsonl" {
+                } else if file.contains("evaluation/eval_metric.py") && name == "load_jsonl" {
                     let target_ids: Vec<_> = targets
                         .iter()
                         .filter_map(|idx| state.graph.node(*idx).map(|node| node.id.clone()))
                         .collect();
-                    println!(
+                    debug!(
                         "[PARITY DEBUG] Behavior targets {} -> {} = {:?}",
-                        display, name, target_ids
+                        file, name, target_ids
                     );
-                } else if display.contains("gen_oracle_locations.py")
+                } else if file.contains("gen_oracle_locations.py")
                     && (name == "parse_python_file"
                         || name == "extract_module_from_patch"
                         || name == "setup_repo"
@@ -407,22 +408,22 @@ fn connect_behavior_edges(
                         .iter()
                         .filter_map(|idx| state.graph.node(*idx).map(|node| node.id.clone()))
                         .collect();
-                    println!(
+                    debug!(
                         "[PARITY DEBUG] Behavior targets {} -> {} = {:?}",
-                        display, name, target_ids
+                        file, name, target_ids
                     );
-                } else if display.contains("action_parser.py")
+                } else if file.contains("action_parser.py")
                     && (name == "CodeActActionParserMessage" || name == "CodeActActionParserFinish")
                 {
                     let target_ids: Vec<_> = targets
                         .iter()
                         .filter_map(|idx| state.graph.node(*idx).map(|node| node.id.clone()))
                         .collect();
-                    println!(
+                    debug!(
                         "[PARITY DEBUG] Behavior targets {} -> {} = {:?}",
-                        display, name, target_ids
+                        file, name, target_ids
                     );
-                } else if display.contains("evaluation/eval_metric.py")
+                } else if file.contains("evaluation/eval_metric.py")
                     && (name == "load_jsonl"
                         || name == "cal_metrics_w_file"
                         || name == "cal_metrics_w_dataset")
@@ -431,9 +432,9 @@ fn connect_behavior_edges(
                         .iter()
                         .filter_map(|idx| state.graph.node(*idx).map(|node| node.id.clone()))
                         .collect();
-                    println!(
+                    debug!(
                         "[PARITY DEBUG] Behavior targets {} -> {} = {:?}",
-                        display, name, target_ids
+                        file, name, target_ids
                     );
                 }
             }
@@ -0,0 +1,201 @@
+//! Cross-repository import linking
+//!
+//! Absolute imports that do not resolve inside the repository are looked up
+//! in the graphs of other indexed repositories, keyed by their top-level
+//! package names. A match is copied into the graph as an external node (see
+//! [`GraphNode::external`]) and linked with an Import edge carrying the bound
+//! name, so the behavior pass adds Invoke/Inherit edges for calls and base
+//! classes that use it - exactly as for in-repository imports.
+
+use super::imports::add_import_edge_if_absent;
+use super::state::BuilderState;
+use crate::graph::{DependencyGraph, EdgeKind, GraphNode, GraphNodeIndex, NodeKind};
+use petgraph::visit::EdgeRef;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Top-level modules that are never treated as importable packages.
+const NON_PACKAGE_MODULES: &[&str] = &["setup", "conftest", "__init__", "__main__"];
+
+/// Source roots searched for top-level packages besides the repository root.
+const SOURCE_ROOTS: &[&str] = &["src"];
+
+#[derive(Debug)]
+struct ExternalRepo {
+    name: String,
+    graph: Arc<DependencyGraph>,
+}
+
+#[derive(Debug)]
+struct ExternalPackage {
+    repo: Arc<ExternalRepo>,
+    /// Path prefix of the package inside its repository (`""` or `"src/"`).
+    prefix: String,
+}
+
+/// Top-level package name -> graph of the repository that provides it.
+#[derive(Debug, Default)]
+pub struct ExternalPackages {
+    packages: HashMap<String, ExternalPackage>,
+}
+
+impl ExternalPackages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the top-level packages of repository `name`.
+    ///
+    /// Packages already provided by a previously added repository are kept
+    /// and returned so callers can report the conflict.
+    pub fn add_repo(&mut self, name: impl Into<String>, graph: Arc<DependencyGraph>) -> Vec<String> {
+        let repo = Arc::new(ExternalRepo {
+            name: name.into(),
+            graph,
+        });
+        let mut conflicts = Vec::new();
+        for (package, prefix) in package_roots(&repo.graph) {
+            if self.packages.contains_key(&package) {
+                conflicts.push(package);
+                continue;
+            }
+            self.packages.insert(
+                package,
+                ExternalPackage {
+                    repo: Arc::clone(&repo),
+                    prefix,
+                },
+            );
+        }
+        conflicts
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    /// Repository providing the top-level `package`, if any.
+    pub fn provider(&self, package: &str) -> Option<&str> {
+        self.packages
+            .get(package)
+            .map(|package| package.repo.name.as_str())
+    }
+
+    /// Resolves a dotted module path (optionally ending in entity names) to a
+    /// node of another repository.
+    fn resolve(&self, segments: &[String]) -> Option<(&ExternalRepo, GraphNodeIndex)> {
+        let package = self.packages.get(segments.first()?)?;
+        let graph = &package.repo.graph;
+        for split in (1..=segments.len()).rev() {
+            let module = format!("{}{}", package.prefix, segments[..split].join("/"));
+            let file_idx = [format!("{module}.py"), format!("{module}/__init__.py")]
+                .iter()
+                .find_map(|id| graph.get_index(id));
+            let Some(file_idx) = file_idx else {
+                continue;
+            };
+            let rest = &segments[split..];
+            if rest.is_empty() {
+                return Some((&package.repo, file_idx));
+            }
+            let file_id = &graph.node(file_idx)?.id;
+            if let Some(idx) = graph.get_index(&format!("{file_id}::{}", rest.join("::"))) {
+                return Some((&package.repo, idx));
+            }
+            if rest.len() == 1 {
+                if let Some(idx) = follow_reexport(graph, file_idx, &rest[0]) {
+                    return Some((&package.repo, idx));
+                }
+            }
+            return None;
+        }
+        None
+    }
+}
+
+/// Top-level importable packages and modules of a graph, e.g. to advertise in a manifest.
+pub fn top_level_packages(graph: &DependencyGraph) -> Vec<String> {
+    let mut packages: Vec<String> = package_roots(graph)
+        .into_iter()
+        .map(|(package, _)| package)
+        .collect();
+    packages.sort();
+    packages.dedup();
+    packages
+}
+
+fn package_roots(graph: &DependencyGraph) -> Vec<(String, String)> {
+    let mut roots = Vec::new();
+    for idx in graph.graph().node_indices() {
+        let Some(node) = graph.node(idx) else {
+            continue;
+        };
+        if node.kind != NodeKind::File || node.is_external() {
+            continue;
+        }
+        let (prefix, rest) = match node.id.split_once('/') {
+            Some((root, rest)) if SOURCE_ROOTS.contains(&root) => (format!("{root}/"), rest),
+            _ => (String::new(), node.id.as_str()),
+        };
+        let package = match rest.split_once('/') {
+            Some((package, "__init__.py")) => package,
+            Some(_) => continue,
+            None => match rest.strip_suffix(".py") {
+                Some(module) if !NON_PACKAGE_MODULES.contains(&module) => module,
+                _ => continue,
+            },
+        };
+        roots.push((package.to_string(), prefix));
+    }
+    roots
+}
+
+/// Follows `from .x import name` style re-exports of a package `__init__`.
+fn follow_reexport(
+    graph: &DependencyGraph,
+    file_idx: GraphNodeIndex,
+    name: &str,
+) -> Option<GraphNodeIndex> {
+    graph
+        .graph()
+        .edges(file_idx)
+        .filter(|edge| {
+            edge.weight().kind == EdgeKind::Import && edge.weight().alias.as_deref() == Some(name)
+        })
+        .map(|edge| edge.target())
+        .find(|&target| {
+            graph
+                .node(target)
+                .is_some_and(|node| matches!(node.kind, NodeKind::Class | NodeKind::Function))
+        })
+}
+
+/// Links `sources` to the external entity named by `segments`.
+///
+/// Returns `false` when no other repository provides the module.
+pub(in crate::graph::builder) fn link_external_import(
+    state: &mut BuilderState,
+    sources: &[GraphNodeIndex],
+    segments: &[String],
+    alias: Option<&str>,
+) -> bool {
+    let Some(external) = state.external.clone() else {
+        return false;
+    };
+    let Some((repo, target)) = external.resolve(segments) else {
+        return false;
+    };
+    let Some(node) = repo.graph.node(target) else {
+        return false;
+    };
+    let stand_in = GraphNode::external(&repo.name, node);
+    let is_new = state.graph.get_index(&stand_in.id).is_none();
+    let target_idx = state.graph.add_node(stand_in);
+    if is_new {
+        state.stats.external_entities += 1;
+    }
+    for &source in sources {
+        add_import_edge_if_absent(state, source, target_idx, alias);
+    }
+    true
+}
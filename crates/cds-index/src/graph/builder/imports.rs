@@ -8,6 +8,7 @@
 //! - Deferred attribute resolution
 //! - Module alias tracking
 
+use super::external::link_external_import;
 use super::python::ast_utils::{finalize_module_path, module_components};
 use super::state::{BuilderState, DeferredAttributeImport, ExportSource, PendingWildcardExport};
 use crate::graph::{
@@ -20,7 +21,7 @@ use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use tracing::warn;
+use tracing::{debug, warn};
 
 /// Main entry point for processing pending imports
 ///
@@ -55,6 +56,17 @@ pub(in crate::graph::builder) fn process_pending_imports(state: &mut BuilderStat
                         if let Some(alias_value) = alias_name {
                             record_module_alias(state, &rel_path, alias_value, path);
                         }
+                    } else if module.level == 0 {
+                        let mut sources = scoped_indices;
+                        sources.push(file_idx);
+                        if !link_external_import(
+                            state,
+                            &sources,
+                            &module.segments,
+                            alias.as_deref(),
+                        ) {
+                            state.stats.unresolved_imports += 1;
+                        }
                     }
                 }
                 ImportDirective::FromModule {
@@ -179,6 +191,13 @@ fn process_from_import(
                     add_file_import_edge(state, idx, module_path, Some(alias_value));
                 }
             }
+        } else if module.level == 0 {
+            let alias_value = entity.alias.as_deref().unwrap_or(entity.name.as_str());
+            let mut sources = scoped_indices.to_vec();
+            sources.push(file_idx);
+            if !link_external_import(state, &sources, &extended_spec.segments, Some(alias_value)) {
+                state.stats.unresolved_imports += 1;
+            }
         }
     }
 }
@@ -269,7 +288,7 @@ fn resolve_attribute_target(
             return Some(idx);
         }
         if debug && module_id == "util/process_output.py" && name == "merge_sample_locations" {
-            println!(
+            debug!(
                 "[PARITY DEBUG] candidate {} missing for {}",
                 candidate_id, module_id
             );
@@ -285,7 +304,7 @@ fn resolve_attribute_target(
             }
         }
         if debug && module_id == "util/process_output.py" && name == "merge_sample_locations" {
-            println!(
+            debug!(
                 "[PARITY DEBUG] symbols for {} did not contain {} (keys: {:?})",
                 module_id,
                 name,
@@ -359,7 +378,7 @@ fn resolve_deferred_attribute_imports(state: &mut BuilderState) {
             );
             if debug {
                 if let Some(source_node) = state.graph.node(entry.source_idx) {
-                    println!(
+                    debug!(
                         "[PARITY DEBUG] Fallback import {} -> {} (module file)",
                         source_node.id,
                         super::state::normalized_path(&entry.module_path)
@@ -374,7 +393,7 @@ fn resolve_deferred_attribute_imports(state: &mut BuilderState) {
     if debug {
         for entry in &still_unresolved {
             if let Some(source_node) = state.graph.node(entry.source_idx) {
-                println!(
+                debug!(
                     "[PARITY DEBUG] Deferred import unresolved {} -> {}::{}",
                     source_node.id,
                     super::state::normalized_path(&entry.module_path),
@@ -546,7 +565,7 @@ fn resolve_export_source(
     }
 }
 
-fn add_import_edge_if_absent(
+pub(super) fn add_import_edge_if_absent(
     state: &mut BuilderState,
     source_idx: GraphNodeIndex,
     target_idx: GraphNodeIndex,
@@ -658,7 +677,7 @@ pub(in crate::graph::builder) fn build_alias_map(
     let mut visited = HashSet::new();
     let debug = std::env::var_os("PARITY_DEBUG").is_some();
     if debug && !state.file_index_lookup.contains_key(&file_idx) {
-        println!(
+        debug!(
             "[PARITY DEBUG] Missing file_index_lookup entry for node {:?}",
             file_idx.index()
         );
@@ -681,7 +700,7 @@ pub(in crate::graph::builder) fn build_alias_map(
         if let Some(rel_path) = state.file_index_lookup.get(&file_idx) {
             let normalized = crate::graph::builder::state::normalized_path(rel_path);
             if normalized.contains("gen_oracle_locations") {
-                println!(
+                debug!(
                     "[PARITY DEBUG] Building alias map for {} (debug target)",
                     normalized
                 );
@@ -690,7 +709,7 @@ pub(in crate::graph::builder) fn build_alias_map(
                 || normalized.contains("evaluation/eval_metric.py");
             if should_log {
                 let keys: Vec<_> = aliases.keys().cloned().collect();
-                println!("[PARITY DEBUG] Alias map {} -> {:?}", normalized, keys);
+                debug!("[PARITY DEBUG] Alias map {} -> {:?}", normalized, keys);
             }
         }
     }
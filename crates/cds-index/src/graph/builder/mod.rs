@@ -7,13 +7,17 @@
 
 pub mod aliases;
 pub mod behaviors;
+pub mod external;
 pub mod imports;
 pub mod language;
+pub mod progress;
 pub mod python;
 pub mod state;
 
 // Re-export public API from submodules
+pub use external::{top_level_packages, ExternalPackages};
 pub use language::LanguageConfig;
+pub use progress::{BuildPhase, BuildProgress, NoopProgress};
 pub use state::{
     GraphBuildStats, GraphBuilder, GraphBuilderConfig, GraphBuilderResult, GraphError,
 };
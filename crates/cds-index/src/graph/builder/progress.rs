@@ -0,0 +1,57 @@
+//! Build progress reporting and cancellation
+//!
+//! Long-running builds (service rebuild jobs, CLI progress bars) observe the
+//! builder through [`BuildProgress`]. All hooks default to no-ops so callers
+//! only implement what they need.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Coarse build phases reported while an index is (re)built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildPhase {
+    /// Walking the repository to collect candidate source files.
+    Walk,
+    /// Parsing source files and adding contain edges.
+    Parse,
+    /// Resolving import directives into import edges.
+    Imports,
+    /// Building invoke/inherit edges.
+    Behaviors,
+    /// Building the BM25 content index (driven outside the graph builder).
+    Bm25,
+}
+
+impl BuildPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuildPhase::Walk => "walk",
+            BuildPhase::Parse => "parse",
+            BuildPhase::Imports => "imports",
+            BuildPhase::Behaviors => "behaviors",
+            BuildPhase::Bm25 => "bm25",
+        }
+    }
+}
+
+/// Observer hooks invoked by [`GraphBuilder::build_with_progress`](super::GraphBuilder::build_with_progress).
+pub trait BuildProgress: Sync {
+    /// Called when a phase starts; `total` is the number of work items when known.
+    fn phase_started(&self, _phase: BuildPhase, _total: Option<usize>) {}
+
+    /// Called after each source file has been parsed.
+    fn file_processed(&self, _rel_path: &Path) {}
+
+    /// Polled between work items; returning `true` aborts the build with
+    /// [`GraphError::Cancelled`](super::GraphError::Cancelled).
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Progress observer that ignores every event.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopProgress;
+
+impl BuildProgress for NoopProgress {}
@@ -4,6 +4,8 @@
 //! for building dependency graphs. Language-specific operations are delegated
 //! to the `python` module (and future language modules).
 
+use super::external::ExternalPackages;
+use super::progress::{BuildPhase, BuildProgress, NoopProgress};
 use super::python::ast_utils::collect_module_data_from_ast;
 use crate::graph::{
     DependencyGraph, EdgeKind, GraphNode, GraphNodeIndex, ImportDirective, ModuleSpecifier,
@@ -17,6 +19,7 @@ use std::{
     fs,
     path::{Path, PathBuf},
     rc::Rc,
+    sync::Arc,
 };
 use thiserror::Error;
 use tracing::warn;
@@ -84,14 +87,19 @@ impl AstModuleData {
     }
 }
 
-#[derive(Debug, Clone)]
-#[derive(Default)]
+#[derive(Debug, Clone, Default)]
 pub struct GraphBuilderConfig {
     pub follow_symlinks: bool,
     pub max_python_files: Option<usize>,
     pub allowed_python_files: Option<HashSet<String>>,
     pub required_directories: Option<HashSet<String>>,
     pub allowed_edges: Option<HashMap<(String, String, EdgeKind), usize>>,
+    /// Other indexed repositories to resolve otherwise unresolved absolute
+    /// imports against (cross-repository linking); `None` disables the pass.
+    pub external_packages: Option<Arc<ExternalPackages>>,
+    /// Directory names skipped in addition to the built-in list (`.git`,
+    /// `node_modules`, virtualenvs, build output, ...).
+    pub skip_dirs: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -129,40 +137,30 @@ impl GraphBuilder {
     }
 
     pub fn build(&self) -> Result<GraphBuilderResult, GraphError> {
+        self.build_with_progress(&NoopProgress)
+    }
+
+    /// Builds the graph while reporting phase/file progress and honouring cancellation.
+    pub fn build_with_progress(
+        &self,
+        progress: &dyn BuildProgress,
+    ) -> Result<GraphBuilderResult, GraphError> {
         let mut parser = PythonParser::new()?;
         let mut state = BuilderState::new(self.repo_root.clone());
-
-        let walker = WalkDir::new(&self.repo_root)
-            .follow_links(self.config.follow_symlinks)
-            .into_iter()
-            .filter_entry(|entry| !should_skip(entry));
-
-        let mut processed_files = 0usize;
-        let max_files = self.config.max_python_files;
-
-        for entry in walker {
-            if let Some(limit) = max_files {
-                if processed_files >= limit {
-                    break;
-                }
-            }
-            let entry = entry?;
-            let rel_path = relative_path(&self.repo_root, entry.path());
-
-            if entry.file_type().is_dir() {
-                continue;
-            }
-
-            if is_python_file(&entry) {
-                if let Some(allowed) = &self.config.allowed_python_files {
-                    let normalized = normalized_path(&rel_path);
-                    if !allowed.contains(&normalized) {
-                        continue;
-                    }
-                }
-                state.process_python_file(&mut parser, &rel_path, entry.path())?;
-                processed_files += 1;
-            }
+        state.external = self
+            .config
+            .external_packages
+            .clone()
+            .filter(|packages| !packages.is_empty());
+
+        progress.phase_started(BuildPhase::Walk, None);
+        let python_files = self.collect_python_files(progress)?;
+
+        progress.phase_started(BuildPhase::Parse, Some(python_files.len()));
+        for (rel_path, absolute_path) in &python_files {
+            ensure_not_cancelled(progress)?;
+            state.process_python_file(&mut parser, rel_path, absolute_path)?;
+            progress.file_processed(rel_path);
         }
 
         if let Some(required_dirs) = &self.config.required_directories {
@@ -176,8 +174,14 @@ impl GraphBuilder {
             }
         }
 
+        ensure_not_cancelled(progress)?;
+        progress.phase_started(BuildPhase::Imports, None);
         state.process_pending_imports();
+
+        ensure_not_cancelled(progress)?;
+        progress.phase_started(BuildPhase::Behaviors, None);
         state.process_behavior_edges();
+        ensure_not_cancelled(progress)?;
 
         if let Some(allowed_edges) = &self.config.allowed_edges {
             let mut remaining = allowed_edges.clone();
@@ -224,6 +228,56 @@ impl GraphBuilder {
 
         Ok(state.finish())
     }
+
+    /// Walks the repository and returns `(relative, absolute)` paths of the Python
+    /// files to parse, honouring skip dirs, `max_python_files` and the allow-list.
+    fn collect_python_files(
+        &self,
+        progress: &dyn BuildProgress,
+    ) -> Result<Vec<(PathBuf, PathBuf)>, GraphError> {
+        let walker = WalkDir::new(&self.repo_root)
+            .follow_links(self.config.follow_symlinks)
+            .into_iter()
+            .filter_entry(|entry| !should_skip(entry, &self.config.skip_dirs));
+
+        let mut files = Vec::new();
+        let max_files = self.config.max_python_files;
+
+        for entry in walker {
+            if let Some(limit) = max_files {
+                if files.len() >= limit {
+                    break;
+                }
+            }
+            ensure_not_cancelled(progress)?;
+            let entry = entry?;
+            let rel_path = relative_path(&self.repo_root, entry.path());
+
+            if entry.file_type().is_dir() {
+                continue;
+            }
+
+            if is_python_file(&entry) {
+                if let Some(allowed) = &self.config.allowed_python_files {
+                    let normalized = normalized_path(&rel_path);
+                    if !allowed.contains(&normalized) {
+                        continue;
+                    }
+                }
+                files.push((rel_path, entry.path().to_path_buf()));
+            }
+        }
+
+        Ok(files)
+    }
+}
+
+fn ensure_not_cancelled(progress: &dyn BuildProgress) -> Result<(), GraphError> {
+    if progress.is_cancelled() {
+        Err(GraphError::Cancelled)
+    } else {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -231,6 +285,14 @@ pub struct GraphBuildStats {
     pub directories: usize,
     pub files: usize,
     pub entities: usize,
+    /// Nodes standing in for entities of other repositories.
+    pub external_entities: usize,
+    /// Absolute imports no file of the repository (or a linked repository)
+    /// provides, standard library and third-party packages included.
+    pub unresolved_imports: usize,
+    /// Files the Python AST parser rejected; their entities come from the
+    /// tree-sitter pass only, so their behavior edges are missing.
+    pub parse_failures: Vec<PathBuf>,
 }
 
 pub struct GraphBuilderResult {
@@ -246,6 +308,8 @@ pub enum GraphError {
     Walk(#[from] walkdir::Error),
     #[error("parser error: {0}")]
     Parser(#[from] ParserError),
+    #[error("build cancelled")]
+    Cancelled,
 }
 
 pub(super) struct BuilderState {
@@ -267,6 +331,7 @@ pub(super) struct BuilderState {
     pub(super) module_aliases: HashMap<PathBuf, HashMap<String, PathBuf>>,
     pub(super) resolved_exports: HashMap<PathBuf, HashSet<String>>,
     pub(super) wildcard_imports: HashMap<PathBuf, Vec<PathBuf>>,
+    pub(super) external: Option<Arc<ExternalPackages>>,
     pub(super) stats: GraphBuildStats,
 }
 
@@ -303,6 +368,7 @@ impl BuilderState {
             module_aliases: HashMap::new(),
             resolved_exports: HashMap::new(),
             wildcard_imports: HashMap::new(),
+            external: None,
             stats: GraphBuildStats {
                 directories: 1,
                 ..GraphBuildStats::default()
@@ -352,11 +418,7 @@ impl BuilderState {
             }
             Err(err) => {
                 warn!("Failed to parse Python AST for {:?}: {err}", rel_path);
-                println!(
-                    "[PARITY DEBUG] rustpython parse failed for {}: {}",
-                    rel_path.display(),
-                    err
-                );
+                self.stats.parse_failures.push(rel_path.to_path_buf());
                 AstModuleData {
                     imports: PythonParser::collect_imports_from_tree(&tree, &source),
                     exports: ModuleExports::default(),
@@ -447,10 +509,7 @@ impl BuilderState {
 
         let file_id = normalized_path(rel_path);
         let mut local_lookup: HashMap<String, GraphNodeIndex> = HashMap::new();
-        let symbol_table = self
-            .file_symbols
-            .entry(rel_path.to_path_buf())
-            .or_default();
+        let symbol_table = self.file_symbols.entry(rel_path.to_path_buf()).or_default();
         symbol_table.clear();
         let entity_list = self
             .file_entities
@@ -489,9 +548,7 @@ impl BuilderState {
             self.stats.entities += 1;
 
             if let Some(identifier) = entity.identifier() {
-                let entry = symbol_table
-                    .entry(identifier.to_string())
-                    .or_default();
+                let entry = symbol_table.entry(identifier.to_string()).or_default();
                 if !entry.contains(&node_idx) {
                     entry.push(node_idx);
                 }
@@ -551,10 +608,10 @@ fn is_python_file(entry: &DirEntry) -> bool {
         .unwrap_or(false)
 }
 
-fn should_skip(entry: &DirEntry) -> bool {
+fn should_skip(entry: &DirEntry, extra: &[String]) -> bool {
     if !entry.file_type().is_dir() {
         return false;
     }
     let name = entry.file_name().to_string_lossy();
-    SKIP_DIRS.iter().any(|skip| *skip == name)
+    SKIP_DIRS.iter().any(|skip| *skip == name) || extra.iter().any(|skip| *skip == name)
 }
@@ -0,0 +1,146 @@
+//! Change impact: the entities a unified diff touches and their dependents.
+//!
+//! Backs `diff_impact`. [`parse_unified_diff`] reduces a diff (`git diff`,
+//! `diff -u`) to the lines it changes in each file, numbered as in the new
+//! version; [`changed_entities`] maps those lines to the innermost class or
+//! function containing them (the file itself for module-level lines).
+//! Walking backward from the changed entities with [`super::bfs_subgraph`]
+//! then gives their callers, importers and subclasses.
+
+use crate::graph::{DependencyGraph, EdgeKind, GraphNodeIndex};
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use std::collections::{BTreeSet, HashSet};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DiffError {
+    #[error("not a unified diff: no '+++' file header found")]
+    NotADiff,
+    #[error("malformed hunk header '{0}'")]
+    BadHunk(String),
+}
+
+/// Lines a diff changes in one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// Path in the new version, without the `b/` prefix.
+    pub path: String,
+    /// Added lines, plus the line before each deletion, numbered as in the
+    /// new version.
+    pub lines: BTreeSet<u32>,
+}
+
+/// Parses the file headers and hunks of a unified diff. Deleted files and
+/// files without hunks (renames, mode or binary changes) are left out; a
+/// blank diff has no changes.
+pub fn parse_unified_diff(diff: &str) -> Result<Vec<FileChange>, DiffError> {
+    let mut changes: Vec<FileChange> = Vec::new();
+    // The file hunks apply to; `None` for a deleted one.
+    let mut current: Option<FileChange> = None;
+    let mut saw_header = false;
+    // Old and new lines left in the current hunk, and the next new line.
+    let (mut old_left, mut new_left, mut new_line) = (0u32, 0u32, 0u32);
+    for line in diff.lines() {
+        if old_left > 0 || new_left > 0 {
+            match line.as_bytes().first() {
+                Some(b'+') => {
+                    record(&mut current, new_line);
+                    new_line += 1;
+                    new_left = new_left.saturating_sub(1);
+                }
+                Some(b'-') => {
+                    record(&mut current, new_line.saturating_sub(1).max(1));
+                    old_left = old_left.saturating_sub(1);
+                }
+                Some(b'\\') => {}
+                _ => {
+                    new_line += 1;
+                    old_left = old_left.saturating_sub(1);
+                    new_left = new_left.saturating_sub(1);
+                }
+            }
+            continue;
+        }
+        if let Some(target) = line.strip_prefix("+++ ") {
+            saw_header = true;
+            // `diff -u` appends a tab and the modification time.
+            let target = target.split('\t').next().unwrap_or(target).trim_end();
+            changes.extend(current.take());
+            current = (target != "/dev/null").then(|| FileChange {
+                path: target.strip_prefix("b/").unwrap_or(target).to_string(),
+                lines: BTreeSet::new(),
+            });
+        } else if line.starts_with("@@") {
+            let (old, new) = hunk_ranges(line).ok_or_else(|| DiffError::BadHunk(line.into()))?;
+            (old_left, new_left) = (old.1, new.1);
+            // Without new lines, `new.0` is the line before the deletion.
+            new_line = if new.1 == 0 { new.0 + 1 } else { new.0 };
+        }
+    }
+    changes.extend(current);
+    if !saw_header && !diff.trim().is_empty() {
+        return Err(DiffError::NotADiff);
+    }
+    changes.retain(|change| !change.lines.is_empty());
+    Ok(changes)
+}
+
+fn record(current: &mut Option<FileChange>, line: u32) {
+    if let Some(change) = current {
+        change.lines.insert(line);
+    }
+}
+
+/// `@@ -start,count +start,count @@` as `((start, count), (start, count))`;
+/// an omitted count is 1.
+fn hunk_ranges(header: &str) -> Option<((u32, u32), (u32, u32))> {
+    let mut parts = header.strip_prefix("@@ ")?.split_whitespace();
+    let range = |part: Option<&str>, sign: char| -> Option<(u32, u32)> {
+        let part = part?.strip_prefix(sign)?;
+        match part.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((part.parse().ok()?, 1)),
+        }
+    };
+    Some((range(parts.next(), '-')?, range(parts.next(), '+')?))
+}
+
+/// The entities `change` touches, in line order: for each changed line the
+/// innermost class or function whose range holds it, or the file node.
+/// `None` when the file is not in the graph.
+pub fn changed_entities(
+    graph: &DependencyGraph,
+    change: &FileChange,
+) -> Option<Vec<GraphNodeIndex>> {
+    let file = graph.get_index(&change.path)?;
+    let mut seen = HashSet::new();
+    let mut entities = Vec::new();
+    for &line in &change.lines {
+        let entity = innermost(graph, file, line);
+        if seen.insert(entity) {
+            entities.push(entity);
+        }
+    }
+    Some(entities)
+}
+
+fn innermost(graph: &DependencyGraph, mut idx: GraphNodeIndex, line: u32) -> GraphNodeIndex {
+    let storage = graph.graph();
+    loop {
+        let child = storage
+            .edges_directed(idx, Direction::Outgoing)
+            .filter(|edge| edge.weight().kind == EdgeKind::Contain)
+            .map(|edge| edge.target())
+            .find(|&child| {
+                graph
+                    .node(child)
+                    .and_then(|node| node.range)
+                    .is_some_and(|range| range.start_line <= line && line <= range.end_line)
+            });
+        match child {
+            Some(child) => idx = child,
+            None => return idx,
+        }
+    }
+}
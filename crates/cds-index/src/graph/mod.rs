@@ -4,17 +4,25 @@
 //! - 4 node types: directory, file, class, function
 //! - 4 edge types: contain, import, invoke, inherit
 
+pub mod analytics;
 pub mod builder;
+pub mod impact;
 pub mod parser;
 pub mod traversal;
 
+pub use analytics::{analyze, FileEntityCount, GraphAnalytics, InvokedEntity};
+pub use impact::{changed_entities, parse_unified_diff, DiffError, FileChange};
 pub use builder::{
-    GraphBuildStats, GraphBuilder, GraphBuilderConfig, GraphBuilderResult, GraphError,
+    top_level_packages, BuildPhase, BuildProgress, ExternalPackages, GraphBuildStats,
+    GraphBuilder, GraphBuilderConfig, GraphBuilderResult, GraphError, NoopProgress,
 };
 pub use parser::{
-    ImportDirective, ImportEntity, ModuleSpecifier, ParsedEntity, ParserError, PythonParser,
+    EntityMetadata, ImportDirective, ImportEntity, ModuleSpecifier, ParsedEntity, ParserError, PythonParser,
+};
+pub use traversal::{
+    bfs_subgraph, bfs_traversal, bfs_visit, shortest_paths, BfsEvent, GraphPath,
+    TraversalDirection, TraversalFilter, TraversalResult,
 };
-pub use traversal::{bfs_traversal, TraversalFilter};
 
 use petgraph::stable_graph::{EdgeIndex, NodeIndex, StableDiGraph};
 use serde::{Deserialize, Serialize};
@@ -35,6 +43,18 @@ pub enum NodeKind {
     Function,
 }
 
+impl NodeKind {
+    /// Lower-case name used in the JSON-RPC API (`entity_type`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeKind::Directory => "directory",
+            NodeKind::File => "file",
+            NodeKind::Class => "class",
+            NodeKind::Function => "function",
+        }
+    }
+}
+
 /// Supported edge kinds as defined in PRD-02 FR-CG-2.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -45,6 +65,18 @@ pub enum EdgeKind {
     Inherit,
 }
 
+impl EdgeKind {
+    /// Lower-case name used in the JSON-RPC API (`relation`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EdgeKind::Contain => "contain",
+            EdgeKind::Import => "import",
+            EdgeKind::Invoke => "invoke",
+            EdgeKind::Inherit => "inherit",
+        }
+    }
+}
+
 /// Line-based source range (1-indexed) for parity comparisons with LocAgent output.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SourceRange {
@@ -60,8 +92,25 @@ impl SourceRange {
             end_line,
         }
     }
+
+    /// Extracts the covered lines from `source`, joined with `\n`.
+    pub fn extract(&self, source: &str) -> String {
+        let start = self.start_line.max(1) as usize - 1;
+        let len = (self.end_line as usize).saturating_sub(start);
+        source
+            .lines()
+            .skip(start)
+            .take(len)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
+/// Attribute naming the repository an external node belongs to.
+pub const EXTERNAL_REPO_ATTR: &str = "external_repo";
+/// Attribute holding the entity id of an external node inside its own repository.
+pub const EXTERNAL_ID_ATTR: &str = "external_id";
+
 /// Node metadata stored inside the dependency graph.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GraphNode {
@@ -115,6 +164,35 @@ impl GraphNode {
             attributes: HashMap::new(),
         }
     }
+
+    /// Stand-in for `node`, an entity of the indexed repository `repo`, used
+    /// by cross-repository import edges. Its id is `@<repo>/<entity id>`.
+    pub fn external(repo: &str, node: &GraphNode) -> Self {
+        let mut attributes = HashMap::new();
+        attributes.insert(EXTERNAL_REPO_ATTR.to_string(), repo.to_string());
+        attributes.insert(EXTERNAL_ID_ATTR.to_string(), node.id.clone());
+        Self {
+            id: format!("@{repo}/{}", node.id),
+            kind: node.kind,
+            display_name: node.display_name.clone(),
+            file_path: node.file_path.clone(),
+            range: node.range,
+            attributes,
+        }
+    }
+
+    /// `(repository, entity id)` of an external node.
+    pub fn external_ref(&self) -> Option<(&str, &str)> {
+        Some((
+            self.attributes.get(EXTERNAL_REPO_ATTR)?.as_str(),
+            self.attributes.get(EXTERNAL_ID_ATTR)?.as_str(),
+        ))
+    }
+
+    /// Whether this node belongs to another repository (see [`GraphNode::external`]).
+    pub fn is_external(&self) -> bool {
+        self.attributes.contains_key(EXTERNAL_REPO_ATTR)
+    }
 }
 
 /// Graph wrapper that keeps a stable mapping between node ids and indices.
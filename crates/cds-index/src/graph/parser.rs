@@ -1,6 +1,7 @@
 //! AST parsing with tree-sitter for Python repositories.
 
 use crate::graph::{NodeKind, SourceRange};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use thiserror::Error;
 use tree_sitter::{Language, Node, Parser, Point, Tree};
@@ -24,6 +25,29 @@ impl ParsedEntity {
     }
 }
 
+/// Signature details of a class or function (`retrieve_entity` metadata).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntityMetadata {
+    /// Parameters as written, annotations and defaults included.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub return_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docstring: Option<String>,
+    /// Decorator expressions without the `@`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub decorators: Vec<String>,
+    /// Base classes (and class keywords such as `metaclass=...`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bases: Vec<String>,
+    /// Id of the class defining a method; filled in from the graph.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_class: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_async: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct ModuleSpecifier {
     pub level: usize,
@@ -118,6 +142,96 @@ impl PythonParser {
     pub fn collect_imports_from_tree(tree: &Tree, source: &str) -> Vec<ImportDirective> {
         collect_imports(tree, source.as_bytes())
     }
+
+    /// Metadata of the class or function whose definition starts at
+    /// `start_line` (1-based, as in [`SourceRange`]) of `source`.
+    pub fn entity_metadata(&mut self, source: &str, start_line: u32) -> Option<EntityMetadata> {
+        let tree = self.parse(source).ok()?;
+        let row = (start_line as usize).checked_sub(1)?;
+        let definition = find_definition(tree.root_node(), row)?;
+        Some(definition_metadata(definition, source.as_bytes()))
+    }
+}
+
+/// The class or function definition starting on `row`.
+fn find_definition(node: Node, row: usize) -> Option<Node> {
+    if matches!(node.kind(), "class_definition" | "function_definition")
+        && node.start_position().row == row
+    {
+        return Some(node);
+    }
+    let mut cursor = node.walk();
+    let found = node
+        .children(&mut cursor)
+        .filter(|child| child.start_position().row <= row && row <= child.end_position().row)
+        .find_map(|child| find_definition(child, row));
+    found
+}
+
+fn definition_metadata(definition: Node, source: &[u8]) -> EntityMetadata {
+    let text = |node: Node| node.utf8_text(source).unwrap_or_default().to_string();
+    let named_children = |node: Option<Node>| -> Vec<String> {
+        let Some(node) = node else {
+            return Vec::new();
+        };
+        let mut cursor = node.walk();
+        let children: Vec<String> = node
+            .named_children(&mut cursor)
+            .filter(|child| child.kind() != "comment")
+            .map(text)
+            .collect();
+        children
+    };
+
+    let mut metadata = EntityMetadata::default();
+    if definition.kind() == "function_definition" {
+        metadata.parameters = named_children(definition.child_by_field_name("parameters"));
+        metadata.return_type = definition.child_by_field_name("return_type").map(text);
+        metadata.is_async = definition
+            .child(0)
+            .is_some_and(|first| first.kind() == "async");
+    } else {
+        metadata.bases = named_children(definition.child_by_field_name("superclasses"));
+    }
+    if let Some(parent) = definition
+        .parent()
+        .filter(|parent| parent.kind() == "decorated_definition")
+    {
+        let mut cursor = parent.walk();
+        metadata.decorators = parent
+            .named_children(&mut cursor)
+            .filter(|child| child.kind() == "decorator")
+            .map(|decorator| text(decorator).trim_start_matches('@').trim().to_string())
+            .collect();
+    }
+    metadata.docstring = definition
+        .child_by_field_name("body")
+        .and_then(|body| body.named_child(0))
+        .filter(|statement| statement.kind() == "expression_statement")
+        .and_then(|statement| statement.named_child(0))
+        .filter(|expression| expression.kind() == "string")
+        .map(|string| docstring_text(&text(string)));
+    metadata
+}
+
+/// The contents of a string literal, quotes and indentation stripped.
+fn docstring_text(literal: &str) -> String {
+    let body = literal.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    let quote = if body.starts_with("\"\"\"") || body.starts_with("'''") {
+        &body[..3]
+    } else {
+        &body[..1.min(body.len())]
+    };
+    let inner = body
+        .strip_prefix(quote)
+        .and_then(|rest| rest.strip_suffix(quote))
+        .unwrap_or(body);
+    inner
+        .trim()
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 fn collect_entities(tree: &Tree, source: &[u8]) -> Vec<ParsedEntity> {
@@ -441,10 +555,7 @@ fn parse_import_entities(raw: &str) -> Vec<ImportEntity> {
         .trim()
         .trim_matches(|c| c == '(' || c == ')')
         .to_string();
-    cleaned
-        .split(',')
-        .filter_map(parse_import_entity)
-        .collect()
+    cleaned.split(',').filter_map(parse_import_entity).collect()
 }
 
 fn parse_import_entity(entry: &str) -> Option<ImportEntity> {
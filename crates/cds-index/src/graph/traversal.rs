@@ -1,13 +1,32 @@
 //! Graph traversal and dependency exploration utilities.
 
 use crate::graph::{DependencyGraph, EdgeKind, GraphNodeIndex};
+use petgraph::stable_graph::EdgeIndex;
 use petgraph::visit::EdgeRef;
-use std::collections::{HashSet, VecDeque};
+use petgraph::Direction;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::ControlFlow;
+
+/// Which edge orientation a traversal follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraversalDirection {
+    /// Follow outgoing edges (callees, imports, children).
+    #[default]
+    Forward,
+    /// Follow incoming edges (callers, importers, parents).
+    Backward,
+    /// Follow edges in both orientations.
+    Bidirectional,
+}
 
 #[derive(Debug, Clone)]
 pub struct TraversalFilter {
     pub max_depth: usize,
     pub relations: Vec<EdgeKind>,
+    pub direction: TraversalDirection,
 }
 
 impl Default for TraversalFilter {
@@ -15,50 +34,324 @@ impl Default for TraversalFilter {
         Self {
             max_depth: 1,
             relations: Vec::new(),
+            direction: TraversalDirection::Forward,
         }
     }
 }
 
+impl TraversalFilter {
+    fn allowed_relations(&self) -> Option<HashSet<EdgeKind>> {
+        if self.relations.is_empty() {
+            None
+        } else {
+            Some(self.relations.iter().copied().collect())
+        }
+    }
+}
+
+/// Nodes and edges discovered by [`bfs_subgraph`].
+#[derive(Debug, Default, Clone)]
+pub struct TraversalResult {
+    /// Visited nodes in discovery order, paired with their BFS depth.
+    pub nodes: Vec<(GraphNodeIndex, usize)>,
+    /// Every matching edge examined between visited nodes.
+    pub edges: Vec<EdgeIndex>,
+    /// The subset of `edges` that first discovered each node (a BFS spanning forest).
+    pub tree_edges: Vec<EdgeIndex>,
+}
+
+impl TraversalResult {
+    /// Deepest level reached by the traversal.
+    pub fn max_depth_reached(&self) -> usize {
+        self.nodes
+            .iter()
+            .map(|(_, depth)| *depth)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
 /// Breadth-first traversal constrained by relation types and depth (mirrors LocAgent BFS).
 pub fn bfs_traversal(
     graph: &DependencyGraph,
     start: GraphNodeIndex,
     filter: &TraversalFilter,
 ) -> Vec<GraphNodeIndex> {
-    let mut results = Vec::new();
-    let mut visited = HashSet::new();
+    bfs_subgraph(graph, &[start], filter)
+        .nodes
+        .into_iter()
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// One step of [`bfs_visit`], reported in discovery order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BfsEvent {
+    /// A node reached for the first time, at its BFS depth.
+    Node(GraphNodeIndex, usize),
+    /// A matching edge examined for the first time. Both endpoints have been
+    /// reported already; `tree` marks the edge that discovered its far end.
+    Edge { edge: EdgeIndex, tree: bool },
+}
+
+/// Multi-source BFS that also records the traversed edges and node depths.
+pub fn bfs_subgraph(
+    graph: &DependencyGraph,
+    starts: &[GraphNodeIndex],
+    filter: &TraversalFilter,
+) -> TraversalResult {
+    let mut result = TraversalResult::default();
+    bfs_visit(graph, starts, filter, |event| {
+        match event {
+            BfsEvent::Node(node, depth) => result.nodes.push((node, depth)),
+            BfsEvent::Edge { edge, tree } => {
+                if tree {
+                    result.tree_edges.push(edge);
+                }
+                result.edges.push(edge);
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    result
+}
+
+/// Runs the [`bfs_subgraph`] traversal, handing each step to `visit` as it
+/// happens; `visit` can stop the traversal early with [`ControlFlow::Break`].
+pub fn bfs_visit(
+    graph: &DependencyGraph,
+    starts: &[GraphNodeIndex],
+    filter: &TraversalFilter,
+    mut visit: impl FnMut(BfsEvent) -> ControlFlow<()>,
+) {
+    let mut depths: HashMap<GraphNodeIndex, usize> = HashMap::new();
+    let mut seen_edges = HashSet::new();
     let mut queue = VecDeque::new();
 
-    visited.insert(start);
-    queue.push_back((start, 0usize));
-    results.push(start);
+    for &start in starts {
+        if depths.contains_key(&start) {
+            continue;
+        }
+        depths.insert(start, 0);
+        queue.push_back((start, 0usize));
+        if visit(BfsEvent::Node(start, 0)).is_break() {
+            return;
+        }
+    }
 
-    let allowed = if filter.relations.is_empty() {
-        None
-    } else {
-        Some(filter.relations.iter().copied().collect::<HashSet<_>>())
-    };
+    let allowed = filter.allowed_relations();
 
     while let Some((node, depth)) = queue.pop_front() {
         if depth >= filter.max_depth {
             continue;
         }
 
-        for edge in graph.graph().edges(node) {
-            let weight = edge.weight();
+        for (edge_idx, neighbor) in neighbor_edges(graph, node, filter.direction) {
+            let Some(weight) = graph.graph().edge_weight(edge_idx) else {
+                continue;
+            };
             if let Some(ref allow) = allowed {
                 if !allow.contains(&weight.kind) {
                     continue;
                 }
             }
 
-            let neighbor = edge.target();
-            if visited.insert(neighbor) {
-                results.push(neighbor);
+            let mut tree = false;
+            if let Entry::Vacant(entry) = depths.entry(neighbor) {
+                entry.insert(depth + 1);
+                tree = true;
                 queue.push_back((neighbor, depth + 1));
+                if visit(BfsEvent::Node(neighbor, depth + 1)).is_break() {
+                    return;
+                }
+            }
+            if seen_edges.insert(edge_idx)
+                && visit(BfsEvent::Edge {
+                    edge: edge_idx,
+                    tree,
+                })
+                .is_break()
+            {
+                return;
             }
         }
     }
+}
 
-    results
+/// Lists `(edge, neighbor)` pairs adjacent to `node` in the requested direction.
+pub fn neighbor_edges(
+    graph: &DependencyGraph,
+    node: GraphNodeIndex,
+    direction: TraversalDirection,
+) -> Vec<(EdgeIndex, GraphNodeIndex)> {
+    let storage = graph.graph();
+    let mut neighbors = Vec::new();
+    if matches!(
+        direction,
+        TraversalDirection::Forward | TraversalDirection::Bidirectional
+    ) {
+        neighbors.extend(
+            storage
+                .edges_directed(node, Direction::Outgoing)
+                .map(|edge| (edge.id(), edge.target())),
+        );
+    }
+    if matches!(
+        direction,
+        TraversalDirection::Backward | TraversalDirection::Bidirectional
+    ) {
+        neighbors.extend(
+            storage
+                .edges_directed(node, Direction::Incoming)
+                .map(|edge| (edge.id(), edge.source())),
+        );
+    }
+    neighbors
+}
+
+/// A path found by [`shortest_paths`]: where it starts and, per hop, the edge
+/// followed and the node it leads to. Edges keep their own orientation, so a
+/// backward hop reaches the source of its edge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphPath {
+    pub start: GraphNodeIndex,
+    pub hops: Vec<(EdgeIndex, GraphNodeIndex)>,
+}
+
+impl GraphPath {
+    /// Nodes along the path, the start included.
+    pub fn nodes(&self) -> impl Iterator<Item = GraphNodeIndex> + '_ {
+        std::iter::once(self.start).chain(self.hops.iter().map(|&(_, node)| node))
+    }
+}
+
+/// Up to `k` shortest paths from `from` to `to` that visit no node twice,
+/// shortest first, following the relations and direction of `filter` for at
+/// most `filter.max_depth` hops. Yen's algorithm over breadth-first searches;
+/// ties between paths of equal length are broken by the ids along them.
+pub fn shortest_paths(
+    graph: &DependencyGraph,
+    from: GraphNodeIndex,
+    to: GraphNodeIndex,
+    filter: &TraversalFilter,
+    k: usize,
+) -> Vec<GraphPath> {
+    let allowed = filter.allowed_relations();
+    let search = PathSearch {
+        graph,
+        to,
+        direction: filter.direction,
+        allowed: allowed.as_ref(),
+    };
+    let Some(first) = search.run(from, filter.max_depth, &HashSet::new(), &HashSet::new()) else {
+        return Vec::new();
+    };
+    let mut found = vec![first];
+    let mut candidates: Vec<Vec<(EdgeIndex, GraphNodeIndex)>> = Vec::new();
+    while found.len() < k {
+        let last = found[found.len() - 1].hops.clone();
+        for i in 0..last.len() {
+            // Deviate from `last` after its first `i` hops, along an edge no
+            // path found so far takes from there.
+            let root = &last[..i];
+            let spur = root.last().map_or(from, |&(_, node)| node);
+            let banned_edges: HashSet<EdgeIndex> = found
+                .iter()
+                .filter(|path| path.hops.len() > i && path.hops[..i] == *root)
+                .map(|path| path.hops[i].0)
+                .collect();
+            let banned_nodes: HashSet<GraphNodeIndex> = std::iter::once(from)
+                .chain(root.iter().map(|&(_, node)| node))
+                .filter(|&node| node != spur)
+                .collect();
+            let Some(deviation) =
+                search.run(spur, filter.max_depth - i, &banned_nodes, &banned_edges)
+            else {
+                continue;
+            };
+            let mut hops = root.to_vec();
+            hops.extend(deviation.hops);
+            if !candidates.contains(&hops) && !found.iter().any(|path| path.hops == hops) {
+                candidates.push(hops);
+            }
+        }
+        let ids = |hops: &[(EdgeIndex, GraphNodeIndex)]| -> Vec<&str> {
+            hops.iter()
+                .filter_map(|&(_, node)| graph.node(node).map(|node| node.id.as_str()))
+                .collect()
+        };
+        let Some(shortest) = candidates
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.len().cmp(&b.len()).then_with(|| ids(a).cmp(&ids(b))))
+            .map(|(i, _)| i)
+        else {
+            break;
+        };
+        found.push(GraphPath {
+            start: from,
+            hops: candidates.remove(shortest),
+        });
+    }
+    found
+}
+
+/// Breadth-first search for one shortest path to `to`.
+struct PathSearch<'a> {
+    graph: &'a DependencyGraph,
+    to: GraphNodeIndex,
+    direction: TraversalDirection,
+    allowed: Option<&'a HashSet<EdgeKind>>,
+}
+
+impl PathSearch<'_> {
+    fn run(
+        &self,
+        from: GraphNodeIndex,
+        max_hops: usize,
+        banned_nodes: &HashSet<GraphNodeIndex>,
+        banned_edges: &HashSet<EdgeIndex>,
+    ) -> Option<GraphPath> {
+        // Node -> the edge that reached it and the node it came from.
+        let mut reached: HashMap<GraphNodeIndex, (EdgeIndex, GraphNodeIndex)> = HashMap::new();
+        let mut queue = VecDeque::from([(from, 0usize)]);
+        while let Some((node, depth)) = queue.pop_front() {
+            if node == self.to {
+                let mut hops = Vec::with_capacity(depth);
+                let mut current = node;
+                while let Some(&(edge, previous)) = reached.get(&current) {
+                    hops.push((edge, current));
+                    current = previous;
+                }
+                hops.reverse();
+                return Some(GraphPath { start: from, hops });
+            }
+            if depth >= max_hops {
+                continue;
+            }
+            let mut next = neighbor_edges(self.graph, node, self.direction);
+            next.sort_by_key(|&(_, neighbor)| self.graph.node(neighbor).map(|node| &node.id));
+            for (edge, neighbor) in next {
+                if neighbor == from
+                    || reached.contains_key(&neighbor)
+                    || banned_nodes.contains(&neighbor)
+                    || banned_edges.contains(&edge)
+                {
+                    continue;
+                }
+                let Some(weight) = self.graph.graph().edge_weight(edge) else {
+                    continue;
+                };
+                if self
+                    .allowed
+                    .is_some_and(|allow| !allow.contains(&weight.kind))
+                {
+                    continue;
+                }
+                reached.insert(neighbor, (edge, node));
+                queue.push_back((neighbor, depth + 1));
+            }
+        }
+        None
+    }
 }
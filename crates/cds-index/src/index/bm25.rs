@@ -1,4 +1,215 @@
 //! BM25 content search using tantivy
+//!
+//! Lower tier of the hierarchical index (PRD-02 FR-HI-2). Every class and
+//! function is indexed as one document made of its name and source text.
 
-// TODO: Implement BM25Index with tantivy
-// Reference: PRD-02 FR-HI-2, k1=1.5, b=0.75
+use crate::graph::{DependencyGraph, NodeKind};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tantivy::collector::TopDocs;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexReader, ReloadPolicy, TantivyDocument};
+use thiserror::Error;
+
+const WRITER_HEAP_BYTES: usize = 50_000_000;
+const NAME_FIELD_BOOST: f32 = 2.0;
+
+/// Smallest writer heap tantivy accepts for one indexing thread.
+pub const MIN_WRITER_HEAP_BYTES: usize = 15_000_000;
+
+/// Tunable BM25 settings (`[bm25]` in the service configuration).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bm25Params {
+    /// Weight of a match in the entity name relative to one in its source.
+    pub name_boost: f32,
+    /// Memory the index writer buffers before flushing a segment.
+    pub writer_heap_bytes: usize,
+}
+
+impl Default for Bm25Params {
+    fn default() -> Self {
+        Self {
+            name_boost: NAME_FIELD_BOOST,
+            writer_heap_bytes: WRITER_HEAP_BYTES,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Bm25Error {
+    #[error("tantivy error: {0}")]
+    Tantivy(#[from] tantivy::TantivyError),
+    #[error("failed to open BM25 index directory: {0}")]
+    Directory(#[from] tantivy::directory::error::OpenDirectoryError),
+    #[error("filesystem error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// One searchable unit of source code.
+#[derive(Debug, Clone)]
+pub struct Bm25Document {
+    pub entity_id: String,
+    pub name: String,
+    pub content: String,
+}
+
+/// Collects BM25 documents for every class/function in the graph.
+///
+/// Source files are read once each; entities whose file can no longer be read
+/// are indexed by name only.
+pub fn documents_from_graph(graph: &DependencyGraph) -> Vec<Bm25Document> {
+    let mut sources: HashMap<PathBuf, Option<String>> = HashMap::new();
+    let mut documents = Vec::new();
+    for idx in graph.graph().node_indices() {
+        let Some(node) = graph.node(idx) else {
+            continue;
+        };
+        if !matches!(node.kind, NodeKind::Class | NodeKind::Function) || node.is_external() {
+            continue;
+        }
+        let content = match (&node.file_path, node.range) {
+            (Some(path), Some(range)) => sources
+                .entry(path.clone())
+                .or_insert_with(|| fs::read_to_string(path).ok())
+                .as_deref()
+                .map(|source| range.extract(source))
+                .unwrap_or_default(),
+            _ => String::new(),
+        };
+        documents.push(Bm25Document {
+            entity_id: node.id.clone(),
+            name: node.display_name.clone(),
+            content,
+        });
+    }
+    documents
+}
+
+pub struct BM25Index {
+    index: Index,
+    reader: IndexReader,
+    id_field: Field,
+    name_field: Field,
+    content_field: Field,
+}
+
+impl std::fmt::Debug for BM25Index {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BM25Index")
+            .field("documents", &self.num_docs())
+            .finish()
+    }
+}
+
+impl BM25Index {
+    fn schema() -> Schema {
+        let mut builder = Schema::builder();
+        builder.add_text_field("id", STRING | STORED);
+        builder.add_text_field("name", TEXT);
+        builder.add_text_field("content", TEXT);
+        builder.build()
+    }
+
+    /// Builds a volatile index held entirely in memory.
+    pub fn build_in_ram(documents: &[Bm25Document]) -> Result<Self, Bm25Error> {
+        let index = Index::create_in_ram(Self::schema());
+        Self::populate(index, documents, &Bm25Params::default())
+    }
+
+    /// Builds a persistent index in `dir`, replacing any index already there.
+    pub fn build_in_dir(
+        dir: &Path,
+        documents: &[Bm25Document],
+        params: &Bm25Params,
+    ) -> Result<Self, Bm25Error> {
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+        fs::create_dir_all(dir)?;
+        let index = Index::create_in_dir(dir, Self::schema())?;
+        Self::populate(index, documents, params)
+    }
+
+    /// Opens an index previously written by [`BM25Index::build_in_dir`].
+    pub fn open(dir: &Path) -> Result<Self, Bm25Error> {
+        let index = Index::open_in_dir(dir)?;
+        Self::from_index(index)
+    }
+
+    fn populate(
+        index: Index,
+        documents: &[Bm25Document],
+        params: &Bm25Params,
+    ) -> Result<Self, Bm25Error> {
+        let this = Self::from_index(index)?;
+        let mut writer: tantivy::IndexWriter = this.index.writer(params.writer_heap_bytes)?;
+        for document in documents {
+            writer.add_document(doc!(
+                this.id_field => document.entity_id.as_str(),
+                this.name_field => document.name.as_str(),
+                this.content_field => document.content.as_str(),
+            ))?;
+        }
+        writer.commit()?;
+        this.reader.reload()?;
+        Ok(this)
+    }
+
+    fn from_index(index: Index) -> Result<Self, Bm25Error> {
+        let schema = index.schema();
+        let id_field = schema.get_field("id")?;
+        let name_field = schema.get_field("name")?;
+        let content_field = schema.get_field("content")?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        Ok(Self {
+            index,
+            reader,
+            id_field,
+            name_field,
+            content_field,
+        })
+    }
+
+    /// Number of indexed documents.
+    pub fn num_docs(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    /// Returns up to `limit` `(entity_id, score)` pairs ordered by BM25 score,
+    /// with name matches weighted by `name_boost`.
+    ///
+    /// Query syntax errors are tolerated: the parser keeps whatever terms it
+    /// could understand.
+    pub fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        name_boost: f32,
+    ) -> Result<Vec<(String, f32)>, Bm25Error> {
+        if limit == 0 || query.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut parser =
+            QueryParser::for_index(&self.index, vec![self.name_field, self.content_field]);
+        parser.set_field_boost(self.name_field, name_boost);
+        let (query, _errors) = parser.parse_query_lenient(query);
+
+        let searcher = self.reader.searcher();
+        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        let mut hits = Vec::with_capacity(top_docs.len());
+        for (score, address) in top_docs {
+            let document: TantivyDocument = searcher.doc(address)?;
+            if let Some(id) = document.get_first(self.id_field).and_then(|v| v.as_str()) {
+                hits.push((id.to_string(), score));
+            }
+        }
+        Ok(hits)
+    }
+}
@@ -7,6 +7,5 @@
 pub mod bm25;
 pub mod name_index;
 
-// Placeholder types
-pub struct NameIndex;
-pub struct BM25Index;
+pub use bm25::{documents_from_graph, BM25Index, Bm25Document, Bm25Error, Bm25Params};
+pub use name_index::NameIndex;
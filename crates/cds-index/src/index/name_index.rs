@@ -1,4 +1,92 @@
 //! Name/ID HashMap index with prefix matching
+//!
+//! Upper tier of the hierarchical index (PRD-02 FR-HI-1). Entity names are
+//! stored case-insensitively in a sorted map so exact and prefix lookups are
+//! both cheap.
 
-// TODO: Implement NameIndex
-// Reference: PRD-02 FR-HI-1
+use crate::graph::{DependencyGraph, GraphNodeIndex, NodeKind};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default, Clone)]
+pub struct NameIndex {
+    entries: BTreeMap<String, Vec<GraphNodeIndex>>,
+}
+
+impl NameIndex {
+    /// Indexes every file, class and function node by its display name.
+    ///
+    /// Files are additionally indexed by their stem so `utils` finds `utils.py`.
+    /// External nodes are left to the index of the repository they belong to.
+    pub fn from_graph(graph: &DependencyGraph) -> Self {
+        let mut index = Self::default();
+        for idx in graph.graph().node_indices() {
+            let Some(node) = graph.node(idx) else {
+                continue;
+            };
+            if node.is_external() {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Directory => {}
+                NodeKind::File => {
+                    index.insert(&node.display_name, idx);
+                    if let Some((stem, _)) = node.display_name.rsplit_once('.') {
+                        index.insert(stem, idx);
+                    }
+                }
+                NodeKind::Class | NodeKind::Function => index.insert(&node.display_name, idx),
+            }
+        }
+        index
+    }
+
+    fn insert(&mut self, name: &str, idx: GraphNodeIndex) {
+        if name.is_empty() {
+            return;
+        }
+        let entry = self.entries.entry(name.to_lowercase()).or_default();
+        if !entry.contains(&idx) {
+            entry.push(idx);
+        }
+    }
+
+    /// Returns nodes whose name matches `name` exactly (case-insensitive).
+    pub fn exact(&self, name: &str) -> &[GraphNodeIndex] {
+        self.entries
+            .get(&name.to_lowercase())
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// Returns nodes whose name starts with `prefix` (case-insensitive), excluding exact matches.
+    pub fn prefix(&self, prefix: &str, limit: usize) -> Vec<GraphNodeIndex> {
+        let needle = prefix.to_lowercase();
+        let mut results = Vec::new();
+        for (name, indices) in self.entries.range(needle.clone()..) {
+            if !name.starts_with(&needle) {
+                break;
+            }
+            if *name == needle {
+                continue;
+            }
+            for idx in indices {
+                if results.len() >= limit {
+                    return results;
+                }
+                if !results.contains(idx) {
+                    results.push(*idx);
+                }
+            }
+        }
+        results
+    }
+
+    /// Number of distinct names in the index.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -27,4 +27,4 @@ pub mod index;
 pub mod persistence;
 pub mod service;
 
-pub use config::IndexServiceConfig;
+pub use config::{IndexServiceConfig, ServiceTransport};
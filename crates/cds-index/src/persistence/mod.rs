@@ -1,6 +1,279 @@
 //! Index serialization and persistence
+//!
+//! On-disk layout of a persisted index:
+//! - `<graph_dir>/graph.json` - nodes and edges (edges reference node ids)
+//! - `<graph_dir>/manifest.json` - [`IndexManifest`]
+//! - `<graph_dir>/fingerprints.json` - content hash per file/class/function,
+//!   used to report what changed between generations (optional)
+//! - `<bm25_dir>/` - tantivy index (tantivy handles its own persistence)
+//!
+//! Writers stage everything next to the target directories and swap them in
+//! with renames so readers never observe a half-written index.
+//! Reference: PRD-02 FR-GS-1
 
-// TODO: Implement serialization for:
-// - Graph structure (JSON or bincode)
-// - BM25 index (tantivy handles its own persistence)
-// Reference: PRD-02 FR-GS-1
+use crate::graph::{top_level_packages, DependencyGraph, EdgeKind, GraphBuildStats, GraphNode};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+pub const GRAPH_FILE: &str = "graph.json";
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const FINGERPRINTS_FILE: &str = "fingerprints.json";
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum PersistenceError {
+    #[error("filesystem error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serialization error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("no persisted index at {0}")]
+    Missing(PathBuf),
+    #[error("unsupported index format version {found} (expected {FORMAT_VERSION})")]
+    Version { found: u32 },
+    #[error("edge references unknown node id {0}")]
+    DanglingEdge(String),
+}
+
+/// Where the graph and BM25 parts of one index live.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexLayout {
+    pub graph_dir: PathBuf,
+    pub bm25_dir: PathBuf,
+}
+
+impl IndexLayout {
+    pub fn new(graph_dir: impl Into<PathBuf>, bm25_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            graph_dir: graph_dir.into(),
+            bm25_dir: bm25_dir.into(),
+        }
+    }
+
+    /// Layout rooted at a single directory (`<root>/graph`, `<root>/bm25`), e.g. `repo/.cds-index`.
+    pub fn under(root: &Path) -> Self {
+        Self::new(root.join("graph"), root.join("bm25"))
+    }
+
+    /// Sibling layout used while a new index is being written.
+    pub fn staging(&self, tag: &str) -> Self {
+        Self::new(
+            sibling_with_suffix(&self.graph_dir, tag),
+            sibling_with_suffix(&self.bm25_dir, tag),
+        )
+    }
+
+    pub fn manifest_path(&self) -> PathBuf {
+        self.graph_dir.join(MANIFEST_FILE)
+    }
+
+    /// Whether a manifest exists, i.e. the layout looks like a persisted index.
+    pub fn exists(&self) -> bool {
+        self.manifest_path().is_file()
+    }
+
+    /// Replaces this layout's directories with the ones in `staged`.
+    ///
+    /// Open readers of the old index keep working on unix because unlinked
+    /// files stay alive until their last handle is dropped.
+    pub fn promote(&self, staged: &IndexLayout) -> Result<(), PersistenceError> {
+        replace_dir(&staged.bm25_dir, &self.bm25_dir)?;
+        replace_dir(&staged.graph_dir, &self.graph_dir)?;
+        Ok(())
+    }
+
+    /// Removes the directories of a (staging) layout, ignoring missing ones.
+    pub fn remove(&self) -> Result<(), PersistenceError> {
+        for dir in [&self.graph_dir, &self.bm25_dir] {
+            if dir.exists() {
+                fs::remove_dir_all(dir)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Metadata describing a persisted index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexManifest {
+    pub format_version: u32,
+    pub repo_root: PathBuf,
+    pub generation: u64,
+    pub built_at: DateTime<Utc>,
+    pub node_count: usize,
+    pub edge_count: usize,
+    /// Top-level packages the repository provides, used for cross-repository linking.
+    #[serde(default)]
+    pub packages: Vec<String>,
+    /// What the build could not resolve; empty for indexes persisted before it was recorded.
+    #[serde(default)]
+    pub diagnostics: BuildDiagnostics,
+}
+
+/// Imports and files the graph builder had to give up on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildDiagnostics {
+    pub unresolved_imports: usize,
+    /// Repository-relative paths of files the Python AST parser rejected.
+    pub parse_failures: Vec<PathBuf>,
+}
+
+impl From<&GraphBuildStats> for BuildDiagnostics {
+    fn from(stats: &GraphBuildStats) -> Self {
+        Self {
+            unresolved_imports: stats.unresolved_imports,
+            parse_failures: stats.parse_failures.clone(),
+        }
+    }
+}
+
+impl IndexManifest {
+    pub fn new(repo_root: PathBuf, generation: u64, graph: &DependencyGraph) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            repo_root,
+            generation,
+            built_at: Utc::now(),
+            node_count: graph.node_count(),
+            edge_count: graph.edge_count(),
+            packages: top_level_packages(graph),
+            diagnostics: BuildDiagnostics::default(),
+        }
+    }
+
+    pub fn with_diagnostics(mut self, diagnostics: BuildDiagnostics) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedGraph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<PersistedEdge>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedEdge {
+    source: String,
+    target: String,
+    kind: EdgeKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alias: Option<String>,
+}
+
+/// Writes `graph.json` and `manifest.json` into `dir` (created if needed).
+pub fn save_graph(
+    graph: &DependencyGraph,
+    manifest: &IndexManifest,
+    dir: &Path,
+) -> Result<(), PersistenceError> {
+    fs::create_dir_all(dir)?;
+    let storage = graph.graph();
+    let nodes = storage
+        .node_indices()
+        .filter_map(|idx| storage.node_weight(idx).cloned())
+        .collect();
+    let edges = storage
+        .edge_indices()
+        .filter_map(|idx| {
+            let (source, target) = storage.edge_endpoints(idx)?;
+            let weight = storage.edge_weight(idx)?;
+            Some(PersistedEdge {
+                source: storage.node_weight(source)?.id.clone(),
+                target: storage.node_weight(target)?.id.clone(),
+                kind: weight.kind,
+                alias: weight.alias.clone(),
+            })
+        })
+        .collect();
+
+    let writer = BufWriter::new(fs::File::create(dir.join(GRAPH_FILE))?);
+    serde_json::to_writer(writer, &PersistedGraph { nodes, edges })?;
+    let writer = BufWriter::new(fs::File::create(dir.join(MANIFEST_FILE))?);
+    serde_json::to_writer_pretty(writer, manifest)?;
+    Ok(())
+}
+
+/// Entity id -> content hash of its source text.
+pub type Fingerprints = BTreeMap<String, u64>;
+
+/// Writes `fingerprints.json` into `dir` (created if needed).
+pub fn save_fingerprints(fingerprints: &Fingerprints, dir: &Path) -> Result<(), PersistenceError> {
+    fs::create_dir_all(dir)?;
+    let writer = BufWriter::new(fs::File::create(dir.join(FINGERPRINTS_FILE))?);
+    serde_json::to_writer(writer, fingerprints)?;
+    Ok(())
+}
+
+/// Reads the fingerprints stored next to a graph; empty for indices written
+/// before fingerprints were recorded.
+pub fn load_fingerprints(dir: &Path) -> Result<Fingerprints, PersistenceError> {
+    let path = dir.join(FINGERPRINTS_FILE);
+    if !path.is_file() {
+        return Ok(Fingerprints::new());
+    }
+    Ok(serde_json::from_reader(BufReader::new(fs::File::open(
+        path,
+    )?))?)
+}
+
+/// Reads only the manifest of a persisted graph.
+pub fn load_manifest(dir: &Path) -> Result<IndexManifest, PersistenceError> {
+    let path = dir.join(MANIFEST_FILE);
+    if !path.is_file() {
+        return Err(PersistenceError::Missing(dir.to_path_buf()));
+    }
+    let manifest: IndexManifest = serde_json::from_reader(BufReader::new(fs::File::open(path)?))?;
+    if manifest.format_version != FORMAT_VERSION {
+        return Err(PersistenceError::Version {
+            found: manifest.format_version,
+        });
+    }
+    Ok(manifest)
+}
+
+/// Loads a graph written by [`save_graph`].
+pub fn load_graph(dir: &Path) -> Result<(DependencyGraph, IndexManifest), PersistenceError> {
+    let manifest = load_manifest(dir)?;
+    let persisted: PersistedGraph =
+        serde_json::from_reader(BufReader::new(fs::File::open(dir.join(GRAPH_FILE))?))?;
+
+    let mut graph = DependencyGraph::new();
+    for node in persisted.nodes {
+        graph.add_node(node);
+    }
+    for edge in persisted.edges {
+        let source = graph
+            .get_index(&edge.source)
+            .ok_or_else(|| PersistenceError::DanglingEdge(edge.source.clone()))?;
+        let target = graph
+            .get_index(&edge.target)
+            .ok_or_else(|| PersistenceError::DanglingEdge(edge.target.clone()))?;
+        graph.add_edge_with_alias(source, target, edge.kind, edge.alias);
+    }
+    Ok((graph, manifest))
+}
+
+fn sibling_with_suffix(dir: &Path, tag: &str) -> PathBuf {
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "index".to_string());
+    dir.with_file_name(format!(".{name}.{tag}"))
+}
+
+fn replace_dir(staged: &Path, target: &Path) -> Result<(), PersistenceError> {
+    if target.exists() {
+        fs::remove_dir_all(target)?;
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(staged, target)?;
+    Ok(())
+}
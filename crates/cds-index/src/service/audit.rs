@@ -0,0 +1,513 @@
+//! Opt-in JSONL audit log of RPC calls, and replay against another index
+//!
+//! With `INDEX_AUDIT_LOG` set, every call that reaches the handlers - from
+//! any transport - is appended to that file as one JSON object per line:
+//! when it ran, which client (and agent session) sent it, the method and
+//! params, the index generation that answered it, the entity ids and scores
+//! it returned and how long it took. Before a record would push the file past
+//! `INDEX_AUDIT_LOG_MAX_BYTES` it is rotated to `<file>.1`, shifting older
+//! files up to `<file>.<INDEX_AUDIT_LOG_MAX_FILES>`; the oldest is dropped.
+//!
+//! Clients name their session with the `X-CDS-Session` header (or gRPC
+//! metadata), else records take the call's `session_id` param. Audit
+//! failures are logged and never fail the request.
+//!
+//! [`replay`] re-runs a query record against whatever index the state now
+//! serves (typically another generation) and reports what changed; the
+//! `cds-audit-replay` binary applies it to whole logs.
+
+use super::cursor::PageCursor;
+use super::handlers::handle_request_from;
+use super::jsonrpc::{JsonRpcError, JsonRpcRequest};
+use super::repos::DEFAULT_REPO;
+use super::state::ServiceState;
+use crate::config::IndexServiceConfig;
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+/// Header (and gRPC metadata key) naming the caller's session.
+pub const SESSION_HEADER: &str = "x-cds-session";
+
+/// Methods [`replay`] re-runs; the others change service state.
+pub const REPLAYED_METHODS: &[&str] = &["search_entities", "traverse_graph", "retrieve_entity"];
+
+/// Scores closer than this are reported as unchanged.
+const SCORE_TOLERANCE: f64 = 1e-6;
+
+/// Who sent a request, as recorded in the audit log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caller {
+    /// `token:<name>` for authenticated callers, else the peer address or
+    /// the transport (`local`, `stdio`, `mcp`).
+    pub client: String,
+    pub session: Option<String>,
+}
+
+impl Caller {
+    pub fn new(client: impl Into<String>) -> Self {
+        Self {
+            client: client.into(),
+            session: None,
+        }
+    }
+
+    pub fn with_session(mut self, session: Option<String>) -> Self {
+        self.session = session.filter(|session| !session.is_empty());
+        self
+    }
+}
+
+/// The session named by the [`SESSION_HEADER`] header, if any.
+pub fn session_header(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|session| session.trim().to_string())
+        .filter(|session| !session.is_empty())
+}
+
+/// One entity returned by a call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditHit {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+/// One line of the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    pub client: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+    /// Repository the call addressed.
+    pub repo: String,
+    /// Generation the repository served when the call finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<u64>,
+    pub latency_ms: f64,
+    /// Entities returned, in result order: search hits with their scores,
+    /// traversal nodes or retrieved entities.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub results: Vec<AuditHit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl AuditRecord {
+    /// The record for `method` called by `caller`, which returned `outcome`
+    /// (see [`result_hits`]) after `latency`.
+    pub fn new(
+        state: &ServiceState,
+        caller: &Caller,
+        method: &str,
+        params: Value,
+        outcome: Result<Vec<AuditHit>, JsonRpcError>,
+        latency: Duration,
+    ) -> Self {
+        let (repo, generation) = served_generation(state, &params);
+        let (results, error) = match outcome {
+            Ok(results) => (results, None),
+            Err(error) => (Vec::new(), Some(error)),
+        };
+        let session = caller.session.clone().or_else(|| {
+            params
+                .get("session_id")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        });
+        Self {
+            timestamp: Utc::now(),
+            client: caller.client.clone(),
+            session,
+            method: method.to_string(),
+            params,
+            repo,
+            generation,
+            latency_ms: latency.as_secs_f64() * 1000.0,
+            results,
+            error,
+        }
+    }
+}
+
+/// Repository named by `params` and the generation it currently serves.
+fn served_generation(state: &ServiceState, params: &Value) -> (String, Option<u64>) {
+    let repo = params.get("repo").and_then(Value::as_str);
+    let generation = state
+        .repos()
+        .get(repo)
+        .ok()
+        .and_then(|handle| handle.current())
+        .map(|index| index.generation);
+    (repo.unwrap_or(DEFAULT_REPO).to_string(), generation)
+}
+
+/// Entity ids (and search scores) in a method's result.
+pub fn result_hits(method: &str, result: &Value) -> Vec<AuditHit> {
+    let items = match method {
+        "search_entities" | "retrieve_entity" => result.get("entities"),
+        "traverse_graph" => result.pointer("/subgraph/nodes"),
+        _ => None,
+    };
+    items
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            Some(AuditHit {
+                id: item.get("id")?.as_str()?.to_string(),
+                score: item.get("score").and_then(Value::as_f64),
+            })
+        })
+        .collect()
+}
+
+/// Appends [`AuditRecord`]s to the configured file, rotating it by size.
+#[derive(Debug)]
+pub struct AuditLog {
+    /// `None` when auditing is disabled.
+    sink: Option<Mutex<Sink>>,
+}
+
+#[derive(Debug)]
+struct Sink {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    /// Opened lazily, and dropped after a write error so the next record retries.
+    file: Option<File>,
+    size: u64,
+}
+
+impl AuditLog {
+    pub fn new(config: &IndexServiceConfig) -> Self {
+        let sink = config.audit_log.as_ref().map(|path| {
+            Mutex::new(Sink {
+                path: path.clone(),
+                max_bytes: config.audit_log_max_bytes,
+                max_files: config.audit_log_max_files,
+                file: None,
+                size: 0,
+            })
+        });
+        Self { sink }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    pub fn append(&self, record: &AuditRecord) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+        let mut sink = sink.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Err(err) = sink.write(record) {
+            warn!("Failed to write audit log {}: {err}", sink.path.display());
+            sink.file = None;
+        }
+    }
+}
+
+impl Sink {
+    fn write(&mut self, record: &AuditRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record).map_err(io::Error::other)?;
+        line.push(b'\n');
+        if self.file.is_none() {
+            self.open()?;
+        }
+        let len = line.len() as u64;
+        if self.max_bytes > 0 && self.size > 0 && self.size + len > self.max_bytes {
+            self.rotate()?;
+            self.open()?;
+        }
+        let file = self.file.as_mut().expect("audit log was just opened");
+        file.write_all(&line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        if let Some(parent) = self
+            .path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = file.metadata()?.len();
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// Shifts `<path>.N-1` to `<path>.N` (dropping the oldest) and the live
+    /// file to `<path>.1`; with no rotated files kept, the log is discarded.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path);
+        }
+        for n in (1..self.max_files).rev() {
+            let from = rotated_path(&self.path, n);
+            if from.exists() {
+                fs::rename(from, rotated_path(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))
+    }
+}
+
+/// `<path>.<n>`, the `n`th most recent rotated log.
+pub fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Reads every record of an audit log file.
+pub fn read_log(path: &Path) -> io::Result<Vec<AuditRecord>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {err}", path.display(), number + 1),
+            )
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// A result position that moved between the recorded and replayed call.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RankChange {
+    pub id: String,
+    pub from: usize,
+    pub to: usize,
+}
+
+/// A search score that changed between the recorded and replayed call.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScoreChange {
+    pub id: String,
+    pub from: f64,
+    pub to: f64,
+}
+
+/// Differences between two result lists.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ResultChanges {
+    /// Returned now but not when recorded.
+    pub added: Vec<String>,
+    /// Returned when recorded but not now.
+    pub removed: Vec<String>,
+    pub moved: Vec<RankChange>,
+    pub rescored: Vec<ScoreChange>,
+}
+
+/// Error codes of the recorded and replayed call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ErrorChange {
+    pub recorded: Option<i32>,
+    pub replayed: Option<i32>,
+}
+
+/// How a replayed call differs from the recorded one.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReplayDiff {
+    pub timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    pub method: String,
+    pub params: Value,
+    pub recorded_generation: Option<u64>,
+    pub replayed_generation: Option<u64>,
+    #[serde(flatten)]
+    pub changes: ResultChanges,
+    /// Set when the call now fails differently (or no longer fails).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorChange>,
+}
+
+impl ReplayDiff {
+    pub fn is_unchanged(&self) -> bool {
+        self.changes == ResultChanges::default() && self.error.is_none()
+    }
+}
+
+/// Re-runs `record` against the index `state` serves now; `None` for
+/// records of methods that are not replayed (see [`REPLAYED_METHODS`]).
+///
+/// Paged calls are replayed from the first page, following `next_cursor`
+/// up to the end of the recorded page, and compared on that page only,
+/// since their cursor belongs to the recorded generation.
+pub async fn replay(state: &Arc<ServiceState>, record: &AuditRecord) -> Option<ReplayDiff> {
+    if !REPLAYED_METHODS.contains(&record.method.as_str()) {
+        return None;
+    }
+    let replayed = match (&record.error, recorded_page(&record.method, &record.params)) {
+        (None, Some(page)) => replay_pages(state, &record.method, page).await,
+        _ => run(state, &record.method, record.params.clone())
+            .await
+            .map(|result| result_hits(&record.method, &result)),
+    };
+    let (_, generation) = served_generation(state, &record.params);
+    let (replayed, replayed_code) = match replayed {
+        Ok(hits) => (hits, None),
+        Err(error) => (Vec::new(), Some(error.code)),
+    };
+    let recorded_code = record.error.as_ref().map(|error| error.code);
+    Some(ReplayDiff {
+        timestamp: record.timestamp,
+        session: record.session.clone(),
+        method: record.method.clone(),
+        params: record.params.clone(),
+        recorded_generation: record.generation,
+        replayed_generation: generation,
+        changes: compare(&record.results, &replayed),
+        error: (recorded_code != replayed_code).then_some(ErrorChange {
+            recorded: recorded_code,
+            replayed: replayed_code,
+        }),
+    })
+}
+
+async fn run(
+    state: &Arc<ServiceState>,
+    method: &str,
+    params: Value,
+) -> Result<Value, JsonRpcError> {
+    let request = JsonRpcRequest::new(method, params, json!(0));
+    let response = handle_request_from(state, request, &Caller::new("replay")).await;
+    match response.error {
+        Some(error) => Err(error),
+        None => Ok(response.result.unwrap_or_default()),
+    }
+}
+
+/// A page the recorded call asked for with a cursor.
+struct RecordedPage {
+    /// The recorded params without their cursor.
+    params: Value,
+    offset: usize,
+    limit: usize,
+    /// Largest page the method accepts.
+    max_page: usize,
+}
+
+fn recorded_page(method: &str, params: &Value) -> Option<RecordedPage> {
+    let offset = PageCursor::decode(params.get("cursor")?.as_str()?)
+        .ok()?
+        .offset;
+    let mut params = params.as_object()?.clone();
+    params.remove("cursor");
+    let limit = params.get("limit").and_then(Value::as_u64);
+    let (limit, max_page) = match (method, limit) {
+        ("search_entities", limit) => (
+            limit.map_or_else(super::handlers::default_search_limit, |limit| {
+                limit as usize
+            }),
+            super::handlers::MAX_SEARCH_LIMIT,
+        ),
+        ("traverse_graph", Some(limit)) => (limit as usize, super::handlers::MAX_TRAVERSAL_PAGE),
+        _ => return None,
+    };
+    Some(RecordedPage {
+        params: Value::Object(params),
+        offset,
+        limit,
+        max_page,
+    })
+}
+
+/// Pages from the first result to the end of `page` and returns the hits
+/// on `page`. Pages are as large as the method allows, which leaves the
+/// results unchanged since a cursor does not depend on the page size.
+async fn replay_pages(
+    state: &Arc<ServiceState>,
+    method: &str,
+    page: RecordedPage,
+) -> Result<Vec<AuditHit>, JsonRpcError> {
+    let end = page.offset.saturating_add(page.limit);
+    let mut params = page.params;
+    params["limit"] = json!(end.min(page.max_page));
+    let mut hits = Vec::new();
+    loop {
+        let result = run(state, method, params.clone()).await?;
+        hits.extend(result_hits(method, &result));
+        match result.get("next_cursor") {
+            Some(cursor) if hits.len() < end => params["cursor"] = cursor.clone(),
+            _ => break,
+        }
+    }
+    hits.truncate(end);
+    Ok(hits.split_off(page.offset.min(hits.len())))
+}
+
+fn compare(recorded: &[AuditHit], replayed: &[AuditHit]) -> ResultChanges {
+    let before: HashMap<&str, (usize, Option<f64>)> = recorded
+        .iter()
+        .enumerate()
+        .map(|(rank, hit)| (hit.id.as_str(), (rank, hit.score)))
+        .collect();
+    let after: HashMap<&str, usize> = replayed
+        .iter()
+        .enumerate()
+        .map(|(rank, hit)| (hit.id.as_str(), rank))
+        .collect();
+    let mut changes = ResultChanges {
+        removed: recorded
+            .iter()
+            .filter(|hit| !after.contains_key(hit.id.as_str()))
+            .map(|hit| hit.id.clone())
+            .collect(),
+        ..ResultChanges::default()
+    };
+    for (rank, hit) in replayed.iter().enumerate() {
+        let Some(&(from, score)) = before.get(hit.id.as_str()) else {
+            changes.added.push(hit.id.clone());
+            continue;
+        };
+        if from != rank {
+            changes.moved.push(RankChange {
+                id: hit.id.clone(),
+                from,
+                to: rank,
+            });
+        }
+        if let (Some(from), Some(to)) = (score, hit.score) {
+            if (from - to).abs() > SCORE_TOLERANCE {
+                changes.rescored.push(ScoreChange {
+                    id: hit.id.clone(),
+                    from,
+                    to,
+                });
+            }
+        }
+    }
+    changes
+}
@@ -0,0 +1,263 @@
+//! Bearer-token authentication and roles for the HTTP transports
+//!
+//! Authentication is off until at least one token is configured
+//! (`INDEX_SERVICE_TOKENS` / `INDEX_SERVICE_TOKENS_FILE`). Once enabled, every
+//! `POST /rpc` and `GET /events` request must carry
+//! `Authorization: Bearer <token>` (or `X-API-Key: <token>`); `GET /health`
+//! stays open for probes. The stdio and MCP transports are not authenticated:
+//! whoever spawned the process already controls it.
+//!
+//! Each token has a [`Role`]: `read` tokens may only call the query methods
+//! in [`READ_ONLY_METHODS`], `admin` tokens may call everything.
+
+use super::jsonrpc::JsonRpcError;
+use anyhow::{Context, Result};
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{RwLock, RwLockReadGuard};
+
+/// Methods a `read` token may call; anything else requires `admin`.
+pub const READ_ONLY_METHODS: &[&str] = &[
+    "search_entities",
+    "traverse_graph",
+    "retrieve_entity",
+    "session_summary",
+    "get_index_stats",
+    "find_paths",
+    "diff_impact",
+    "get_job_status",
+    "list_repos",
+    "health_check",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Queries only (see [`READ_ONLY_METHODS`]).
+    #[serde(alias = "readonly")]
+    Read,
+    /// Queries plus rebuilds, job cancellation and repository management.
+    Admin,
+}
+
+impl Role {
+    pub fn permits(self, method: &str) -> bool {
+        match self {
+            Role::Admin => true,
+            Role::Read => READ_ONLY_METHODS.contains(&method),
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "read" | "readonly" => Ok(Role::Read),
+            "admin" => Ok(Role::Admin),
+            other => anyhow::bail!("unknown role '{other}' (expected read or admin)"),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Read => "read",
+            Role::Admin => "admin",
+        })
+    }
+}
+
+/// A configured token. The secret is never serialized or debug-printed.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub name: String,
+    pub role: Role,
+    #[serde(skip_serializing)]
+    pub token: String,
+}
+
+impl fmt::Debug for ApiToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiToken")
+            .field("name", &self.name)
+            .field("role", &self.role)
+            .field("token", &"<redacted>")
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenFile {
+    #[serde(default)]
+    tokens: Vec<ApiToken>,
+}
+
+/// Reads a TOML token file:
+///
+/// ```toml
+/// [[tokens]]
+/// name = "review-agent"
+/// role = "read"
+/// token = "..."
+/// ```
+pub fn load_token_file(path: &Path) -> Result<Vec<ApiToken>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read token file {}", path.display()))?;
+    let file: TokenFile =
+        toml::from_str(&text).with_context(|| format!("Invalid token file {}", path.display()))?;
+    Ok(file.tokens)
+}
+
+/// Parses `INDEX_SERVICE_TOKENS`: comma-separated `role:token` entries,
+/// named `env-1`, `env-2`, ... in order.
+pub fn parse_token_list(value: &str) -> Result<Vec<ApiToken>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .enumerate()
+        .map(|(position, entry)| {
+            let (role, token) = entry
+                .split_once(':')
+                .with_context(|| format!("token entry {} must be role:token", position + 1))?;
+            Ok(ApiToken {
+                name: format!("env-{}", position + 1),
+                role: role.parse()?,
+                token: token.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Checks that tokens are non-empty and unique.
+pub fn validate_tokens(tokens: &[ApiToken]) -> Result<()> {
+    for (position, token) in tokens.iter().enumerate() {
+        if token.token.trim().is_empty() {
+            anyhow::bail!("token '{}' has an empty secret", token.name);
+        }
+        if tokens[..position]
+            .iter()
+            .any(|earlier| earlier.token == token.token)
+        {
+            anyhow::bail!("token '{}' duplicates another token's secret", token.name);
+        }
+    }
+    Ok(())
+}
+
+/// The authenticated caller of a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+}
+
+/// Resolves request credentials against the configured tokens.
+///
+/// The tokens can be replaced while the service runs (on `SIGHUP`, see
+/// [`super::lifecycle`]); requests already authenticated are unaffected.
+#[derive(Debug, Default)]
+pub struct Authenticator {
+    tokens: RwLock<Vec<ApiToken>>,
+}
+
+impl Authenticator {
+    pub fn new(tokens: Vec<ApiToken>) -> Self {
+        Self {
+            tokens: RwLock::new(tokens),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens().is_empty()
+    }
+
+    /// Number of configured tokens.
+    pub fn token_count(&self) -> usize {
+        self.tokens().len()
+    }
+
+    /// Replaces the accepted tokens; an empty list disables authentication.
+    pub fn replace(&self, tokens: Vec<ApiToken>) {
+        *self.tokens.write().unwrap_or_else(|p| p.into_inner()) = tokens;
+    }
+
+    /// Identifies the caller; `Ok(None)` when authentication is disabled.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<Option<Principal>, JsonRpcError> {
+        let tokens = self.tokens();
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+        let presented =
+            presented_token(headers).ok_or_else(|| JsonRpcError::unauthorized("missing token"))?;
+        // Compare against every token so timing does not reveal which one matched.
+        let mut matched = None;
+        for token in tokens.iter() {
+            if constant_time_eq(token.token.as_bytes(), presented.as_bytes()) {
+                matched = Some(token);
+            }
+        }
+        matched
+            .map(|token| {
+                Some(Principal {
+                    name: token.name.clone(),
+                    role: token.role,
+                })
+            })
+            .ok_or_else(|| JsonRpcError::unauthorized("invalid token"))
+    }
+
+    /// Authenticates the caller and checks that its role may call `method`.
+    pub fn authorize(
+        &self,
+        headers: &HeaderMap,
+        method: &str,
+    ) -> Result<Option<Principal>, JsonRpcError> {
+        let principal = self.authenticate(headers)?;
+        if let Some(principal) = &principal {
+            if !principal.role.permits(method) {
+                return Err(JsonRpcError::forbidden(method, principal.role));
+            }
+        }
+        Ok(principal)
+    }
+
+    fn tokens(&self) -> RwLockReadGuard<'_, Vec<ApiToken>> {
+        self.tokens.read().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+/// `Authorization: Bearer <token>`, else `X-API-Key: <token>`.
+fn presented_token(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            let (scheme, token) = value.trim().split_once(' ')?;
+            scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+        });
+    bearer
+        .or_else(|| {
+            headers
+                .get("x-api-key")
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+        })
+        .filter(|token| !token.is_empty())
+}
+
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    if left.len() != right.len() {
+        return false;
+    }
+    left.iter()
+        .zip(right)
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
@@ -0,0 +1,108 @@
+//! LRU cache for `search_entities` and `traverse_graph` results
+//!
+//! Entries are keyed by repository, index generation, method and the
+//! normalized params, so a generation swap can never serve a stale result.
+//! Entries of older generations are dropped eagerly when a rebuild swaps a
+//! repository's index or the repository is added or removed.
+
+use lru::LruCache;
+use serde::Serialize;
+use serde_json::Value;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub repo: String,
+    pub generation: u64,
+    pub method: &'static str,
+    /// Params serialized after normalization (see the handlers).
+    pub params: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub capacity: usize,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug)]
+pub struct QueryCache {
+    /// `None` when caching is disabled (capacity 0).
+    entries: Option<Mutex<LruCache<CacheKey, Arc<Value>>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl QueryCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: NonZeroUsize::new(capacity)
+                .map(|capacity| Mutex::new(LruCache::new(capacity))),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.entries.is_some()
+    }
+
+    /// Looks up `key`, marking it most recently used and counting the hit or miss.
+    pub fn get(&self, key: &CacheKey) -> Option<Arc<Value>> {
+        let entries = self.entries.as_ref()?;
+        let found = lock(entries).get(key).cloned();
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    pub fn insert(&self, key: CacheKey, value: Value) {
+        if let Some(entries) = &self.entries {
+            lock(entries).put(key, Arc::new(value));
+        }
+    }
+
+    /// Drops every entry of `repo`, returning how many were removed.
+    pub fn invalidate_repo(&self, repo: &str) -> usize {
+        let Some(entries) = &self.entries else {
+            return 0;
+        };
+        let mut entries = lock(entries);
+        let stale: Vec<CacheKey> = entries
+            .iter()
+            .filter(|(key, _)| key.repo == repo)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &stale {
+            entries.pop(key);
+        }
+        stale.len()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let (capacity, entries) = self.entries.as_ref().map_or((0, 0), |entries| {
+            let entries = lock(entries);
+            (entries.cap().get(), entries.len())
+        });
+        CacheStats {
+            capacity,
+            entries,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,148 @@
+//! Per-file change sets between two index generations
+//!
+//! Every build records a content hash per file, class and function
+//! ([`Fingerprints`]). Comparing the fingerprints of the previous and the new
+//! generation yields the files that were added, removed or changed, and which
+//! entity ids inside them appeared, disappeared or changed - the payload of
+//! `files_updated` notifications.
+
+use crate::graph::{DependencyGraph, NodeKind};
+use crate::persistence::Fingerprints;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// How one file and the entities defined in it changed between two generations.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub file: String,
+    pub status: FileStatus,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities_added: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities_removed: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities_changed: Vec<String>,
+}
+
+/// Hashes the source of every file, class and function of the repository.
+///
+/// External nodes and files that can no longer be read are skipped.
+pub fn fingerprint_graph(graph: &DependencyGraph) -> Fingerprints {
+    let mut sources: HashMap<PathBuf, Option<String>> = HashMap::new();
+    let mut fingerprints = Fingerprints::new();
+    for idx in graph.graph().node_indices() {
+        let Some(node) = graph.node(idx) else {
+            continue;
+        };
+        if node.kind == NodeKind::Directory || node.is_external() {
+            continue;
+        }
+        let Some(path) = &node.file_path else {
+            continue;
+        };
+        let source = sources
+            .entry(path.clone())
+            .or_insert_with(|| fs::read_to_string(path).ok());
+        let Some(source) = source.as_deref() else {
+            continue;
+        };
+        let hash = match (node.kind, node.range) {
+            (NodeKind::File, _) => fnv1a(source.as_bytes()),
+            (_, Some(range)) => fnv1a(range.extract(source).as_bytes()),
+            (_, None) => continue,
+        };
+        fingerprints.insert(node.id.clone(), hash);
+    }
+    fingerprints
+}
+
+/// Files whose fingerprints differ between `old` and `new`, sorted by path.
+pub fn diff_fingerprints(old: &Fingerprints, new: &Fingerprints) -> Vec<FileChange> {
+    let old_files = group_by_file(old);
+    let new_files = group_by_file(new);
+    let files: BTreeSet<&str> = old_files.keys().chain(new_files.keys()).copied().collect();
+
+    let mut changes = Vec::new();
+    for file in files {
+        let change = match (old_files.get(file), new_files.get(file)) {
+            (None, Some(added)) => FileChange {
+                entities_added: added.entities.keys().map(|id| id.to_string()).collect(),
+                ..FileChange::new(file, FileStatus::Added)
+            },
+            (Some(removed), None) => FileChange {
+                entities_removed: removed.entities.keys().map(|id| id.to_string()).collect(),
+                ..FileChange::new(file, FileStatus::Removed)
+            },
+            (Some(before), Some(after)) if before != after => {
+                let mut change = FileChange::new(file, FileStatus::Changed);
+                for (id, hash) in &after.entities {
+                    match before.entities.get(id) {
+                        None => change.entities_added.push(id.to_string()),
+                        Some(previous) if previous != hash => {
+                            change.entities_changed.push(id.to_string())
+                        }
+                        Some(_) => {}
+                    }
+                }
+                change.entities_removed = before
+                    .entities
+                    .keys()
+                    .filter(|id| !after.entities.contains_key(*id))
+                    .map(|id| id.to_string())
+                    .collect();
+                change
+            }
+            _ => continue,
+        };
+        changes.push(change);
+    }
+    changes
+}
+
+impl FileChange {
+    fn new(file: &str, status: FileStatus) -> Self {
+        Self {
+            file: file.to_string(),
+            status,
+            entities_added: Vec::new(),
+            entities_removed: Vec::new(),
+            entities_changed: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct FileFingerprints<'a> {
+    file: Option<u64>,
+    entities: BTreeMap<&'a str, u64>,
+}
+
+fn group_by_file(fingerprints: &Fingerprints) -> BTreeMap<&str, FileFingerprints<'_>> {
+    let mut files: BTreeMap<&str, FileFingerprints<'_>> = BTreeMap::new();
+    for (id, &hash) in fingerprints {
+        match id.split_once("::") {
+            Some((file, _)) => {
+                files.entry(file).or_default().entities.insert(id, hash);
+            }
+            None => files.entry(id).or_default().file = Some(hash),
+        }
+    }
+    files
+}
+
+/// 64-bit FNV-1a; stable across builds and platforms, unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,110 @@
+//! Opaque pagination cursors for `search_entities` and `traverse_graph`
+//!
+//! A cursor records the index generation it was issued for, the offset of
+//! the next page and a fingerprint of the query it continues. Pages are
+//! slices of one deterministic result list, so a cursor stays valid for as
+//! long as the generation does; after a rebuild swaps the index, or when it
+//! is replayed with a different query, it is rejected with `-32602` and the
+//! client restarts from the first page.
+
+use super::jsonrpc::JsonRpcError;
+use serde::Serialize;
+
+const CURSOR_VERSION: u8 = 1;
+const CURSOR_BYTES: usize = 1 + 3 * 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageCursor {
+    pub generation: u64,
+    /// Position of the first result of the next page.
+    pub offset: usize,
+    /// [`fingerprint`] of the query the cursor continues.
+    pub fingerprint: u64,
+}
+
+impl PageCursor {
+    pub fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(CURSOR_BYTES);
+        bytes.push(CURSOR_VERSION);
+        bytes.extend_from_slice(&self.generation.to_be_bytes());
+        bytes.extend_from_slice(&(self.offset as u64).to_be_bytes());
+        bytes.extend_from_slice(&self.fingerprint.to_be_bytes());
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, JsonRpcError> {
+        let malformed = || JsonRpcError::invalid_cursor("cursor is malformed");
+        if cursor.len() != CURSOR_BYTES * 2 || !cursor.is_ascii() {
+            return Err(malformed());
+        }
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|at| u8::from_str_radix(&cursor[at..at + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| malformed())?;
+        if bytes[0] != CURSOR_VERSION {
+            return Err(malformed());
+        }
+        let word = |at: usize| {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(&bytes[at..at + 8]);
+            u64::from_be_bytes(buf)
+        };
+        Ok(Self {
+            generation: word(1),
+            offset: usize::try_from(word(9)).map_err(|_| malformed())?,
+            fingerprint: word(17),
+        })
+    }
+
+    /// Offset to resume from: 0 without a cursor, else the decoded offset
+    /// once the cursor is checked against the current generation and query.
+    pub fn resume(
+        cursor: Option<&str>,
+        generation: u64,
+        fingerprint: u64,
+    ) -> Result<usize, JsonRpcError> {
+        let Some(cursor) = cursor else {
+            return Ok(0);
+        };
+        let cursor = Self::decode(cursor)?;
+        if cursor.generation != generation {
+            return Err(JsonRpcError::invalid_cursor(format!(
+                "cursor was issued for index generation {}, but the index is at generation {generation}",
+                cursor.generation
+            )));
+        }
+        if cursor.fingerprint != fingerprint {
+            return Err(JsonRpcError::invalid_cursor(
+                "cursor belongs to a different query",
+            ));
+        }
+        Ok(cursor.offset)
+    }
+
+    /// The cursor for the page starting at `offset`, or `None` past the end.
+    pub fn next(generation: u64, fingerprint: u64, offset: usize, more: bool) -> Option<String> {
+        more.then(|| {
+            Self {
+                generation,
+                offset,
+                fingerprint,
+            }
+            .encode()
+        })
+    }
+}
+
+/// Stable 64-bit FNV-1a hash of `method` and its normalized, page-independent
+/// params; `repo` is the resolved repository name.
+pub fn fingerprint(repo: &str, method: &str, params: &impl Serialize) -> u64 {
+    let params = serde_json::to_vec(params).unwrap_or_default();
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for chunk in [repo.as_bytes(), &[0], method.as_bytes(), &[0], &params] {
+        for &byte in chunk {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}
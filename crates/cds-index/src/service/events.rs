@@ -0,0 +1,108 @@
+//! Index change notifications
+//!
+//! Rebuild jobs publish [`IndexEvent`]s on the service's [`EventBus`]:
+//! `job_progress` while a job runs, `generation_swapped` once a new index is
+//! served, and `files_updated` with the files and entity ids that changed
+//! compared to the previous generation. `GET /events` streams them to
+//! clients as server-sent events; in-process users call
+//! [`EventBus::subscribe`] directly.
+//!
+//! Delivery is best effort: a subscriber that falls more than
+//! [`EVENT_BUFFER`] events behind is told how many it missed (`lagged`) and
+//! should re-query the index.
+
+use super::changes::FileChange;
+use super::jobs::JobSnapshot;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before it starts lagging.
+pub const EVENT_BUFFER: usize = 256;
+
+/// Most files reported in a single `files_updated` event; larger change sets
+/// are split over several events.
+pub const MAX_FILES_PER_EVENT: usize = 100;
+
+/// A new generation replaced the index served for `repo`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationSwapped {
+    pub repo: String,
+    pub generation: u64,
+    pub built_at: DateTime<Utc>,
+    pub node_count: usize,
+    pub edge_count: usize,
+    /// Files reported by the `files_updated` events that follow; absent when
+    /// there was no previous generation to compare against.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files_changed: Option<usize>,
+}
+
+/// Files of `repo` that differ between `generation` and the one before it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilesUpdated {
+    pub repo: String,
+    pub generation: u64,
+    pub files: Vec<FileChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IndexEvent {
+    JobProgress(JobSnapshot),
+    GenerationSwapped(GenerationSwapped),
+    FilesUpdated(FilesUpdated),
+}
+
+impl IndexEvent {
+    /// Event name, also used as the SSE `event:` field.
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndexEvent::JobProgress(_) => "job_progress",
+            IndexEvent::GenerationSwapped(_) => "generation_swapped",
+            IndexEvent::FilesUpdated(_) => "files_updated",
+        }
+    }
+
+    /// Repository the event is about.
+    pub fn repo(&self) -> &str {
+        match self {
+            IndexEvent::JobProgress(job) => &job.repo,
+            IndexEvent::GenerationSwapped(swap) => &swap.repo,
+            IndexEvent::FilesUpdated(update) => &update.repo,
+        }
+    }
+}
+
+/// Fan-out of [`IndexEvent`]s to any number of subscribers.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<IndexEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(EVENT_BUFFER)
+    }
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Receives every event published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<IndexEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Publishes `event`; a no-op when nobody is subscribed.
+    pub fn publish(&self, event: IndexEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
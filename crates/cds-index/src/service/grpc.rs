@@ -0,0 +1,634 @@
+//! gRPC transport (`cds.index.v1.IndexService`, see docs/api/cds_index.proto)
+//!
+//! Unary RPCs are translated into JSON-RPC params and run through the same
+//! dispatch as `/rpc`, so validation, caching and results are identical.
+//! `TraverseStream` walks the graph itself (see [`bfs_visit`]) so nodes reach
+//! the client as BFS discovers them.
+//!
+//! Bearer tokens (`authorization` metadata), roles and rate limits apply as
+//! on the HTTP transports. Errors map to the closest gRPC status; the
+//! JSON-RPC code travels in the `cds-error-code` metadata entry and its data
+//! as JSON in `cds-error-data-bin`.
+
+use super::audit::{session_header, AuditHit, AuditRecord, Caller};
+use super::handlers::{
+    call, default_depth, default_search_limit, subgraph_edge, subgraph_node, EntityDetails,
+    EntityResult, RebuildIndexParams, RetrieveEntityParams, RetrieveEntityResult,
+    SearchEntitiesParams, SearchEntitiesResult, SessionInfo, SessionOptions, SnippetMode,
+    SubgraphEdge, SubgraphNode, TraversalMetadata, TraversalPlan, TraverseFormat,
+    TraverseGraphParams, TraverseGraphResult,
+};
+use super::jobs::{JobSnapshot, JobStatus};
+use super::jsonrpc::{self, JsonRpcError};
+use super::limits::Admission;
+use super::state::{LoadedIndex, RebuildStats};
+use super::ServiceState;
+use crate::graph::{bfs_visit, BfsEvent, EdgeKind, NodeKind, TraversalDirection};
+use proto::index_service_server::{IndexService, IndexServiceServer};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::metadata::{BinaryMetadataValue, MetadataValue};
+use tonic::{Code, Request, Response, Status};
+use tracing::info;
+
+/// Messages, server and client generated from docs/api/cds_index.proto.
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("cds.index.v1");
+}
+
+/// Traversal events buffered ahead of a slow client.
+const STREAM_BUFFER: usize = 256;
+
+/// The gRPC service over the shared [`ServiceState`].
+#[derive(Debug, Clone)]
+pub struct GrpcService {
+    state: Arc<ServiceState>,
+}
+
+impl GrpcService {
+    pub fn new(state: Arc<ServiceState>) -> Self {
+        Self { state }
+    }
+
+    pub fn into_server(self) -> IndexServiceServer<Self> {
+        IndexServiceServer::new(self)
+    }
+
+    /// Authenticates and authorizes the caller for `method`, then admits the
+    /// request through the rate limits; the admission is held until the
+    /// response (or stream) is finished.
+    fn admit<T>(&self, request: &Request<T>, method: &str) -> Result<(Admission, Caller), Status> {
+        let headers = request.metadata().clone().into_headers();
+        let principal = self
+            .state
+            .auth()
+            .authorize(&headers, method)
+            .map_err(status)?;
+        let client = match (principal, request.remote_addr()) {
+            (Some(principal), _) => format!("token:{}", principal.name),
+            (None, Some(addr)) => addr.ip().to_string(),
+            (None, None) => "local".to_string(),
+        };
+        let admission = self.state.limits().admit(&client).map_err(status)?;
+        Ok((
+            admission,
+            Caller::new(client).with_session(session_header(&headers)),
+        ))
+    }
+}
+
+/// Binds `addr` and serves gRPC until the process exits.
+pub async fn serve_grpc(state: Arc<ServiceState>, addr: SocketAddr) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(
+        "CDS-Index gRPC service listening on {}",
+        listener.local_addr()?
+    );
+    serve_grpc_listener(state, listener).await
+}
+
+/// Serves gRPC on an already bound listener until the service shuts down.
+pub async fn serve_grpc_listener(
+    state: Arc<ServiceState>,
+    listener: TcpListener,
+) -> anyhow::Result<()> {
+    let shutdown = state.shutdown_signal();
+    tonic::transport::Server::builder()
+        .add_service(GrpcService::new(state).into_server())
+        .serve_with_incoming_shutdown(TcpListenerStream::new(listener), shutdown)
+        .await?;
+    Ok(())
+}
+
+#[tonic::async_trait]
+impl IndexService for GrpcService {
+    async fn search(
+        &self,
+        request: Request<proto::SearchRequest>,
+    ) -> Result<Response<proto::SearchResponse>, Status> {
+        let (_admission, caller) = self.admit(&request, "search_entities")?;
+        let request = request.into_inner();
+        let params = SearchEntitiesParams {
+            repo: non_empty(request.repo.clone()),
+            entity_types: entity_types(&request.entity_types)?,
+            limit: match request.limit {
+                0 => default_search_limit(),
+                limit => limit as usize,
+            },
+            use_bm25: request.use_bm25.unwrap_or(true),
+            snippet_mode: match request.snippet_mode() {
+                proto::SnippetMode::Preview => SnippetMode::Preview,
+                proto::SnippetMode::Fold => SnippetMode::Fold,
+                proto::SnippetMode::Full => SnippetMode::Full,
+            },
+            cursor: non_empty(request.cursor),
+            session: session_options(request.session),
+            query: request.query,
+        };
+        let result: SearchEntitiesResult = call(&self.state, &caller, "search_entities", &params)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::SearchResponse {
+            entities: result.entities.into_iter().map(Into::into).collect(),
+            total_count: result.total_count as u64,
+            query_metadata: Some(proto::QueryMetadata {
+                used_upper_index: result.query_metadata.used_upper_index,
+                used_bm25: result.query_metadata.used_bm25,
+                execution_time_ms: result.query_metadata.execution_time_ms,
+                cache_hit: result.query_metadata.cache_hit,
+            }),
+            next_cursor: result.next_cursor.unwrap_or_default(),
+            session: result.session.map(Into::into),
+        }))
+    }
+
+    async fn traverse(
+        &self,
+        request: Request<proto::TraverseRequest>,
+    ) -> Result<Response<proto::TraverseResponse>, Status> {
+        let (_admission, caller) = self.admit(&request, "traverse_graph")?;
+        let params = traverse_params(request.into_inner())?;
+        let result: TraverseGraphResult = call(&self.state, &caller, "traverse_graph", &params)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::TraverseResponse {
+            nodes: result.subgraph.nodes.into_iter().map(Into::into).collect(),
+            edges: result.subgraph.edges.into_iter().map(Into::into).collect(),
+            metadata: Some(result.metadata.into()),
+            next_cursor: result.next_cursor.unwrap_or_default(),
+            session: result.session.map(Into::into),
+        }))
+    }
+
+    type TraverseStreamStream = ReceiverStream<Result<proto::TraverseEvent, Status>>;
+
+    async fn traverse_stream(
+        &self,
+        request: Request<proto::TraverseRequest>,
+    ) -> Result<Response<Self::TraverseStreamStream>, Status> {
+        let (admission, caller) = self.admit(&request, "traverse_graph")?;
+        let started = Instant::now();
+        let params = TraverseGraphParams {
+            limit: None,
+            cursor: None,
+            session: SessionOptions::default(),
+            ..traverse_params(request.into_inner())?
+        };
+        let prepared = match self.state.require_index(params.repo.as_deref()).await {
+            Ok(index) => TraversalPlan::new(&index, &params).map(|plan| (index, plan)),
+            Err(error) => Err(error),
+        };
+        let (index, plan) = match prepared {
+            Ok(prepared) => prepared,
+            Err(error) => {
+                audit_stream(&self.state, &caller, &params, Err(error.clone()), started);
+                return Err(status(error));
+            }
+        };
+        let state = Arc::clone(&self.state);
+        let (events, receiver) = mpsc::channel(STREAM_BUFFER);
+        tokio::task::spawn_blocking(move || {
+            let _admission = admission;
+            let nodes = stream_traversal(&index, &plan, &events);
+            audit_stream(&state, &caller, &params, Ok(nodes), started);
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn retrieve(
+        &self,
+        request: Request<proto::RetrieveRequest>,
+    ) -> Result<Response<proto::RetrieveResponse>, Status> {
+        let (_admission, caller) = self.admit(&request, "retrieve_entity")?;
+        let request = request.into_inner();
+        let params = RetrieveEntityParams {
+            repo: non_empty(request.repo),
+            entity_ids: request.entity_ids,
+            include_context: request.include_context as usize,
+            include_metadata: request.include_metadata,
+            snippet_mode: None,
+            session: session_options(request.session),
+        };
+        let result: RetrieveEntityResult = call(&self.state, &caller, "retrieve_entity", &params)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::RetrieveResponse {
+            entities: result.entities.into_iter().map(Into::into).collect(),
+            session: result.session.map(Into::into),
+        }))
+    }
+
+    async fn rebuild(
+        &self,
+        request: Request<proto::RebuildRequest>,
+    ) -> Result<Response<proto::Job>, Status> {
+        let (_admission, caller) = self.admit(&request, "rebuild_index")?;
+        let request = request.into_inner();
+        let params = RebuildIndexParams {
+            repo: non_empty(request.repo),
+            repo_path: PathBuf::from(request.repo_path),
+            languages: (!request.languages.is_empty()).then_some(request.languages),
+            incremental: request.incremental,
+            output_path: non_empty(request.output_path).map(PathBuf::from),
+            link_repos: request.link_repos,
+        };
+        let job: JobSnapshot = call(&self.state, &caller, "rebuild_index", &params)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(job.into()))
+    }
+
+    async fn get_job(
+        &self,
+        request: Request<proto::GetJobRequest>,
+    ) -> Result<Response<proto::Job>, Status> {
+        let (_admission, caller) = self.admit(&request, "get_job_status")?;
+        let params = super::handlers::JobParams {
+            job_id: request.into_inner().job_id,
+        };
+        let job: JobSnapshot = call(&self.state, &caller, "get_job_status", &params)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(job.into()))
+    }
+}
+
+/// Streamed traversals bypass dispatch, so they are recorded here.
+fn audit_stream(
+    state: &ServiceState,
+    caller: &Caller,
+    params: &TraverseGraphParams,
+    outcome: Result<Vec<AuditHit>, JsonRpcError>,
+    started: Instant,
+) {
+    if state.audit().is_enabled() {
+        let params = serde_json::to_value(params).unwrap_or_default();
+        let record = AuditRecord::new(
+            state,
+            caller,
+            "traverse_graph",
+            params,
+            outcome,
+            started.elapsed(),
+        );
+        state.audit().append(&record);
+    }
+}
+
+/// Sends each kept node as BFS reaches it and each edge once both endpoints
+/// were sent, then the summary; stops early when the client goes away.
+/// Returns the nodes sent.
+fn stream_traversal(
+    index: &LoadedIndex,
+    plan: &TraversalPlan,
+    events: &mpsc::Sender<Result<proto::TraverseEvent, Status>>,
+) -> Vec<AuditHit> {
+    use proto::traverse_event::Event;
+
+    let started = Instant::now();
+    let mut sent = HashSet::new();
+    let mut nodes = Vec::new();
+    let mut summary = TraversalMetadata {
+        total_nodes: 0,
+        total_edges: 0,
+        max_depth_reached: 0,
+        execution_time_ms: 0.0,
+        cache_hit: false,
+    };
+    let send = |event: Event| {
+        let event = proto::TraverseEvent { event: Some(event) };
+        match events.blocking_send(Ok(event)) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(_) => ControlFlow::Break(()),
+        }
+    };
+    bfs_visit(
+        &index.graph,
+        &plan.starts,
+        &plan.filter,
+        |step| match step {
+            BfsEvent::Node(idx, depth) => match index.graph.node(idx) {
+                Some(node) if plan.keeps(idx, node) => {
+                    sent.insert(idx);
+                    nodes.push(AuditHit {
+                        id: node.id.clone(),
+                        score: None,
+                    });
+                    summary.total_nodes += 1;
+                    summary.max_depth_reached = summary.max_depth_reached.max(depth);
+                    send(Event::Node(subgraph_node(index, node, depth).into()))
+                }
+                _ => ControlFlow::Continue(()),
+            },
+            BfsEvent::Edge { edge, tree } => {
+                let wanted = tree || plan.format == TraverseFormat::Graph;
+                let endpoints = index.graph.graph().edge_endpoints(edge);
+                let delivered = endpoints.is_some_and(|(source, target)| {
+                    sent.contains(&source) && sent.contains(&target)
+                });
+                match subgraph_edge(index, edge) {
+                    Some(edge) if wanted && delivered => {
+                        summary.total_edges += 1;
+                        send(Event::Edge(edge.into()))
+                    }
+                    _ => ControlFlow::Continue(()),
+                }
+            }
+        },
+    );
+    summary.execution_time_ms = started.elapsed().as_secs_f64() * 1000.0;
+    let _ = send(Event::Done(summary.into()));
+    nodes
+}
+
+fn traverse_params(request: proto::TraverseRequest) -> Result<TraverseGraphParams, Status> {
+    let relations = request
+        .relations
+        .iter()
+        .map(|&relation| match proto::Relation::try_from(relation) {
+            Ok(proto::Relation::Contain) => Ok(EdgeKind::Contain),
+            Ok(proto::Relation::Import) => Ok(EdgeKind::Import),
+            Ok(proto::Relation::Invoke) => Ok(EdgeKind::Invoke),
+            Ok(proto::Relation::Inherit) => Ok(EdgeKind::Inherit),
+            _ => Err(Status::invalid_argument(format!(
+                "unknown relation {relation}"
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(TraverseGraphParams {
+        repo: non_empty(request.repo.clone()),
+        entity_types: entity_types(&request.entity_types)?,
+        depth: match request.depth {
+            0 => default_depth(),
+            depth => depth as usize,
+        },
+        direction: match request.direction() {
+            proto::Direction::Forward => TraversalDirection::Forward,
+            proto::Direction::Backward => TraversalDirection::Backward,
+            proto::Direction::Bidirectional => TraversalDirection::Bidirectional,
+        },
+        format: match request.format() {
+            proto::TraverseFormat::Graph => TraverseFormat::Graph,
+            proto::TraverseFormat::Tree => TraverseFormat::Tree,
+        },
+        relations: (!relations.is_empty()).then_some(relations),
+        limit: (request.limit > 0).then_some(request.limit as usize),
+        cursor: non_empty(request.cursor),
+        session: session_options(request.session),
+        start_entities: request.start_entities,
+    })
+}
+
+/// An unset message or empty `session_id` disables session tracking.
+fn session_options(session: Option<proto::Session>) -> SessionOptions {
+    match session.filter(|session| !session.session_id.is_empty()) {
+        Some(session) => SessionOptions {
+            session_id: Some(session.session_id),
+            exclude_seen: session.exclude_seen,
+            mark_seen: session.mark_seen,
+        },
+        None => SessionOptions::default(),
+    }
+}
+
+/// An empty repeated field means "no filter".
+fn entity_types(types: &[i32]) -> Result<Option<Vec<NodeKind>>, Status> {
+    if types.is_empty() {
+        return Ok(None);
+    }
+    types
+        .iter()
+        .map(|&kind| match proto::EntityType::try_from(kind) {
+            Ok(proto::EntityType::Directory) => Ok(NodeKind::Directory),
+            Ok(proto::EntityType::File) => Ok(NodeKind::File),
+            Ok(proto::EntityType::Class) => Ok(NodeKind::Class),
+            Ok(proto::EntityType::Function) => Ok(NodeKind::Function),
+            _ => Err(Status::invalid_argument(format!(
+                "unknown entity type {kind}"
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+fn non_empty(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+/// Maps a JSON-RPC error to the closest gRPC status, keeping the original
+/// code and data in metadata.
+fn status(error: JsonRpcError) -> Status {
+    let code = match error.code {
+        jsonrpc::PARSE_ERROR | jsonrpc::INVALID_REQUEST | jsonrpc::INVALID_PARAMS => {
+            Code::InvalidArgument
+        }
+        jsonrpc::METHOD_NOT_FOUND => Code::Unimplemented,
+        jsonrpc::INDEX_NOT_FOUND => Code::FailedPrecondition,
+        jsonrpc::ENTITY_NOT_FOUND | jsonrpc::JOB_NOT_FOUND | jsonrpc::REPO_NOT_FOUND => {
+            Code::NotFound
+        }
+        jsonrpc::QUERY_TIMEOUT => Code::DeadlineExceeded,
+        jsonrpc::JOB_CONFLICT => Code::AlreadyExists,
+        jsonrpc::UNAUTHORIZED => Code::Unauthenticated,
+        jsonrpc::FORBIDDEN => Code::PermissionDenied,
+        jsonrpc::RATE_LIMITED => Code::ResourceExhausted,
+        jsonrpc::SHUTTING_DOWN => Code::Unavailable,
+        _ => Code::Internal,
+    };
+    let detail = error
+        .data
+        .as_ref()
+        .and_then(|data| data.get("detail"))
+        .and_then(|detail| detail.as_str());
+    let message = match detail {
+        Some(detail) => format!("{}: {detail}", error.message),
+        None => error.message.clone(),
+    };
+    let mut status = Status::new(code, message);
+    let metadata = status.metadata_mut();
+    metadata.insert("cds-error-code", MetadataValue::from(error.code));
+    if let Some(data) = &error.data {
+        metadata.insert_bin(
+            "cds-error-data-bin",
+            BinaryMetadataValue::from_bytes(data.to_string().as_bytes()),
+        );
+    }
+    status
+}
+
+// ---------------------------------------------------------------------------
+// Result conversions
+// ---------------------------------------------------------------------------
+
+fn entity_type(kind: NodeKind) -> i32 {
+    match kind {
+        NodeKind::Directory => proto::EntityType::Directory,
+        NodeKind::File => proto::EntityType::File,
+        NodeKind::Class => proto::EntityType::Class,
+        NodeKind::Function => proto::EntityType::Function,
+    }
+    .into()
+}
+
+fn line_range([start, end]: [u32; 2]) -> proto::LineRange {
+    proto::LineRange { start, end }
+}
+
+fn external(external: Option<super::handlers::ExternalEntity>) -> Option<proto::ExternalEntity> {
+    external.map(|external| proto::ExternalEntity {
+        repo: external.repo,
+        entity_id: external.entity_id,
+    })
+}
+
+impl From<SessionInfo> for proto::SessionInfo {
+    fn from(info: SessionInfo) -> Self {
+        Self {
+            session_id: info.session_id,
+            excluded: info.excluded as u64,
+            seen: info.seen as u64,
+        }
+    }
+}
+
+impl From<EntityResult> for proto::Entity {
+    fn from(entity: EntityResult) -> Self {
+        Self {
+            id: entity.id,
+            name: entity.name,
+            entity_type: entity_type(entity.entity_type),
+            file_path: entity.file_path,
+            line_range: Some(line_range(entity.line_range)),
+            score: entity.score,
+            snippet: entity.snippet.map(|snippet| proto::Snippet {
+                fold: snippet.fold,
+                preview: snippet.preview,
+                full: snippet.full,
+            }),
+        }
+    }
+}
+
+impl From<SubgraphNode> for proto::GraphNode {
+    fn from(node: SubgraphNode) -> Self {
+        Self {
+            id: node.id,
+            name: node.name,
+            entity_type: entity_type(node.entity_type),
+            file_path: node.file_path,
+            line_range: node.line_range.map(line_range),
+            depth: node.depth as u32,
+            external: external(node.external),
+        }
+    }
+}
+
+impl From<SubgraphEdge> for proto::GraphEdge {
+    fn from(edge: SubgraphEdge) -> Self {
+        let relation = match edge.relation {
+            EdgeKind::Contain => proto::Relation::Contain,
+            EdgeKind::Import => proto::Relation::Import,
+            EdgeKind::Invoke => proto::Relation::Invoke,
+            EdgeKind::Inherit => proto::Relation::Inherit,
+        };
+        Self {
+            source: edge.source,
+            target: edge.target,
+            relation: relation.into(),
+            alias: edge.alias,
+        }
+    }
+}
+
+impl From<TraversalMetadata> for proto::TraversalMetadata {
+    fn from(metadata: TraversalMetadata) -> Self {
+        Self {
+            total_nodes: metadata.total_nodes as u64,
+            total_edges: metadata.total_edges as u64,
+            max_depth_reached: metadata.max_depth_reached as u32,
+            execution_time_ms: metadata.execution_time_ms,
+            cache_hit: metadata.cache_hit,
+        }
+    }
+}
+
+impl From<EntityDetails> for proto::EntityDetails {
+    fn from(entity: EntityDetails) -> Self {
+        Self {
+            id: entity.id,
+            name: entity.name,
+            entity_type: entity_type(entity.entity_type),
+            file_path: entity.file_path,
+            line_range: Some(line_range(entity.line_range)),
+            code: entity.code,
+            context_before: entity.context_before,
+            context_after: entity.context_after,
+            external: external(entity.external),
+        }
+    }
+}
+
+impl From<RebuildStats> for proto::RebuildStats {
+    fn from(stats: RebuildStats) -> Self {
+        let entities = stats.entities_found;
+        let edges = stats.edges_created;
+        Self {
+            files_indexed: stats.files_indexed as u64,
+            entities_found: Some(proto::EntityCounts {
+                directories: entities.directories as u64,
+                files: entities.files as u64,
+                classes: entities.classes as u64,
+                functions: entities.functions as u64,
+                external: entities.external as u64,
+            }),
+            edges_created: Some(proto::EdgeCounts {
+                contain: edges.contain as u64,
+                import: edges.import as u64,
+                invoke: edges.invoke as u64,
+                inherit: edges.inherit as u64,
+            }),
+            build_time_ms: stats.build_time_ms,
+        }
+    }
+}
+
+impl From<JobSnapshot> for proto::Job {
+    fn from(job: JobSnapshot) -> Self {
+        let status = match job.status {
+            JobStatus::Queued => proto::JobStatus::Queued,
+            JobStatus::Running => proto::JobStatus::Running,
+            JobStatus::Completed => proto::JobStatus::Completed,
+            JobStatus::Failed => proto::JobStatus::Failed,
+            JobStatus::Cancelled => proto::JobStatus::Cancelled,
+        };
+        let phase = job
+            .phase
+            .and_then(|phase| serde_json::to_value(phase).ok())
+            .and_then(|phase| phase.as_str().map(str::to_string))
+            .unwrap_or_default();
+        Self {
+            job_id: job.job_id,
+            status: status.into(),
+            repo: job.repo,
+            repo_path: job.repo_path.display().to_string(),
+            created_at: job.created_at.to_rfc3339(),
+            phase,
+            files_total: job.files_total.map(|total| total as u64),
+            files_processed: job.files_processed as u64,
+            eta_ms: job.eta_ms,
+            elapsed_ms: job.elapsed_ms,
+            cancel_requested: job.cancel_requested,
+            generation: job.generation,
+            stats: job.stats.map(Into::into),
+            error: job.error,
+        }
+    }
+}
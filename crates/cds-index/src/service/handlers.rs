@@ -1,8 +1,1885 @@
 //! JSON-RPC method handlers
+//!
+//! Query handlers (`search_entities`, `traverse_graph`, `retrieve_entity`) are
+//! plain functions over a [`LoadedIndex`] snapshot so every transport - and
+//! in-process callers - produce identical results. [`handle_request`] is the
+//! single dispatch entry point used by the transports, and every call through
+//! it is written to the audit log when one is configured (see [`super::audit`]).
+//!
+//! Every method accepts an optional `repo` naming the repository to query
+//! (see [`super::repos`]); without it the `default` repository is used.
+//! `search_entities` and `traverse_graph` results are cached per index
+//! generation (see [`super::cache`]) and paged with opaque cursors (see
+//! [`super::cursor`]). Query calls with a `session_id` remember what they
+//! returned (see [`super::sessions`]); `session_summary` reports it.
+//! `find_paths` returns the shortest dependency paths between two entities,
+//! `diff_impact` the entities a diff touches and their dependents, and
+//! `get_index_stats` summarizes an index's composition and hotspots.
+//! Reference: PRD-05 §3, docs/api/jsonrpc-schema.json
 
-// TODO: Implement RPC handlers for:
-// - search_entities
-// - traverse_dependencies
-// - retrieve_code
-// - health_check
-// Reference: PRD-05 §3
+use super::audit::{result_hits, AuditRecord, Caller};
+use super::cache::CacheKey;
+use super::cursor::{fingerprint, PageCursor};
+use super::jobs::{spawn_rebuild, JobSnapshot, RebuildRequest};
+use super::jsonrpc::{parse_params, JsonRpcError, JsonRpcRequest, JsonRpcResponse};
+use super::repos::{delete_index_root, RepoInfo, DEFAULT_REPO};
+use super::sessions::SeenLevel;
+use super::state::{EdgeCounts, EntityCounts, LoadedIndex, ServiceState};
+use crate::graph::{
+    analyze, bfs_subgraph, changed_entities, parse_unified_diff, shortest_paths, EdgeKind,
+    EntityMetadata, GraphAnalytics, GraphNode, GraphNodeIndex, NodeKind, PythonParser,
+    TraversalDirection, TraversalFilter,
+};
+use crate::index::Bm25Params;
+use crate::persistence::IndexLayout;
+use petgraph::stable_graph::EdgeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+pub const MAX_SEARCH_LIMIT: usize = 100;
+/// BM25 hits considered when paging through search results.
+pub const MAX_SEARCH_RESULTS: usize = 1000;
+/// Largest `traverse_graph` page, in nodes.
+pub const MAX_TRAVERSAL_PAGE: usize = 1000;
+pub const MAX_TRAVERSAL_DEPTH: usize = 10;
+pub const MAX_CONTEXT_LINES: usize = 50;
+pub const MAX_SESSION_ID_LEN: usize = 256;
+pub const MAX_FRONTIER: usize = 200;
+pub const MAX_STATS_TOP: usize = 100;
+pub const MAX_PATHS: usize = 10;
+const PREVIEW_LINES: usize = 5;
+
+const EXACT_MATCH_SCORE: f64 = 1.0;
+const PREFIX_MATCH_SCORE: f64 = 0.8;
+const BM25_MAX_SCORE: f64 = 0.7;
+
+// ---------------------------------------------------------------------------
+// Params and results
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnippetMode {
+    Fold,
+    #[default]
+    Preview,
+    Full,
+}
+
+impl From<SnippetMode> for SeenLevel {
+    fn from(mode: SnippetMode) -> Self {
+        match mode {
+            SnippetMode::Fold => Self::Fold,
+            SnippetMode::Preview => Self::Preview,
+            SnippetMode::Full => Self::Full,
+        }
+    }
+}
+
+/// Session tracking accepted by the query methods (see [`super::sessions`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionOptions {
+    /// Remember the returned entities under this session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Leave out entities the session has already seen in as much detail.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclude_seen: bool,
+    /// Record the returned entities in the session (default `true`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mark_seen: Option<bool>,
+}
+
+/// What a session-tracked call did, returned as `session` in its result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub session_id: String,
+    /// Entities left out because the session had already seen them.
+    pub excluded: usize,
+    /// Entities of the repository the session has seen so far.
+    pub seen: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchEntitiesParams {
+    #[serde(default)]
+    pub repo: Option<String>,
+    pub query: String,
+    #[serde(default)]
+    pub entity_types: Option<Vec<NodeKind>>,
+    #[serde(default = "default_search_limit")]
+    pub limit: usize,
+    #[serde(default = "default_true")]
+    pub use_bm25: bool,
+    #[serde(default)]
+    pub snippet_mode: SnippetMode,
+    /// `next_cursor` of the previous page.
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(flatten)]
+    pub session: SessionOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    pub fold: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityResult {
+    pub id: String,
+    pub name: String,
+    pub entity_type: NodeKind,
+    pub file_path: String,
+    pub line_range: [u32; 2],
+    pub score: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<Snippet>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryMetadata {
+    pub used_upper_index: bool,
+    pub used_bm25: bool,
+    pub execution_time_ms: f64,
+    /// Whether the result was served from the query cache.
+    #[serde(default)]
+    pub cache_hit: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchEntitiesResult {
+    pub entities: Vec<EntityResult>,
+    pub total_count: usize,
+    pub query_metadata: QueryMetadata,
+    /// Continues with the next page; absent on the last one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraverseFormat {
+    /// Every traversed edge between returned nodes.
+    #[default]
+    Graph,
+    /// Only the edges that discovered each node (a BFS spanning forest).
+    Tree,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraverseGraphParams {
+    #[serde(default)]
+    pub repo: Option<String>,
+    pub start_entities: Vec<String>,
+    #[serde(default = "default_depth")]
+    pub depth: usize,
+    #[serde(default)]
+    pub relations: Option<Vec<EdgeKind>>,
+    #[serde(default)]
+    pub entity_types: Option<Vec<NodeKind>>,
+    #[serde(default)]
+    pub direction: TraversalDirection,
+    #[serde(default)]
+    pub format: TraverseFormat,
+    /// Nodes per page; the whole subgraph when absent.
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// `next_cursor` of the previous page.
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(flatten)]
+    pub session: SessionOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubgraphNode {
+    pub id: String,
+    pub name: String,
+    pub entity_type: NodeKind,
+    pub file_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_range: Option<[u32; 2]>,
+    pub depth: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external: Option<ExternalEntity>,
+}
+
+/// Where an external node (linked from another repository's index) lives.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalEntity {
+    pub repo: String,
+    /// Entity id inside `repo`.
+    pub entity_id: String,
+}
+
+impl ExternalEntity {
+    pub fn of(node: &GraphNode) -> Option<Self> {
+        node.external_ref().map(|(repo, entity_id)| Self {
+            repo: repo.to_string(),
+            entity_id: entity_id.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubgraphEdge {
+    pub source: String,
+    pub target: String,
+    pub relation: EdgeKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Subgraph {
+    pub nodes: Vec<SubgraphNode>,
+    pub edges: Vec<SubgraphEdge>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraversalMetadata {
+    pub total_nodes: usize,
+    pub total_edges: usize,
+    pub max_depth_reached: usize,
+    pub execution_time_ms: f64,
+    /// Whether the result was served from the query cache.
+    #[serde(default)]
+    pub cache_hit: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraverseGraphResult {
+    pub subgraph: Subgraph,
+    pub metadata: TraversalMetadata,
+    /// Continues with the next page; absent on the last one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrieveEntityParams {
+    #[serde(default)]
+    pub repo: Option<String>,
+    pub entity_ids: Vec<String>,
+    #[serde(default)]
+    pub include_context: usize,
+    #[serde(default)]
+    pub include_metadata: bool,
+    /// Shortens `code` to its first line (`fold`) or first lines
+    /// (`preview`); the whole entity is returned when absent.
+    #[serde(default)]
+    pub snippet_mode: Option<SnippetMode>,
+    #[serde(flatten)]
+    pub session: SessionOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityDetails {
+    pub id: String,
+    pub name: String,
+    pub entity_type: NodeKind,
+    pub file_path: String,
+    pub line_range: [u32; 2],
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_before: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_after: Option<String>,
+    /// Signature details of a class or function (`include_metadata`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<EntityMetadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external: Option<ExternalEntity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrieveEntityResult {
+    pub entities: Vec<EntityDetails>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FindPathsParams {
+    #[serde(default)]
+    pub repo: Option<String>,
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub relations: Option<Vec<EdgeKind>>,
+    #[serde(default)]
+    pub direction: TraversalDirection,
+    /// Longest path considered, in hops.
+    #[serde(default = "default_path_depth")]
+    pub max_depth: usize,
+    /// Paths to return, shortest first.
+    #[serde(default = "default_path_count")]
+    pub k: usize,
+}
+
+fn default_path_depth() -> usize {
+    MAX_TRAVERSAL_DEPTH
+}
+
+fn default_path_count() -> usize {
+    1
+}
+
+/// One path between the two entities: its nodes in order (`depth` is the
+/// position on the path) and the edge of each hop, in the edge's own
+/// orientation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityPath {
+    pub length: usize,
+    pub nodes: Vec<SubgraphNode>,
+    pub hops: Vec<SubgraphEdge>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FindPathsResult {
+    /// Shortest first; empty when the entities are not connected.
+    pub paths: Vec<EntityPath>,
+    pub execution_time_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffImpactParams {
+    #[serde(default)]
+    pub repo: Option<String>,
+    /// Unified diff, e.g. the output of `git diff`.
+    pub diff: String,
+    /// Hops walked backward from the changed entities.
+    #[serde(default = "default_impact_depth")]
+    pub depth: usize,
+    /// Relations a dependent may reach a changed entity by; invoke, import
+    /// and inherit when absent.
+    #[serde(default)]
+    pub relations: Option<Vec<EdgeKind>>,
+}
+
+fn default_impact_depth() -> usize {
+    2
+}
+
+/// An entity depending on a changed one, and the edge it was reached by.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpactedEntity {
+    #[serde(flatten)]
+    pub entity: SubgraphNode,
+    pub via: SubgraphEdge,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffImpactResult {
+    /// Entities holding changed lines, in diff order.
+    pub changed: Vec<SubgraphNode>,
+    /// Their dependents, nearest first; `depth` counts the hops.
+    pub impacted: Vec<ImpactedEntity>,
+    /// Changed files the index does not know (not Python, new, or outside
+    /// the indexed root).
+    pub unindexed_files: Vec<String>,
+    pub execution_time_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummaryParams {
+    #[serde(default)]
+    pub repo: Option<String>,
+    pub session_id: String,
+    /// Frontier entries returned, most connected first.
+    #[serde(default = "default_frontier_limit")]
+    pub frontier_limit: usize,
+}
+
+/// An entity the session has been shown.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExploredEntity {
+    pub id: String,
+    /// Most detail it was shown in.
+    pub level: SeenLevel,
+    /// Calls that returned it.
+    pub times_returned: u32,
+    /// Absent when the entity is no longer in the index.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity_type: Option<NodeKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_path: Option<String>,
+}
+
+/// An unexplored entity linked to explored ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrontierEntity {
+    pub id: String,
+    pub name: String,
+    pub entity_type: NodeKind,
+    pub file_path: String,
+    /// Relations linking it to explored entities.
+    pub relations: Vec<EdgeKind>,
+    /// Explored entities it is linked to, in exploration order.
+    pub linked_from: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummaryResult {
+    pub session_id: String,
+    pub repo: String,
+    /// Absent for unknown or expired sessions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_active_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub calls: u64,
+    /// In the order the entities were first returned.
+    pub explored: Vec<ExploredEntity>,
+    pub frontier: Vec<FrontierEntity>,
+    /// Frontier size before `frontier_limit` was applied.
+    pub frontier_total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetIndexStatsParams {
+    #[serde(default)]
+    pub repo: Option<String>,
+    /// Entries kept in each hotspot list.
+    #[serde(default = "default_stats_top")]
+    pub top: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexStatsResult {
+    pub repo: String,
+    pub repo_root: PathBuf,
+    pub generation: u64,
+    pub built_at: chrono::DateTime<chrono::Utc>,
+    pub entities: EntityCounts,
+    pub edges: EdgeCounts,
+    #[serde(flatten)]
+    pub analytics: GraphAnalytics,
+    pub unresolved_imports: usize,
+    pub parse_failures: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebuildIndexParams {
+    #[serde(default)]
+    pub repo: Option<String>,
+    pub repo_path: PathBuf,
+    #[serde(default)]
+    pub languages: Option<Vec<String>>,
+    #[serde(default)]
+    pub incremental: bool,
+    #[serde(default)]
+    pub output_path: Option<PathBuf>,
+    /// Link imports of other registered repositories' packages; defaults to
+    /// `INDEX_CROSS_REPO_LINKS`.
+    #[serde(default)]
+    pub link_repos: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobParams {
+    pub job_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListReposResult {
+    pub repos: Vec<RepoInfo>,
+    pub max_loaded_repos: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddRepoParams {
+    pub name: String,
+    pub repo_path: PathBuf,
+    /// Start a rebuild right away (default `true`).
+    #[serde(default = "default_true")]
+    pub build: bool,
+    /// See [`RebuildIndexParams::link_repos`].
+    #[serde(default)]
+    pub link_repos: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddRepoResult {
+    pub repo: RepoInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job: Option<JobSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoveRepoParams {
+    pub name: String,
+    /// Also delete the repository's index directory.
+    #[serde(default)]
+    pub delete_index: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoveRepoResult {
+    pub repo: RepoInfo,
+    pub index_deleted: bool,
+}
+
+pub(crate) fn default_search_limit() -> usize {
+    10
+}
+
+pub(crate) fn default_depth() -> usize {
+    1
+}
+
+fn default_frontier_limit() -> usize {
+    20
+}
+
+fn default_stats_top() -> usize {
+    10
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// ---------------------------------------------------------------------------
+// Dispatch
+// ---------------------------------------------------------------------------
+
+/// Handles one JSON-RPC request against the shared service state.
+pub async fn handle_request(state: &Arc<ServiceState>, request: JsonRpcRequest) -> JsonRpcResponse {
+    handle_request_from(state, request, &Caller::new("local")).await
+}
+
+/// [`handle_request`] on behalf of `caller`, who is named in the audit log.
+pub async fn handle_request_from(
+    state: &Arc<ServiceState>,
+    request: JsonRpcRequest,
+    caller: &Caller,
+) -> JsonRpcResponse {
+    if request.jsonrpc != super::jsonrpc::JSONRPC_VERSION {
+        return JsonRpcResponse::failure(
+            request.id,
+            JsonRpcError::invalid_request("jsonrpc must be \"2.0\""),
+        );
+    }
+    let id = request.id.clone();
+    match audited(state, caller, &request.method, request.params).await {
+        Ok(result) => JsonRpcResponse::success(id, result),
+        Err(error) => JsonRpcResponse::failure(id, error),
+    }
+}
+
+/// Runs `method` with typed params and result through the same dispatch as
+/// [`handle_request`], for transports that do not speak JSON (see [`super::grpc`]).
+pub async fn call<P: Serialize, R: DeserializeOwned>(
+    state: &Arc<ServiceState>,
+    caller: &Caller,
+    method: &str,
+    params: &P,
+) -> Result<R, JsonRpcError> {
+    let params = serde_json::to_value(params).map_err(JsonRpcError::internal)?;
+    let result = audited(state, caller, method, Some(params)).await?;
+    serde_json::from_value(result).map_err(JsonRpcError::internal)
+}
+
+/// [`dispatch`], appending the call to the audit log when it is enabled.
+async fn audited(
+    state: &Arc<ServiceState>,
+    caller: &Caller,
+    method: &str,
+    params: Option<Value>,
+) -> Result<Value, JsonRpcError> {
+    if !state.audit().is_enabled() {
+        return dispatch(state, method, params).await;
+    }
+    let started = Instant::now();
+    let logged = params.clone().unwrap_or(Value::Null);
+    let result = dispatch(state, method, params).await;
+    let outcome = match &result {
+        Ok(value) => Ok(result_hits(method, value)),
+        Err(error) => Err(error.clone()),
+    };
+    let record = AuditRecord::new(state, caller, method, logged, outcome, started.elapsed());
+    state.audit().append(&record);
+    result
+}
+
+async fn dispatch(
+    state: &Arc<ServiceState>,
+    method: &str,
+    params: Option<Value>,
+) -> Result<Value, JsonRpcError> {
+    match method {
+        "search_entities" => {
+            let params: SearchEntitiesParams = parse_params(params)?;
+            validate_session(&params.session)?;
+            let index = state.require_index(params.repo.as_deref()).await?;
+            let key = search_cache_key(&index, &params)?;
+            let result = cached(state, key, "query_metadata", || {
+                to_result(search_entities(&index, &params, &state.config().bm25)?)
+            })?;
+            let level = SeenLevel::from(params.snippet_mode);
+            Ok(track_session(
+                state,
+                &params.session,
+                params.repo.as_deref(),
+                "/entities",
+                level,
+                result,
+            ))
+        }
+        "traverse_graph" => {
+            let params: TraverseGraphParams = parse_params(params)?;
+            validate_session(&params.session)?;
+            let index = state.require_index(params.repo.as_deref()).await?;
+            let key = traverse_cache_key(&index, &params)?;
+            let result = cached(state, key, "metadata", || {
+                to_result(traverse_graph(&index, &params)?)
+            })?;
+            let level = SeenLevel::Listed;
+            Ok(track_session(
+                state,
+                &params.session,
+                params.repo.as_deref(),
+                "/subgraph/nodes",
+                level,
+                result,
+            ))
+        }
+        "retrieve_entity" => {
+            let params: RetrieveEntityParams = parse_params(params)?;
+            validate_session(&params.session)?;
+            let index = state.require_index(params.repo.as_deref()).await?;
+            let result = to_result(retrieve_entity(&index, &params)?)?;
+            let level = params.snippet_mode.map_or(SeenLevel::Full, SeenLevel::from);
+            Ok(track_session(
+                state,
+                &params.session,
+                params.repo.as_deref(),
+                "/entities",
+                level,
+                result,
+            ))
+        }
+        "find_paths" => {
+            let params: FindPathsParams = parse_params(params)?;
+            let index = state.require_index(params.repo.as_deref()).await?;
+            to_result(find_paths(&index, &params)?)
+        }
+        "diff_impact" => {
+            let params: DiffImpactParams = parse_params(params)?;
+            let index = state.require_index(params.repo.as_deref()).await?;
+            to_result(diff_impact(&index, &params)?)
+        }
+        "session_summary" => {
+            let params: SessionSummaryParams = parse_params(params)?;
+            let index = state.require_index(params.repo.as_deref()).await?;
+            to_result(session_summary(state, &index, &params)?)
+        }
+        "get_index_stats" => {
+            let params: GetIndexStatsParams = parse_params(params)?;
+            let index = state.require_index(params.repo.as_deref()).await?;
+            to_result(get_index_stats(&index, &params)?)
+        }
+        "rebuild_index" => to_result(rebuild_index(state, parse_params(params)?)?),
+        "get_job_status" => {
+            let params: JobParams = parse_params(params)?;
+            let job = state
+                .jobs()
+                .get(&params.job_id)
+                .ok_or_else(|| JsonRpcError::job_not_found(&params.job_id))?;
+            to_result(job.snapshot())
+        }
+        "cancel_job" => {
+            let params: JobParams = parse_params(params)?;
+            let job = state
+                .jobs()
+                .get(&params.job_id)
+                .ok_or_else(|| JsonRpcError::job_not_found(&params.job_id))?;
+            if !job.status().is_finished() {
+                job.request_cancel();
+            }
+            to_result(job.snapshot())
+        }
+        "list_repos" => to_result(list_repos(state)),
+        "add_repo" => to_result(add_repo(state, parse_params(params)?)?),
+        "remove_repo" => to_result(remove_repo(state, parse_params(params)?)?),
+        "health_check" => Ok(health_status(state)),
+        other => Err(JsonRpcError::method_not_found(other)),
+    }
+}
+
+fn validate_session(options: &SessionOptions) -> Result<(), JsonRpcError> {
+    match &options.session_id {
+        Some(id) if id.is_empty() || id.len() > MAX_SESSION_ID_LEN => {
+            Err(JsonRpcError::invalid_params(format!(
+                "session_id must be 1 to {MAX_SESSION_ID_LEN} bytes"
+            )))
+        }
+        None if options.exclude_seen || options.mark_seen.is_some() => Err(
+            JsonRpcError::invalid_params("exclude_seen and mark_seen require a session_id"),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Applies `options` to the entities at `pointer` in a query result, which
+/// show each entity at `level`: drops the ones the session has seen in as
+/// much detail when `exclude_seen` is set, records the rest unless
+/// `mark_seen` is `false`, and adds the `session` summary.
+///
+/// Traversal edges are kept when both endpoints were returned or seen
+/// before, since the session already knows the seen ones.
+fn track_session(
+    state: &ServiceState,
+    options: &SessionOptions,
+    repo: Option<&str>,
+    pointer: &str,
+    level: SeenLevel,
+    mut result: Value,
+) -> Value {
+    let Some(session_id) = &options.session_id else {
+        return result;
+    };
+    let repo = repo.unwrap_or(DEFAULT_REPO);
+    let sessions = state.sessions();
+    let seen = if options.exclude_seen {
+        sessions.seen_at(session_id, repo, level)
+    } else {
+        HashMap::new()
+    };
+    let id_of = |item: &Value| item.get("id").and_then(Value::as_str).map(str::to_string);
+    let mut excluded = 0;
+    let mut returned = Vec::new();
+    if let Some(items) = result.pointer_mut(pointer).and_then(Value::as_array_mut) {
+        let before = items.len();
+        items.retain(|item| id_of(item).is_none_or(|id| !seen.contains_key(&id)));
+        excluded = before - items.len();
+        returned = items.iter().filter_map(id_of).collect();
+    }
+    if excluded > 0 {
+        if let Some(edges) = result
+            .pointer_mut("/subgraph/edges")
+            .and_then(Value::as_array_mut)
+        {
+            let known = |id: Option<&Value>| {
+                id.and_then(Value::as_str)
+                    .is_some_and(|id| seen.contains_key(id) || returned.iter().any(|r| r == id))
+            };
+            edges.retain(|edge| known(edge.get("source")) && known(edge.get("target")));
+        }
+    }
+    let total = if options.mark_seen.unwrap_or(true) {
+        sessions.record(session_id, repo, returned.into_iter().map(|id| (id, level)))
+    } else {
+        sessions
+            .snapshot(session_id, repo)
+            .map_or(0, |snapshot| snapshot.seen.len())
+    };
+    result["session"] = json!(SessionInfo {
+        session_id: session_id.clone(),
+        excluded,
+        seen: total,
+    });
+    result
+}
+
+fn to_result<T: Serialize>(value: T) -> Result<Value, JsonRpcError> {
+    serde_json::to_value(value).map_err(JsonRpcError::internal)
+}
+
+/// Serves `key` from the query cache, or runs `query` and caches its result.
+/// Hits report `cache_hit: true` and their own lookup time in `metadata_field`.
+fn cached(
+    state: &ServiceState,
+    key: CacheKey,
+    metadata_field: &str,
+    query: impl FnOnce() -> Result<Value, JsonRpcError>,
+) -> Result<Value, JsonRpcError> {
+    let started = Instant::now();
+    if let Some(hit) = state.cache().get(&key) {
+        let mut result = (*hit).clone();
+        if let Some(metadata) = result.get_mut(metadata_field) {
+            metadata["cache_hit"] = json!(true);
+            metadata["execution_time_ms"] = json!(elapsed_ms(started));
+        }
+        return Ok(result);
+    }
+    let result = query()?;
+    if state.cache().is_enabled() {
+        state.cache().insert(key, result.clone());
+    }
+    Ok(result)
+}
+
+/// Search params with the repository and session options dropped, the query
+/// trimmed and the type filter sorted, so equivalent requests share cache
+/// entries and cursors.
+fn normalized_search(params: &SearchEntitiesParams) -> SearchEntitiesParams {
+    let mut normalized = params.clone();
+    normalized.repo = None;
+    normalized.session = SessionOptions::default();
+    normalized.query = params.query.trim().to_string();
+    if let Some(types) = &mut normalized.entity_types {
+        types.sort_by_key(|kind| *kind as u8);
+        types.dedup();
+    }
+    normalized
+}
+
+fn normalized_traverse(params: &TraverseGraphParams) -> TraverseGraphParams {
+    let mut normalized = params.clone();
+    normalized.repo = None;
+    normalized.session = SessionOptions::default();
+    if let Some(relations) = &mut normalized.relations {
+        relations.sort_by_key(|kind| *kind as u8);
+        relations.dedup();
+    }
+    if let Some(types) = &mut normalized.entity_types {
+        types.sort_by_key(|kind| *kind as u8);
+        types.dedup();
+    }
+    normalized
+}
+
+fn search_cache_key(
+    index: &LoadedIndex,
+    params: &SearchEntitiesParams,
+) -> Result<CacheKey, JsonRpcError> {
+    cache_key(
+        params.repo.as_deref(),
+        index,
+        "search_entities",
+        &normalized_search(params),
+    )
+}
+
+fn traverse_cache_key(
+    index: &LoadedIndex,
+    params: &TraverseGraphParams,
+) -> Result<CacheKey, JsonRpcError> {
+    cache_key(
+        params.repo.as_deref(),
+        index,
+        "traverse_graph",
+        &normalized_traverse(params),
+    )
+}
+
+fn cache_key(
+    repo: Option<&str>,
+    index: &LoadedIndex,
+    method: &'static str,
+    normalized: &impl Serialize,
+) -> Result<CacheKey, JsonRpcError> {
+    Ok(CacheKey {
+        repo: repo.unwrap_or(DEFAULT_REPO).to_string(),
+        generation: index.generation,
+        method,
+        params: serde_json::to_string(normalized).map_err(JsonRpcError::internal)?,
+    })
+}
+
+/// Summary used by `health_check` and the `/health` endpoint.
+///
+/// `index_loaded`, `generation` and `repo_root` describe the default repository.
+pub fn health_status(state: &ServiceState) -> Value {
+    let index = state.current();
+    json!({
+        "status": "ok",
+        "index_loaded": index.is_some(),
+        "generation": index.as_ref().map(|index| index.generation),
+        "repo_root": index.as_ref().map(|index| index.repo_root.display().to_string()),
+        "repos": state.repos().list().len(),
+        "loaded_repos": state.repos().loaded_count(),
+        "query_cache": state.cache().stats(),
+        "active_jobs": state
+            .jobs()
+            .active()
+            .iter()
+            .map(|job| job.id().to_string())
+            .collect::<Vec<_>>(),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// list_repos / add_repo / remove_repo
+// ---------------------------------------------------------------------------
+
+fn repo_info(state: &ServiceState, repo: &super::repos::RepoHandle) -> RepoInfo {
+    let mut info = repo.info();
+    info.active_job = state
+        .jobs()
+        .active_for(repo.name())
+        .map(|job| job.id().to_string());
+    info
+}
+
+pub fn list_repos(state: &ServiceState) -> ListReposResult {
+    ListReposResult {
+        repos: state
+            .repos()
+            .list()
+            .iter()
+            .map(|repo| repo_info(state, repo))
+            .collect(),
+        max_loaded_repos: state.repos().max_loaded(),
+    }
+}
+
+/// Registers a named repository and, unless `build` is false, starts indexing it.
+pub fn add_repo(
+    state: &Arc<ServiceState>,
+    params: AddRepoParams,
+) -> Result<AddRepoResult, JsonRpcError> {
+    let repo_root = canonical_repo_dir(&params.repo_path)?;
+    let repo = state.repos().add(&params.name, repo_root.clone())?;
+    // A re-added name restarts its generations.
+    state.cache().invalidate_repo(repo.name());
+    let job = if params.build {
+        let layout = repo.layout().clone();
+        Some(spawn_rebuild(
+            state,
+            Arc::clone(&repo),
+            RebuildRequest {
+                repo_root,
+                layout,
+                config: state.config().builder_config(repo.name()),
+                link_repos: params
+                    .link_repos
+                    .unwrap_or(state.config().cross_repo_links_for(repo.name())),
+            },
+        )?)
+    } else {
+        None
+    };
+    Ok(AddRepoResult {
+        repo: repo_info(state, &repo),
+        job,
+    })
+}
+
+/// Unregisters a named repository; fails with `-32005` while it is being rebuilt.
+pub fn remove_repo(
+    state: &ServiceState,
+    params: RemoveRepoParams,
+) -> Result<RemoveRepoResult, JsonRpcError> {
+    if let Some(active) = state.jobs().active_for(&params.name) {
+        return Err(JsonRpcError::job_conflict(active.id()));
+    }
+    let repo = state.repos().remove(&params.name)?;
+    state.cache().invalidate_repo(repo.name());
+    state.sessions().forget_repo(repo.name());
+    let info = repo.info();
+    let index_deleted = if params.delete_index {
+        delete_index_root(&state.repos().index_root(repo.name())).map_err(JsonRpcError::internal)?
+    } else {
+        false
+    };
+    Ok(RemoveRepoResult {
+        repo: info,
+        index_deleted,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// rebuild_index
+// ---------------------------------------------------------------------------
+
+/// Validates the request and starts a background rebuild job.
+pub fn rebuild_index(
+    state: &Arc<ServiceState>,
+    params: RebuildIndexParams,
+) -> Result<JobSnapshot, JsonRpcError> {
+    if let Some(languages) = &params.languages {
+        if let Some(unsupported) = languages.iter().find(|lang| lang.as_str() != "python") {
+            return Err(JsonRpcError::invalid_params(format!(
+                "language '{unsupported}' is not supported yet (only python)"
+            )));
+        }
+    }
+    let repo = state.repos().get(params.repo.as_deref())?;
+    let repo_root = canonical_repo_dir(&params.repo_path)?;
+    let layout = match &params.output_path {
+        Some(output) => IndexLayout::under(output),
+        None => repo.layout().clone(),
+    };
+    state.repos().set_repo_path(&repo, repo_root.clone())?;
+    let request = RebuildRequest {
+        repo_root,
+        layout,
+        config: state.config().builder_config(repo.name()),
+        link_repos: params
+            .link_repos
+            .unwrap_or(state.config().cross_repo_links_for(repo.name())),
+    };
+    spawn_rebuild(state, repo, request)
+}
+
+fn canonical_repo_dir(path: &Path) -> Result<PathBuf, JsonRpcError> {
+    fs::canonicalize(path)
+        .ok()
+        .filter(|path| path.is_dir())
+        .ok_or_else(|| {
+            JsonRpcError::invalid_params(format!(
+                "repo_path '{}' is not a directory",
+                path.display()
+            ))
+        })
+}
+
+// ---------------------------------------------------------------------------
+// search_entities
+// ---------------------------------------------------------------------------
+
+/// Hierarchical search: exact id/name and prefix matches from the upper index,
+/// topped up with BM25 content hits when they do not fill the requested page.
+///
+/// `total_count` counts the matches gathered so far, so it can grow on the
+/// page where BM25 hits are first added. `bm25` weights the content search.
+pub fn search_entities(
+    index: &LoadedIndex,
+    params: &SearchEntitiesParams,
+    bm25: &Bm25Params,
+) -> Result<SearchEntitiesResult, JsonRpcError> {
+    let started = Instant::now();
+    let query = params.query.trim();
+    if query.is_empty() {
+        return Err(JsonRpcError::invalid_params("query must not be empty"));
+    }
+    if params.limit == 0 || params.limit > MAX_SEARCH_LIMIT {
+        return Err(JsonRpcError::invalid_params(format!(
+            "limit must be between 1 and {MAX_SEARCH_LIMIT}"
+        )));
+    }
+    // Pages depend on neither their size nor the snippets they carry.
+    let query_fingerprint = fingerprint(
+        params.repo.as_deref().unwrap_or(DEFAULT_REPO),
+        "search_entities",
+        &SearchEntitiesParams {
+            limit: default_search_limit(),
+            snippet_mode: SnippetMode::default(),
+            cursor: None,
+            ..normalized_search(params)
+        },
+    );
+    let offset = PageCursor::resume(
+        params.cursor.as_deref(),
+        index.generation,
+        query_fingerprint,
+    )?;
+    let end = offset.saturating_add(params.limit);
+
+    let allowed: Option<HashSet<NodeKind>> = params
+        .entity_types
+        .as_ref()
+        .map(|types| types.iter().copied().collect());
+    let mut hits: Vec<(GraphNodeIndex, f64)> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut push = |idx: GraphNodeIndex, score: f64, hits: &mut Vec<(GraphNodeIndex, f64)>| {
+        let kind_allowed = index
+            .graph
+            .node(idx)
+            .map(|node| allowed.as_ref().is_none_or(|set| set.contains(&node.kind)))
+            .unwrap_or(false);
+        if kind_allowed && seen.insert(idx) {
+            hits.push((idx, score));
+        }
+    };
+
+    if let Some(idx) = index.entity(query) {
+        push(idx, EXACT_MATCH_SCORE, &mut hits);
+    }
+    for &idx in index.name_index.exact(query) {
+        push(idx, EXACT_MATCH_SCORE, &mut hits);
+    }
+    for idx in index.name_index.prefix(query, usize::MAX) {
+        push(idx, PREFIX_MATCH_SCORE, &mut hits);
+    }
+
+    let mut used_bm25 = false;
+    if params.use_bm25 && hits.len() < end {
+        used_bm25 = true;
+        let bm25_hits = index
+            .bm25
+            .search(query, MAX_SEARCH_RESULTS, bm25.name_boost)
+            .map_err(JsonRpcError::internal)?;
+        let top = bm25_hits.first().map(|(_, score)| *score).unwrap_or(0.0);
+        for (id, score) in bm25_hits {
+            if let Some(idx) = index.entity(&id) {
+                let normalized = if top > 0.0 {
+                    BM25_MAX_SCORE * f64::from(score / top)
+                } else {
+                    0.0
+                };
+                push(idx, normalized, &mut hits);
+            }
+        }
+    }
+
+    let total_count = hits.len();
+    let more = total_count > end || (params.use_bm25 && !used_bm25);
+    let mut sources = SourceCache::default();
+    let entities = hits
+        .into_iter()
+        .skip(offset)
+        .take(params.limit)
+        .filter_map(|(idx, score)| {
+            let node = index.graph.node(idx)?;
+            Some(entity_result(
+                index,
+                node,
+                score,
+                params.snippet_mode,
+                &mut sources,
+            ))
+        })
+        .collect();
+
+    Ok(SearchEntitiesResult {
+        entities,
+        total_count,
+        query_metadata: QueryMetadata {
+            used_upper_index: true,
+            used_bm25,
+            execution_time_ms: elapsed_ms(started),
+            cache_hit: false,
+        },
+        next_cursor: PageCursor::next(index.generation, query_fingerprint, end, more),
+        session: None,
+    })
+}
+
+fn entity_result(
+    index: &LoadedIndex,
+    node: &GraphNode,
+    score: f64,
+    mode: SnippetMode,
+    sources: &mut SourceCache,
+) -> EntityResult {
+    let code = node_code(node, sources);
+    let line_range = line_range(node, code.as_deref());
+    let snippet = match code {
+        Some(code) => build_snippet(&code, mode),
+        None => Snippet {
+            fold: format!("{}/", relative_file_path(index, node)),
+            preview: None,
+            full: None,
+        },
+    };
+    EntityResult {
+        id: node.id.clone(),
+        name: node.display_name.clone(),
+        entity_type: node.kind,
+        file_path: relative_file_path(index, node),
+        line_range,
+        score: score.clamp(0.0, 1.0),
+        snippet: Some(snippet),
+    }
+}
+
+fn build_snippet(code: &str, mode: SnippetMode) -> Snippet {
+    let fold = fold_line(code).trim_end().to_string();
+    let preview = matches!(mode, SnippetMode::Preview | SnippetMode::Full)
+        .then(|| preview_lines(code).join("\n"));
+    let full = (mode == SnippetMode::Full).then(|| code.to_string());
+    Snippet {
+        fold,
+        preview,
+        full,
+    }
+}
+
+/// The first non-blank line of `code`.
+fn fold_line(code: &str) -> &str {
+    code.lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("")
+}
+
+fn preview_lines(code: &str) -> Vec<&str> {
+    code.lines().take(PREVIEW_LINES).collect()
+}
+
+// ---------------------------------------------------------------------------
+// traverse_graph
+// ---------------------------------------------------------------------------
+
+/// A validated `traverse_graph` request: resolved start nodes, the BFS
+/// filter and the node-type filter. Shared with the streaming gRPC traversal.
+pub(crate) struct TraversalPlan {
+    pub starts: Vec<GraphNodeIndex>,
+    pub filter: TraversalFilter,
+    pub format: TraverseFormat,
+    start_set: HashSet<GraphNodeIndex>,
+    allowed: Option<HashSet<NodeKind>>,
+}
+
+impl TraversalPlan {
+    pub fn new(index: &LoadedIndex, params: &TraverseGraphParams) -> Result<Self, JsonRpcError> {
+        if params.start_entities.is_empty() {
+            return Err(JsonRpcError::invalid_params(
+                "start_entities must contain at least one id",
+            ));
+        }
+        if params.depth == 0 || params.depth > MAX_TRAVERSAL_DEPTH {
+            return Err(JsonRpcError::invalid_params(format!(
+                "depth must be between 1 and {MAX_TRAVERSAL_DEPTH}"
+            )));
+        }
+        let starts = params
+            .start_entities
+            .iter()
+            .map(|id| {
+                index
+                    .entity(id)
+                    .ok_or_else(|| JsonRpcError::entity_not_found(id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            filter: TraversalFilter {
+                max_depth: params.depth,
+                relations: params.relations.clone().unwrap_or_default(),
+                direction: params.direction,
+            },
+            format: params.format,
+            start_set: starts.iter().copied().collect(),
+            starts,
+            allowed: params
+                .entity_types
+                .as_ref()
+                .map(|types| types.iter().copied().collect()),
+        })
+    }
+
+    /// Whether a visited node is returned: start nodes always are, others
+    /// when they pass the `entity_types` filter.
+    pub fn keeps(&self, idx: GraphNodeIndex, node: &GraphNode) -> bool {
+        self.start_set.contains(&idx)
+            || self
+                .allowed
+                .as_ref()
+                .is_none_or(|set| set.contains(&node.kind))
+    }
+}
+
+pub(crate) fn subgraph_node(index: &LoadedIndex, node: &GraphNode, depth: usize) -> SubgraphNode {
+    SubgraphNode {
+        id: node.id.clone(),
+        name: node.display_name.clone(),
+        entity_type: node.kind,
+        file_path: relative_file_path(index, node),
+        line_range: node.range.map(|range| [range.start_line, range.end_line]),
+        depth,
+        external: ExternalEntity::of(node),
+    }
+}
+
+pub(crate) fn subgraph_edge(index: &LoadedIndex, edge_idx: EdgeIndex) -> Option<SubgraphEdge> {
+    let storage = index.graph.graph();
+    let (source, target) = storage.edge_endpoints(edge_idx)?;
+    let weight = storage.edge_weight(edge_idx)?;
+    Some(SubgraphEdge {
+        source: storage.node_weight(source)?.id.clone(),
+        target: storage.node_weight(target)?.id.clone(),
+        relation: weight.kind,
+        alias: weight.alias.clone(),
+    })
+}
+
+/// BFS from the start entities following the requested relations and direction.
+///
+/// With a `limit`, nodes are paged in BFS order and each edge is returned on
+/// the page of whichever endpoint comes later, so the pages together hold the
+/// whole subgraph. The metadata always describes the whole subgraph.
+pub fn traverse_graph(
+    index: &LoadedIndex,
+    params: &TraverseGraphParams,
+) -> Result<TraverseGraphResult, JsonRpcError> {
+    let started = Instant::now();
+    let plan = TraversalPlan::new(index, params)?;
+    if params
+        .limit
+        .is_some_and(|limit| limit == 0 || limit > MAX_TRAVERSAL_PAGE)
+    {
+        return Err(JsonRpcError::invalid_params(format!(
+            "limit must be between 1 and {MAX_TRAVERSAL_PAGE}"
+        )));
+    }
+    let query_fingerprint = fingerprint(
+        params.repo.as_deref().unwrap_or(DEFAULT_REPO),
+        "traverse_graph",
+        &TraverseGraphParams {
+            limit: None,
+            cursor: None,
+            ..normalized_traverse(params)
+        },
+    );
+    let offset = PageCursor::resume(
+        params.cursor.as_deref(),
+        index.generation,
+        query_fingerprint,
+    )?;
+    let traversal = bfs_subgraph(&index.graph, &plan.starts, &plan.filter);
+
+    // Position of each returned node in BFS order.
+    let mut included = HashMap::new();
+    let mut nodes = Vec::new();
+    for &(idx, depth) in &traversal.nodes {
+        let Some(node) = index.graph.node(idx) else {
+            continue;
+        };
+        if plan.keeps(idx, node) {
+            included.insert(idx, nodes.len());
+            nodes.push(subgraph_node(index, node, depth));
+        }
+    }
+
+    let edge_source = match plan.format {
+        TraverseFormat::Graph => &traversal.edges,
+        TraverseFormat::Tree => &traversal.tree_edges,
+    };
+    let storage = index.graph.graph();
+    let edges: Vec<(usize, SubgraphEdge)> = edge_source
+        .iter()
+        .filter_map(|&edge_idx| {
+            let (source, target) = storage.edge_endpoints(edge_idx)?;
+            let position = (*included.get(&source)?).max(*included.get(&target)?);
+            Some((position, subgraph_edge(index, edge_idx)?))
+        })
+        .collect();
+
+    let metadata = TraversalMetadata {
+        total_nodes: nodes.len(),
+        total_edges: edges.len(),
+        max_depth_reached: nodes.iter().map(|node| node.depth).max().unwrap_or(0),
+        execution_time_ms: 0.0,
+        cache_hit: false,
+    };
+    let start = offset.min(nodes.len());
+    let end = params.limit.map_or(nodes.len(), |limit| {
+        start.saturating_add(limit).min(nodes.len())
+    });
+    let page = start..end;
+    let nodes = nodes.drain(page.clone()).collect();
+    let edges = edges
+        .into_iter()
+        .filter(|(position, _)| page.contains(position))
+        .map(|(_, edge)| edge)
+        .collect();
+
+    Ok(TraverseGraphResult {
+        metadata: TraversalMetadata {
+            execution_time_ms: elapsed_ms(started),
+            ..metadata
+        },
+        subgraph: Subgraph { nodes, edges },
+        next_cursor: PageCursor::next(
+            index.generation,
+            query_fingerprint,
+            end,
+            end < metadata.total_nodes,
+        ),
+        session: None,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// retrieve_entity
+// ---------------------------------------------------------------------------
+
+/// Returns the source of each requested entity plus optional surrounding
+/// lines and signature metadata. `line_range` always spans the whole entity;
+/// a folded or previewed `code` covers its first lines only and drops the
+/// lines after it.
+pub fn retrieve_entity(
+    index: &LoadedIndex,
+    params: &RetrieveEntityParams,
+) -> Result<RetrieveEntityResult, JsonRpcError> {
+    if params.entity_ids.is_empty() {
+        return Err(JsonRpcError::invalid_params(
+            "entity_ids must contain at least one id",
+        ));
+    }
+    if params.include_context > MAX_CONTEXT_LINES {
+        return Err(JsonRpcError::invalid_params(format!(
+            "include_context must be at most {MAX_CONTEXT_LINES}"
+        )));
+    }
+
+    let mut sources = SourceCache::default();
+    let mut parser = None;
+    let mut entities = Vec::with_capacity(params.entity_ids.len());
+    for id in &params.entity_ids {
+        let idx = index
+            .entity(id)
+            .ok_or_else(|| JsonRpcError::entity_not_found(id))?;
+        let node = index
+            .graph
+            .node(idx)
+            .ok_or_else(|| JsonRpcError::entity_not_found(id))?;
+        let code = node_code(node, &mut sources);
+        let line_range = line_range(node, code.as_deref());
+
+        let (context_before, mut context_after) = match (params.include_context, &node.file_path) {
+            (0, _) | (_, None) => (None, None),
+            (context, Some(path)) => match sources.get(path) {
+                Some(source) if node.kind != NodeKind::File => {
+                    surrounding_lines(&source, line_range, context)
+                }
+                _ => (None, None),
+            },
+        };
+
+        let mut code = code.unwrap_or_default();
+        let shortened = match params.snippet_mode {
+            Some(SnippetMode::Fold) => Some(fold_line(&code).to_string()),
+            Some(SnippetMode::Preview) => Some(preview_lines(&code).join("\n")),
+            Some(SnippetMode::Full) | None => None,
+        };
+        if let Some(shortened) = shortened.filter(|shortened| shortened.len() < code.len()) {
+            code = shortened;
+            context_after = None;
+        }
+
+        let metadata = if params.include_metadata {
+            entity_metadata(index, idx, node, &mut sources, &mut parser)?
+        } else {
+            None
+        };
+
+        entities.push(EntityDetails {
+            id: node.id.clone(),
+            name: node.display_name.clone(),
+            entity_type: node.kind,
+            file_path: relative_file_path(index, node),
+            line_range,
+            code,
+            context_before,
+            context_after,
+            metadata,
+            external: ExternalEntity::of(node),
+        });
+    }
+
+    Ok(RetrieveEntityResult {
+        entities,
+        session: None,
+    })
+}
+
+/// Parses the entity's file for the signature of a class or function and
+/// fills in the class containing it from the graph. `parser` is created on
+/// first use and shared across the entities of one request.
+fn entity_metadata(
+    index: &LoadedIndex,
+    idx: GraphNodeIndex,
+    node: &GraphNode,
+    sources: &mut SourceCache,
+    parser: &mut Option<PythonParser>,
+) -> Result<Option<EntityMetadata>, JsonRpcError> {
+    if !matches!(node.kind, NodeKind::Class | NodeKind::Function) {
+        return Ok(None);
+    }
+    let (Some(path), Some(range)) = (&node.file_path, node.range) else {
+        return Ok(None);
+    };
+    let Some(source) = sources.get(path) else {
+        return Ok(None);
+    };
+    let parser = match parser {
+        Some(parser) => parser,
+        None => parser.insert(PythonParser::new().map_err(|err| {
+            JsonRpcError::internal(format!("failed to initialize parser: {err}"))
+        })?),
+    };
+    let Some(mut metadata) = parser.entity_metadata(&source, range.start_line) else {
+        return Ok(None);
+    };
+    metadata.parent_class = index
+        .graph
+        .graph()
+        .edges_directed(idx, Direction::Incoming)
+        .filter(|edge| edge.weight().kind == EdgeKind::Contain)
+        .filter_map(|edge| index.graph.node(edge.source()))
+        .find(|parent| parent.kind == NodeKind::Class)
+        .map(|parent| parent.id.clone());
+    Ok(Some(metadata))
+}
+
+fn surrounding_lines(
+    source: &str,
+    [start, end]: [u32; 2],
+    context: usize,
+) -> (Option<String>, Option<String>) {
+    let lines: Vec<&str> = source.lines().collect();
+    let start_idx = (start as usize).saturating_sub(1);
+    let end_idx = (end as usize).min(lines.len());
+    let before_start = start_idx.saturating_sub(context);
+    let after_end = (end_idx + context).min(lines.len());
+    // A single blank line of context joins to "" but is still context.
+    let before = &lines[before_start..start_idx.min(lines.len())];
+    let after = &lines[end_idx..after_end];
+    (
+        (!before.is_empty()).then(|| before.join("\n")),
+        (!after.is_empty()).then(|| after.join("\n")),
+    )
+}
+
+// ---------------------------------------------------------------------------
+// find_paths
+// ---------------------------------------------------------------------------
+
+/// The `k` shortest paths from one entity to another over the requested
+/// relations and direction, none revisiting an entity.
+pub fn find_paths(
+    index: &LoadedIndex,
+    params: &FindPathsParams,
+) -> Result<FindPathsResult, JsonRpcError> {
+    let started = Instant::now();
+    if params.max_depth == 0 || params.max_depth > MAX_TRAVERSAL_DEPTH {
+        return Err(JsonRpcError::invalid_params(format!(
+            "max_depth must be between 1 and {MAX_TRAVERSAL_DEPTH}"
+        )));
+    }
+    if params.k == 0 || params.k > MAX_PATHS {
+        return Err(JsonRpcError::invalid_params(format!(
+            "k must be between 1 and {MAX_PATHS}"
+        )));
+    }
+    if params.from == params.to {
+        return Err(JsonRpcError::invalid_params("from and to must differ"));
+    }
+    let from = index
+        .entity(&params.from)
+        .ok_or_else(|| JsonRpcError::entity_not_found(&params.from))?;
+    let to = index
+        .entity(&params.to)
+        .ok_or_else(|| JsonRpcError::entity_not_found(&params.to))?;
+    let filter = TraversalFilter {
+        max_depth: params.max_depth,
+        relations: params.relations.clone().unwrap_or_default(),
+        direction: params.direction,
+    };
+
+    let paths = shortest_paths(&index.graph, from, to, &filter, params.k)
+        .iter()
+        .map(|path| EntityPath {
+            length: path.hops.len(),
+            nodes: path
+                .nodes()
+                .enumerate()
+                .filter_map(|(depth, idx)| {
+                    let node = index.graph.node(idx)?;
+                    Some(subgraph_node(index, node, depth))
+                })
+                .collect(),
+            hops: path
+                .hops
+                .iter()
+                .filter_map(|&(edge, _)| subgraph_edge(index, edge))
+                .collect(),
+        })
+        .collect();
+    Ok(FindPathsResult {
+        paths,
+        execution_time_ms: elapsed_ms(started),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// diff_impact
+// ---------------------------------------------------------------------------
+
+/// The entities a unified diff changes and, walking backward over the
+/// requested relations, the entities depending on them: the blast radius of
+/// the change. Line numbers are read as in the indexed version of the files.
+pub fn diff_impact(
+    index: &LoadedIndex,
+    params: &DiffImpactParams,
+) -> Result<DiffImpactResult, JsonRpcError> {
+    let started = Instant::now();
+    if params.depth > MAX_TRAVERSAL_DEPTH {
+        return Err(JsonRpcError::invalid_params(format!(
+            "depth must be at most {MAX_TRAVERSAL_DEPTH}"
+        )));
+    }
+    let changes = parse_unified_diff(&params.diff)
+        .map_err(|err| JsonRpcError::invalid_params(format!("diff: {err}")))?;
+
+    let mut starts = Vec::new();
+    let mut unindexed_files = Vec::new();
+    for change in &changes {
+        match changed_entities(&index.graph, change) {
+            Some(entities) => starts.extend(entities),
+            None => unindexed_files.push(change.path.clone()),
+        }
+    }
+    let filter = TraversalFilter {
+        max_depth: params.depth,
+        relations: params
+            .relations
+            .clone()
+            .unwrap_or_else(|| vec![EdgeKind::Invoke, EdgeKind::Import, EdgeKind::Inherit]),
+        direction: TraversalDirection::Backward,
+    };
+    let traversal = bfs_subgraph(&index.graph, &starts, &filter);
+
+    let depths: HashMap<GraphNodeIndex, usize> = traversal.nodes.iter().copied().collect();
+    let storage = index.graph.graph();
+    let discovered_by: HashMap<GraphNodeIndex, EdgeIndex> = traversal
+        .tree_edges
+        .iter()
+        .filter_map(|&edge| {
+            let (source, target) = storage.edge_endpoints(edge)?;
+            let far = if depths.get(&source) > depths.get(&target) {
+                source
+            } else {
+                target
+            };
+            Some((far, edge))
+        })
+        .collect();
+
+    let mut changed = Vec::new();
+    let mut impacted = Vec::new();
+    for &(idx, depth) in &traversal.nodes {
+        let Some(node) = index.graph.node(idx) else {
+            continue;
+        };
+        let entity = subgraph_node(index, node, depth);
+        if depth == 0 {
+            changed.push(entity);
+        } else if let Some(via) = discovered_by
+            .get(&idx)
+            .and_then(|&edge| subgraph_edge(index, edge))
+        {
+            impacted.push(ImpactedEntity { entity, via });
+        }
+    }
+    Ok(DiffImpactResult {
+        changed,
+        impacted,
+        unindexed_files,
+        execution_time_ms: elapsed_ms(started),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// session_summary
+// ---------------------------------------------------------------------------
+
+/// What a session has explored in a repository and the frontier around it:
+/// unexplored entities linked to explored ones by any relation except
+/// containment by a parent, ranked by how many explored entities link to them.
+pub fn session_summary(
+    state: &ServiceState,
+    index: &LoadedIndex,
+    params: &SessionSummaryParams,
+) -> Result<SessionSummaryResult, JsonRpcError> {
+    validate_session(&SessionOptions {
+        session_id: Some(params.session_id.clone()),
+        ..SessionOptions::default()
+    })?;
+    if params.frontier_limit > MAX_FRONTIER {
+        return Err(JsonRpcError::invalid_params(format!(
+            "frontier_limit must be at most {MAX_FRONTIER}"
+        )));
+    }
+    let repo = params.repo.as_deref().unwrap_or(DEFAULT_REPO);
+    let snapshot = state.sessions().snapshot(&params.session_id, repo);
+    let seen = snapshot
+        .as_ref()
+        .map(|snapshot| snapshot.seen.as_slice())
+        .unwrap_or_default();
+    let explored_ids: HashSet<&str> = seen.iter().map(|(id, _)| id.as_str()).collect();
+
+    let explored = seen
+        .iter()
+        .map(|(id, entry)| {
+            let node = index.entity(id).and_then(|idx| index.graph.node(idx));
+            ExploredEntity {
+                id: id.clone(),
+                level: entry.level,
+                times_returned: entry.times,
+                entity_type: node.map(|node| node.kind),
+                file_path: node.map(|node| relative_file_path(index, node)),
+            }
+        })
+        .collect();
+
+    let graph = index.graph.graph();
+    let mut frontier: Vec<FrontierEntity> = Vec::new();
+    let mut positions: HashMap<GraphNodeIndex, usize> = HashMap::new();
+    for (id, _) in seen {
+        let Some(idx) = index.entity(id) else {
+            continue;
+        };
+        let outgoing = graph
+            .edges_directed(idx, Direction::Outgoing)
+            .map(|edge| (edge.target(), edge.weight().kind));
+        let incoming = graph
+            .edges_directed(idx, Direction::Incoming)
+            .filter(|edge| edge.weight().kind != EdgeKind::Contain)
+            .map(|edge| (edge.source(), edge.weight().kind));
+        for (neighbor, relation) in outgoing.chain(incoming) {
+            let Some(node) = index.graph.node(neighbor) else {
+                continue;
+            };
+            if node.kind == NodeKind::Directory || explored_ids.contains(node.id.as_str()) {
+                continue;
+            }
+            let position = *positions.entry(neighbor).or_insert_with(|| {
+                frontier.push(FrontierEntity {
+                    id: node.id.clone(),
+                    name: node.display_name.clone(),
+                    entity_type: node.kind,
+                    file_path: relative_file_path(index, node),
+                    relations: Vec::new(),
+                    linked_from: Vec::new(),
+                });
+                frontier.len() - 1
+            });
+            let entry = &mut frontier[position];
+            if !entry.relations.contains(&relation) {
+                entry.relations.push(relation);
+            }
+            if !entry.linked_from.contains(id) {
+                entry.linked_from.push(id.clone());
+            }
+        }
+    }
+    // Stable, so ties keep the order the frontier was discovered in.
+    frontier.sort_by_key(|entry| std::cmp::Reverse(entry.linked_from.len()));
+    let frontier_total = frontier.len();
+    frontier.truncate(params.frontier_limit);
+
+    Ok(SessionSummaryResult {
+        session_id: params.session_id.clone(),
+        repo: repo.to_string(),
+        created_at: snapshot.as_ref().map(|snapshot| snapshot.created_at),
+        last_active_at: snapshot.as_ref().map(|snapshot| snapshot.last_active_at),
+        calls: snapshot.as_ref().map_or(0, |snapshot| snapshot.calls),
+        explored,
+        frontier,
+        frontier_total,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// get_index_stats
+// ---------------------------------------------------------------------------
+
+/// Composition of an index (entity and edge counts), its hotspots (see
+/// [`crate::graph::analytics`]) and what its build could not resolve.
+pub fn get_index_stats(
+    index: &LoadedIndex,
+    params: &GetIndexStatsParams,
+) -> Result<IndexStatsResult, JsonRpcError> {
+    if params.top == 0 || params.top > MAX_STATS_TOP {
+        return Err(JsonRpcError::invalid_params(format!(
+            "top must be between 1 and {MAX_STATS_TOP}"
+        )));
+    }
+    Ok(IndexStatsResult {
+        repo: params
+            .repo
+            .clone()
+            .unwrap_or_else(|| DEFAULT_REPO.to_string()),
+        repo_root: index.repo_root.clone(),
+        generation: index.generation,
+        built_at: index.built_at,
+        entities: EntityCounts::from_graph(&index.graph),
+        edges: EdgeCounts::from_graph(&index.graph),
+        analytics: analyze(&index.graph, params.top),
+        unresolved_imports: index.diagnostics.unresolved_imports,
+        parse_failures: index.diagnostics.parse_failures.clone(),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Shared helpers
+// ---------------------------------------------------------------------------
+
+/// Per-request cache of source files read from disk.
+#[derive(Default)]
+struct SourceCache {
+    files: HashMap<PathBuf, Option<Arc<str>>>,
+}
+
+impl SourceCache {
+    fn get(&mut self, path: &Path) -> Option<Arc<str>> {
+        self.files
+            .entry(path.to_path_buf())
+            .or_insert_with(|| fs::read_to_string(path).ok().map(Arc::from))
+            .clone()
+    }
+}
+
+/// Source text of a file/class/function node (`None` for directories or unreadable files).
+fn node_code(node: &GraphNode, sources: &mut SourceCache) -> Option<String> {
+    let path = node.file_path.as_ref()?;
+    match node.kind {
+        NodeKind::Directory => None,
+        NodeKind::File => sources.get(path).map(|source| source.to_string()),
+        NodeKind::Class | NodeKind::Function => {
+            let source = sources.get(path)?;
+            node.range.map(|range| range.extract(&source))
+        }
+    }
+}
+
+fn line_range(node: &GraphNode, code: Option<&str>) -> [u32; 2] {
+    match node.range {
+        Some(range) => [range.start_line.max(1), range.end_line.max(1)],
+        None => {
+            let lines = code.map(|code| code.lines().count()).unwrap_or(1).max(1);
+            [1, lines as u32]
+        }
+    }
+}
+
+/// Repository-relative path of the file (or directory) that owns `node`.
+///
+/// External nodes report the path inside their own repository.
+pub fn relative_file_path(index: &LoadedIndex, node: &GraphNode) -> String {
+    if let Some((_, entity_id)) = node.external_ref() {
+        return entity_id
+            .split_once("::")
+            .map_or(entity_id, |(file, _)| file)
+            .to_string();
+    }
+    if node.kind == NodeKind::Directory {
+        return node
+            .file_path
+            .as_ref()
+            .and_then(|path| path.strip_prefix(&index.repo_root).ok())
+            .map(|rel| rel.to_string_lossy().replace('\\', "/"))
+            .filter(|rel| !rel.is_empty())
+            .unwrap_or_else(|| node.id.clone());
+    }
+    node.id
+        .split_once("::")
+        .map(|(file, _)| file.to_string())
+        .unwrap_or_else(|| node.id.clone())
+}
+
+fn elapsed_ms(started: Instant) -> f64 {
+    started.elapsed().as_secs_f64() * 1000.0
+}
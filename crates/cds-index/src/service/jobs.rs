@@ -0,0 +1,506 @@
+//! Background rebuild jobs
+//!
+//! `rebuild_index` only validates its params and enqueues a [`RebuildJob`];
+//! the build itself runs on tokio's blocking pool. Clients poll
+//! `get_job_status` for phase/file progress and may `cancel_job` at any point
+//! before the new index is promoted. The previous index keeps serving queries
+//! until [`RepoHandle::swap`] installs the new one. Each repository may have
+//! one active job at a time; different repositories rebuild concurrently.
+//!
+//! With `link_repos`, the graphs of the other registered repositories are
+//! offered to the builder as [`ExternalPackages`] so imports of their
+//! packages become external nodes instead of being dropped. Links reflect
+//! the peers' indices at build time; rebuild to pick up their changes.
+//!
+//! Jobs report their progress, the generation swap and the files that
+//! changed on the service's [`EventBus`].
+
+use super::changes::{diff_fingerprints, FileChange};
+use super::events::{EventBus, FilesUpdated, GenerationSwapped, IndexEvent, MAX_FILES_PER_EVENT};
+use super::jsonrpc::JsonRpcError;
+use super::repos::RepoHandle;
+use super::state::{IndexBuildError, LoadedIndex, RebuildStats, ServiceState};
+use crate::graph::{
+    BuildPhase, BuildProgress, DependencyGraph, ExternalPackages, GraphBuilderConfig,
+};
+use crate::persistence::{self, IndexLayout};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Finished jobs kept around for `get_job_status` before being pruned.
+const MAX_FINISHED_JOBS: usize = 32;
+
+/// Parsed files between two `job_progress` events during the parse phase.
+const PROGRESS_EVENT_FILES: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+/// Point-in-time view of a job, returned by `rebuild_index`, `get_job_status` and `cancel_job`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobSnapshot {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub repo: String,
+    pub repo_path: PathBuf,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phase: Option<BuildPhase>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files_total: Option<usize>,
+    pub files_processed: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eta_ms: Option<u64>,
+    pub elapsed_ms: u64,
+    pub cancel_requested: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<RebuildStats>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct JobProgress {
+    phase: Option<BuildPhase>,
+    files_total: Option<usize>,
+    parse_started: Option<Instant>,
+    started: Option<Instant>,
+    finished: Option<Instant>,
+    generation: Option<u64>,
+    stats: Option<RebuildStats>,
+    error: Option<String>,
+}
+
+/// A single rebuild request and its live progress.
+#[derive(Debug)]
+pub struct RebuildJob {
+    id: String,
+    repo: String,
+    repo_path: PathBuf,
+    created_at: DateTime<Utc>,
+    status: Mutex<JobStatus>,
+    progress: Mutex<JobProgress>,
+    files_processed: AtomicUsize,
+    cancel: AtomicBool,
+    events: EventBus,
+}
+
+impl RebuildJob {
+    fn new(id: String, repo: String, repo_path: PathBuf, events: EventBus) -> Self {
+        Self {
+            id,
+            repo,
+            repo_path,
+            created_at: Utc::now(),
+            status: Mutex::new(JobStatus::Queued),
+            progress: Mutex::new(JobProgress::default()),
+            files_processed: AtomicUsize::new(0),
+            cancel: AtomicBool::new(false),
+            events,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Name of the repository being rebuilt.
+    pub fn repo(&self) -> &str {
+        &self.repo
+    }
+
+    pub fn repo_path(&self) -> &Path {
+        &self.repo_path
+    }
+
+    pub fn status(&self) -> JobStatus {
+        *self.status.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn set_status(&self, status: JobStatus) {
+        *self.status.lock().unwrap_or_else(|p| p.into_inner()) = status;
+        self.publish_progress();
+    }
+
+    fn publish_progress(&self) {
+        if self.events.subscriber_count() > 0 {
+            self.events
+                .publish(IndexEvent::JobProgress(self.snapshot()));
+        }
+    }
+
+    fn with_progress<R>(&self, f: impl FnOnce(&mut JobProgress) -> R) -> R {
+        f(&mut self.progress.lock().unwrap_or_else(|p| p.into_inner()))
+    }
+
+    /// Requests cancellation; the worker stops at the next checkpoint.
+    pub fn request_cancel(&self) {
+        self.cancel.store(true, Ordering::SeqCst);
+        self.publish_progress();
+    }
+
+    fn mark_running(&self) {
+        self.with_progress(|progress| progress.started = Some(Instant::now()));
+        self.set_status(JobStatus::Running);
+    }
+
+    fn finish(&self, outcome: Result<(u64, RebuildStats), IndexBuildError>) {
+        let status = self.with_progress(|progress| {
+            progress.finished = Some(Instant::now());
+            match outcome {
+                Ok((generation, stats)) => {
+                    progress.generation = Some(generation);
+                    progress.stats = Some(stats);
+                    JobStatus::Completed
+                }
+                Err(IndexBuildError::Cancelled) => JobStatus::Cancelled,
+                Err(err) => {
+                    progress.error = Some(err.to_string());
+                    JobStatus::Failed
+                }
+            }
+        });
+        self.set_status(status);
+    }
+
+    pub fn snapshot(&self) -> JobSnapshot {
+        let status = self.status();
+        let files_processed = self.files_processed.load(Ordering::SeqCst);
+        self.with_progress(|progress| {
+            let elapsed = match (progress.started, progress.finished) {
+                (Some(start), Some(end)) => end.duration_since(start),
+                (Some(start), None) => start.elapsed(),
+                _ => Duration::ZERO,
+            };
+            let eta_ms = match (
+                status,
+                progress.phase,
+                progress.files_total,
+                progress.parse_started,
+            ) {
+                (JobStatus::Running, Some(BuildPhase::Parse), Some(total), Some(start))
+                    if files_processed > 0 =>
+                {
+                    let per_file = start.elapsed().as_secs_f64() / files_processed as f64;
+                    let remaining = total.saturating_sub(files_processed) as f64;
+                    Some((per_file * remaining * 1000.0) as u64)
+                }
+                _ => None,
+            };
+            JobSnapshot {
+                job_id: self.id.clone(),
+                status,
+                repo: self.repo.clone(),
+                repo_path: self.repo_path.clone(),
+                created_at: self.created_at,
+                phase: progress.phase,
+                files_total: progress.files_total,
+                files_processed,
+                eta_ms,
+                elapsed_ms: elapsed.as_millis() as u64,
+                cancel_requested: self.cancel.load(Ordering::SeqCst),
+                generation: progress.generation,
+                stats: progress.stats.clone(),
+                error: progress.error.clone(),
+            }
+        })
+    }
+}
+
+impl BuildProgress for RebuildJob {
+    fn phase_started(&self, phase: BuildPhase, total: Option<usize>) {
+        self.with_progress(|progress| {
+            progress.phase = Some(phase);
+            if phase == BuildPhase::Parse {
+                progress.files_total = total;
+                progress.parse_started = Some(Instant::now());
+            }
+        });
+        self.publish_progress();
+    }
+
+    fn file_processed(&self, _rel_path: &Path) {
+        let processed = self.files_processed.fetch_add(1, Ordering::SeqCst) + 1;
+        if processed.is_multiple_of(PROGRESS_EVENT_FILES) {
+            self.publish_progress();
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+}
+
+/// Registry of rebuild jobs; at most one job per repository may be active.
+#[derive(Debug)]
+pub struct JobManager {
+    jobs: Mutex<Vec<Arc<RebuildJob>>>,
+    next_id: AtomicU64,
+    events: EventBus,
+}
+
+impl JobManager {
+    /// Jobs created by this manager publish their progress on `events`.
+    pub fn new(events: EventBus) -> Self {
+        Self {
+            jobs: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+            events,
+        }
+    }
+
+    /// Registers a new queued job for `repo`, or fails with `-32005` if that
+    /// repository already has an active one.
+    pub fn enqueue(&self, repo: &str, repo_path: PathBuf) -> Result<Arc<RebuildJob>, JsonRpcError> {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|p| p.into_inner());
+        if let Some(active) = jobs
+            .iter()
+            .find(|job| job.repo() == repo && !job.status().is_finished())
+        {
+            return Err(JsonRpcError::job_conflict(active.id()));
+        }
+
+        // Keep only the most recent finished jobs.
+        let finished = jobs.iter().filter(|job| job.status().is_finished()).count();
+        if finished >= MAX_FINISHED_JOBS {
+            let mut excess = finished + 1 - MAX_FINISHED_JOBS;
+            jobs.retain(|job| {
+                if excess > 0 && job.status().is_finished() {
+                    excess -= 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        let id = format!("job-{}", self.next_id.fetch_add(1, Ordering::SeqCst) + 1);
+        let job = Arc::new(RebuildJob::new(
+            id,
+            repo.to_string(),
+            repo_path,
+            self.events.clone(),
+        ));
+        jobs.push(Arc::clone(&job));
+        drop(jobs);
+        job.publish_progress();
+        Ok(job)
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<Arc<RebuildJob>> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .iter()
+            .find(|job| job.id() == job_id)
+            .cloned()
+    }
+
+    /// All queued or running jobs.
+    pub fn active(&self) -> Vec<Arc<RebuildJob>> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .iter()
+            .filter(|job| !job.status().is_finished())
+            .cloned()
+            .collect()
+    }
+
+    /// The queued or running job of `repo`, if any.
+    pub fn active_for(&self, repo: &str) -> Option<Arc<RebuildJob>> {
+        self.active().into_iter().find(|job| job.repo() == repo)
+    }
+}
+
+/// What to build and where to put it.
+#[derive(Debug, Clone)]
+pub struct RebuildRequest {
+    pub repo_root: PathBuf,
+    pub layout: IndexLayout,
+    pub config: GraphBuilderConfig,
+    /// Link imports to the packages of the other registered repositories.
+    pub link_repos: bool,
+}
+
+/// Enqueues `request` for `repo` and runs it in the background; fails with
+/// `-32011` once the service is shutting down.
+///
+/// Must be called from within a tokio runtime.
+pub fn spawn_rebuild(
+    state: &Arc<ServiceState>,
+    repo: Arc<RepoHandle>,
+    request: RebuildRequest,
+) -> Result<JobSnapshot, JsonRpcError> {
+    if state.is_shutting_down() {
+        return Err(JsonRpcError::shutting_down());
+    }
+    let job = state
+        .jobs()
+        .enqueue(repo.name(), request.repo_root.clone())?;
+    let snapshot = job.snapshot();
+    let state = Arc::clone(state);
+    tokio::task::spawn_blocking(move || run_rebuild(&state, &repo, &job, request));
+    Ok(snapshot)
+}
+
+fn run_rebuild(state: &ServiceState, repo: &RepoHandle, job: &RebuildJob, request: RebuildRequest) {
+    let RebuildRequest {
+        repo_root,
+        layout,
+        mut config,
+        link_repos,
+    } = request;
+    if job.is_cancelled() {
+        job.finish(Err(IndexBuildError::Cancelled));
+        return;
+    }
+    job.mark_running();
+    info!(
+        "{}: rebuilding index '{}' for {}",
+        job.id(),
+        repo.name(),
+        repo_root.display()
+    );
+
+    if link_repos {
+        let external = external_packages(state, repo, &repo_root);
+        if !external.is_empty() {
+            config.external_packages = Some(Arc::new(external));
+        }
+    }
+
+    // Compare against what clients were served, even when it was unloaded.
+    let previous = repo
+        .current()
+        .map(|index| Arc::clone(&index.fingerprints))
+        .or_else(|| {
+            persistence::load_fingerprints(&repo.layout().graph_dir)
+                .ok()
+                .map(Arc::new)
+        })
+        .filter(|fingerprints| !fingerprints.is_empty());
+
+    let generation = repo.next_generation();
+    let bm25 = state.config().bm25;
+    let outcome = LoadedIndex::build(&repo_root, config, &bm25, &layout, generation, job).map(
+        |(index, stats)| {
+            let changes = previous
+                .as_ref()
+                .map(|previous| diff_fingerprints(previous, &index.fingerprints));
+            let swapped = GenerationSwapped {
+                repo: repo.name().to_string(),
+                generation,
+                built_at: index.built_at,
+                node_count: index.graph.node_count(),
+                edge_count: index.graph.edge_count(),
+                files_changed: changes.as_ref().map(Vec::len),
+            };
+            repo.swap(index);
+            state.cache().invalidate_repo(repo.name());
+            state.repos().touch(repo);
+            state.repos().enforce_limit(repo.name());
+            publish_changes(state.events(), swapped, changes.unwrap_or_default());
+            (generation, stats)
+        },
+    );
+    match &outcome {
+        Ok((generation, stats)) => info!(
+            "{}: generation {} ready ({} files, {:.0} ms)",
+            job.id(),
+            generation,
+            stats.files_indexed,
+            stats.build_time_ms
+        ),
+        Err(IndexBuildError::Cancelled) => info!("{}: cancelled", job.id()),
+        Err(err) => warn!("{}: rebuild failed: {err}", job.id()),
+    }
+    job.finish(outcome);
+}
+
+/// Publishes the generation swap followed by the files that changed.
+pub(super) fn publish_changes(
+    events: &EventBus,
+    swapped: GenerationSwapped,
+    changes: Vec<FileChange>,
+) {
+    let (repo, generation) = (swapped.repo.clone(), swapped.generation);
+    events.publish(IndexEvent::GenerationSwapped(swapped));
+    for files in changes.chunks(MAX_FILES_PER_EVENT) {
+        events.publish(IndexEvent::FilesUpdated(FilesUpdated {
+            repo: repo.clone(),
+            generation,
+            files: files.to_vec(),
+        }));
+    }
+}
+
+/// Packages of every other registered repository with an index.
+///
+/// Loaded peers share their in-memory graph; the others are read from disk
+/// without being registered as loaded. Repositories pointing at the same
+/// checkout as `repo` are skipped.
+fn external_packages(
+    state: &ServiceState,
+    repo: &RepoHandle,
+    repo_root: &Path,
+) -> ExternalPackages {
+    let mut external = ExternalPackages::new();
+    for peer in state.repos().list() {
+        if peer.name() == repo.name() || peer.repo_path().as_deref() == Some(repo_root) {
+            continue;
+        }
+        let Some(graph) = peer_graph(&peer) else {
+            continue;
+        };
+        let conflicts = external.add_repo(peer.name(), graph);
+        if !conflicts.is_empty() {
+            warn!(
+                "packages {} of '{}' are already provided by another repository; skipping",
+                conflicts.join(", "),
+                peer.name()
+            );
+        }
+    }
+    external
+}
+
+fn peer_graph(peer: &RepoHandle) -> Option<Arc<DependencyGraph>> {
+    if let Some(index) = peer.current() {
+        return Some(Arc::clone(&index.graph));
+    }
+    let manifest = persistence::load_manifest(&peer.layout().graph_dir).ok()?;
+    if manifest.packages.is_empty() {
+        return None;
+    }
+    match persistence::load_graph(&peer.layout().graph_dir) {
+        Ok((graph, _)) => Some(Arc::new(graph)),
+        Err(err) => {
+            warn!("cannot link against '{}': {err}", peer.name());
+            None
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! JSON-RPC 2.0 protocol types and error codes
+//!
+//! Error codes follow docs/api/error-codes.md: the standard -327xx/-326xx
+//! range plus CDSAgent custom codes starting at -32001.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+// Standard JSON-RPC 2.0 errors
+pub const PARSE_ERROR: i32 = -32700;
+pub const INVALID_REQUEST: i32 = -32600;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+pub const INTERNAL_ERROR: i32 = -32603;
+
+// CDSAgent custom errors
+pub const INDEX_NOT_FOUND: i32 = -32001;
+pub const ENTITY_NOT_FOUND: i32 = -32002;
+pub const CODE_PARSE_ERROR: i32 = -32003;
+pub const QUERY_TIMEOUT: i32 = -32004;
+pub const JOB_CONFLICT: i32 = -32005;
+pub const JOB_NOT_FOUND: i32 = -32006;
+pub const REPO_NOT_FOUND: i32 = -32007;
+pub const UNAUTHORIZED: i32 = -32008;
+pub const FORBIDDEN: i32 = -32009;
+pub const RATE_LIMITED: i32 = -32010;
+pub const SHUTTING_DOWN: i32 = -32011;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcRequest {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+    #[serde(default)]
+    pub id: Value,
+}
+
+impl JsonRpcRequest {
+    pub fn new(method: impl Into<String>, params: Value, id: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            method: method.into(),
+            params: Some(params),
+            id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl JsonRpcResponse {
+    pub fn success(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn failure(id: Value, error: JsonRpcError) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonRpcError {
+    pub code: i32,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl JsonRpcError {
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn parse_error(detail: impl std::fmt::Display) -> Self {
+        Self::new(PARSE_ERROR, "Parse error").with_data(json!({ "detail": detail.to_string() }))
+    }
+
+    pub fn invalid_request(detail: impl std::fmt::Display) -> Self {
+        Self::new(INVALID_REQUEST, "Invalid Request")
+            .with_data(json!({ "detail": detail.to_string() }))
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(METHOD_NOT_FOUND, "Method not found").with_data(json!({ "method": method }))
+    }
+
+    pub fn invalid_params(detail: impl std::fmt::Display) -> Self {
+        Self::new(INVALID_PARAMS, "Invalid params")
+            .with_data(json!({ "detail": detail.to_string() }))
+    }
+
+    /// `-32602` for a pagination cursor that is malformed, stale or replayed
+    /// with a different query.
+    pub fn invalid_cursor(detail: impl std::fmt::Display) -> Self {
+        Self::new(INVALID_PARAMS, "Invalid params").with_data(json!({
+            "detail": detail.to_string(),
+            "suggestion": "Restart from the first page without a cursor",
+        }))
+    }
+
+    pub fn internal(detail: impl std::fmt::Display) -> Self {
+        Self::new(INTERNAL_ERROR, "Internal error")
+            .with_data(json!({ "detail": detail.to_string() }))
+    }
+
+    pub fn index_not_found(index_path: &std::path::Path) -> Self {
+        Self::new(INDEX_NOT_FOUND, "Index not found").with_data(json!({
+            "index_path": index_path.display().to_string(),
+            "suggestion": "Run 'cds init <repo>' or call rebuild_index",
+        }))
+    }
+
+    pub fn entity_not_found(entity_id: &str) -> Self {
+        Self::new(ENTITY_NOT_FOUND, "Entity not found").with_data(json!({ "entity_id": entity_id }))
+    }
+
+    pub fn job_conflict(job_id: &str) -> Self {
+        Self::new(JOB_CONFLICT, "Rebuild already in progress").with_data(json!({
+            "job_id": job_id,
+            "suggestion": "Poll get_job_status or cancel_job before starting another rebuild",
+        }))
+    }
+
+    pub fn job_not_found(job_id: &str) -> Self {
+        Self::new(JOB_NOT_FOUND, "Job not found").with_data(json!({ "job_id": job_id }))
+    }
+
+    pub fn unauthorized(reason: &str) -> Self {
+        Self::new(UNAUTHORIZED, "Unauthorized").with_data(json!({
+            "reason": reason,
+            "suggestion": "Send 'Authorization: Bearer <token>' with a configured token",
+        }))
+    }
+
+    pub fn forbidden(method: &str, role: super::auth::Role) -> Self {
+        Self::new(FORBIDDEN, "Forbidden").with_data(json!({
+            "method": method,
+            "role": role,
+            "suggestion": "Use a token with the admin role for this method",
+        }))
+    }
+
+    pub fn rate_limited(limit: &str, client: &str, retry_after: std::time::Duration) -> Self {
+        Self::new(RATE_LIMITED, "Rate limited").with_data(json!({
+            "limit": limit,
+            "client": client,
+            "retry_after_ms": retry_after.as_millis().max(1),
+            "suggestion": "Retry after retry_after_ms, or reduce request concurrency",
+        }))
+    }
+
+    pub fn shutting_down() -> Self {
+        Self::new(SHUTTING_DOWN, "Service shutting down").with_data(json!({
+            "suggestion": "Retry once the service has restarted",
+        }))
+    }
+
+    pub fn repo_not_found(repo: &str, available: Vec<&str>) -> Self {
+        Self::new(REPO_NOT_FOUND, "Repository not found").with_data(json!({
+            "repo": repo,
+            "available": available,
+            "suggestion": "Call list_repos, or add_repo to register it",
+        }))
+    }
+}
+
+/// Parses a raw request body, as received by any transport.
+///
+/// On failure returns the error response to send back (`-32700` for
+/// malformed JSON, `-32600` for a well-formed but invalid request), echoing
+/// the request id when one could be read.
+pub fn parse_request(body: &str) -> Result<JsonRpcRequest, Box<JsonRpcResponse>> {
+    let value: Value = serde_json::from_str(body).map_err(|err| {
+        Box::new(JsonRpcResponse::failure(
+            Value::Null,
+            JsonRpcError::parse_error(err),
+        ))
+    })?;
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|err| {
+        Box::new(JsonRpcResponse::failure(
+            id,
+            JsonRpcError::invalid_request(err),
+        ))
+    })
+}
+
+/// Deserializes method params, mapping failures to `-32602 Invalid params`.
+pub fn parse_params<T: serde::de::DeserializeOwned>(
+    params: Option<Value>,
+) -> Result<T, JsonRpcError> {
+    let value = match params {
+        None | Some(Value::Null) => json!({}),
+        Some(value) => value,
+    };
+    serde_json::from_value(value).map_err(JsonRpcError::invalid_params)
+}
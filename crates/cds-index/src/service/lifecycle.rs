@@ -0,0 +1,212 @@
+//! Process lifecycle: signals, graceful shutdown and reload
+//!
+//! `SIGTERM` and `SIGINT` start a graceful shutdown
+//! ([`ServiceState::begin_shutdown`]): the transports stop accepting
+//! connections and input and answer the requests in flight, and new rebuilds
+//! are refused with `-32011`. Rebuild jobs already running get until
+//! `INDEX_SHUTDOWN_TIMEOUT` to finish; the rest are cancelled, which leaves the
+//! previous index in place because builds are staged and only promoted once
+//! complete. A second signal exits immediately.
+//!
+//! `SIGHUP` re-reads the configuration and applies it with
+//! [`ServiceState::reload`]: tokens are replaced, newly registered
+//! repositories are picked up and indices rebuilt on disk by another process
+//! are reloaded. Each step is reported to systemd (see [`super::systemd`]).
+
+use super::state::{ReloadReport, ServiceState};
+use super::systemd;
+use crate::config::IndexServiceConfig;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// How often the rebuild jobs are checked while waiting for them.
+const JOB_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long cancelled rebuild jobs get to reach their next checkpoint.
+const CANCEL_GRACE: Duration = Duration::from_secs(10);
+
+/// Handles `SIGTERM`/`SIGINT` (shutdown) and `SIGHUP` (reload with the
+/// configuration returned by `load_config`) until the process exits.
+pub async fn handle_signals<F>(state: Arc<ServiceState>, load_config: F)
+where
+    F: Fn() -> anyhow::Result<IndexServiceConfig>,
+{
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let (Ok(mut terminate), Ok(mut interrupt), Ok(mut hangup)) = (
+            signal(SignalKind::terminate()),
+            signal(SignalKind::interrupt()),
+            signal(SignalKind::hangup()),
+        ) else {
+            warn!("Failed to install signal handlers");
+            return;
+        };
+        loop {
+            tokio::select! {
+                _ = terminate.recv() => stop(&state, "SIGTERM"),
+                _ = interrupt.recv() => stop(&state, "SIGINT"),
+                _ = hangup.recv() => {
+                    info!("Received SIGHUP; reloading");
+                    systemd::notify_reloading();
+                    reload(&state, load_config());
+                    systemd::notify_ready(&status(&state));
+                }
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = load_config;
+        loop {
+            if tokio::signal::ctrl_c().await.is_err() {
+                return;
+            }
+            stop(&state, "Ctrl-C");
+        }
+    }
+}
+
+fn stop(state: &ServiceState, signal: &str) {
+    if state.begin_shutdown() {
+        info!("Received {signal}; shutting down gracefully (send it again to exit now)");
+    } else {
+        warn!("Received {signal} again; exiting without waiting");
+        std::process::exit(1);
+    }
+}
+
+/// Applies a re-read configuration, keeping the current one when it is invalid.
+pub fn reload(
+    state: &ServiceState,
+    config: anyhow::Result<IndexServiceConfig>,
+) -> Option<ReloadReport> {
+    let config = match config.and_then(|config| config.validate().map(|()| config)) {
+        Ok(config) => config,
+        Err(err) => {
+            warn!("Reload failed; keeping the current configuration: {err:#}");
+            return None;
+        }
+    };
+    let report = match state.reload(&config) {
+        Ok(report) => report,
+        Err(err) => {
+            warn!("Reload failed: {err}");
+            return None;
+        }
+    };
+    info!(
+        "Reloaded: {} tokens, {} repositories registered, {} indices reloaded",
+        report.tokens,
+        report.registered,
+        report.reloaded.len()
+    );
+    if !report.restart_required.is_empty() {
+        warn!(
+            "Changed settings need a restart: {}",
+            report.restart_required.join(", ")
+        );
+    }
+    Some(report)
+}
+
+/// Status line for systemd: what the default repository serves.
+pub fn status(state: &ServiceState) -> String {
+    match state.current() {
+        Some(index) => format!(
+            "Serving generation {} ({} nodes)",
+            index.generation,
+            index.graph.node_count()
+        ),
+        None => "Serving; no index built yet".to_string(),
+    }
+}
+
+/// Sends `WATCHDOG=1` at half the interval systemd expects, if it has one.
+pub fn spawn_watchdog() {
+    let Some(interval) = systemd::watchdog_interval() else {
+        return;
+    };
+    info!("systemd watchdog enabled ({} ms)", interval.as_millis());
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval / 2);
+        loop {
+            ticks.tick().await;
+            systemd::notify_watchdog();
+        }
+    });
+}
+
+/// Runs `serve` (all transports) and then shuts down gracefully.
+///
+/// Once the service begins shutting down, requests in flight and rebuild jobs
+/// share `INDEX_SHUTDOWN_TIMEOUT`: transports still busy after it are
+/// dropped, and jobs still running are cancelled. When `serve` returns on
+/// its own (stdin closed, or an error) the shutdown starts then.
+pub async fn run<F>(state: &ServiceState, serve: F) -> anyhow::Result<()>
+where
+    F: Future<Output = anyhow::Result<()>>,
+{
+    let timeout = Duration::from_secs(state.config().shutdown_timeout_secs);
+    tokio::pin!(serve);
+    let (result, deadline) = tokio::select! {
+        result = &mut serve => (result, Instant::now() + timeout),
+        () = state.shutdown_signal() => {
+            systemd::notify_stopping("Draining requests");
+            let deadline = Instant::now() + timeout;
+            let result = tokio::time::timeout_at(deadline, &mut serve)
+                .await
+                .unwrap_or_else(|_| {
+                    warn!("Requests still in flight after {}s; closing them", timeout.as_secs());
+                    Ok(())
+                });
+            (result, deadline)
+        }
+    };
+    state.begin_shutdown();
+    systemd::notify_stopping("Finishing rebuild jobs");
+    finish_jobs(state, deadline).await;
+    info!("CDS-Index Service stopped");
+    result
+}
+
+/// Waits until `deadline` for active rebuild jobs, then cancels the rest and
+/// waits for them to stop. Returns how many jobs were cancelled.
+pub async fn finish_jobs(state: &ServiceState, deadline: Instant) -> usize {
+    let active = state.jobs().active();
+    if active.is_empty() {
+        return 0;
+    }
+    info!(
+        "Waiting up to {}s for {} rebuild jobs",
+        deadline.saturating_duration_since(Instant::now()).as_secs(),
+        active.len()
+    );
+    while Instant::now() < deadline && !state.jobs().active().is_empty() {
+        tokio::time::sleep(JOB_POLL_INTERVAL).await;
+    }
+
+    let remaining = state.jobs().active();
+    for job in &remaining {
+        warn!(
+            "{}: cancelling rebuild of '{}' for shutdown",
+            job.id(),
+            job.repo()
+        );
+        job.request_cancel();
+    }
+    let grace = Instant::now() + CANCEL_GRACE;
+    while Instant::now() < grace && !state.jobs().active().is_empty() {
+        tokio::time::sleep(JOB_POLL_INTERVAL).await;
+    }
+    if !state.jobs().active().is_empty() {
+        warn!(
+            "Rebuild jobs did not stop in time; their staged output is discarded on the next build"
+        );
+    }
+    remaining.len()
+}
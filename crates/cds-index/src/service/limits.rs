@@ -0,0 +1,113 @@
+//! Per-client rate limits and a global concurrent-request cap
+//!
+//! Both are off by default and apply to `POST /rpc` on the HTTP transports.
+//! Clients are identified by their token name when authenticated (see
+//! [`super::auth`]), else by peer IP address; requests over a Unix domain
+//! socket without a token share the `local` client.
+//!
+//! Rate limits are token buckets: each client may send `burst` requests at
+//! once, refilled at `rate_limit_per_second`. The concurrency cap bounds
+//! requests being handled at any moment across all clients. Rejections are
+//! immediate `-32010 Rate limited` errors (HTTP 429) with a retry hint.
+
+use super::jsonrpc::JsonRpcError;
+use crate::config::IndexServiceConfig;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Client buckets kept before idle, fully refilled ones are dropped.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+/// Retry hint when the concurrency cap is reached.
+const CONCURRENCY_RETRY_AFTER: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+#[derive(Debug)]
+struct RateLimit {
+    per_second: f64,
+    burst: f64,
+    buckets: DashMap<String, Bucket>,
+}
+
+impl RateLimit {
+    /// Takes one token from `client`'s bucket, or returns how long until one is available.
+    fn take(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        if self.buckets.len() >= MAX_TRACKED_CLIENTS && !self.buckets.contains_key(client) {
+            self.buckets
+                .retain(|_, bucket| self.refill(*bucket, now).tokens < self.burst);
+        }
+        let mut bucket = self.buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            refilled_at: now,
+        });
+        *bucket = self.refill(*bucket, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.per_second,
+            ))
+        }
+    }
+
+    fn refill(&self, bucket: Bucket, now: Instant) -> Bucket {
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        Bucket {
+            tokens: (bucket.tokens + elapsed.as_secs_f64() * self.per_second).min(self.burst),
+            refilled_at: now,
+        }
+    }
+}
+
+/// Admission control for incoming requests.
+#[derive(Debug, Default)]
+pub struct RequestLimiter {
+    rate: Option<RateLimit>,
+    concurrency: Option<Arc<Semaphore>>,
+}
+
+/// Held while a request is handled; releases its concurrency slot on drop.
+#[derive(Debug)]
+pub struct Admission {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl RequestLimiter {
+    pub fn new(config: &IndexServiceConfig) -> Self {
+        let rate = (config.rate_limit_per_second > 0.0).then(|| RateLimit {
+            per_second: config.rate_limit_per_second,
+            burst: f64::from(config.rate_limit_burst.max(1)),
+            buckets: DashMap::new(),
+        });
+        let concurrency = (config.max_concurrent_requests > 0)
+            .then(|| Arc::new(Semaphore::new(config.max_concurrent_requests)));
+        Self { rate, concurrency }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.rate.is_some() || self.concurrency.is_some()
+    }
+
+    /// Admits one request from `client`, failing with `-32010` when its rate
+    /// limit or the global concurrency cap is exhausted.
+    pub fn admit(&self, client: &str) -> Result<Admission, JsonRpcError> {
+        if let Some(rate) = &self.rate {
+            rate.take(client, Instant::now())
+                .map_err(|retry_after| JsonRpcError::rate_limited("rate", client, retry_after))?;
+        }
+        let permit = match &self.concurrency {
+            Some(semaphore) => Some(Arc::clone(semaphore).try_acquire_owned().map_err(|_| {
+                JsonRpcError::rate_limited("concurrency", client, CONCURRENCY_RETRY_AFTER)
+            })?),
+            None => None,
+        };
+        Ok(Admission { _permit: permit })
+    }
+}
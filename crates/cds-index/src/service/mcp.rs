@@ -0,0 +1,211 @@
+//! Model Context Protocol (MCP) server
+//!
+//! Exposes the index as MCP tools over stdin/stdout so MCP-capable agents can
+//! query it directly instead of shelling out to the `cds` CLI. Each tool is a
+//! JSON-RPC method of this service: its `inputSchema`/`outputSchema` are the
+//! method's `params`/`result` schemas from docs/api/jsonrpc-schema.json (with
+//! `$ref`s inlined), and `tools/call` dispatches through [`handle_request_from`],
+//! so results are identical to the other transports.
+//!
+//! Errors raised by a tool (e.g. `-32001 Index not found`) are returned as
+//! tool results with `isError: true` carrying the JSON-RPC error, which lets
+//! the model read the suggestion and recover; only protocol errors (unknown
+//! tool or method) are JSON-RPC errors.
+
+use super::audit::Caller;
+use super::handlers::handle_request_from;
+use super::jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse};
+use super::state::ServiceState;
+use super::stdio::pump_lines;
+use serde_json::{json, Map, Value};
+use std::io;
+use std::sync::{Arc, OnceLock};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::{debug, info};
+
+const JSONRPC_SCHEMA: &str = include_str!("../../../../docs/api/jsonrpc-schema.json");
+
+/// Service methods exposed as MCP tools, in `tools/list` order.
+pub const MCP_TOOLS: &[&str] = &[
+    "search_entities",
+    "traverse_graph",
+    "retrieve_entity",
+    "session_summary",
+    "rebuild_index",
+    "get_job_status",
+];
+
+/// Protocol revisions this server speaks, newest first.
+pub const MCP_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+const SERVER_INSTRUCTIONS: &str = "Code index of the repository: search_entities finds \
+functions, classes and files by name or keyword; traverse_graph follows contain/import/\
+invoke/inherit relations; retrieve_entity returns source code. Pass the same session_id to \
+each call and exclude_seen: true to skip entities you have already been shown; session_summary \
+lists what you explored and linked entities you have not. If a tool reports 'Index not \
+found', call rebuild_index and poll get_job_status until it completes.";
+
+/// Serves MCP requests from the process's stdin until it is closed.
+pub async fn serve_mcp(state: Arc<ServiceState>) -> io::Result<()> {
+    info!("CDS-Index Service serving MCP on stdin/stdout");
+    serve_mcp_lines(state, tokio::io::stdin(), tokio::io::stdout()).await
+}
+
+/// Serves newline-delimited MCP messages read from `input`, writing replies to `output`.
+pub async fn serve_mcp_lines<R, W>(state: Arc<ServiceState>, input: R, output: W) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let stop = state.shutdown_signal();
+    pump_lines(input, output, stop, move |line| {
+        let state = Arc::clone(&state);
+        async move { handle_message(&state, &line).await }
+    })
+    .await
+}
+
+/// Handles one MCP message; notifications and stray responses yield `None`.
+pub async fn handle_message(state: &Arc<ServiceState>, line: &str) -> Option<Value> {
+    let message: Value = match serde_json::from_str(line) {
+        Ok(message) => message,
+        Err(err) => return reply(Value::Null, Err(JsonRpcError::parse_error(err))),
+    };
+    let Some(object) = message.as_object() else {
+        return reply(
+            Value::Null,
+            Err(JsonRpcError::invalid_request(
+                "expected a single JSON-RPC message",
+            )),
+        );
+    };
+    let Some(method) = object.get("method").and_then(Value::as_str) else {
+        // We never send requests, so responses from the client are ignored.
+        return None;
+    };
+    let Some(id) = object.get("id").cloned() else {
+        debug!("MCP notification: {method}");
+        return None;
+    };
+    let params = object.get("params").cloned().unwrap_or(Value::Null);
+    let result = match method {
+        "initialize" => Ok(initialize(&params)),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+        "tools/call" => call_tool(state, id.clone(), &params).await,
+        other => Err(JsonRpcError::method_not_found(other)),
+    };
+    reply(id, result)
+}
+
+fn reply(id: Value, result: Result<Value, JsonRpcError>) -> Option<Value> {
+    let response = match result {
+        Ok(result) => JsonRpcResponse::success(id, result),
+        Err(error) => JsonRpcResponse::failure(id, error),
+    };
+    serde_json::to_value(response).ok()
+}
+
+fn initialize(params: &Value) -> Value {
+    let requested = params.get("protocolVersion").and_then(Value::as_str);
+    let version = requested
+        .filter(|version| MCP_PROTOCOL_VERSIONS.contains(version))
+        .unwrap_or(MCP_PROTOCOL_VERSIONS[0]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": { "listChanged": false } },
+        "serverInfo": { "name": "cds-index", "version": env!("CARGO_PKG_VERSION") },
+        "instructions": SERVER_INSTRUCTIONS,
+    })
+}
+
+async fn call_tool(
+    state: &Arc<ServiceState>,
+    id: Value,
+    params: &Value,
+) -> Result<Value, JsonRpcError> {
+    let name = params
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| JsonRpcError::invalid_params("tools/call requires a tool name"))?;
+    if !MCP_TOOLS.contains(&name) {
+        return Err(JsonRpcError::invalid_params(format!(
+            "unknown tool '{name}'"
+        )));
+    }
+    let arguments = params
+        .get("arguments")
+        .cloned()
+        .unwrap_or_else(|| json!({}));
+    let request = JsonRpcRequest::new(name, arguments, id);
+    let response = handle_request_from(state, request, &Caller::new("mcp")).await;
+    Ok(match (response.result, response.error) {
+        (_, Some(error)) => json!({
+            "content": [{ "type": "text", "text": json!({ "error": error }).to_string() }],
+            "isError": true,
+        }),
+        (result, None) => {
+            let result = result.unwrap_or(Value::Null);
+            json!({
+                "content": [{ "type": "text", "text": result.to_string() }],
+                "structuredContent": result,
+                "isError": false,
+            })
+        }
+    })
+}
+
+/// `tools/list` entries, derived once from the embedded JSON-RPC schema.
+pub fn tool_definitions() -> &'static [Value] {
+    static TOOLS: OnceLock<Vec<Value>> = OnceLock::new();
+    TOOLS.get_or_init(|| {
+        let schema: Value =
+            serde_json::from_str(JSONRPC_SCHEMA).expect("embedded jsonrpc-schema.json is valid");
+        let definitions = schema["definitions"]
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        MCP_TOOLS
+            .iter()
+            .map(|name| {
+                let method = &schema["methods"][*name];
+                json!({
+                    "name": name,
+                    "description": method["description"],
+                    "inputSchema": inline_refs(&method["params"], &definitions),
+                    "outputSchema": inline_refs(&method["result"], &definitions),
+                })
+            })
+            .collect()
+    })
+}
+
+/// Replaces `{"$ref": "#/definitions/x"}` with the definition itself, since
+/// tool schemas must be self-contained.
+fn inline_refs(schema: &Value, definitions: &Map<String, Value>) -> Value {
+    match schema {
+        Value::Object(object) => {
+            let target = object
+                .get("$ref")
+                .and_then(Value::as_str)
+                .and_then(|reference| reference.strip_prefix("#/definitions/"))
+                .and_then(|name| definitions.get(name));
+            match target {
+                Some(definition) => inline_refs(definition, definitions),
+                None => Value::Object(
+                    object
+                        .iter()
+                        .map(|(key, value)| (key.clone(), inline_refs(value, definitions)))
+                        .collect(),
+                ),
+            }
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| inline_refs(item, definitions))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
@@ -1,9 +1,36 @@
 //! JSON-RPC service layer
 //!
-//! Exposes graph and index functionality via HTTP/JSON-RPC
+//! Exposes graph and index functionality via JSON-RPC over HTTP (TCP or a
+//! Unix domain socket) or stdin/stdout, as MCP tools and over gRPC; every
+//! transport dispatches through [`handle_request`].
 
+pub mod audit;
+pub mod auth;
+pub mod cache;
+pub mod changes;
+pub mod cursor;
+pub mod events;
+pub mod grpc;
 pub mod handlers;
+pub mod jobs;
+pub mod jsonrpc;
+pub mod lifecycle;
+pub mod limits;
+pub mod mcp;
+pub mod repos;
 pub mod server;
+pub mod sessions;
+pub mod state;
+pub mod stdio;
+pub mod systemd;
 
-// Placeholder types
-pub struct RpcHandler;
+pub use audit::{AuditLog, AuditRecord, Caller};
+pub use auth::{ApiToken, Authenticator, Principal, Role};
+pub use changes::{FileChange, FileStatus};
+pub use events::{EventBus, IndexEvent};
+pub use handlers::{handle_request, handle_request_from};
+pub use jobs::{JobManager, JobSnapshot, JobStatus};
+pub use jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse};
+pub use sessions::{SeenLevel, SessionStore};
+pub use repos::{RepoHandle, RepoInfo, RepoRegistry, DEFAULT_REPO};
+pub use state::{LoadedIndex, RebuildStats, ReloadReport, ServiceState};
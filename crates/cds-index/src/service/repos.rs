@@ -0,0 +1,445 @@
+//! Named repositories served by one service process
+//!
+//! Each repository has its own [`IndexLayout`] and served [`LoadedIndex`]
+//! snapshot. The `default` repository maps to `GRAPH_INDEX_DIR` /
+//! `BM25_INDEX_DIR` and answers requests that omit `repo`; further ones are
+//! registered with `add_repo`, persisted in `<repos_dir>/repos.json` and only
+//! loaded on their first query. At most `max_loaded_repos` indices are kept in
+//! memory - the least recently queried ones are dropped and reloaded lazily.
+
+use super::jsonrpc::JsonRpcError;
+use super::state::{IndexBuildError, LoadedIndex};
+use crate::config::IndexServiceConfig;
+use crate::persistence::{self, IndexLayout, PersistenceError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::info;
+
+/// Repository used when a request does not name one.
+pub const DEFAULT_REPO: &str = "default";
+pub const REGISTRY_FILE: &str = "repos.json";
+
+const MAX_REPO_NAME_LEN: usize = 64;
+
+/// Entry of `repos.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RepoRecord {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    repo_path: Option<PathBuf>,
+    layout: IndexLayout,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RegistryFile {
+    repos: Vec<RepoRecord>,
+}
+
+/// Summary of one repository, as returned by `list_repos` / `add_repo` / `remove_repo`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoInfo {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo_path: Option<PathBuf>,
+    pub index_path: PathBuf,
+    pub loaded: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub built_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edge_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_job: Option<String>,
+}
+
+/// One named repository and its (possibly unloaded) index.
+#[derive(Debug)]
+pub struct RepoHandle {
+    name: String,
+    layout: IndexLayout,
+    repo_path: RwLock<Option<PathBuf>>,
+    current: RwLock<Option<Arc<LoadedIndex>>>,
+    latest_generation: AtomicU64,
+    last_used: AtomicU64,
+    load_lock: Mutex<()>,
+}
+
+impl RepoHandle {
+    fn new(name: String, layout: IndexLayout, repo_path: Option<PathBuf>) -> Self {
+        Self {
+            name,
+            layout,
+            repo_path: RwLock::new(repo_path),
+            current: RwLock::new(None),
+            latest_generation: AtomicU64::new(0),
+            last_used: AtomicU64::new(0),
+            load_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn layout(&self) -> &IndexLayout {
+        &self.layout
+    }
+
+    /// Source directory registered for this repository, if known.
+    pub fn repo_path(&self) -> Option<PathBuf> {
+        self.repo_path
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .clone()
+    }
+
+    fn set_repo_path(&self, path: PathBuf) -> bool {
+        let mut current = self.repo_path.write().unwrap_or_else(|p| p.into_inner());
+        if current.as_ref() == Some(&path) {
+            return false;
+        }
+        *current = Some(path);
+        true
+    }
+
+    /// Snapshot of the served index, without loading it.
+    pub fn current(&self) -> Option<Arc<LoadedIndex>> {
+        self.current
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .clone()
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.current().is_some()
+    }
+
+    /// Returns the served index, loading the persisted one on first use.
+    ///
+    /// Returns `Ok(None)` when nothing has been built for this repository yet.
+    pub fn load(&self) -> Result<Option<Arc<LoadedIndex>>, IndexBuildError> {
+        if let Some(index) = self.current() {
+            return Ok(Some(index));
+        }
+        let _guard = self.load_lock.lock().unwrap_or_else(|p| p.into_inner());
+        if let Some(index) = self.current() {
+            return Ok(Some(index));
+        }
+        if !self.layout.exists() {
+            return Ok(None);
+        }
+        let index = LoadedIndex::load(&self.layout)?;
+        info!(
+            "[{}] loaded index generation {} for {} ({} nodes)",
+            self.name,
+            index.generation,
+            index.repo_root.display(),
+            index.graph.node_count()
+        );
+        self.swap(index);
+        Ok(self.current())
+    }
+
+    /// Re-reads the persisted index when it is loaded and the one on disk was
+    /// replaced by another process (e.g. `cds init`). Returns the generation
+    /// now served, or `None` when nothing changed.
+    pub fn reload(&self) -> Result<Option<u64>, IndexBuildError> {
+        let Some(current) = self.current() else {
+            return Ok(None);
+        };
+        let _guard = self.load_lock.lock().unwrap_or_else(|p| p.into_inner());
+        let Ok(manifest) = persistence::load_manifest(&self.layout.graph_dir) else {
+            return Ok(None);
+        };
+        if manifest.generation == current.generation && manifest.built_at == current.built_at {
+            return Ok(None);
+        }
+        let index = LoadedIndex::load(&self.layout)?;
+        let generation = index.generation;
+        info!(
+            "[{}] reloaded index generation {} from disk (was {})",
+            self.name, generation, current.generation
+        );
+        self.swap(index);
+        Ok(Some(generation))
+    }
+
+    /// Reserves the generation number for the next build of this repository.
+    pub fn next_generation(&self) -> u64 {
+        // The index may not be loaded yet; never reuse a persisted generation.
+        if let Ok(manifest) = persistence::load_manifest(&self.layout.graph_dir) {
+            self.latest_generation
+                .fetch_max(manifest.generation, Ordering::SeqCst);
+        }
+        self.latest_generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Atomically replaces the served index, returning the previous one.
+    pub fn swap(&self, index: LoadedIndex) -> Option<Arc<LoadedIndex>> {
+        self.latest_generation
+            .fetch_max(index.generation, Ordering::SeqCst);
+        let mut current = self.current.write().unwrap_or_else(|p| p.into_inner());
+        current.replace(Arc::new(index))
+    }
+
+    /// Drops the in-memory index; queries in flight keep their snapshot.
+    fn unload(&self) -> bool {
+        self.current
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .take()
+            .is_some()
+    }
+
+    pub fn info(&self) -> RepoInfo {
+        let mut info = RepoInfo {
+            name: self.name.clone(),
+            repo_path: self.repo_path(),
+            index_path: self.layout.graph_dir.clone(),
+            loaded: false,
+            generation: None,
+            built_at: None,
+            node_count: None,
+            edge_count: None,
+            active_job: None,
+        };
+        if let Some(index) = self.current() {
+            info.loaded = true;
+            info.repo_path
+                .get_or_insert_with(|| index.repo_root.clone());
+            info.generation = Some(index.generation);
+            info.built_at = Some(index.built_at);
+            info.node_count = Some(index.graph.node_count());
+            info.edge_count = Some(index.graph.edge_count());
+        } else if let Ok(manifest) = persistence::load_manifest(&self.layout.graph_dir) {
+            info.repo_path.get_or_insert(manifest.repo_root);
+            info.generation = Some(manifest.generation);
+            info.built_at = Some(manifest.built_at);
+            info.node_count = Some(manifest.node_count);
+            info.edge_count = Some(manifest.edge_count);
+        }
+        info
+    }
+}
+
+/// All repositories known to the service.
+#[derive(Debug)]
+pub struct RepoRegistry {
+    repos_dir: PathBuf,
+    max_loaded: usize,
+    repos: RwLock<BTreeMap<String, Arc<RepoHandle>>>,
+    clock: AtomicU64,
+}
+
+impl RepoRegistry {
+    /// Registry containing only the `default` repository.
+    pub fn new(config: &IndexServiceConfig) -> Self {
+        let default = RepoHandle::new(
+            DEFAULT_REPO.to_string(),
+            IndexLayout::new(&config.graph_index_dir, &config.bm25_index_dir),
+            None,
+        );
+        let mut repos = BTreeMap::new();
+        repos.insert(DEFAULT_REPO.to_string(), Arc::new(default));
+        Self {
+            repos_dir: config.repos_dir.clone(),
+            max_loaded: config.max_loaded_repos.max(1),
+            repos: RwLock::new(repos),
+            clock: AtomicU64::new(0),
+        }
+    }
+
+    pub fn max_loaded(&self) -> usize {
+        self.max_loaded
+    }
+
+    /// Registers the repositories persisted in `repos.json` without loading
+    /// them. Already registered ones are kept as they are; returns how many
+    /// were added.
+    pub fn load_registered(&self) -> Result<usize, PersistenceError> {
+        let path = self.repos_dir.join(REGISTRY_FILE);
+        if !path.is_file() {
+            return Ok(0);
+        }
+        let file: RegistryFile = serde_json::from_slice(&fs::read(path)?)?;
+        let mut repos = self.write();
+        let mut added = 0;
+        for record in file.repos {
+            repos.entry(record.name.clone()).or_insert_with(|| {
+                added += 1;
+                Arc::new(RepoHandle::new(
+                    record.name,
+                    record.layout,
+                    record.repo_path,
+                ))
+            });
+        }
+        Ok(added)
+    }
+
+    /// Looks up `name` (or the default repository), failing with `-32007`.
+    pub fn get(&self, name: Option<&str>) -> Result<Arc<RepoHandle>, JsonRpcError> {
+        let name = name.unwrap_or(DEFAULT_REPO);
+        let repos = self.read();
+        repos.get(name).cloned().ok_or_else(|| {
+            JsonRpcError::repo_not_found(name, repos.keys().map(String::as_str).collect())
+        })
+    }
+
+    pub fn list(&self) -> Vec<Arc<RepoHandle>> {
+        self.read().values().cloned().collect()
+    }
+
+    pub fn loaded_count(&self) -> usize {
+        self.read().values().filter(|repo| repo.is_loaded()).count()
+    }
+
+    /// Registers a new repository indexed under `<repos_dir>/<name>`.
+    pub fn add(&self, name: &str, repo_path: PathBuf) -> Result<Arc<RepoHandle>, JsonRpcError> {
+        validate_repo_name(name)?;
+        let mut repos = self.write();
+        if repos.contains_key(name) {
+            return Err(JsonRpcError::invalid_params(format!(
+                "repository '{name}' is already registered"
+            )));
+        }
+        let layout = IndexLayout::under(&self.index_root(name));
+        let handle = Arc::new(RepoHandle::new(name.to_string(), layout, Some(repo_path)));
+        repos.insert(name.to_string(), Arc::clone(&handle));
+        if let Err(err) = self.save(&repos) {
+            repos.remove(name);
+            return Err(JsonRpcError::internal(err));
+        }
+        Ok(handle)
+    }
+
+    /// Unregisters `name`; the `default` repository cannot be removed.
+    pub fn remove(&self, name: &str) -> Result<Arc<RepoHandle>, JsonRpcError> {
+        if name == DEFAULT_REPO {
+            return Err(JsonRpcError::invalid_params(
+                "the default repository cannot be removed",
+            ));
+        }
+        let mut repos = self.write();
+        let handle = repos.remove(name).ok_or_else(|| {
+            JsonRpcError::repo_not_found(name, repos.keys().map(String::as_str).collect())
+        })?;
+        if let Err(err) = self.save(&repos) {
+            repos.insert(name.to_string(), Arc::clone(&handle));
+            return Err(JsonRpcError::internal(err));
+        }
+        Ok(handle)
+    }
+
+    /// Records the source directory of a repository, persisting it for named ones.
+    pub fn set_repo_path(
+        &self,
+        handle: &RepoHandle,
+        repo_path: PathBuf,
+    ) -> Result<(), JsonRpcError> {
+        if !handle.set_repo_path(repo_path) || handle.name() == DEFAULT_REPO {
+            return Ok(());
+        }
+        self.save(&self.read()).map_err(JsonRpcError::internal)
+    }
+
+    /// Directory holding the index of a named repository.
+    pub fn index_root(&self, name: &str) -> PathBuf {
+        self.repos_dir.join(name)
+    }
+
+    /// Marks `handle` as just used (for LRU unloading).
+    pub fn touch(&self, handle: &RepoHandle) {
+        let tick = self.clock.fetch_add(1, Ordering::SeqCst) + 1;
+        handle.last_used.store(tick, Ordering::SeqCst);
+    }
+
+    /// Unloads least recently used indices until at most `max_loaded` remain.
+    ///
+    /// `keep` is never unloaded (it is the repository that was just used).
+    pub fn enforce_limit(&self, keep: &str) -> Vec<String> {
+        let mut loaded: Vec<Arc<RepoHandle>> = self
+            .list()
+            .into_iter()
+            .filter(|repo| repo.is_loaded())
+            .collect();
+        if loaded.len() <= self.max_loaded {
+            return Vec::new();
+        }
+        loaded.sort_by_key(|repo| repo.last_used.load(Ordering::SeqCst));
+        let excess = loaded.len() - self.max_loaded;
+        let mut unloaded = Vec::new();
+        for repo in loaded.iter().filter(|repo| repo.name() != keep) {
+            if unloaded.len() == excess {
+                break;
+            }
+            if repo.unload() {
+                info!("[{}] unloaded idle index", repo.name());
+                unloaded.push(repo.name().to_string());
+            }
+        }
+        unloaded
+    }
+
+    fn save(&self, repos: &BTreeMap<String, Arc<RepoHandle>>) -> Result<(), PersistenceError> {
+        let file = RegistryFile {
+            repos: repos
+                .values()
+                .filter(|repo| repo.name() != DEFAULT_REPO)
+                .map(|repo| RepoRecord {
+                    name: repo.name().to_string(),
+                    repo_path: repo.repo_path(),
+                    layout: repo.layout().clone(),
+                })
+                .collect(),
+        };
+        fs::create_dir_all(&self.repos_dir)?;
+        let path = self.repos_dir.join(REGISTRY_FILE);
+        let staged = path.with_extension("json.tmp");
+        fs::write(&staged, serde_json::to_vec_pretty(&file)?)?;
+        fs::rename(&staged, &path)?;
+        Ok(())
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, BTreeMap<String, Arc<RepoHandle>>> {
+        self.repos.read().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, BTreeMap<String, Arc<RepoHandle>>> {
+        self.repos.write().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+/// Repository names double as directory names: `[A-Za-z0-9_.-]`, not starting with `.`.
+pub fn validate_repo_name(name: &str) -> Result<(), JsonRpcError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_REPO_NAME_LEN
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(JsonRpcError::invalid_params(format!(
+            "invalid repository name '{name}' (use up to {MAX_REPO_NAME_LEN} of [A-Za-z0-9_.-], not starting with '.')"
+        )))
+    }
+}
+
+/// Removes the on-disk index of a named repository.
+pub fn delete_index_root(root: &Path) -> Result<bool, PersistenceError> {
+    if !root.exists() {
+        return Ok(false);
+    }
+    fs::remove_dir_all(root)?;
+    Ok(true)
+}
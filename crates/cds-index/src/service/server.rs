@@ -1,4 +1,277 @@
 //! Axum-based JSON-RPC server
+//!
+//! The router is served over TCP ([`serve`]) or a Unix domain socket
+//! ([`serve_unix`]); see [`super::stdio`] for the stdin/stdout transport.
+//!
+//! Routes:
+//! - `POST /rpc` - JSON-RPC 2.0 endpoint
+//! - `GET /health` - liveness plus the served index generation
+//! - `GET /events[?repo=<name>]` - server-sent index change notifications
+//!   (see [`super::events`])
+//!
+//! When tokens are configured, `/rpc` and `/events` require one (see
+//! [`super::auth`]): unknown or missing tokens get `401` with `-32008`, and
+//! methods outside the token's role get `403` with `-32009`. Configured rate
+//! limits and the concurrency cap (see [`super::limits`]) are enforced on
+//! `/rpc` before authentication; rejections get `429` with `-32010`.
+//!
+//! Both servers stop accepting connections once the service begins shutting
+//! down and return after the requests in flight were answered; open `/events`
+//! streams are closed.
+//!
+//! Reference: PRD-02 §4.1, PRD-05 §2.2
 
-// TODO: Implement HTTP server with axum
-// Reference: PRD-02 §4.1, PRD-05 §2.2
+use super::audit::{session_header, Caller};
+use super::events::IndexEvent;
+use super::handlers::{handle_request_from, health_status};
+use super::jsonrpc::{
+    parse_request, JsonRpcError, JsonRpcResponse, FORBIDDEN, RATE_LIMITED, UNAUTHORIZED,
+};
+use super::state::ServiceState;
+use axum::extract::{ConnectInfo, Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::{BroadcastStream, WatchStream};
+use tokio_stream::StreamExt;
+use tower_http::trace::TraceLayer;
+use tracing::info;
+
+/// Builds the HTTP router for the service.
+pub fn router(state: Arc<ServiceState>) -> Router {
+    Router::new()
+        .route(
+            "/rpc",
+            post(rpc_endpoint).route_layer(middleware::from_fn_with_state(
+                Arc::clone(&state),
+                limit_requests,
+            )),
+        )
+        .route("/health", get(health_endpoint))
+        .route("/events", get(events_endpoint))
+        .layer(TraceLayer::new_for_http())
+        .with_state(state)
+}
+
+/// Binds `addr` and serves the router until the service shuts down, then
+/// waits for requests in flight (see [`ServiceState::begin_shutdown`]).
+pub async fn serve(state: Arc<ServiceState>, addr: SocketAddr) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(
+        "CDS-Index Service listening on http://{}/rpc",
+        listener.local_addr()?
+    );
+    let shutdown = state.shutdown_signal();
+    axum::serve(
+        listener,
+        router(state).into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown)
+    .await?;
+    Ok(())
+}
+
+/// Binds a Unix domain socket at `path` (see [`bind_unix`]) and serves the
+/// router on it until the service shuts down.
+#[cfg(unix)]
+pub async fn serve_unix(state: Arc<ServiceState>, path: &Path, mode: u32) -> anyhow::Result<()> {
+    let listener = bind_unix(path, mode)?;
+    info!(
+        "CDS-Index Service listening on unix:{} (mode {mode:o})",
+        path.display()
+    );
+    let shutdown = state.shutdown_signal();
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    Ok(())
+}
+
+/// Binds `path` and restricts the socket file to `mode` (e.g. `0o600`).
+///
+/// A stale socket left behind by a previous process is replaced; binding
+/// fails if the path is not a socket or another process still listens on it.
+#[cfg(unix)]
+pub fn bind_unix(path: &Path, mode: u32) -> anyhow::Result<UnixListener> {
+    use std::fs;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!("{} exists and is not a socket", path.display());
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            anyhow::bail!("another process is listening on {}", path.display());
+        }
+        fs::remove_file(path)?;
+    }
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(listener)
+}
+
+async fn rpc_endpoint(
+    State(state): State<Arc<ServiceState>>,
+    Extension(caller): Extension<Caller>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let request = match parse_request(&body) {
+        Ok(request) => request,
+        Err(response) => return (StatusCode::BAD_REQUEST, Json(*response)).into_response(),
+    };
+    if let Err(error) = state.auth().authorize(&headers, &request.method) {
+        return rejection(request.id, error);
+    }
+    Json(handle_request_from(&state, request, &caller).await).into_response()
+}
+
+/// `401` (with a `WWW-Authenticate` challenge), `403` or `429` (with
+/// `Retry-After`) carrying the JSON-RPC error.
+fn rejection(id: Value, error: JsonRpcError) -> Response {
+    let status = match error.code {
+        UNAUTHORIZED => StatusCode::UNAUTHORIZED,
+        FORBIDDEN => StatusCode::FORBIDDEN,
+        RATE_LIMITED => StatusCode::TOO_MANY_REQUESTS,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let retry_after_ms = error
+        .data
+        .as_ref()
+        .and_then(|data| data.get("retry_after_ms"))
+        .and_then(Value::as_u64);
+    let mut response = (status, Json(JsonRpcResponse::failure(id, error))).into_response();
+    let headers = response.headers_mut();
+    if status == StatusCode::UNAUTHORIZED {
+        headers.insert(
+            header::WWW_AUTHENTICATE,
+            header::HeaderValue::from_static("Bearer"),
+        );
+    }
+    if let Some(retry_after_ms) = retry_after_ms {
+        headers.insert(
+            header::RETRY_AFTER,
+            header::HeaderValue::from(retry_after_ms.div_ceil(1000)),
+        );
+    }
+    response
+}
+
+/// Identifies the caller of `/rpc` (recorded in the audit log) and admits
+/// the request through [`ServiceState::limits`]; the concurrency slot is
+/// held until the response is ready.
+async fn limit_requests(
+    State(state): State<Arc<ServiceState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let caller =
+        Caller::new(client_key(&state, &request)).with_session(session_header(request.headers()));
+    let client = caller.client.clone();
+    request.extensions_mut().insert(caller);
+    if !state.limits().is_enabled() {
+        return next.run(request).await;
+    }
+    match state.limits().admit(&client) {
+        Ok(admission) => {
+            let response = next.run(request).await;
+            drop(admission);
+            response
+        }
+        Err(error) => rejection(Value::Null, error),
+    }
+}
+
+/// `token:<name>` for authenticated callers, else the peer IP (`local` on a Unix socket).
+fn client_key(state: &ServiceState, request: &Request) -> String {
+    if let Ok(Some(principal)) = state.auth().authenticate(request.headers()) {
+        return format!("token:{}", principal.name);
+    }
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "local".to_string())
+}
+
+async fn health_endpoint(State(state): State<Arc<ServiceState>>) -> Json<Value> {
+    Json(health_status(&state))
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    repo: Option<String>,
+}
+
+async fn events_endpoint(
+    State(state): State<Arc<ServiceState>>,
+    headers: HeaderMap,
+    Query(query): Query<EventsQuery>,
+) -> Response {
+    if let Err(error) = state.auth().authenticate(&headers) {
+        return rejection(Value::Null, error);
+    }
+    if let Some(repo) = &query.repo {
+        if let Err(error) = state.repos().get(Some(repo)) {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(JsonRpcResponse::failure(Value::Null, error)),
+            )
+                .into_response();
+        }
+    }
+    // Streams end on shutdown so they do not hold up the graceful drain.
+    let stopping = WatchStream::new(state.subscribe_shutdown())
+        .filter(|stopping| *stopping)
+        .map(|_| None);
+    let stream =
+        BroadcastStream::new(state.events().subscribe()).filter_map(
+            move |received| match received {
+                Ok(event)
+                    if query
+                        .repo
+                        .as_deref()
+                        .is_none_or(|repo| repo == event.repo()) =>
+                {
+                    Some(sse_event(&event))
+                }
+                Ok(_) => None,
+                Err(BroadcastStreamRecvError::Lagged(skipped)) => Some(
+                    Event::default()
+                        .event("lagged")
+                        .json_data(json!({ "skipped": skipped })),
+                ),
+            },
+        );
+    let stream = stream
+        .map(Some)
+        .merge(stopping)
+        .take_while(Option::is_some)
+        .filter_map(|event| event);
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn sse_event(event: &IndexEvent) -> Result<Event, axum::Error> {
+    Event::default().event(event.name()).json_data(event)
+}
@@ -0,0 +1,217 @@
+//! Per-session memory of the entities already returned to an agent
+//!
+//! Query calls that carry a `session_id` record every entity they return,
+//! with the most detailed [`SeenLevel`] it was shown at, per repository. With
+//! `exclude_seen` a call leaves out entities the session has already seen at
+//! its own level or above (a traversal lists entities, a search shows its
+//! snippet mode, a retrieval shows full code); `mark_seen: false` queries
+//! without recording. `session_summary` reports what a session explored and
+//! the frontier of neighbours it has not seen yet.
+//!
+//! Sessions live in memory only. They are dropped after
+//! `INDEX_SESSION_IDLE_TIMEOUT` seconds without a call, and the least
+//! recently active ones go first when [`MAX_SESSIONS`] is reached.
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Sessions tracked at once.
+pub const MAX_SESSIONS: usize = 10_000;
+
+/// How much of an entity a call showed; ordered from least to most detail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeenLevel {
+    /// Id and name only (traversal nodes, search hits without a snippet).
+    Listed,
+    /// The signature line.
+    Fold,
+    /// The first lines of the body.
+    Preview,
+    /// The complete source.
+    Full,
+}
+
+/// An entity returned in a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeenEntity {
+    pub level: SeenLevel,
+    /// Calls that returned it.
+    pub times: u32,
+    /// Position in the order entities were first returned.
+    pub order: u64,
+}
+
+/// A snapshot of one session in one repository.
+#[derive(Debug, Clone)]
+pub struct SessionSnapshot {
+    pub created_at: DateTime<Utc>,
+    pub last_active_at: DateTime<Utc>,
+    pub calls: u64,
+    /// Entities returned in the repository, in the order first returned.
+    pub seen: Vec<(String, SeenEntity)>,
+}
+
+#[derive(Debug)]
+struct Session {
+    created_at: DateTime<Utc>,
+    last_active_at: DateTime<Utc>,
+    last_active: Instant,
+    calls: u64,
+    next_order: u64,
+    /// Seen entities by repository, then entity id.
+    seen: HashMap<String, HashMap<String, SeenEntity>>,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            created_at: Utc::now(),
+            last_active_at: Utc::now(),
+            last_active: Instant::now(),
+            calls: 0,
+            next_order: 0,
+            seen: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SessionStore {
+    sessions: DashMap<String, Session>,
+    idle_timeout: Duration,
+}
+
+impl SessionStore {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            sessions: DashMap::new(),
+            idle_timeout,
+        }
+    }
+
+    /// Entities of `repo` the session has seen at `level` or above.
+    pub fn seen_at(
+        &self,
+        session_id: &str,
+        repo: &str,
+        level: SeenLevel,
+    ) -> HashMap<String, SeenLevel> {
+        let Some(session) = self.live(session_id) else {
+            return HashMap::new();
+        };
+        session
+            .seen
+            .get(repo)
+            .into_iter()
+            .flatten()
+            .filter(|(_, seen)| seen.level >= level)
+            .map(|(id, seen)| (id.clone(), seen.level))
+            .collect()
+    }
+
+    /// Records one call of the session that returned `entities` from
+    /// `repo`, keeping the most detailed level each was shown at. Returns
+    /// how many entities the session has seen in `repo`.
+    pub fn record(
+        &self,
+        session_id: &str,
+        repo: &str,
+        entities: impl IntoIterator<Item = (String, SeenLevel)>,
+    ) -> usize {
+        self.make_room(session_id);
+        let now = Instant::now();
+        let mut session = self
+            .sessions
+            .entry(session_id.to_string())
+            .or_insert_with(Session::new);
+        if now.saturating_duration_since(session.last_active) > self.idle_timeout {
+            *session = Session::new();
+        }
+        session.last_active = now;
+        session.last_active_at = Utc::now();
+        session.calls += 1;
+        let Session {
+            seen, next_order, ..
+        } = &mut *session;
+        let seen = seen.entry(repo.to_string()).or_default();
+        for (id, level) in entities {
+            let entry = seen.entry(id).or_insert_with(|| {
+                *next_order += 1;
+                SeenEntity {
+                    level,
+                    times: 0,
+                    order: *next_order,
+                }
+            });
+            entry.level = entry.level.max(level);
+            entry.times += 1;
+        }
+        seen.len()
+    }
+
+    /// What the session has seen in `repo`; `None` for unknown or expired sessions.
+    pub fn snapshot(&self, session_id: &str, repo: &str) -> Option<SessionSnapshot> {
+        let session = self.live(session_id)?;
+        let mut seen: Vec<(String, SeenEntity)> = session
+            .seen
+            .get(repo)
+            .into_iter()
+            .flatten()
+            .map(|(id, seen)| (id.clone(), *seen))
+            .collect();
+        seen.sort_by_key(|(_, seen)| seen.order);
+        Some(SessionSnapshot {
+            created_at: session.created_at,
+            last_active_at: session.last_active_at,
+            calls: session.calls,
+            seen,
+        })
+    }
+
+    /// Forgets what every session has seen in `repo` (after `remove_repo`).
+    pub fn forget_repo(&self, repo: &str) {
+        for mut session in self.sessions.iter_mut() {
+            session.seen.remove(repo);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    fn live(&self, session_id: &str) -> Option<dashmap::mapref::one::Ref<'_, String, Session>> {
+        self.sessions
+            .get(session_id)
+            .filter(|session| session.last_active.elapsed() <= self.idle_timeout)
+    }
+
+    /// Drops expired sessions, and the least recently active one when a new
+    /// session would exceed [`MAX_SESSIONS`].
+    fn make_room(&self, session_id: &str) {
+        if self.sessions.len() < MAX_SESSIONS || self.sessions.contains_key(session_id) {
+            return;
+        }
+        let now = Instant::now();
+        self.sessions.retain(|_, session| {
+            now.saturating_duration_since(session.last_active) <= self.idle_timeout
+        });
+        if self.sessions.len() >= MAX_SESSIONS {
+            let oldest = self
+                .sessions
+                .iter()
+                .min_by_key(|session| session.last_active)
+                .map(|session| session.key().clone());
+            if let Some(oldest) = oldest {
+                self.sessions.remove(&oldest);
+            }
+        }
+    }
+}
@@ -0,0 +1,449 @@
+//! Shared service state: the served repositories and rebuild jobs
+//!
+//! Each served index is an immutable [`LoadedIndex`] behind an `Arc`. Queries
+//! clone the `Arc` and run against that snapshot, so a rebuild can replace the
+//! index at any time without disturbing requests already in flight.
+//!
+//! The state also carries the process lifecycle: [`ServiceState::begin_shutdown`]
+//! tells every transport to stop taking work, and [`ServiceState::reload`]
+//! applies a re-read configuration and picks up indices rebuilt on disk.
+
+use super::audit::AuditLog;
+use super::auth::Authenticator;
+use super::cache::QueryCache;
+use super::changes::{diff_fingerprints, fingerprint_graph};
+use super::events::{EventBus, GenerationSwapped};
+use super::jobs::{publish_changes, JobManager};
+use super::jsonrpc::JsonRpcError;
+use super::limits::RequestLimiter;
+use super::repos::RepoRegistry;
+use super::sessions::SessionStore;
+use crate::config::IndexServiceConfig;
+use crate::graph::{
+    BuildPhase, BuildProgress, DependencyGraph, EdgeKind, GraphBuilder, GraphBuilderConfig,
+    GraphError, GraphNodeIndex, NodeKind,
+};
+use crate::index::{documents_from_graph, BM25Index, Bm25Error, Bm25Params, NameIndex};
+use crate::persistence::{
+    self, BuildDiagnostics, Fingerprints, IndexLayout, IndexManifest, PersistenceError,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+#[derive(Debug, Error)]
+pub enum IndexBuildError {
+    #[error("graph build failed: {0}")]
+    Graph(GraphError),
+    #[error("BM25 index failed: {0}")]
+    Bm25(#[from] Bm25Error),
+    #[error("persistence failed: {0}")]
+    Persistence(#[from] PersistenceError),
+    #[error("build cancelled")]
+    Cancelled,
+}
+
+impl From<GraphError> for IndexBuildError {
+    fn from(err: GraphError) -> Self {
+        match err {
+            GraphError::Cancelled => IndexBuildError::Cancelled,
+            other => IndexBuildError::Graph(other),
+        }
+    }
+}
+
+/// Node counts per kind, as reported by `rebuild_index` (`entities_found`).
+///
+/// External nodes (entities of other repositories) are only counted in `external`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntityCounts {
+    pub directories: usize,
+    pub files: usize,
+    pub classes: usize,
+    pub functions: usize,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub external: usize,
+}
+
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
+/// Edge counts per kind, as reported by `rebuild_index` (`edges_created`).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EdgeCounts {
+    pub contain: usize,
+    pub import: usize,
+    pub invoke: usize,
+    pub inherit: usize,
+}
+
+impl EntityCounts {
+    pub fn from_graph(graph: &DependencyGraph) -> Self {
+        let mut counts = Self::default();
+        for node in graph.graph().node_weights() {
+            if node.is_external() {
+                counts.external += 1;
+                continue;
+            }
+            match node.kind {
+                NodeKind::Directory => counts.directories += 1,
+                NodeKind::File => counts.files += 1,
+                NodeKind::Class => counts.classes += 1,
+                NodeKind::Function => counts.functions += 1,
+            }
+        }
+        counts
+    }
+}
+
+impl EdgeCounts {
+    pub fn from_graph(graph: &DependencyGraph) -> Self {
+        let mut counts = Self::default();
+        for weight in graph.graph().edge_weights() {
+            match weight.kind {
+                EdgeKind::Contain => counts.contain += 1,
+                EdgeKind::Import => counts.import += 1,
+                EdgeKind::Invoke => counts.invoke += 1,
+                EdgeKind::Inherit => counts.inherit += 1,
+            }
+        }
+        counts
+    }
+}
+
+/// Summary of a finished build (`rebuild_index` result `stats`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebuildStats {
+    pub files_indexed: usize,
+    pub entities_found: EntityCounts,
+    pub edges_created: EdgeCounts,
+    pub build_time_ms: f64,
+}
+
+/// An immutable, fully loaded index ready to answer queries.
+#[derive(Debug)]
+pub struct LoadedIndex {
+    pub repo_root: PathBuf,
+    pub layout: IndexLayout,
+    pub generation: u64,
+    pub built_at: DateTime<Utc>,
+    pub graph: Arc<DependencyGraph>,
+    pub name_index: NameIndex,
+    pub bm25: BM25Index,
+    /// Content hashes used to report per-file changes to the next generation.
+    pub fingerprints: Arc<Fingerprints>,
+    /// Unresolved imports and parse failures of the build (`get_index_stats`).
+    pub diagnostics: BuildDiagnostics,
+}
+
+impl LoadedIndex {
+    pub fn new(
+        manifest: &IndexManifest,
+        layout: IndexLayout,
+        graph: DependencyGraph,
+        bm25: BM25Index,
+    ) -> Self {
+        let name_index = NameIndex::from_graph(&graph);
+        Self {
+            repo_root: manifest.repo_root.clone(),
+            layout,
+            generation: manifest.generation,
+            built_at: manifest.built_at,
+            graph: Arc::new(graph),
+            name_index,
+            bm25,
+            fingerprints: Arc::default(),
+            diagnostics: manifest.diagnostics.clone(),
+        }
+    }
+
+    /// Loads a persisted index from disk.
+    pub fn load(layout: &IndexLayout) -> Result<Self, IndexBuildError> {
+        let (graph, manifest) = persistence::load_graph(&layout.graph_dir)?;
+        let bm25 = BM25Index::open(&layout.bm25_dir)?;
+        let fingerprints = persistence::load_fingerprints(&layout.graph_dir)?;
+        Ok(Self::new(&manifest, layout.clone(), graph, bm25).with_fingerprints(fingerprints))
+    }
+
+    fn with_fingerprints(mut self, fingerprints: Fingerprints) -> Self {
+        self.fingerprints = Arc::new(fingerprints);
+        self
+    }
+
+    /// Builds the graph and BM25 index for `repo_root` and persists them to `layout`.
+    ///
+    /// Everything is written to a staging layout first and promoted only after
+    /// the build succeeded, so a failed or cancelled build leaves the previous
+    /// index on disk untouched.
+    pub fn build(
+        repo_root: &Path,
+        config: GraphBuilderConfig,
+        bm25: &Bm25Params,
+        layout: &IndexLayout,
+        generation: u64,
+        progress: &dyn BuildProgress,
+    ) -> Result<(Self, RebuildStats), IndexBuildError> {
+        let started = Instant::now();
+        let result = GraphBuilder::with_config(repo_root, config).build_with_progress(progress)?;
+        let graph = result.graph;
+
+        let documents = documents_from_graph(&graph);
+        progress.phase_started(BuildPhase::Bm25, Some(documents.len()));
+        if progress.is_cancelled() {
+            return Err(IndexBuildError::Cancelled);
+        }
+
+        let staging = layout.staging(&format!("gen-{generation}"));
+        staging.remove()?;
+        let manifest = IndexManifest::new(repo_root.to_path_buf(), generation, &graph)
+            .with_diagnostics(BuildDiagnostics::from(&result.stats));
+        let fingerprints = fingerprint_graph(&graph);
+        let staged = BM25Index::build_in_dir(&staging.bm25_dir, &documents, bm25)
+            .map_err(IndexBuildError::from)
+            .and_then(|bm25| {
+                drop(bm25);
+                persistence::save_graph(&graph, &manifest, &staging.graph_dir)?;
+                persistence::save_fingerprints(&fingerprints, &staging.graph_dir)?;
+                Ok(())
+            });
+        if let Err(err) = staged {
+            let _ = staging.remove();
+            return Err(err);
+        }
+        if progress.is_cancelled() {
+            staging.remove()?;
+            return Err(IndexBuildError::Cancelled);
+        }
+
+        layout.promote(&staging)?;
+        let bm25 = BM25Index::open(&layout.bm25_dir)?;
+
+        let stats = RebuildStats {
+            files_indexed: result.stats.files,
+            entities_found: EntityCounts::from_graph(&graph),
+            edges_created: EdgeCounts::from_graph(&graph),
+            build_time_ms: started.elapsed().as_secs_f64() * 1000.0,
+        };
+        let index =
+            Self::new(&manifest, layout.clone(), graph, bm25).with_fingerprints(fingerprints);
+        Ok((index, stats))
+    }
+
+    /// Resolves an entity id to its node index.
+    pub fn entity(&self, id: &str) -> Option<GraphNodeIndex> {
+        self.graph.get_index(id)
+    }
+}
+
+/// State shared by all transports of one service process.
+#[derive(Debug)]
+pub struct ServiceState {
+    config: IndexServiceConfig,
+    repos: RepoRegistry,
+    jobs: JobManager,
+    events: EventBus,
+    auth: Authenticator,
+    limits: RequestLimiter,
+    cache: QueryCache,
+    audit: AuditLog,
+    sessions: SessionStore,
+    shutdown: watch::Sender<bool>,
+}
+
+/// What [`ServiceState::reload`] changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReloadReport {
+    /// Tokens accepted after the reload.
+    pub tokens: usize,
+    /// Repositories newly registered from `repos.json`.
+    pub registered: usize,
+    /// Repositories whose index was re-read from disk, with the generation now served.
+    pub reloaded: Vec<(String, u64)>,
+    /// Settings that changed but only take effect after a restart.
+    pub restart_required: Vec<&'static str>,
+}
+
+impl ServiceState {
+    pub fn new(config: IndexServiceConfig) -> Self {
+        let events = EventBus::default();
+        Self {
+            repos: RepoRegistry::new(&config),
+            auth: Authenticator::new(config.auth_tokens.clone()),
+            limits: RequestLimiter::new(&config),
+            cache: QueryCache::new(config.query_cache_size),
+            audit: AuditLog::new(&config),
+            sessions: SessionStore::new(Duration::from_secs(config.session_idle_timeout_secs)),
+            config,
+            jobs: JobManager::new(events.clone()),
+            events,
+            shutdown: watch::Sender::new(false),
+        }
+    }
+
+    pub fn config(&self) -> &IndexServiceConfig {
+        &self.config
+    }
+
+    pub fn auth(&self) -> &Authenticator {
+        &self.auth
+    }
+
+    pub fn limits(&self) -> &RequestLimiter {
+        &self.limits
+    }
+
+    pub fn cache(&self) -> &QueryCache {
+        &self.cache
+    }
+
+    pub fn audit(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// What each agent session has been shown (see [`super::sessions`]).
+    pub fn sessions(&self) -> &SessionStore {
+        &self.sessions
+    }
+
+    /// Layout configured through `GRAPH_INDEX_DIR` / `BM25_INDEX_DIR`.
+    pub fn default_layout(&self) -> IndexLayout {
+        IndexLayout::new(&self.config.graph_index_dir, &self.config.bm25_index_dir)
+    }
+
+    pub fn repos(&self) -> &RepoRegistry {
+        &self.repos
+    }
+
+    pub fn jobs(&self) -> &JobManager {
+        &self.jobs
+    }
+
+    /// Index change notifications (see [`super::events`]).
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Registers the persisted named repositories and loads the default index.
+    ///
+    /// Named repositories stay unloaded until their first query. Returns
+    /// `Ok(false)` when no default index has been built yet.
+    pub fn load_persisted(&self) -> Result<bool, IndexBuildError> {
+        let registered = self.repos.load_registered()?;
+        if registered > 0 {
+            info!("Registered {registered} named repositories (loaded on demand)");
+        }
+        let default = self
+            .repos
+            .get(None)
+            .expect("default repository is always registered");
+        self.repos.touch(&default);
+        Ok(default.load()?.is_some())
+    }
+
+    /// Starts a graceful shutdown: transports stop accepting connections and
+    /// input, and new rebuilds are refused with `-32011`. Returns `false` if
+    /// a shutdown had already begun.
+    pub fn begin_shutdown(&self) -> bool {
+        !self.shutdown.send_replace(true)
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+
+    /// Resolves once [`Self::begin_shutdown`] has been called.
+    pub fn shutdown_signal(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.shutdown.subscribe();
+        async move {
+            let _ = receiver.wait_for(|stopping| *stopping).await;
+        }
+    }
+
+    /// Watches the shutdown flag (for streams that must end on shutdown).
+    pub fn subscribe_shutdown(&self) -> watch::Receiver<bool> {
+        self.shutdown.subscribe()
+    }
+
+    /// Applies a re-read configuration and reloads indices replaced on disk.
+    ///
+    /// Tokens are replaced in place and repositories added to `repos.json` by
+    /// another process are registered. Loaded indices whose persisted
+    /// generation changed are re-read, except while this process rebuilds
+    /// them. Other settings are reported in `restart_required`.
+    pub fn reload(&self, config: &IndexServiceConfig) -> Result<ReloadReport, IndexBuildError> {
+        self.auth.replace(config.auth_tokens.clone());
+        let mut report = ReloadReport {
+            tokens: self.auth.token_count(),
+            registered: self.repos.load_registered()?,
+            restart_required: self.config.changed_settings(config),
+            ..ReloadReport::default()
+        };
+        for repo in self.repos.list() {
+            if self.jobs.active_for(repo.name()).is_some() {
+                continue;
+            }
+            let previous = repo.current();
+            let generation = match repo.reload() {
+                Ok(Some(generation)) => generation,
+                Ok(None) => continue,
+                Err(err) => {
+                    warn!("[{}] failed to reload index: {err}", repo.name());
+                    continue;
+                }
+            };
+            self.cache.invalidate_repo(repo.name());
+            if let Some(index) = repo.current() {
+                let changes = previous
+                    .filter(|previous| !previous.fingerprints.is_empty())
+                    .map(|previous| diff_fingerprints(&previous.fingerprints, &index.fingerprints));
+                let swapped = GenerationSwapped {
+                    repo: repo.name().to_string(),
+                    generation,
+                    built_at: index.built_at,
+                    node_count: index.graph.node_count(),
+                    edge_count: index.graph.edge_count(),
+                    files_changed: changes.as_ref().map(Vec::len),
+                };
+                publish_changes(&self.events, swapped, changes.unwrap_or_default());
+            }
+            report.reloaded.push((repo.name().to_string(), generation));
+        }
+        Ok(report)
+    }
+
+    /// Snapshot of the index currently served for the default repository.
+    pub fn current(&self) -> Option<Arc<LoadedIndex>> {
+        self.repos.get(None).ok().and_then(|repo| repo.current())
+    }
+
+    /// Snapshot of the index for `repo` (default when `None`), loading it if needed.
+    ///
+    /// Fails with `-32007` for unknown repositories and `-32001` when nothing
+    /// has been built for the repository yet.
+    pub async fn require_index(
+        &self,
+        repo: Option<&str>,
+    ) -> Result<Arc<LoadedIndex>, JsonRpcError> {
+        let handle = self.repos.get(repo)?;
+        self.repos.touch(&handle);
+        if let Some(index) = handle.current() {
+            return Ok(index);
+        }
+        let loader = Arc::clone(&handle);
+        let loaded = tokio::task::spawn_blocking(move || loader.load())
+            .await
+            .map_err(JsonRpcError::internal)?
+            .map_err(JsonRpcError::internal)?;
+        let index =
+            loaded.ok_or_else(|| JsonRpcError::index_not_found(&handle.layout().graph_dir))?;
+        self.repos.enforce_limit(handle.name());
+        Ok(index)
+    }
+}
@@ -0,0 +1,101 @@
+//! Line-delimited JSON-RPC over stdin/stdout
+//!
+//! Lets an agent spawn the service as a child process instead of connecting
+//! to a port. Every input line is one JSON-RPC request and every response is
+//! written as one output line. Requests run concurrently, so responses may
+//! arrive out of order and must be matched by `id`. Serving stops when the
+//! input is closed or the service shuts down, once all in-flight requests
+//! have been answered.
+//!
+//! Logs must never be written to stdout in this mode.
+
+use super::audit::Caller;
+use super::handlers::handle_request_from;
+use super::jsonrpc::parse_request;
+use super::state::ServiceState;
+use serde_json::Value;
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tracing::info;
+
+/// Serves requests from the process's stdin until it is closed.
+pub async fn serve_stdio(state: Arc<ServiceState>) -> io::Result<()> {
+    info!("CDS-Index Service reading JSON-RPC requests from stdin");
+    serve_lines(state, tokio::io::stdin(), tokio::io::stdout()).await
+}
+
+/// Serves line-delimited requests read from `input`, writing responses to `output`.
+pub async fn serve_lines<R, W>(state: Arc<ServiceState>, input: R, output: W) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let stop = state.shutdown_signal();
+    pump_lines(input, output, stop, move |line| {
+        let state = Arc::clone(&state);
+        async move {
+            let response = match parse_request(&line) {
+                Ok(request) => handle_request_from(&state, request, &Caller::new("stdio")).await,
+                Err(response) => *response,
+            };
+            serde_json::to_value(response).ok()
+        }
+    })
+    .await
+}
+
+/// Runs `handle` on every non-empty input line concurrently and writes each
+/// message it returns as one output line; `None` writes nothing (notifications).
+/// Reading stops at the end of the input or when `stop` resolves.
+pub(crate) async fn pump_lines<R, W, S, F, Fut>(
+    input: R,
+    output: W,
+    stop: S,
+    handle: F,
+) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+    S: Future<Output = ()>,
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Option<Value>> + Send + 'static,
+{
+    let (sender, mut messages) = mpsc::unbounded_channel::<Value>();
+    let writer = tokio::spawn(async move {
+        let mut output = output;
+        while let Some(message) = messages.recv().await {
+            let mut line = serde_json::to_vec(&message).map_err(io::Error::other)?;
+            line.push(b'\n');
+            output.write_all(&line).await?;
+            output.flush().await?;
+        }
+        Ok::<_, io::Error>(())
+    });
+
+    let mut lines = BufReader::new(input).lines();
+    tokio::pin!(stop);
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => line?,
+            () = &mut stop => break,
+        };
+        let Some(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let sender = sender.clone();
+        let reply = handle(line);
+        tokio::spawn(async move {
+            if let Some(message) = reply.await {
+                let _ = sender.send(message);
+            }
+        });
+    }
+    drop(sender);
+    writer.await.map_err(io::Error::other)?
+}
@@ -0,0 +1,169 @@
+//! systemd integration: readiness notifications and the journal
+//!
+//! Under a `Type=notify` unit the service reports `READY=1` once it serves
+//! requests, `RELOADING=1` while it applies a `SIGHUP` and `STOPPING=1` when
+//! a graceful shutdown starts; with `WatchdogSec=` set it must send
+//! `WATCHDOG=1` within the interval (see [`super::lifecycle`]). Outside
+//! systemd (no `NOTIFY_SOCKET`) and on other platforms these calls do nothing.
+//!
+//! When stderr is connected to the journal (`JOURNAL_STREAM`), logs are better
+//! written as native journal entries through [`JournalWriter`], which keeps
+//! their level as the syslog `PRIORITY` and the tracing target as `TARGET`.
+
+use std::time::Duration;
+
+/// Tells systemd the service is up; `status` shows in `systemctl status`.
+pub fn notify_ready(status: &str) {
+    notify(&[("READY", "1"), ("STATUS", status)]);
+}
+
+/// Tells systemd a reload started; follow with [`notify_ready`].
+pub fn notify_reloading() {
+    notify(&[("RELOADING", "1"), ("STATUS", "Reloading")]);
+}
+
+/// Tells systemd a graceful shutdown started.
+pub fn notify_stopping(status: &str) {
+    notify(&[("STOPPING", "1"), ("STATUS", status)]);
+}
+
+/// Updates the status line without changing the state.
+pub fn notify_status(status: &str) {
+    notify(&[("STATUS", status)]);
+}
+
+/// Resets the watchdog timer.
+pub fn notify_watchdog() {
+    notify(&[("WATCHDOG", "1")]);
+}
+
+/// Interval within which systemd expects [`notify_watchdog`], if enabled.
+pub fn watchdog_interval() -> Option<Duration> {
+    #[cfg(target_os = "linux")]
+    {
+        match ::systemd::daemon::watchdog_enabled(false) {
+            Ok(0) | Err(_) => None,
+            Ok(usec) => Some(Duration::from_micros(usec)),
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn notify(state: &[(&str, &str)]) {
+    if let Err(err) = ::systemd::daemon::notify(false, state.iter()) {
+        tracing::debug!("sd_notify failed: {err}");
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn notify(_state: &[(&str, &str)]) {}
+
+/// Whether stderr is the journal stream systemd connected (`JOURNAL_STREAM`
+/// names its device and inode).
+pub fn journal_connected() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsFd;
+        use std::os::unix::fs::MetadataExt;
+
+        let Ok(stream) = std::env::var("JOURNAL_STREAM") else {
+            return false;
+        };
+        let Some((device, inode)) = stream.split_once(':') else {
+            return false;
+        };
+        let metadata = std::io::stderr()
+            .as_fd()
+            .try_clone_to_owned()
+            .and_then(|fd| std::fs::File::from(fd).metadata());
+        metadata.is_ok_and(|metadata| {
+            device.parse() == Ok(metadata.dev()) && inode.parse() == Ok(metadata.ino())
+        })
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        false
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use journal::{JournalEntry, JournalWriter};
+
+#[cfg(target_os = "linux")]
+mod journal {
+    use std::io::{self, Write};
+    use tracing::{Level, Metadata};
+    use tracing_subscriber::fmt::MakeWriter;
+
+    /// `tracing_subscriber` writer that sends each formatted event to the
+    /// journal as one entry.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct JournalWriter;
+
+    /// One log event, sent to the journal when dropped.
+    #[derive(Debug)]
+    pub struct JournalEntry {
+        priority: u8,
+        target: String,
+        message: Vec<u8>,
+    }
+
+    impl JournalEntry {
+        fn new(priority: u8, target: &str) -> Self {
+            Self {
+                priority,
+                target: target.to_string(),
+                message: Vec::new(),
+            }
+        }
+    }
+
+    impl Write for JournalEntry {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.message.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Drop for JournalEntry {
+        fn drop(&mut self) {
+            let message = String::from_utf8_lossy(&self.message);
+            let message = message.trim_end();
+            if message.is_empty() {
+                return;
+            }
+            ::systemd::journal::send(&[
+                &format!("PRIORITY={}", self.priority),
+                &format!("MESSAGE={message}"),
+                &format!("TARGET={}", self.target),
+            ]);
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for JournalWriter {
+        type Writer = JournalEntry;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            JournalEntry::new(6, "")
+        }
+
+        fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+            // Syslog priorities: err, warning, info, debug.
+            let priority = match *meta.level() {
+                Level::ERROR => 3,
+                Level::WARN => 4,
+                Level::INFO => 6,
+                Level::DEBUG | Level::TRACE => 7,
+            };
+            JournalEntry::new(priority, meta.target())
+        }
+    }
+}
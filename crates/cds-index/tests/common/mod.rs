@@ -0,0 +1,105 @@
+//! Fixtures shared by the service integration tests.
+//!
+//! Each test binary uses a different subset of these helpers.
+#![allow(dead_code)]
+
+use cds_index::service::{handle_request, JsonRpcRequest, ServiceState};
+use cds_index::IndexServiceConfig;
+use jsonschema::{Draft, JSONSchema};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub fn write_file(root: &Path, relative: &str, contents: &str) {
+    let path = root.join(relative);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).expect("failed to create directories");
+    }
+    fs::write(&path, contents).expect("failed to write file");
+}
+
+/// Service configuration with the graph and BM25 indices under `index_root`.
+pub fn config(index_root: &Path) -> IndexServiceConfig {
+    IndexServiceConfig::new(index_root.join("graph"), index_root.join("bm25"))
+}
+
+pub fn service_state(index_root: &Path) -> Arc<ServiceState> {
+    Arc::new(ServiceState::new(config(index_root)))
+}
+
+/// The full JSON-RPC response, error or not.
+pub async fn respond(state: &Arc<ServiceState>, method: &str, params: Value) -> Value {
+    let response = handle_request(state, JsonRpcRequest::new(method, params, json!(1))).await;
+    serde_json::to_value(response).expect("serialize response")
+}
+
+/// The result of a call that must succeed.
+pub async fn call(state: &Arc<ServiceState>, method: &str, params: Value) -> Value {
+    let response = respond(state, method, params).await;
+    assert!(response["error"].is_null(), "{method} failed: {response}");
+    response["result"].clone()
+}
+
+/// Starts a rebuild of `repo` and returns its job id.
+pub async fn start_rebuild(state: &Arc<ServiceState>, repo: &Path) -> String {
+    let job = call(
+        state,
+        "rebuild_index",
+        json!({ "repo_path": repo.display().to_string() }),
+    )
+    .await;
+    job["job_id"].as_str().unwrap().to_string()
+}
+
+/// Polls `get_job_status` until the job has finished and returns its snapshot.
+pub async fn wait_for_job(state: &Arc<ServiceState>, job_id: &str) -> Value {
+    for _ in 0..600 {
+        let status = call(state, "get_job_status", json!({ "job_id": job_id })).await;
+        match status["status"].as_str() {
+            Some("queued") | Some("running") => tokio::time::sleep(Duration::from_millis(50)).await,
+            _ => return status,
+        }
+    }
+    panic!("job {job_id} did not finish in time");
+}
+
+/// Rebuilds `repo` and waits for the job to complete.
+pub async fn rebuild(state: &Arc<ServiceState>, repo: &Path) {
+    let job_id = start_rebuild(state, repo).await;
+    let status = wait_for_job(state, &job_id).await;
+    assert_eq!(status["status"], "completed", "{status}");
+}
+
+/// Validates `result` against the method's result schema in docs/api.
+pub fn assert_matches_schema(method: &str, result: &Value) {
+    let schema: Value =
+        serde_json::from_str(include_str!("../../../../docs/api/jsonrpc-schema.json")).unwrap();
+    let method_result = &schema["methods"][method]["result"];
+    let inline = json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "definitions": schema["definitions"],
+        "type": method_result["type"],
+        "required": method_result["required"],
+        "properties": method_result["properties"],
+    });
+    let validator = JSONSchema::options()
+        .with_draft(Draft::Draft7)
+        .compile(&inline)
+        .expect("compile schema");
+    let messages: Vec<String> = match validator.validate(result) {
+        Ok(()) => return,
+        Err(errors) => errors
+            .map(|e| format!("{e} at {}", e.instance_path))
+            .collect(),
+    };
+    panic!("{method} result violates schema:\n{}", messages.join("\n"));
+}
+
+/// A service over `index_root` with `repo` indexed.
+pub async fn indexed_state(index_root: &Path, repo: &Path) -> Arc<ServiceState> {
+    let state = service_state(index_root);
+    rebuild(&state, repo).await;
+    state
+}
@@ -0,0 +1,222 @@
+//! Tests for the layered service configuration (defaults → file → env → flags).
+
+use cds_index::config::{ConfigLoader, ConfigSource};
+use cds_index::graph::GraphBuilder;
+use cds_index::ServiceTransport;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn write_file(root: &Path, relative: &str, contents: &str) -> PathBuf {
+    let path = root.join(relative);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).expect("failed to create directories");
+    }
+    fs::write(&path, contents).expect("failed to write file");
+    path
+}
+
+const CONFIG_FILE: &str = r#"
+graph_index_dir = "/srv/cds/graph"
+bm25_index_dir = "/srv/cds/bm25"
+port = 4000
+host = "0.0.0.0"
+socket_mode = 0o640
+shutdown_timeout_secs = 5
+
+[builder]
+max_python_files = 100
+skip_dirs = ["vendor"]
+
+[bm25]
+name_boost = 3.0
+
+[repos.legacy]
+skip_dirs = ["generated"]
+follow_symlinks = true
+cross_repo_links = true
+"#;
+
+#[test]
+fn layers_apply_in_order_and_record_sources() {
+    let dir = TempDir::new().unwrap();
+    let file = write_file(dir.path(), "cds-index.toml", CONFIG_FILE);
+
+    let loaded = ConfigLoader::new()
+        .file(&file)
+        .env_vars([
+            ("INDEX_SERVICE_PORT", "5000"),
+            ("INDEX_SERVICE_TRANSPORT", "UDS"),
+            ("INDEX_SKIP_DIRS", "vendor, third_party"),
+        ])
+        .set("port", "6000")
+        .set("repos.legacy.max_python_files", "7")
+        .load()
+        .expect("load configuration");
+    let config = &loaded.config;
+
+    assert_eq!(config.port, 6000);
+    assert_eq!(loaded.source("port"), &ConfigSource::Flag("--set".into()));
+    assert_eq!(config.host, "0.0.0.0");
+    assert_eq!(loaded.source("host"), &ConfigSource::File(file.clone()));
+    assert_eq!(config.transport, ServiceTransport::Unix);
+    assert_eq!(
+        loaded.source("transport"),
+        &ConfigSource::Env("INDEX_SERVICE_TRANSPORT")
+    );
+    assert_eq!(config.socket_mode, 0o640);
+    assert_eq!(config.shutdown_timeout_secs, 5);
+    assert_eq!(config.rate_limit_burst, 20);
+    assert_eq!(loaded.source("rate_limit_burst"), &ConfigSource::Default);
+    // Derived defaults follow the graph index directory from the file.
+    assert_eq!(config.repos_dir, Path::new("/srv/cds/repos"));
+    assert_eq!(config.bm25.name_boost, 3.0);
+    assert_eq!(config.builder.skip_dirs, ["vendor", "third_party"]);
+    assert_eq!(
+        loaded.source("builder.skip_dirs"),
+        &ConfigSource::Env("INDEX_SKIP_DIRS")
+    );
+
+    let legacy = config.builder_config("legacy");
+    assert!(legacy.follow_symlinks);
+    assert_eq!(legacy.max_python_files, Some(7));
+    assert_eq!(legacy.skip_dirs, ["vendor", "third_party", "generated"]);
+    assert!(config.cross_repo_links_for("legacy"));
+
+    let default = config.builder_config("default");
+    assert!(!default.follow_symlinks);
+    assert_eq!(default.max_python_files, Some(100));
+    assert!(!config.cross_repo_links_for("default"));
+}
+
+#[test]
+fn env_only_configuration_matches_from_env_defaults() {
+    let loaded = ConfigLoader::new()
+        .env_vars([
+            ("GRAPH_INDEX_DIR", "/data/graph"),
+            ("BM25_INDEX_DIR", "/data/bm25"),
+            ("INDEX_SERVICE_SOCKET_MODE", "660"),
+            ("INDEX_AUDIT_LOG", ""),
+            ("INDEX_SERVICE_TOKENS", "read:r-secret,admin:a-secret"),
+        ])
+        .load()
+        .expect("load configuration");
+    let config = &loaded.config;
+    assert_eq!(loaded.file, None);
+    assert_eq!(config.port, 3030);
+    assert_eq!(config.socket_mode, 0o660);
+    assert_eq!(config.socket_path, Path::new("/data/cds-index.sock"));
+    assert_eq!(config.audit_log, None);
+    assert_eq!(config.auth_tokens.len(), 2);
+    assert!(config.builder_config("default").skip_dirs.is_empty());
+
+    let missing = ConfigLoader::new()
+        .env_vars([("BM25_INDEX_DIR", "/data/bm25")])
+        .load()
+        .unwrap_err();
+    assert!(format!("{missing:#}").contains("GRAPH_INDEX_DIR not set"));
+}
+
+#[test]
+fn invalid_settings_are_rejected_with_their_origin() {
+    let dir = TempDir::new().unwrap();
+    let env = [("GRAPH_INDEX_DIR", "/g"), ("BM25_INDEX_DIR", "/b")];
+
+    let typo = write_file(dir.path(), "typo.toml", "prot = 4000\n");
+    let err = ConfigLoader::new()
+        .file(&typo)
+        .env_vars(env)
+        .load()
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("unknown setting 'prot'"),
+        "{err:#}"
+    );
+
+    let err = ConfigLoader::new()
+        .env_vars(env.into_iter().chain([("INDEX_SERVICE_PORT", "http")]))
+        .load()
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("Invalid INDEX_SERVICE_PORT"),
+        "{err:#}"
+    );
+
+    let err = ConfigLoader::new()
+        .env_vars(env)
+        .set("builder.skip", "x")
+        .load()
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("unknown setting 'builder.skip'"));
+
+    let err = ConfigLoader::new()
+        .env_vars(env)
+        .set("port", "70000")
+        .load()
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("Invalid configuration"));
+
+    let missing = ConfigLoader::new()
+        .file(dir.path().join("missing.toml"))
+        .env_vars(env)
+        .load()
+        .unwrap_err();
+    assert!(format!("{missing:#}").contains("Failed to read config file"));
+}
+
+#[test]
+fn print_config_shows_values_with_sources_and_redacts_tokens() {
+    let dir = TempDir::new().unwrap();
+    let file = write_file(dir.path(), "cds-index.toml", CONFIG_FILE);
+    let loaded = ConfigLoader::new()
+        .file(&file)
+        .env_vars([("INDEX_SERVICE_TOKENS", "read:top-secret")])
+        .flag("--mcp", "transport", "mcp")
+        .load()
+        .expect("load configuration");
+    let printed = loaded.to_string();
+
+    let line = |key: &str| {
+        printed
+            .lines()
+            .find(|line| line.starts_with(&format!("{key} = ")))
+            .unwrap_or_else(|| panic!("no line for {key} in:\n{printed}"))
+            .to_string()
+    };
+    assert!(line("port").ends_with(&format!("# file {}", file.display())));
+    assert!(line("transport").contains("\"mcp\"") && line("transport").ends_with("# flag --mcp"));
+    assert!(line("socket_mode").starts_with("socket_mode = 0o640"));
+    assert!(line("query_cache_size").ends_with("# default"));
+    assert!(line("tokens").ends_with("# env INDEX_SERVICE_TOKENS"));
+    assert!(!printed.contains("top-secret"));
+    assert!(printed.contains("[repos.legacy]"));
+    assert!(printed.contains("# grpc_port is unset"));
+
+    // The output is itself a valid configuration file.
+    let reparsed: toml::Table = toml::from_str(&printed).expect("print-config is TOML");
+    assert_eq!(reparsed["builder"]["skip_dirs"][0].as_str(), Some("vendor"));
+}
+
+#[test]
+fn builder_skips_configured_directories() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "pkg/__init__.py", "");
+    write_file(repo.path(), "pkg/core.py", "def run():\n    pass\n");
+    write_file(repo.path(), "vendor/lib.py", "def vendored():\n    pass\n");
+
+    let loaded = ConfigLoader::new()
+        .env_vars([
+            ("GRAPH_INDEX_DIR", "/g"),
+            ("BM25_INDEX_DIR", "/b"),
+            ("INDEX_SKIP_DIRS", "vendor"),
+        ])
+        .load()
+        .expect("load configuration");
+    let config = loaded.config.builder_config("default");
+    let graph = GraphBuilder::with_config(repo.path(), config)
+        .build()
+        .expect("build graph")
+        .graph;
+    assert!(graph.get_index("pkg/core.py::run").is_some());
+    assert!(graph.get_index("vendor/lib.py::vendored").is_none());
+}
@@ -1,7 +1,10 @@
-use cds_index::graph::{EdgeKind, GraphBuilder, NodeKind};
+use cds_index::graph::{
+    top_level_packages, EdgeKind, ExternalPackages, GraphBuilder, GraphBuilderConfig, NodeKind,
+};
 use petgraph::visit::EdgeRef;
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 use tempfile::TempDir;
 
 fn write_file(root: &Path, relative: &str, contents: &str) {
@@ -1020,3 +1023,81 @@ class Runner:
         "async __init__ should record invoke edge to setup()"
     );
 }
+
+#[test]
+fn cross_repo_imports_link_external_entities() {
+    let (_billing_dir, billing) = build_graph_with_files(&[
+        ("src/billing/__init__.py", "from .models import Invoice\n"),
+        (
+            "src/billing/models.py",
+            r#"
+class Invoice:
+    pass
+"#,
+        ),
+        (
+            "src/billing/api.py",
+            r#"
+def send_invoice(invoice):
+    return invoice
+"#,
+        ),
+    ]);
+    assert_eq!(top_level_packages(&billing), vec!["billing".to_string()]);
+
+    let mut packages = ExternalPackages::new();
+    assert!(packages.add_repo("billing", Arc::new(billing)).is_empty());
+    assert_eq!(packages.provider("billing"), Some("billing"));
+
+    let shop_files = [
+        (
+            "shop/checkout.py",
+            r#"
+from billing import Invoice
+from billing.api import send_invoice as notify
+import requests
+
+class Order(Invoice):
+    pass
+
+def checkout(order):
+    notify(order)
+"#,
+        ),
+    ];
+    let shop_dir = TempDir::new().expect("tempdir");
+    for (path, contents) in &shop_files {
+        write_file(shop_dir.path(), path, contents);
+    }
+    let config = GraphBuilderConfig {
+        external_packages: Some(Arc::new(packages)),
+        ..GraphBuilderConfig::default()
+    };
+    let result = GraphBuilder::with_config(shop_dir.path(), config)
+        .build()
+        .expect("graph build");
+    let graph = result.graph;
+    assert_eq!(result.stats.external_entities, 2);
+
+    let invoice = graph
+        .get_index("@billing/src/billing/models.py::Invoice")
+        .expect("external Invoice node");
+    let send = graph
+        .get_index("@billing/src/billing/api.py::send_invoice")
+        .expect("external send_invoice node");
+    let node = graph.node(send).unwrap();
+    assert!(node.is_external());
+    assert_eq!(
+        node.external_ref(),
+        Some(("billing", "src/billing/api.py::send_invoice"))
+    );
+
+    let file = graph.get_index("shop/checkout.py").unwrap();
+    let order = graph.get_index("shop/checkout.py::Order").unwrap();
+    let checkout = graph.get_index("shop/checkout.py::checkout").unwrap();
+    assert!(has_edge(&graph, file, send, EdgeKind::Import));
+    assert!(has_edge(&graph, order, invoice, EdgeKind::Inherit));
+    assert!(has_edge(&graph, checkout, send, EdgeKind::Invoke));
+    // Imports nobody provides are still dropped.
+    assert!(find_node(&graph, |node| node.id.contains("requests")).is_none());
+}
@@ -0,0 +1,228 @@
+//! Service tests for the JSONL audit log, its rotation and replay.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::Request;
+use cds_index::service::audit::{read_log, replay, rotated_path};
+use cds_index::service::server::router;
+use cds_index::service::ServiceState;
+use common::{call, config, rebuild, write_file};
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+fn audited_state(index_root: &Path, max_bytes: u64, max_files: usize) -> Arc<ServiceState> {
+    let mut config = config(index_root);
+    config.audit_log = Some(index_root.join("audit").join("audit.jsonl"));
+    config.audit_log_max_bytes = max_bytes;
+    config.audit_log_max_files = max_files;
+    Arc::new(ServiceState::new(config))
+}
+
+#[tokio::test]
+async fn calls_are_logged_with_results_and_caller() {
+    let repo = TempDir::new().unwrap();
+    write_file(
+        repo.path(),
+        "orders.py",
+        "def place_order(cart):\n    return charge(cart)\n\n\ndef charge(cart):\n    return cart\n",
+    );
+    let index_root = TempDir::new().unwrap();
+    let state = audited_state(index_root.path(), 0, 5);
+    rebuild(&state, repo.path()).await;
+
+    call(&state, "search_entities", json!({ "query": "place_order" })).await;
+    call(
+        &state,
+        "traverse_graph",
+        json!({ "start_entities": ["orders.py::place_order"], "relations": ["invoke"] }),
+    )
+    .await;
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "retrieve_entity",
+        "params": { "entity_ids": ["orders.py::missing"] },
+    });
+    let request = Request::post("/rpc")
+        .header("content-type", "application/json")
+        .header("x-cds-session", "django__django-11099")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    router(Arc::clone(&state)).oneshot(request).await.unwrap();
+
+    let records = read_log(&index_root.path().join("audit/audit.jsonl")).unwrap();
+    let methods: Vec<&str> = records.iter().map(|r| r.method.as_str()).collect();
+    assert_eq!(
+        &methods[methods.len() - 3..],
+        ["search_entities", "traverse_graph", "retrieve_entity"]
+    );
+    assert_eq!(methods[0], "rebuild_index");
+
+    let search = &records[records.len() - 3];
+    assert_eq!(search.client, "local");
+    assert_eq!(search.session, None);
+    assert_eq!(search.repo, "default");
+    assert_eq!(search.generation, Some(1));
+    assert_eq!(search.params["query"], "place_order");
+    assert_eq!(search.results[0].id, "orders.py::place_order");
+    assert_eq!(search.results[0].score, Some(1.0));
+    assert!(search.latency_ms >= 0.0);
+
+    let traversal = &records[records.len() - 2];
+    let nodes: Vec<&str> = traversal
+        .results
+        .iter()
+        .map(|hit| hit.id.as_str())
+        .collect();
+    assert_eq!(nodes, ["orders.py::place_order", "orders.py::charge"]);
+    assert!(traversal.results.iter().all(|hit| hit.score.is_none()));
+
+    let retrieval = records.last().unwrap();
+    assert_eq!(retrieval.client, "local");
+    assert_eq!(retrieval.session.as_deref(), Some("django__django-11099"));
+    assert!(retrieval.results.is_empty());
+    assert_eq!(retrieval.error.as_ref().unwrap().code, -32002);
+}
+
+#[tokio::test]
+async fn the_log_rotates_and_keeps_the_newest_files() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "app.py", "def main():\n    pass\n");
+    let index_root = TempDir::new().unwrap();
+    let state = audited_state(index_root.path(), 1024, 2);
+    rebuild(&state, repo.path()).await;
+
+    for i in 0..40 {
+        call(
+            &state,
+            "search_entities",
+            json!({ "query": format!("main {i}") }),
+        )
+        .await;
+    }
+
+    let log = index_root.path().join("audit/audit.jsonl");
+    let mut lines = 0;
+    for path in [rotated_path(&log, 2), rotated_path(&log, 1), log.clone()] {
+        assert!(
+            fs::metadata(&path).unwrap().len() <= 1024,
+            "{}",
+            path.display()
+        );
+        lines += read_log(&path).unwrap().len();
+    }
+    assert!(!rotated_path(&log, 3).exists());
+    assert!(lines < 40, "the oldest records were dropped");
+    let newest = read_log(&log).unwrap();
+    assert_eq!(newest.last().unwrap().params["query"], "main 39");
+}
+
+#[tokio::test]
+async fn replay_reports_results_that_changed_since_the_recorded_generation() {
+    let repo = TempDir::new().unwrap();
+    write_file(
+        repo.path(),
+        "billing.py",
+        "def invoice_amount(items):\n    return sum(items)\n\n\ndef invoice_tax(total):\n    return total\n",
+    );
+    let index_root = TempDir::new().unwrap();
+    let state = audited_state(index_root.path(), 0, 5);
+    rebuild(&state, repo.path()).await;
+
+    call(
+        &state,
+        "search_entities",
+        json!({ "query": "invoice", "use_bm25": false, "limit": 1 }),
+    )
+    .await;
+    let first = call(
+        &state,
+        "search_entities",
+        json!({ "query": "invoice", "use_bm25": false, "limit": 1 }),
+    )
+    .await;
+    call(
+        &state,
+        "search_entities",
+        json!({ "query": "invoice", "use_bm25": false, "limit": 1, "cursor": first["next_cursor"] }),
+    )
+    .await;
+    call(
+        &state,
+        "retrieve_entity",
+        json!({ "entity_ids": ["billing.py::invoice_tax"] }),
+    )
+    .await;
+
+    write_file(
+        repo.path(),
+        "billing.py",
+        "def invoice_amount(items):\n    return sum(items)\n\n\ndef invoice_vat(total):\n    return total\n",
+    );
+    rebuild(&state, repo.path()).await;
+
+    let records = read_log(&index_root.path().join("audit/audit.jsonl")).unwrap();
+    let mut diffs = Vec::new();
+    for record in &records {
+        if let Some(diff) = replay(&state, record).await {
+            diffs.push(diff);
+        }
+    }
+    // Only the query methods are replayed, not rebuild_index or get_job_status.
+    assert_eq!(diffs.len(), 4);
+    for diff in &diffs {
+        assert_eq!(diff.recorded_generation, Some(1));
+        assert_eq!(diff.replayed_generation, Some(2));
+    }
+    assert!(diffs[0].is_unchanged(), "{:?}", diffs[0]);
+
+    // The second page is replayed from the first, so its cursor cannot expire.
+    let second_page = &diffs[2];
+    assert_eq!(second_page.error, None);
+    assert_eq!(second_page.changes.removed, ["billing.py::invoice_tax"]);
+    assert_eq!(second_page.changes.added, ["billing.py::invoice_vat"]);
+
+    let retrieval = &diffs[3];
+    let error = retrieval.error.unwrap();
+    assert_eq!((error.recorded, error.replayed), (None, Some(-32002)));
+    assert_eq!(retrieval.changes.removed, ["billing.py::invoice_tax"]);
+    assert!(!retrieval.is_unchanged());
+}
+
+#[tokio::test]
+async fn replay_pages_through_to_offsets_past_the_search_limit() {
+    let repo = TempDir::new().unwrap();
+    let mut source = String::new();
+    for i in 0..130 {
+        source.push_str(&format!(
+            "def handler_{i:03}(event):\n    return event\n\n\n"
+        ));
+    }
+    write_file(repo.path(), "handlers.py", &source);
+    let index_root = TempDir::new().unwrap();
+    let state = audited_state(index_root.path(), 0, 5);
+    rebuild(&state, repo.path()).await;
+
+    let params = json!({ "query": "handler_", "entity_types": ["function"], "use_bm25": false, "limit": 20 });
+    let mut page = call(&state, "search_entities", params.clone()).await;
+    for _ in 0..6 {
+        let mut next = params.clone();
+        next["cursor"] = page["next_cursor"].clone();
+        page = call(&state, "search_entities", next).await;
+    }
+    assert_eq!(page["entities"].as_array().unwrap().len(), 10);
+
+    let records = read_log(&index_root.path().join("audit/audit.jsonl")).unwrap();
+    let last = records
+        .iter()
+        .rfind(|record| record.method == "search_entities")
+        .unwrap();
+    assert_eq!(last.results.len(), 10);
+    let diff = replay(&state, last).await.unwrap();
+    assert!(diff.is_unchanged(), "{diff:?}");
+}
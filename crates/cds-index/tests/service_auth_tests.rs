@@ -0,0 +1,169 @@
+//! Service tests for bearer-token authentication and read-only roles.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use cds_index::service::auth::{load_token_file, parse_token_list, validate_tokens};
+use cds_index::service::server::router;
+use cds_index::service::{ApiToken, Role, ServiceState};
+use common::config;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+const READ_TOKEN: &str = "read-secret-0123456789";
+const ADMIN_TOKEN: &str = "admin-secret-0123456789";
+
+fn service_state(index_root: &Path) -> Arc<ServiceState> {
+    let mut config = config(index_root);
+    config.auth_tokens = vec![
+        ApiToken {
+            name: "agent".to_string(),
+            role: Role::Read,
+            token: READ_TOKEN.to_string(),
+        },
+        ApiToken {
+            name: "operator".to_string(),
+            role: Role::Admin,
+            token: ADMIN_TOKEN.to_string(),
+        },
+    ];
+    Arc::new(ServiceState::new(config))
+}
+
+async fn rpc(
+    state: &Arc<ServiceState>,
+    header: Option<(&str, String)>,
+    method: &str,
+    params: Value,
+) -> (StatusCode, Option<String>, Value) {
+    let mut request = Request::post("/rpc").header("content-type", "application/json");
+    if let Some((name, value)) = header {
+        request = request.header(name, value);
+    }
+    let body = json!({ "jsonrpc": "2.0", "id": 9, "method": method, "params": params });
+    let response = router(Arc::clone(state))
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let challenge = response
+        .headers()
+        .get("www-authenticate")
+        .map(|value| value.to_str().unwrap().to_string());
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, challenge, serde_json::from_slice(&bytes).unwrap())
+}
+
+fn bearer(token: &str) -> Option<(&'static str, String)> {
+    Some(("authorization", format!("Bearer {token}")))
+}
+
+#[tokio::test]
+async fn tokens_are_required_and_scoped_by_role() {
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("app.py"), "def main():\n    pass\n").unwrap();
+    let index_root = TempDir::new().unwrap();
+    let state = service_state(index_root.path());
+    let search = json!({ "query": "main" });
+
+    let (status, challenge, body) = rpc(&state, None, "search_entities", search.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(challenge.as_deref(), Some("Bearer"));
+    assert_eq!(body["id"], 9);
+    assert_eq!(body["error"]["code"], -32008);
+    assert_eq!(body["error"]["data"]["reason"], "missing token");
+
+    let (status, _, body) = rpc(&state, bearer("guess"), "search_entities", search.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["data"]["reason"], "invalid token");
+
+    // A read token passes authentication; there is simply no index yet.
+    let (status, _, body) = rpc(
+        &state,
+        bearer(READ_TOKEN),
+        "search_entities",
+        search.clone(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["error"]["code"], -32001);
+    let (status, _, body) = rpc(
+        &state,
+        Some(("x-api-key", READ_TOKEN.to_string())),
+        "list_repos",
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"]["repos"][0]["name"], "default");
+
+    let rebuild = json!({ "repo_path": repo.path().display().to_string() });
+    for method in ["rebuild_index", "cancel_job", "add_repo", "remove_repo"] {
+        let (status, _, body) = rpc(&state, bearer(READ_TOKEN), method, rebuild.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{method}");
+        assert_eq!(body["error"]["code"], -32009, "{method}");
+        assert_eq!(body["error"]["data"]["role"], "read");
+    }
+
+    // Had the read token started a rebuild, this would be -32005.
+    let (status, _, body) = rpc(&state, bearer(ADMIN_TOKEN), "rebuild_index", rebuild).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["result"]["job_id"].is_string(), "{body}");
+
+    // Probes stay open; event streams need a token.
+    let health = router(Arc::clone(&state))
+        .oneshot(Request::get("/health").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(health.status(), StatusCode::OK);
+    let events = router(Arc::clone(&state))
+        .oneshot(Request::get("/events").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(events.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn tokens_load_from_env_lists_and_files() {
+    let tokens = parse_token_list("read:abc, admin:def").unwrap();
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(
+        (tokens[0].name.as_str(), tokens[0].role),
+        ("env-1", Role::Read)
+    );
+    assert_eq!(tokens[1].role, Role::Admin);
+    assert_eq!(tokens[1].token, "def");
+    assert!(parse_token_list("superuser:abc").is_err());
+    assert!(parse_token_list("abc").is_err());
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("tokens.toml");
+    fs::write(
+        &path,
+        "[[tokens]]\nname = \"ci\"\nrole = \"readonly\"\ntoken = \"xyz\"\n",
+    )
+    .unwrap();
+    let loaded = load_token_file(&path).unwrap();
+    assert_eq!(
+        (loaded[0].name.as_str(), loaded[0].role),
+        ("ci", Role::Read)
+    );
+
+    // Secrets never show up in logs or serialized config.
+    let debug = format!("{:?}", loaded[0]);
+    assert!(!debug.contains("xyz"), "{debug}");
+    assert!(!serde_json::to_string(&loaded[0]).unwrap().contains("xyz"));
+
+    let mut duplicate = tokens.clone();
+    duplicate.push(loaded[0].clone());
+    duplicate[2].token = "abc".to_string();
+    assert!(validate_tokens(&tokens).is_ok());
+    assert!(validate_tokens(&duplicate).is_err());
+}
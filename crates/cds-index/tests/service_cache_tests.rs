@@ -0,0 +1,108 @@
+//! Service tests for the generation-aware query cache.
+
+mod common;
+
+use cds_index::service::ServiceState;
+use common::{call, config, rebuild, write_file};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn cached_state(index_root: &Path, cache_size: usize) -> Arc<ServiceState> {
+    let mut config = config(index_root);
+    config.query_cache_size = cache_size;
+    Arc::new(ServiceState::new(config))
+}
+
+/// The result without its per-call metadata.
+fn entities(result: &Value) -> Value {
+    result["entities"].clone()
+}
+
+#[tokio::test]
+async fn repeated_queries_hit_until_the_generation_changes() {
+    let repo = TempDir::new().unwrap();
+    write_file(
+        repo.path(),
+        "app.py",
+        "def handle_order(order):\n    return ship(order)\n\n\ndef ship(order):\n    return order\n",
+    );
+    let index_root = TempDir::new().unwrap();
+    let state = cached_state(index_root.path(), 16);
+    rebuild(&state, repo.path()).await;
+
+    let search = json!({ "query": "handle_order", "entity_types": ["function", "class"] });
+    let first = call(&state, "search_entities", search.clone()).await;
+    assert_eq!(first["query_metadata"]["cache_hit"], false);
+    // Equivalent params (repo spelled out, padded query, reordered filter) share the entry.
+    let second = call(
+        &state,
+        "search_entities",
+        json!({ "repo": "default", "query": " handle_order ", "entity_types": ["class", "function"] }),
+    )
+    .await;
+    assert_eq!(second["query_metadata"]["cache_hit"], true);
+    assert_eq!(entities(&first), entities(&second));
+    let other_limit = call(
+        &state,
+        "search_entities",
+        json!({ "query": "handle_order", "limit": 1 }),
+    )
+    .await;
+    assert_eq!(other_limit["query_metadata"]["cache_hit"], false);
+
+    let traverse = json!({ "start_entities": ["app.py::handle_order"], "relations": ["invoke"] });
+    let first = call(&state, "traverse_graph", traverse.clone()).await;
+    assert_eq!(first["metadata"]["cache_hit"], false);
+    let second = call(&state, "traverse_graph", traverse.clone()).await;
+    assert_eq!(second["metadata"]["cache_hit"], true);
+    assert_eq!(first["subgraph"], second["subgraph"]);
+
+    let health = call(&state, "health_check", json!({})).await;
+    assert_eq!(health["query_cache"]["hits"], 2);
+    assert_eq!(health["query_cache"]["misses"], 3);
+    assert_eq!(health["query_cache"]["entries"], 3);
+
+    // A rebuild swaps the generation: the old entries are dropped and the
+    // next queries see the new code.
+    write_file(
+        repo.path(),
+        "app.py",
+        "def handle_order(order):\n    return pack(order)\n\n\ndef pack(order):\n    return order\n",
+    );
+    rebuild(&state, repo.path()).await;
+    let health = call(&state, "health_check", json!({})).await;
+    assert_eq!(health["query_cache"]["entries"], 0);
+
+    let after = call(&state, "traverse_graph", traverse).await;
+    assert_eq!(after["metadata"]["cache_hit"], false);
+    let targets: Vec<&str> = after["subgraph"]["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|node| node["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(targets, vec!["app.py::handle_order", "app.py::pack"]);
+    let after = call(&state, "search_entities", search).await;
+    assert_eq!(after["query_metadata"]["cache_hit"], false);
+}
+
+#[tokio::test]
+async fn a_zero_sized_cache_never_hits() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "app.py", "def main():\n    pass\n");
+    let index_root = TempDir::new().unwrap();
+    let state = cached_state(index_root.path(), 0);
+    rebuild(&state, repo.path()).await;
+
+    for _ in 0..2 {
+        let result = call(&state, "search_entities", json!({ "query": "main" })).await;
+        assert_eq!(result["query_metadata"]["cache_hit"], false);
+    }
+    let health = call(&state, "health_check", json!({})).await;
+    assert_eq!(
+        health["query_cache"],
+        json!({ "capacity": 0, "entries": 0, "hits": 0, "misses": 0 })
+    );
+}
@@ -0,0 +1,172 @@
+//! Service tests for index change notifications (`GET /events`).
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use cds_index::service::events::IndexEvent;
+use cds_index::service::server::router;
+use cds_index::service::FileStatus;
+use common::{service_state, start_rebuild, write_file};
+use serde_json::Value;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::sync::broadcast::Receiver;
+use tokio_stream::StreamExt;
+use tower::ServiceExt;
+
+/// Collects events until the job `job_id` reports a finished status.
+async fn events_until_finished(events: &mut Receiver<IndexEvent>, job_id: &str) -> Vec<IndexEvent> {
+    let mut received = Vec::new();
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(30), events.recv())
+            .await
+            .expect("timed out waiting for events")
+            .expect("event bus closed");
+        let finished = matches!(
+            &event,
+            IndexEvent::JobProgress(job) if job.job_id == job_id && job.status.is_finished()
+        );
+        received.push(event);
+        if finished {
+            return received;
+        }
+    }
+}
+
+#[tokio::test]
+async fn rebuilds_publish_progress_swaps_and_file_changes() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "pkg/__init__.py", "");
+    write_file(
+        repo.path(),
+        "pkg/util.py",
+        "def keep():\n    return 1\n\n\ndef edit():\n    return 1\n\n\ndef drop():\n    return 1\n",
+    );
+    write_file(repo.path(), "pkg/old.py", "def legacy():\n    pass\n");
+    let index_root = TempDir::new().unwrap();
+    let state = service_state(index_root.path());
+    let mut events = state.events().subscribe();
+
+    let first = start_rebuild(&state, repo.path()).await;
+    let received = events_until_finished(&mut events, &first).await;
+    let names: Vec<&str> = received.iter().map(IndexEvent::name).collect();
+    assert_eq!(names.first(), Some(&"job_progress"));
+    assert!(names.contains(&"generation_swapped"), "{names:?}");
+    // Nothing to compare the first generation against.
+    assert!(!names.contains(&"files_updated"), "{names:?}");
+
+    write_file(
+        repo.path(),
+        "pkg/util.py",
+        "def keep():\n    return 1\n\n\ndef edit():\n    return 2\n\n\ndef fresh():\n    return 3\n",
+    );
+    fs::remove_file(repo.path().join("pkg/old.py")).unwrap();
+    write_file(repo.path(), "pkg/new.py", "class Added:\n    pass\n");
+
+    let second = start_rebuild(&state, repo.path()).await;
+    let received = events_until_finished(&mut events, &second).await;
+    let swapped = received
+        .iter()
+        .find_map(|event| match event {
+            IndexEvent::GenerationSwapped(swap) => Some(swap),
+            _ => None,
+        })
+        .expect("generation_swapped");
+    assert_eq!(swapped.generation, 2);
+    assert_eq!(swapped.repo, "default");
+    assert_eq!(swapped.files_changed, Some(3));
+
+    let files: Vec<_> = received
+        .iter()
+        .filter_map(|event| match event {
+            IndexEvent::FilesUpdated(update) => Some(update.files.clone()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    let summary: Vec<(&str, FileStatus)> = files
+        .iter()
+        .map(|change| (change.file.as_str(), change.status))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("pkg/new.py", FileStatus::Added),
+            ("pkg/old.py", FileStatus::Removed),
+            ("pkg/util.py", FileStatus::Changed),
+        ]
+    );
+    assert_eq!(files[0].entities_added, vec!["pkg/new.py::Added"]);
+    assert_eq!(files[1].entities_removed, vec!["pkg/old.py::legacy"]);
+    assert_eq!(files[2].entities_added, vec!["pkg/util.py::fresh"]);
+    assert_eq!(files[2].entities_removed, vec!["pkg/util.py::drop"]);
+    assert_eq!(files[2].entities_changed, vec!["pkg/util.py::edit"]);
+
+    // Fingerprints are persisted, so a restarted service still reports changes.
+    let restarted = service_state(index_root.path());
+    assert!(restarted.load_persisted().unwrap());
+    let mut events = restarted.events().subscribe();
+    write_file(repo.path(), "pkg/__init__.py", "VERSION = 2\n");
+    let third = start_rebuild(&restarted, repo.path()).await;
+    let received = events_until_finished(&mut events, &third).await;
+    let changed: Vec<&str> = received
+        .iter()
+        .filter_map(|event| match event {
+            IndexEvent::FilesUpdated(update) => Some(update.files.iter()),
+            _ => None,
+        })
+        .flatten()
+        .map(|change| change.file.as_str())
+        .collect();
+    assert_eq!(changed, vec!["pkg/__init__.py"]);
+}
+
+#[tokio::test]
+async fn events_endpoint_streams_server_sent_events() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "app.py", "def main():\n    pass\n");
+    let index_root = TempDir::new().unwrap();
+    let state = service_state(index_root.path());
+
+    let unknown = router(Arc::clone(&state))
+        .oneshot(
+            Request::get("/events?repo=missing")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+
+    let response = router(Arc::clone(&state))
+        .oneshot(Request::get("/events").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+    start_rebuild(&state, repo.path()).await;
+    let mut body = response.into_body().into_data_stream();
+    let mut text = String::new();
+    while !text.contains("event: generation_swapped") {
+        let chunk = tokio::time::timeout(Duration::from_secs(30), body.next())
+            .await
+            .expect("timed out waiting for SSE data")
+            .expect("stream ended")
+            .unwrap();
+        text.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+    let data = text
+        .split("event: generation_swapped\n")
+        .nth(1)
+        .and_then(|rest| rest.lines().next())
+        .and_then(|line| line.strip_prefix("data: "))
+        .expect("data line");
+    let payload: Value = serde_json::from_str(data).unwrap();
+    assert_eq!(payload["type"], "generation_swapped");
+    assert_eq!(payload["generation"], 1);
+    assert!(text.contains("event: job_progress"));
+}
@@ -0,0 +1,266 @@
+//! Service tests for the gRPC transport, including the streaming traversal.
+
+mod common;
+
+use cds_index::service::grpc::proto::index_service_client::IndexServiceClient;
+use cds_index::service::grpc::proto::{
+    self, traverse_event::Event, GetJobRequest, JobStatus, RebuildRequest, RetrieveRequest,
+    SearchRequest, TraverseRequest,
+};
+use cds_index::service::grpc::serve_grpc_listener;
+use cds_index::service::{ApiToken, Role, ServiceState};
+use cds_index::IndexServiceConfig;
+use common::{call, config, write_file};
+use serde_json::json;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+use tonic::transport::Channel;
+use tonic::{Code, Request};
+
+const ADMIN_TOKEN: &str = "admin-secret-0123456789";
+const READ_TOKEN: &str = "read-secret-0123456789";
+
+async fn start(config: IndexServiceConfig) -> (Arc<ServiceState>, IndexServiceClient<Channel>) {
+    let state = Arc::new(ServiceState::new(config));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve_grpc_listener(Arc::clone(&state), listener));
+    let client = IndexServiceClient::connect(format!("http://{addr}"))
+        .await
+        .expect("connect to the gRPC server");
+    (state, client)
+}
+
+fn authorized<T>(message: T, token: &str) -> Request<T> {
+    let mut request = Request::new(message);
+    request
+        .metadata_mut()
+        .insert("authorization", format!("Bearer {token}").parse().unwrap());
+    request
+}
+
+async fn rebuild(client: &mut IndexServiceClient<Channel>, repo: &Path, token: &str) {
+    let job = client
+        .rebuild(authorized(
+            RebuildRequest {
+                repo_path: repo.display().to_string(),
+                ..Default::default()
+            },
+            token,
+        ))
+        .await
+        .expect("rebuild")
+        .into_inner();
+    for _ in 0..600 {
+        let status = client
+            .get_job(authorized(
+                GetJobRequest {
+                    job_id: job.job_id.clone(),
+                },
+                token,
+            ))
+            .await
+            .expect("get_job")
+            .into_inner();
+        match status.status() {
+            JobStatus::Queued | JobStatus::Running => {
+                tokio::time::sleep(Duration::from_millis(50)).await
+            }
+            JobStatus::Completed => {
+                assert!(status.generation.is_some());
+                assert!(status.stats.is_some());
+                return;
+            }
+            other => panic!("rebuild ended as {other:?}: {status:?}"),
+        }
+    }
+    panic!("rebuild did not finish in time");
+}
+
+fn sample_repo() -> TempDir {
+    let repo = TempDir::new().unwrap();
+    write_file(
+        repo.path(),
+        "app.py",
+        "class Order:\n    pass\n\n\ndef handle_order(order):\n    return ship(order)\n\n\ndef ship(order):\n    return audit(order)\n\n\ndef audit(order):\n    return order\n",
+    );
+    repo
+}
+
+#[tokio::test]
+async fn unary_rpcs_match_json_rpc_results() {
+    let repo = sample_repo();
+    let index_root = TempDir::new().unwrap();
+    let (state, mut client) = start(config(index_root.path())).await;
+
+    let missing = client
+        .search(SearchRequest {
+            query: "handle_order".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(missing.code(), Code::FailedPrecondition);
+    assert_eq!(missing.metadata().get("cds-error-code").unwrap(), "-32001");
+
+    rebuild(&mut client, repo.path(), "").await;
+
+    let search = client
+        .search(SearchRequest {
+            query: "handle_order".to_string(),
+            entity_types: vec![proto::EntityType::Function.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let json = call(
+        &state,
+        "search_entities",
+        json!({ "query": "handle_order", "entity_types": ["function"] }),
+    )
+    .await;
+    let ids: Vec<&str> = search.entities.iter().map(|e| e.id.as_str()).collect();
+    let json_ids: Vec<&str> = json["entities"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, json_ids);
+    assert_eq!(search.entities[0].id, "app.py::handle_order");
+    assert_eq!(
+        search.entities[0].entity_type(),
+        proto::EntityType::Function
+    );
+    assert_eq!(search.entities[0].line_range.unwrap().start, 5);
+
+    let traversal = client
+        .traverse(TraverseRequest {
+            start_entities: vec!["app.py::handle_order".to_string()],
+            depth: 2,
+            relations: vec![proto::Relation::Invoke.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let nodes: Vec<&str> = traversal.nodes.iter().map(|n| n.id.as_str()).collect();
+    assert_eq!(
+        nodes,
+        vec!["app.py::handle_order", "app.py::ship", "app.py::audit"]
+    );
+    assert_eq!(traversal.metadata.unwrap().total_edges, 2);
+    assert!(traversal.next_cursor.is_empty());
+
+    let retrieved = client
+        .retrieve(RetrieveRequest {
+            entity_ids: vec!["app.py::ship".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(retrieved.entities[0].code.starts_with("def ship(order):"));
+
+    let unknown = client
+        .traverse(TraverseRequest {
+            start_entities: vec!["app.py::nope".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(unknown.code(), Code::NotFound);
+    assert_eq!(unknown.metadata().get("cds-error-code").unwrap(), "-32002");
+    let data = unknown.metadata().get_bin("cds-error-data-bin").unwrap();
+    let data: serde_json::Value = serde_json::from_slice(&data.to_bytes().unwrap()).unwrap();
+    assert_eq!(data["entity_id"], "app.py::nope");
+}
+
+#[tokio::test]
+async fn traverse_stream_yields_nodes_then_edges_in_discovery_order() {
+    let repo = sample_repo();
+    let index_root = TempDir::new().unwrap();
+    let (_state, mut client) = start(config(index_root.path())).await;
+    rebuild(&mut client, repo.path(), "").await;
+
+    let request = TraverseRequest {
+        start_entities: vec!["app.py::handle_order".to_string()],
+        depth: 3,
+        relations: vec![proto::Relation::Invoke.into()],
+        ..Default::default()
+    };
+    let whole = client.traverse(request.clone()).await.unwrap().into_inner();
+    let mut stream = client.traverse_stream(request).await.unwrap().into_inner();
+
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    let mut done = None;
+    while let Some(event) = stream.message().await.unwrap() {
+        assert!(done.is_none(), "events after the summary");
+        match event.event.unwrap() {
+            Event::Node(node) => nodes.push(node),
+            Event::Edge(edge) => {
+                // Edges only refer to nodes that were already streamed.
+                assert!(nodes.iter().any(|node| node.id == edge.source));
+                assert!(nodes.iter().any(|node| node.id == edge.target));
+                edges.push(edge);
+            }
+            Event::Done(metadata) => done = Some(metadata),
+        }
+    }
+    assert_eq!(nodes, whole.nodes);
+    assert_eq!(edges, whole.edges);
+    let done = done.expect("summary event");
+    assert_eq!(done.total_nodes, 3);
+    assert_eq!(done.total_edges, 2);
+    assert_eq!(done.max_depth_reached, 2);
+}
+
+#[tokio::test]
+async fn tokens_and_roles_apply_to_grpc() {
+    let repo = sample_repo();
+    let index_root = TempDir::new().unwrap();
+    let mut config = config(index_root.path());
+    config.auth_tokens = vec![
+        ApiToken {
+            name: "agent".to_string(),
+            role: Role::Read,
+            token: READ_TOKEN.to_string(),
+        },
+        ApiToken {
+            name: "operator".to_string(),
+            role: Role::Admin,
+            token: ADMIN_TOKEN.to_string(),
+        },
+    ];
+    let (_state, mut client) = start(config).await;
+
+    let search = SearchRequest {
+        query: "ship".to_string(),
+        ..Default::default()
+    };
+    let anonymous = client.search(search.clone()).await.unwrap_err();
+    assert_eq!(anonymous.code(), Code::Unauthenticated);
+
+    let rebuild_request = RebuildRequest {
+        repo_path: repo.path().display().to_string(),
+        ..Default::default()
+    };
+    let denied = client
+        .rebuild(authorized(rebuild_request, READ_TOKEN))
+        .await
+        .unwrap_err();
+    assert_eq!(denied.code(), Code::PermissionDenied);
+
+    rebuild(&mut client, repo.path(), ADMIN_TOKEN).await;
+    let found = client
+        .search(authorized(search, READ_TOKEN))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(found.entities[0].id, "app.py::ship");
+}
@@ -0,0 +1,129 @@
+//! Service tests for `diff_impact` and the unified diff parsing behind it.
+
+mod common;
+
+use cds_index::graph::{parse_unified_diff, DiffError};
+use common::{assert_matches_schema, call, indexed_state, respond, write_file};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+const CORE: &str = "import os\n\n\ndef connect():\n    return os.getpid()\n\n\nclass Store:\n    def save(self):\n        connect()\n\n    def load(self):\n        return 1\n\n\nclass CachedStore(Store):\n    pass\n";
+
+const API: &str = "from core import Store\n\n\ndef handle():\n    Store().save()\n";
+
+fn core_repo() -> TempDir {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "core.py", CORE);
+    write_file(repo.path(), "api.py", API);
+    repo
+}
+
+fn ids(entities: &Value) -> Vec<&str> {
+    entities
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entity| entity["id"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn unified_diffs_reduce_to_new_side_lines() {
+    let diff = "diff --git a/core.py b/core.py\n\
+        index 1111111..2222222 100644\n\
+        --- a/core.py\n\
+        +++ b/core.py\n\
+        @@ -5 +5,2 @@ def connect():\n\
+        -    return 0\n\
+        +    pid = os.getpid()\n\
+        +    return pid\n\
+        @@ -12,3 +13,2 @@ class Store:\n\
+         \x20   def load(self):\n\
+        -    # --- removed\n\
+        \x20       return 1\n\
+        @@ -20,2 +20,0 @@\n\
+        -x = 1\n\
+        -y = 2\n\
+        diff --git a/old.py b/old.py\n\
+        deleted file mode 100644\n\
+        --- a/old.py\n\
+        +++ /dev/null\n\
+        @@ -1 +0,0 @@\n\
+        -gone = True\n\
+        --- notes.txt\t2026-01-01 10:00:00\n\
+        +++ notes.txt\t2026-01-02 10:00:00\n\
+        @@ -1 +1 @@\n\
+        -old\n\
+        +new\n";
+    let changes = parse_unified_diff(diff).unwrap();
+    let summary: Vec<(&str, Vec<u32>)> = changes
+        .iter()
+        .map(|change| (change.path.as_str(), change.lines.iter().copied().collect()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![("core.py", vec![4, 5, 6, 13, 20]), ("notes.txt", vec![1])]
+    );
+
+    assert_eq!(parse_unified_diff("").unwrap(), vec![]);
+    assert_eq!(parse_unified_diff("hello\n"), Err(DiffError::NotADiff));
+    assert!(matches!(
+        parse_unified_diff("+++ b/core.py\n@@ nonsense @@\n"),
+        Err(DiffError::BadHunk(_))
+    ));
+}
+
+#[tokio::test]
+async fn changed_entities_and_their_dependents_are_reported() {
+    let repo = core_repo();
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+
+    // `connect` changes; `save` calls it, `handle` calls `save`.
+    let diff =
+        "--- a/core.py\n+++ b/core.py\n@@ -5 +5 @@\n-    return 0\n+    return os.getpid()\n\
+                --- a/README.md\n+++ b/README.md\n@@ -1 +1 @@\n-a\n+b\n";
+    let result = call(&state, "diff_impact", json!({ "diff": diff })).await;
+    assert_matches_schema("diff_impact", &result);
+    assert_eq!(ids(&result["changed"]), ["core.py::connect"]);
+    assert_eq!(
+        ids(&result["impacted"]),
+        ["core.py::Store::save", "api.py::handle"]
+    );
+    assert_eq!(result["impacted"][1]["depth"], 2);
+    assert_eq!(
+        result["impacted"][1]["via"],
+        json!({ "source": "api.py::handle", "target": "core.py::Store::save", "relation": "invoke" })
+    );
+    assert_eq!(result["unindexed_files"], json!(["README.md"]));
+
+    let near = call(&state, "diff_impact", json!({ "diff": diff, "depth": 1 })).await;
+    assert_eq!(ids(&near["impacted"]), ["core.py::Store::save"]);
+
+    // A class body line maps to the class, an import to its file.
+    let diff = "--- a/core.py\n+++ b/core.py\n@@ -1 +1 @@\n-import sys\n+import os\n@@ -17 +17 @@\n-    x = 1\n+    pass\n";
+    let result = call(
+        &state,
+        "diff_impact",
+        json!({ "diff": diff, "relations": ["inherit"] }),
+    )
+    .await;
+    assert_eq!(ids(&result["changed"]), ["core.py", "core.py::CachedStore"]);
+    assert_eq!(result["impacted"], json!([]));
+}
+
+#[tokio::test]
+async fn diff_impact_params_are_validated() {
+    let repo = core_repo();
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+    for params in [
+        json!({ "diff": "not a diff" }),
+        json!({ "diff": "", "depth": 11 }),
+    ] {
+        let response = respond(&state, "diff_impact", params).await;
+        assert_eq!(response["error"]["code"], -32602, "{response}");
+    }
+    let empty = call(&state, "diff_impact", json!({ "diff": "" })).await;
+    assert_eq!(empty["changed"], json!([]));
+}
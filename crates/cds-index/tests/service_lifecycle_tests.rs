@@ -0,0 +1,158 @@
+//! Service tests for graceful shutdown and configuration/index reloads.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::Request;
+use cds_index::service::lifecycle::{finish_jobs, reload};
+use cds_index::service::server::router;
+use cds_index::service::stdio::serve_lines;
+use cds_index::service::{ApiToken, IndexEvent, Role, ServiceState};
+use common::{call, config, rebuild, respond, start_rebuild, write_file};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::time::{timeout, Instant};
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn shutdown_stops_transports_and_refuses_rebuilds() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "app.py", "def main():\n    pass\n");
+    let index_root = TempDir::new().unwrap();
+    let state = Arc::new(ServiceState::new(config(index_root.path())));
+
+    // stdin stays open; only the shutdown ends the stdio transport.
+    let (_client, input) = tokio::io::duplex(1024);
+    let stdio = tokio::spawn(serve_lines(Arc::clone(&state), input, tokio::io::sink()));
+    let events = router(Arc::clone(&state))
+        .oneshot(Request::get("/events").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    let http = tokio::spawn(cds_index::service::server::serve(Arc::clone(&state), addr));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(state.begin_shutdown());
+    assert!(!state.begin_shutdown());
+    assert!(state.is_shutting_down());
+
+    timeout(Duration::from_secs(5), stdio)
+        .await
+        .expect("stdio transport did not stop")
+        .unwrap()
+        .unwrap();
+    timeout(Duration::from_secs(5), http)
+        .await
+        .expect("HTTP server did not stop")
+        .unwrap()
+        .unwrap();
+    timeout(
+        Duration::from_secs(5),
+        axum::body::to_bytes(events.into_body(), usize::MAX),
+    )
+    .await
+    .expect("event stream did not end")
+    .unwrap();
+
+    let response = respond(
+        &state,
+        "rebuild_index",
+        json!({ "repo_path": repo.path().display().to_string() }),
+    )
+    .await;
+    assert_eq!(response["error"]["code"], -32011);
+}
+
+#[tokio::test]
+async fn shutdown_waits_for_running_rebuilds() {
+    let repo = TempDir::new().unwrap();
+    for i in 0..20 {
+        write_file(
+            repo.path(),
+            &format!("pkg/module_{i}.py"),
+            &format!("def handler_{i}(event):\n    return event\n"),
+        );
+    }
+    let index_root = TempDir::new().unwrap();
+    let state = Arc::new(ServiceState::new(config(index_root.path())));
+
+    let job_id = start_rebuild(&state, repo.path()).await;
+    state.begin_shutdown();
+    let cancelled = finish_jobs(&state, Instant::now() + Duration::from_secs(60)).await;
+    assert_eq!(cancelled, 0);
+    let status = call(&state, "get_job_status", json!({ "job_id": job_id })).await;
+    assert_eq!(status["status"], "completed");
+    assert_eq!(state.current().unwrap().generation, 1);
+}
+
+#[tokio::test]
+async fn reload_applies_tokens_and_indices_rebuilt_on_disk() {
+    let repo = TempDir::new().unwrap();
+    write_file(
+        repo.path(),
+        "billing.py",
+        "def invoice(order):\n    return order\n",
+    );
+    let index_root = TempDir::new().unwrap();
+    let state = Arc::new(ServiceState::new(config(index_root.path())));
+    rebuild(&state, repo.path()).await;
+    let search = json!({ "query": "refund", "use_bm25": false });
+    let before = call(&state, "search_entities", search.clone()).await;
+    assert_eq!(before["entities"], json!([]));
+
+    // Another process (e.g. `cds init`) rebuilds the same index directories.
+    write_file(
+        repo.path(),
+        "billing.py",
+        "def invoice(order):\n    return order\n\n\ndef refund(order):\n    return order\n",
+    );
+    let other = Arc::new(ServiceState::new(config(index_root.path())));
+    assert!(other.load_persisted().unwrap());
+    rebuild(&other, repo.path()).await;
+    assert_eq!(state.current().unwrap().generation, 1);
+
+    let mut events = state.events().subscribe();
+    let mut reloaded = config(index_root.path());
+    reloaded.auth_tokens = vec![ApiToken {
+        name: "ops".to_string(),
+        role: Role::Admin,
+        token: "s3cret".to_string(),
+    }];
+    reloaded.port = 4040;
+    let report = reload(&state, Ok(reloaded)).expect("reload failed");
+    assert_eq!(report.tokens, 1);
+    assert_eq!(report.reloaded, [("default".to_string(), 2)]);
+    assert_eq!(report.restart_required, ["INDEX_SERVICE_PORT"]);
+    assert!(state.auth().is_enabled());
+
+    assert_eq!(state.current().unwrap().generation, 2);
+    let after = call(&state, "search_entities", search).await;
+    assert_eq!(after["entities"][0]["id"], "billing.py::refund");
+    match events.recv().await.unwrap() {
+        IndexEvent::GenerationSwapped(swapped) => {
+            assert_eq!(swapped.generation, 2);
+            assert_eq!(swapped.files_changed, Some(1));
+        }
+        other => panic!("unexpected event {other:?}"),
+    }
+
+    // Nothing changed on disk since: a second reload keeps the index.
+    let report = reload(&state, Ok(config(index_root.path()))).unwrap();
+    assert!(report.reloaded.is_empty());
+    assert_eq!(report.tokens, 0);
+
+    // An invalid configuration is rejected as a whole.
+    let mut invalid = config(index_root.path());
+    invalid.rate_limit_burst = 0;
+    invalid.auth_tokens = vec![ApiToken {
+        name: "ops".to_string(),
+        role: Role::Admin,
+        token: "s3cret".to_string(),
+    }];
+    assert!(reload(&state, Ok(invalid)).is_none());
+    assert!(!state.auth().is_enabled());
+}
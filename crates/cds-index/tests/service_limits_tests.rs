@@ -0,0 +1,105 @@
+//! Service tests for per-client rate limits and the concurrency cap.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use cds_index::service::limits::RequestLimiter;
+use cds_index::service::server::router;
+use cds_index::service::{ApiToken, Role, ServiceState};
+use cds_index::IndexServiceConfig;
+use common::config;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+async fn list_repos(
+    state: &Arc<ServiceState>,
+    token: Option<&str>,
+) -> (StatusCode, Option<String>, Value) {
+    let mut request = Request::post("/rpc").header("content-type", "application/json");
+    if let Some(token) = token {
+        request = request.header("authorization", format!("Bearer {token}"));
+    }
+    let body = json!({ "jsonrpc": "2.0", "id": 1, "method": "list_repos" });
+    let response = router(Arc::clone(state))
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let retry_after = response
+        .headers()
+        .get("retry-after")
+        .map(|value| value.to_str().unwrap().to_string());
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, retry_after, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+async fn clients_are_rate_limited_independently() {
+    let index_root = TempDir::new().unwrap();
+    let mut config = config(index_root.path());
+    config.rate_limit_per_second = 0.5;
+    config.rate_limit_burst = 2;
+    config.auth_tokens = ["alpha", "beta"]
+        .into_iter()
+        .map(|name| ApiToken {
+            name: name.to_string(),
+            role: Role::Read,
+            token: format!("{name}-token"),
+        })
+        .collect();
+    let state = Arc::new(ServiceState::new(config));
+
+    for _ in 0..2 {
+        let (status, _, body) = list_repos(&state, Some("alpha-token")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["result"]["repos"].is_array());
+    }
+    let (status, retry_after, body) = list_repos(&state, Some("alpha-token")).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["error"]["code"], -32010);
+    assert_eq!(body["error"]["data"]["limit"], "rate");
+    assert_eq!(body["error"]["data"]["client"], "token:alpha");
+    let retry_after_ms = body["error"]["data"]["retry_after_ms"].as_u64().unwrap();
+    assert!((1..=2000).contains(&retry_after_ms), "{retry_after_ms}");
+    assert_eq!(retry_after.as_deref(), Some("2"));
+
+    // Another token has its own bucket.
+    let (status, _, _) = list_repos(&state, Some("beta-token")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Unauthenticated callers are limited by address, before they can probe tokens.
+    for _ in 0..2 {
+        let (status, _, _) = list_repos(&state, Some("guess")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    let (status, _, body) = list_repos(&state, Some("guess")).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["error"]["data"]["client"], "local");
+}
+
+#[test]
+fn concurrency_cap_holds_slots_until_requests_finish() {
+    let index_root = TempDir::new().unwrap();
+    let mut config = config(index_root.path());
+    config.max_concurrent_requests = 2;
+    let limiter = RequestLimiter::new(&config);
+
+    let first = limiter.admit("a").unwrap();
+    let _second = limiter.admit("b").unwrap();
+    let error = limiter.admit("c").unwrap_err();
+    assert_eq!(error.code, -32010);
+    assert_eq!(error.data.as_ref().unwrap()["limit"], "concurrency");
+
+    drop(first);
+    assert!(limiter.admit("c").is_ok());
+
+    let unlimited = RequestLimiter::new(&IndexServiceConfig::new("graph", "bm25"));
+    assert!(!unlimited.is_enabled());
+    let held: Vec<_> = (0..100).map(|_| unlimited.admit("a").unwrap()).collect();
+    assert_eq!(held.len(), 100);
+}
@@ -0,0 +1,276 @@
+//! Service tests for the Model Context Protocol server.
+
+mod common;
+
+use cds_index::service::mcp::{serve_mcp_lines, tool_definitions, MCP_TOOLS};
+use common::{service_state, write_file};
+use jsonschema::{Draft, JSONSchema};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, DuplexStream, Lines, ReadHalf, WriteHalf};
+
+type Reader = Lines<tokio::io::BufReader<ReadHalf<DuplexStream>>>;
+
+async fn send(writer: &mut WriteHalf<DuplexStream>, message: Value) {
+    let mut line = serde_json::to_vec(&message).unwrap();
+    line.push(b'\n');
+    writer.write_all(&line).await.unwrap();
+}
+
+async fn request(
+    writer: &mut WriteHalf<DuplexStream>,
+    reader: &mut Reader,
+    message: Value,
+) -> Value {
+    send(writer, message).await;
+    let line = tokio::time::timeout(Duration::from_secs(30), reader.next_line())
+        .await
+        .expect("timed out waiting for a reply")
+        .unwrap()
+        .expect("server closed the stream");
+    serde_json::from_str(&line).unwrap()
+}
+
+async fn call_tool(
+    writer: &mut WriteHalf<DuplexStream>,
+    reader: &mut Reader,
+    id: u64,
+    name: &str,
+    arguments: Value,
+) -> Value {
+    let reply = request(
+        writer,
+        reader,
+        json!({ "jsonrpc": "2.0", "id": id, "method": "tools/call",
+                "params": { "name": name, "arguments": arguments } }),
+    )
+    .await;
+    assert_eq!(reply["id"], id);
+    reply["result"].clone()
+}
+
+#[test]
+fn tool_schemas_are_derived_from_the_jsonrpc_schema() {
+    let tools = tool_definitions();
+    let names: Vec<&str> = tools
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, MCP_TOOLS);
+
+    for tool in tools {
+        assert!(tool["description"]
+            .as_str()
+            .is_some_and(|text| !text.is_empty()));
+        for key in ["inputSchema", "outputSchema"] {
+            let schema = &tool[key];
+            assert_eq!(schema["type"], "object", "{key} of {}", tool["name"]);
+            assert!(
+                !schema.to_string().contains("$ref"),
+                "{key} of {}",
+                tool["name"]
+            );
+            JSONSchema::options()
+                .with_draft(Draft::Draft7)
+                .compile(schema)
+                .unwrap_or_else(|err| panic!("{key} of {}: {err}", tool["name"]));
+        }
+    }
+
+    let search = &tools[0];
+    assert_eq!(search["inputSchema"]["required"], json!(["query"]));
+    // `$ref`s into the definitions are inlined.
+    assert_eq!(
+        search["inputSchema"]["properties"]["entity_types"]["items"]["enum"],
+        json!(["directory", "file", "class", "function"])
+    );
+}
+
+#[tokio::test]
+async fn mcp_session_rebuilds_and_searches_the_index() {
+    let repo = TempDir::new().unwrap();
+    write_file(
+        repo.path(),
+        "billing.py",
+        "def send_invoice(order):\n    return order\n",
+    );
+    let index_root = TempDir::new().unwrap();
+    let state = service_state(index_root.path());
+
+    let (client, server) = tokio::io::duplex(256 * 1024);
+    let (server_read, server_write) = tokio::io::split(server);
+    let serving = tokio::spawn(serve_mcp_lines(state, server_read, server_write));
+    let (client_read, mut writer) = tokio::io::split(client);
+    let mut reader = tokio::io::BufReader::new(client_read).lines();
+
+    let init = request(
+        &mut writer,
+        &mut reader,
+        json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {
+            "protocolVersion": "2025-03-26",
+            "capabilities": {},
+            "clientInfo": { "name": "test", "version": "0" },
+        }}),
+    )
+    .await;
+    assert_eq!(init["result"]["protocolVersion"], "2025-03-26");
+    assert_eq!(init["result"]["serverInfo"]["name"], "cds-index");
+    assert!(init["result"]["capabilities"]["tools"].is_object());
+
+    // Notifications get no reply, so the next line answers tools/list.
+    send(
+        &mut writer,
+        json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+    )
+    .await;
+    let list = request(
+        &mut writer,
+        &mut reader,
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" }),
+    )
+    .await;
+    assert_eq!(list["id"], 1);
+    assert_eq!(
+        list["result"]["tools"].as_array().unwrap().len(),
+        MCP_TOOLS.len()
+    );
+
+    // Service errors are tool results the model can act on.
+    let missing = call_tool(
+        &mut writer,
+        &mut reader,
+        2,
+        "search_entities",
+        json!({ "query": "x" }),
+    )
+    .await;
+    assert_eq!(missing["isError"], true);
+    let error: Value =
+        serde_json::from_str(missing["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(error["error"]["code"], -32001);
+
+    let rebuild = call_tool(
+        &mut writer,
+        &mut reader,
+        3,
+        "rebuild_index",
+        json!({ "repo_path": repo.path().display().to_string() }),
+    )
+    .await;
+    assert_eq!(rebuild["isError"], false);
+    let job_id = rebuild["structuredContent"]["job_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let mut id = 4;
+    loop {
+        let status = call_tool(
+            &mut writer,
+            &mut reader,
+            id,
+            "get_job_status",
+            json!({ "job_id": job_id }),
+        )
+        .await;
+        id += 1;
+        match status["structuredContent"]["status"].as_str() {
+            Some("queued") | Some("running") => tokio::time::sleep(Duration::from_millis(50)).await,
+            other => {
+                assert_eq!(other, Some("completed"), "{status}");
+                break;
+            }
+        }
+    }
+
+    let search = call_tool(
+        &mut writer,
+        &mut reader,
+        id,
+        "search_entities",
+        json!({ "query": "send_invoice" }),
+    )
+    .await;
+    assert_eq!(search["isError"], false);
+    let content = &search["structuredContent"];
+    assert_eq!(content["entities"][0]["name"], "send_invoice");
+    let text: Value = serde_json::from_str(search["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(&text, content);
+    let output_schema = JSONSchema::options()
+        .with_draft(Draft::Draft7)
+        .compile(&tool_definitions()[0]["outputSchema"])
+        .unwrap();
+    assert!(output_schema.is_valid(content));
+
+    let unknown = request(
+        &mut writer,
+        &mut reader,
+        json!({ "jsonrpc": "2.0", "id": "u", "method": "tools/call",
+                "params": { "name": "add_repo", "arguments": {} } }),
+    )
+    .await;
+    assert_eq!(unknown["error"]["code"], -32602);
+
+    writer.shutdown().await.unwrap();
+    assert!(reader.next_line().await.unwrap().is_none());
+    serving.await.unwrap().unwrap();
+}
+
+#[test]
+fn mcp_stdout_stays_json_when_a_source_file_fails_to_parse() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::{Command, Stdio};
+
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "ok.py", "def ok():\n    return 1\n");
+    write_file(
+        repo.path(),
+        "legacy.py",
+        "def legacy():\n    print \"python 2\"\n",
+    );
+    let index_root = TempDir::new().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cds-index-service"))
+        .arg("--mcp")
+        .env("GRAPH_INDEX_DIR", index_root.path().join("graph"))
+        .env("BM25_INDEX_DIR", index_root.path().join("bm25"))
+        .env("PARITY_DEBUG", "1")
+        .env("RUST_LOG", "debug")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("spawn cds-index-service");
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+    let mut call = |id: u64, name: &str, arguments: Value| -> Value {
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": "tools/call",
+                              "params": { "name": name, "arguments": arguments } });
+        writeln!(stdin, "{message}").unwrap();
+        let line = stdout.next().expect("reply line").unwrap();
+        let reply: Value =
+            serde_json::from_str(&line).unwrap_or_else(|err| panic!("{err}: {line:?}"));
+        reply["result"]["structuredContent"].clone()
+    };
+
+    let job = call(
+        1,
+        "rebuild_index",
+        json!({ "repo_path": repo.path().display().to_string() }),
+    );
+    let mut id = 2;
+    let status = loop {
+        let status = call(id, "get_job_status", json!({ "job_id": job["job_id"] }));
+        id += 1;
+        match status["status"].as_str() {
+            Some("queued") | Some("running") => std::thread::sleep(Duration::from_millis(50)),
+            _ => break status,
+        }
+    };
+    assert_eq!(status["status"], "completed", "{status}");
+    assert_eq!(status["stats"]["files_indexed"], 2);
+
+    drop(stdin);
+    let rest: Vec<String> = stdout.map(Result::unwrap).collect();
+    assert!(child.wait().unwrap().success());
+    assert!(rest.is_empty(), "unexpected output: {rest:?}");
+}
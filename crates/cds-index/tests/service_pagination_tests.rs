@@ -0,0 +1,225 @@
+//! Service tests for cursor pagination of search and traversal results.
+
+mod common;
+
+use cds_index::service::cursor::{fingerprint, PageCursor};
+use common::{call, rebuild, respond, service_state, write_file};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use tempfile::TempDir;
+
+/// A module whose `dispatch` calls `handler_00` .. `handler_{count-1}`.
+fn handlers_module(count: usize) -> String {
+    let mut source = String::from("def dispatch(event):\n");
+    for i in 0..count {
+        source.push_str(&format!("    handler_{i:02}(event)\n"));
+    }
+    for i in 0..count {
+        source.push_str(&format!(
+            "\n\ndef handler_{i:02}(event):\n    return event\n"
+        ));
+    }
+    source
+}
+
+fn ids(items: &Value) -> Vec<String> {
+    items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn search_pages_cover_every_match_once() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "handlers.py", &handlers_module(25));
+    let index_root = TempDir::new().unwrap();
+    let state = service_state(index_root.path());
+    rebuild(&state, repo.path()).await;
+
+    let params = json!({ "query": "handler_", "entity_types": ["function"], "use_bm25": false });
+    let everything = call(
+        &state,
+        "search_entities",
+        json!({ "query": "handler_", "entity_types": ["function"], "use_bm25": false, "limit": 100 }),
+    )
+    .await;
+    assert!(everything.get("next_cursor").is_none(), "{everything}");
+    assert_eq!(everything["total_count"], 25);
+
+    let mut seen = Vec::new();
+    let mut cursor: Option<String> = None;
+    let mut pages = 0;
+    loop {
+        let mut request = params.clone();
+        request["limit"] = json!(10);
+        if let Some(cursor) = &cursor {
+            request["cursor"] = json!(cursor);
+        }
+        let page = call(&state, "search_entities", request).await;
+        pages += 1;
+        seen.extend(ids(&page["entities"]));
+        match page["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+    assert_eq!(pages, 3);
+    assert_eq!(seen, ids(&everything["entities"]));
+
+    // Page size may change between pages; the cursor only fixes the position.
+    let first = call(
+        &state,
+        "search_entities",
+        json!({ "query": "handler_", "entity_types": ["function"], "use_bm25": false, "limit": 5 }),
+    )
+    .await;
+    let second = call(
+        &state,
+        "search_entities",
+        json!({
+            "query": " handler_ ",
+            "entity_types": ["function"],
+            "use_bm25": false,
+            "limit": 20,
+            "snippet_mode": "fold",
+            "cursor": first["next_cursor"],
+        }),
+    )
+    .await;
+    assert_eq!(ids(&second["entities"]), seen[5..].to_vec());
+    assert!(second.get("next_cursor").is_none());
+}
+
+#[tokio::test]
+async fn traversal_pages_partition_nodes_and_edges() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "handlers.py", &handlers_module(12));
+    let index_root = TempDir::new().unwrap();
+    let state = service_state(index_root.path());
+    rebuild(&state, repo.path()).await;
+
+    let params = json!({
+        "start_entities": ["handlers.py::dispatch"],
+        "relations": ["invoke"],
+    });
+    let whole = call(&state, "traverse_graph", params.clone()).await;
+    assert!(whole.get("next_cursor").is_none());
+    assert_eq!(whole["metadata"]["total_nodes"], 13);
+    assert_eq!(whole["metadata"]["total_edges"], 12);
+
+    let mut nodes = Vec::new();
+    let mut edges = HashSet::new();
+    let mut request = params.clone();
+    request["limit"] = json!(5);
+    loop {
+        let page = call(&state, "traverse_graph", request.clone()).await;
+        assert_eq!(page["metadata"]["total_nodes"], 13);
+        assert_eq!(page["metadata"]["total_edges"], 12);
+        let page_nodes = ids(&page["subgraph"]["nodes"]);
+        assert!(page_nodes.len() <= 5);
+        nodes.extend(page_nodes);
+        for edge in page["subgraph"]["edges"].as_array().unwrap() {
+            // Each edge is returned once, with both endpoints already delivered.
+            assert!(nodes.contains(&edge["source"].as_str().unwrap().to_string()));
+            assert!(nodes.contains(&edge["target"].as_str().unwrap().to_string()));
+            assert!(edges.insert(edge.to_string()), "duplicate edge {edge}");
+        }
+        match page["next_cursor"].as_str() {
+            Some(next) => request["cursor"] = json!(next),
+            None => break,
+        }
+    }
+    assert_eq!(nodes, ids(&whole["subgraph"]["nodes"]));
+    let whole_edges: HashSet<String> = whole["subgraph"]["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(Value::to_string)
+        .collect();
+    assert_eq!(edges, whole_edges);
+
+    let error = respond(
+        &state,
+        "traverse_graph",
+        json!({ "start_entities": ["handlers.py::dispatch"], "limit": 0 }),
+    )
+    .await;
+    assert_eq!(error["error"]["code"], -32602);
+}
+
+#[tokio::test]
+async fn cursors_expire_with_the_generation_and_bind_to_their_query() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "handlers.py", &handlers_module(4));
+    let index_root = TempDir::new().unwrap();
+    let state = service_state(index_root.path());
+    rebuild(&state, repo.path()).await;
+
+    let first = call(
+        &state,
+        "search_entities",
+        json!({ "query": "handler_", "limit": 2, "use_bm25": false }),
+    )
+    .await;
+    let cursor = first["next_cursor"].as_str().unwrap().to_string();
+
+    let other_query = respond(
+        &state,
+        "search_entities",
+        json!({ "query": "dispatch", "limit": 2, "cursor": cursor }),
+    )
+    .await;
+    assert_eq!(other_query["error"]["code"], -32602);
+    assert!(other_query["error"]["data"]["detail"]
+        .as_str()
+        .unwrap()
+        .contains("different query"));
+    let garbage = respond(
+        &state,
+        "search_entities",
+        json!({ "query": "handler_", "cursor": "not-a-cursor" }),
+    )
+    .await;
+    assert_eq!(garbage["error"]["code"], -32602);
+
+    rebuild(&state, repo.path()).await;
+    let stale = respond(
+        &state,
+        "search_entities",
+        json!({ "query": "handler_", "limit": 2, "use_bm25": false, "cursor": cursor }),
+    )
+    .await;
+    assert_eq!(stale["error"]["code"], -32602);
+    assert!(stale["error"]["data"]["detail"]
+        .as_str()
+        .unwrap()
+        .contains("generation"));
+    assert!(stale["error"]["data"]["suggestion"].is_string());
+}
+
+#[test]
+fn cursors_round_trip_and_reject_tampering() {
+    let cursor = PageCursor {
+        generation: 7,
+        offset: 40,
+        fingerprint: fingerprint("default", "search_entities", &json!({ "query": "a" })),
+    };
+    let encoded = cursor.encode();
+    assert_eq!(PageCursor::decode(&encoded).unwrap(), cursor);
+    assert_eq!(
+        PageCursor::resume(Some(&encoded), 7, cursor.fingerprint).unwrap(),
+        40
+    );
+    assert_eq!(PageCursor::resume(None, 7, cursor.fingerprint).unwrap(), 0);
+    assert!(PageCursor::resume(Some(&encoded), 8, cursor.fingerprint).is_err());
+
+    assert_ne!(
+        fingerprint("default", "search_entities", &json!({ "query": "a" })),
+        fingerprint("other", "search_entities", &json!({ "query": "a" }))
+    );
+    assert!(PageCursor::decode(&encoded[1..]).is_err());
+    assert!(PageCursor::decode(&format!("02{}", &encoded[2..])).is_err());
+}
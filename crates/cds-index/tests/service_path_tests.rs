@@ -0,0 +1,128 @@
+//! Service tests for `find_paths`: shortest dependency paths between entities.
+
+mod common;
+
+use common::{assert_matches_schema, call, indexed_state, respond, write_file};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+const CORE: &str = "def connect():\n    pass\n\n\ndef save():\n    connect()\n\n\ndef load():\n    connect()\n\n\ndef handle():\n    save()\n    load()\n\n\ndef audit():\n    pass\n";
+
+const API: &str = "from core import handle as entry\n\n\ndef main():\n    entry()\n";
+
+fn core_repo() -> TempDir {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "core.py", CORE);
+    write_file(repo.path(), "api.py", API);
+    repo
+}
+
+fn path_ids(path: &Value) -> Vec<&str> {
+    path["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|node| node["id"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn paths_are_the_k_shortest_over_the_selected_relations() {
+    let repo = core_repo();
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+
+    let result = call(
+        &state,
+        "find_paths",
+        json!({ "from": "core.py::handle", "to": "core.py::connect", "relations": ["invoke"], "k": 5 }),
+    )
+    .await;
+    assert_matches_schema("find_paths", &result);
+    let paths = result["paths"].as_array().unwrap();
+    // Equal lengths: ordered by the ids along the path.
+    assert_eq!(paths.len(), 2, "{result}");
+    assert_eq!(
+        path_ids(&paths[0]),
+        ["core.py::handle", "core.py::load", "core.py::connect"]
+    );
+    assert_eq!(
+        path_ids(&paths[1]),
+        ["core.py::handle", "core.py::save", "core.py::connect"]
+    );
+    assert_eq!(paths[0]["length"], 2);
+    assert_eq!(paths[0]["nodes"][2]["depth"], 2);
+    assert_eq!(
+        paths[0]["hops"][0],
+        json!({ "source": "core.py::handle", "target": "core.py::load", "relation": "invoke" })
+    );
+
+    let one = call(
+        &state,
+        "find_paths",
+        json!({ "from": "core.py::handle", "to": "core.py::connect" }),
+    )
+    .await;
+    assert_eq!(one["paths"].as_array().unwrap().len(), 1);
+
+    // Too short a reach, the wrong direction or an unconnected entity: no paths.
+    for params in [
+        json!({ "from": "core.py::handle", "to": "core.py::connect", "max_depth": 1 }),
+        json!({ "from": "core.py::connect", "to": "core.py::handle", "relations": ["invoke"] }),
+        json!({ "from": "core.py::handle", "to": "core.py::audit", "relations": ["invoke"] }),
+    ] {
+        let result = call(&state, "find_paths", params).await;
+        assert_eq!(result["paths"], json!([]), "{result}");
+    }
+    let backward = call(
+        &state,
+        "find_paths",
+        json!({ "from": "core.py::connect", "to": "core.py::handle", "relations": ["invoke"], "direction": "backward" }),
+    )
+    .await;
+    let hop = &backward["paths"][0]["hops"][0];
+    assert_eq!(hop["source"], "core.py::load");
+    assert_eq!(hop["target"], "core.py::connect");
+}
+
+#[tokio::test]
+async fn import_hops_carry_their_alias() {
+    let repo = core_repo();
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+
+    let result = call(
+        &state,
+        "find_paths",
+        json!({ "from": "api.py", "to": "core.py::handle", "relations": ["import"] }),
+    )
+    .await;
+    assert_eq!(
+        result["paths"][0]["hops"],
+        json!([{ "source": "api.py", "target": "core.py::handle", "relation": "import", "alias": "entry" }])
+    );
+}
+
+#[tokio::test]
+async fn path_params_are_validated() {
+    let repo = core_repo();
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+    for params in [
+        json!({ "from": "core.py::save", "to": "core.py::save" }),
+        json!({ "from": "core.py::save", "to": "core.py::load", "k": 0 }),
+        json!({ "from": "core.py::save", "to": "core.py::load", "k": 11 }),
+        json!({ "from": "core.py::save", "to": "core.py::load", "max_depth": 0 }),
+        json!({ "from": "core.py::save", "to": "core.py::load", "max_depth": 11 }),
+    ] {
+        let response = respond(&state, "find_paths", params).await;
+        assert_eq!(response["error"]["code"], -32602, "{response}");
+    }
+    let response = respond(
+        &state,
+        "find_paths",
+        json!({ "from": "core.py::save", "to": "core.py::nope" }),
+    )
+    .await;
+    assert_eq!(response["error"]["code"], -32002, "{response}");
+}
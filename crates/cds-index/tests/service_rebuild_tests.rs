@@ -0,0 +1,210 @@
+//! Service tests for background rebuild jobs and the query methods they feed.
+
+mod common;
+
+use cds_index::graph::{BuildPhase, BuildProgress, GraphBuilderConfig};
+use cds_index::index::Bm25Params;
+use cds_index::persistence::IndexLayout;
+use cds_index::service::LoadedIndex;
+use common::{assert_matches_schema, call, respond, service_state, wait_for_job, write_file};
+use serde_json::json;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tempfile::TempDir;
+
+fn sample_repo() -> TempDir {
+    let repo = TempDir::new().expect("tempdir");
+    write_file(repo.path(), "pkg/__init__.py", "");
+    write_file(
+        repo.path(),
+        "pkg/util.py",
+        r#"def sanitize_input(text):
+    """Strip dangerous characters."""
+    return text.strip()
+
+
+class Helper:
+    def run(self, value):
+        return sanitize_input(value)
+"#,
+    );
+    write_file(
+        repo.path(),
+        "pkg/main.py",
+        r#"from pkg.util import Helper
+
+
+def main():
+    return Helper().run(" hi ")
+"#,
+    );
+    repo
+}
+
+#[tokio::test]
+async fn rebuild_job_reports_progress_and_swaps_index() {
+    let repo = sample_repo();
+    let index_root = TempDir::new().unwrap();
+    let state = service_state(index_root.path());
+
+    let before = respond(&state, "search_entities", json!({ "query": "Helper" })).await;
+    assert_eq!(before["error"]["code"], -32001);
+
+    let started = call(
+        &state,
+        "rebuild_index",
+        json!({ "repo_path": repo.path().display().to_string() }),
+    )
+    .await;
+    let job_id = started["job_id"].as_str().expect("job id").to_string();
+
+    let finished = wait_for_job(&state, &job_id).await;
+    assert_eq!(finished["status"], "completed", "{finished}");
+    assert_eq!(finished["phase"], "bm25");
+    assert_eq!(finished["files_total"], 3);
+    assert_eq!(finished["files_processed"], 3);
+    assert_eq!(finished["generation"], 1);
+    assert_eq!(finished["stats"]["entities_found"]["classes"], 1);
+    assert_matches_schema("rebuild_index", &started);
+    assert_matches_schema("get_job_status", &finished);
+
+    let search = call(&state, "search_entities", json!({ "query": "helper" })).await;
+    assert_matches_schema("search_entities", &search);
+    assert_eq!(search["entities"][0]["id"], "pkg/util.py::Helper");
+    assert_eq!(search["entities"][0]["snippet"]["fold"], "class Helper:");
+
+    let bm25 = call(
+        &state,
+        "search_entities",
+        json!({ "query": "dangerous characters", "snippet_mode": "full" }),
+    )
+    .await;
+    assert_eq!(bm25["query_metadata"]["used_bm25"], true);
+    assert_eq!(bm25["entities"][0]["id"], "pkg/util.py::sanitize_input");
+
+    let traverse = call(
+        &state,
+        "traverse_graph",
+        json!({ "start_entities": ["pkg/util.py::sanitize_input"], "direction": "backward", "relations": ["invoke"] }),
+    )
+    .await;
+    assert_matches_schema("traverse_graph", &traverse);
+    let ids: Vec<&str> = traverse["subgraph"]["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|node| node["id"].as_str())
+        .collect();
+    assert!(ids.contains(&"pkg/util.py::Helper::run"), "{ids:?}");
+
+    let retrieve = call(
+        &state,
+        "retrieve_entity",
+        json!({ "entity_ids": ["pkg/util.py::Helper::run"], "include_context": 1 }),
+    )
+    .await;
+    assert_matches_schema("retrieve_entity", &retrieve);
+    let entity = &retrieve["entities"][0];
+    assert!(entity["code"].as_str().unwrap().starts_with("    def run"));
+    assert_eq!(entity["context_before"], "class Helper:");
+
+    // A fresh service picks the persisted index back up.
+    let restarted = service_state(index_root.path());
+    assert!(restarted.load_persisted().expect("load persisted"));
+    assert_eq!(restarted.current().unwrap().generation, 1);
+}
+
+#[tokio::test]
+async fn unknown_jobs_and_methods_return_structured_errors() {
+    let index_root = TempDir::new().unwrap();
+    let state = service_state(index_root.path());
+
+    let status = respond(&state, "get_job_status", json!({ "job_id": "job-42" })).await;
+    assert_eq!(status["error"]["code"], -32006);
+    let cancel = respond(&state, "cancel_job", json!({ "job_id": "job-42" })).await;
+    assert_eq!(cancel["error"]["code"], -32006);
+
+    let missing_repo = respond(
+        &state,
+        "rebuild_index",
+        json!({ "repo_path": "/no/such/repo" }),
+    )
+    .await;
+    assert_eq!(missing_repo["error"]["code"], -32602);
+    let unknown = respond(&state, "frobnicate", json!({})).await;
+    assert_eq!(unknown["error"]["code"], -32601);
+}
+
+/// Cancels the build as soon as the parse phase has processed one file.
+#[derive(Default)]
+struct CancelAfterFirstFile {
+    files: AtomicUsize,
+}
+
+impl BuildProgress for CancelAfterFirstFile {
+    fn file_processed(&self, _rel_path: &Path) {
+        self.files.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.files.load(Ordering::SeqCst) > 0
+    }
+}
+
+struct PhaseRecorder(std::sync::Mutex<Vec<BuildPhase>>);
+
+impl BuildProgress for PhaseRecorder {
+    fn phase_started(&self, phase: BuildPhase, _total: Option<usize>) {
+        self.0.lock().unwrap().push(phase);
+    }
+}
+
+#[test]
+fn cancelled_build_keeps_previous_index() {
+    let repo = sample_repo();
+    let index_root = TempDir::new().unwrap();
+    let layout = IndexLayout::under(index_root.path());
+
+    let recorder = PhaseRecorder(Default::default());
+    let (first, _) = LoadedIndex::build(
+        repo.path(),
+        GraphBuilderConfig::default(),
+        &Bm25Params::default(),
+        &layout,
+        1,
+        &recorder,
+    )
+    .expect("initial build");
+    assert_eq!(
+        *recorder.0.lock().unwrap(),
+        vec![
+            BuildPhase::Walk,
+            BuildPhase::Parse,
+            BuildPhase::Imports,
+            BuildPhase::Behaviors,
+            BuildPhase::Bm25
+        ]
+    );
+
+    write_file(repo.path(), "pkg/extra.py", "def extra():\n    pass\n");
+    let cancelled = LoadedIndex::build(
+        repo.path(),
+        GraphBuilderConfig::default(),
+        &Bm25Params::default(),
+        &layout,
+        2,
+        &CancelAfterFirstFile::default(),
+    );
+    assert!(matches!(
+        cancelled,
+        Err(cds_index::service::state::IndexBuildError::Cancelled)
+    ));
+
+    // The in-memory snapshot is untouched and the persisted index is still generation 1.
+    assert!(first.entity("pkg/util.py::Helper").is_some());
+    let reloaded = LoadedIndex::load(&layout).expect("reload");
+    assert_eq!(reloaded.generation, 1);
+    assert!(reloaded.entity("pkg/extra.py::extra").is_none());
+    assert_eq!(reloaded.graph.node_count(), first.graph.node_count());
+    assert_eq!(reloaded.graph.edge_count(), first.graph.edge_count());
+}
@@ -0,0 +1,283 @@
+//! Service tests for serving several named repositories from one process.
+
+mod common;
+
+use cds_index::service::ServiceState;
+use common::{call, config, respond, wait_for_job, write_file};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn repo_with(module: &str, function: &str) -> TempDir {
+    let repo = TempDir::new().expect("tempdir");
+    write_file(
+        repo.path(),
+        &format!("{module}.py"),
+        &format!("def {function}():\n    return 1\n"),
+    );
+    repo
+}
+
+fn limited_state(index_root: &Path, max_loaded_repos: usize) -> Arc<ServiceState> {
+    let mut config = config(index_root);
+    config.max_loaded_repos = max_loaded_repos;
+    Arc::new(ServiceState::new(config))
+}
+
+async fn add_and_build(state: &Arc<ServiceState>, name: &str, repo: &Path) {
+    let added = call(
+        state,
+        "add_repo",
+        json!({ "name": name, "repo_path": repo.display().to_string() }),
+    )
+    .await;
+    let job_id = added["job"]["job_id"].as_str().unwrap().to_string();
+    let finished = wait_for_job(state, &job_id).await;
+    assert_eq!(finished["status"], "completed", "{finished}");
+    assert_eq!(finished["repo"], name);
+}
+
+fn first_hit(response: &Value) -> &Value {
+    &response["result"]["entities"][0]["id"]
+}
+
+fn repo_entry<'a>(list: &'a Value, name: &str) -> &'a Value {
+    list["result"]["repos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|repo| repo["name"] == name)
+        .unwrap_or_else(|| panic!("{name} missing from {list}"))
+}
+
+#[tokio::test]
+async fn named_repositories_are_queried_independently() {
+    let payments = repo_with("payments", "charge_card");
+    let billing = repo_with("billing", "send_invoice");
+    let index_root = TempDir::new().unwrap();
+    let state = limited_state(index_root.path(), 4);
+
+    add_and_build(&state, "payments", payments.path()).await;
+    add_and_build(&state, "billing", billing.path()).await;
+
+    let hit = respond(
+        &state,
+        "search_entities",
+        json!({ "repo": "payments", "query": "charge_card" }),
+    )
+    .await;
+    assert_eq!(first_hit(&hit), "payments.py::charge_card");
+    let miss = respond(
+        &state,
+        "search_entities",
+        json!({ "repo": "billing", "query": "charge_card", "use_bm25": false }),
+    )
+    .await;
+    assert_eq!(miss["result"]["total_count"], 0);
+
+    let retrieve = respond(
+        &state,
+        "retrieve_entity",
+        json!({ "repo": "billing", "entity_ids": ["billing.py::send_invoice"] }),
+    )
+    .await;
+    assert!(retrieve["result"]["entities"][0]["code"]
+        .as_str()
+        .unwrap()
+        .starts_with("def send_invoice"));
+
+    // Nothing was built for the default repository.
+    let default = respond(&state, "search_entities", json!({ "query": "charge_card" })).await;
+    assert_eq!(default["error"]["code"], -32001);
+
+    let unknown = respond(
+        &state,
+        "traverse_graph",
+        json!({ "repo": "shipping", "start_entities": ["x.py"] }),
+    )
+    .await;
+    assert_eq!(unknown["error"]["code"], -32007);
+    assert_eq!(
+        unknown["error"]["data"]["available"],
+        json!(["billing", "default", "payments"])
+    );
+
+    let duplicate = respond(
+        &state,
+        "add_repo",
+        json!({ "name": "billing", "repo_path": billing.path().display().to_string() }),
+    )
+    .await;
+    assert_eq!(duplicate["error"]["code"], -32602);
+    let invalid = respond(
+        &state,
+        "add_repo",
+        json!({ "name": "../escape", "repo_path": billing.path().display().to_string() }),
+    )
+    .await;
+    assert_eq!(invalid["error"]["code"], -32602);
+}
+
+#[tokio::test]
+async fn idle_repositories_are_unloaded_and_reloaded_lazily() {
+    let payments = repo_with("payments", "charge_card");
+    let billing = repo_with("billing", "send_invoice");
+    let index_root = TempDir::new().unwrap();
+    let state = limited_state(index_root.path(), 1);
+
+    add_and_build(&state, "payments", payments.path()).await;
+    add_and_build(&state, "billing", billing.path()).await;
+
+    // Only the most recently built repository stays in memory.
+    let list = respond(&state, "list_repos", json!({})).await;
+    assert_eq!(list["result"]["max_loaded_repos"], 1);
+    assert_eq!(repo_entry(&list, "payments")["loaded"], false);
+    assert_eq!(repo_entry(&list, "payments")["generation"], 1);
+    assert_eq!(repo_entry(&list, "billing")["loaded"], true);
+
+    let hit = respond(
+        &state,
+        "search_entities",
+        json!({ "repo": "payments", "query": "charge_card" }),
+    )
+    .await;
+    assert_eq!(first_hit(&hit), "payments.py::charge_card");
+    let list = respond(&state, "list_repos", json!({})).await;
+    assert_eq!(repo_entry(&list, "payments")["loaded"], true);
+    assert_eq!(repo_entry(&list, "billing")["loaded"], false);
+
+    // Registrations survive a restart; named repositories load on first use.
+    let restarted = limited_state(index_root.path(), 1);
+    assert!(!restarted.load_persisted().expect("load persisted"));
+    let list = respond(&restarted, "list_repos", json!({})).await;
+    assert_eq!(repo_entry(&list, "billing")["loaded"], false);
+    assert_eq!(
+        repo_entry(&list, "billing")["repo_path"],
+        fs::canonicalize(billing.path())
+            .unwrap()
+            .display()
+            .to_string()
+    );
+    let hit = respond(
+        &restarted,
+        "search_entities",
+        json!({ "repo": "billing", "query": "send_invoice" }),
+    )
+    .await;
+    assert_eq!(first_hit(&hit), "billing.py::send_invoice");
+
+    // A rebuild after restart continues the persisted generation sequence.
+    let rebuilt = respond(
+        &restarted,
+        "rebuild_index",
+        json!({ "repo": "payments", "repo_path": payments.path().display().to_string() }),
+    )
+    .await;
+    let job_id = rebuilt["result"]["job_id"].as_str().unwrap().to_string();
+    assert_eq!(wait_for_job(&restarted, &job_id).await["generation"], 2);
+}
+
+#[tokio::test]
+async fn removed_repositories_stop_resolving() {
+    let payments = repo_with("payments", "charge_card");
+    let index_root = TempDir::new().unwrap();
+    let state = limited_state(index_root.path(), 4);
+    add_and_build(&state, "payments", payments.path()).await;
+
+    let default = respond(&state, "remove_repo", json!({ "name": "default" })).await;
+    assert_eq!(default["error"]["code"], -32602);
+
+    let removed = respond(
+        &state,
+        "remove_repo",
+        json!({ "name": "payments", "delete_index": true }),
+    )
+    .await;
+    assert_eq!(removed["result"]["index_deleted"], true, "{removed}");
+    assert!(!index_root.path().join("repos/payments").exists());
+
+    let query = respond(
+        &state,
+        "search_entities",
+        json!({ "repo": "payments", "query": "charge_card" }),
+    )
+    .await;
+    assert_eq!(query["error"]["code"], -32007);
+    let list = respond(&state, "list_repos", json!({})).await;
+    assert_eq!(list["result"]["repos"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn linked_rebuilds_follow_imports_into_sibling_repositories() {
+    let billing = TempDir::new().unwrap();
+    write_file(billing.path(), "billing/__init__.py", "");
+    write_file(
+        billing.path(),
+        "billing/invoice.py",
+        "def send_invoice(order):\n    return order\n",
+    );
+    let shop = TempDir::new().unwrap();
+    write_file(
+        shop.path(),
+        "shop/checkout.py",
+        "from billing.invoice import send_invoice\n\n\ndef checkout(order):\n    return send_invoice(order)\n",
+    );
+    let index_root = TempDir::new().unwrap();
+    let state = limited_state(index_root.path(), 4);
+    add_and_build(&state, "billing", billing.path()).await;
+
+    let added = call(
+        &state,
+        "add_repo",
+        json!({ "name": "shop", "repo_path": shop.path().display().to_string(), "link_repos": true }),
+    )
+    .await;
+    let job_id = added["job"]["job_id"].as_str().unwrap().to_string();
+    let finished = wait_for_job(&state, &job_id).await;
+    assert_eq!(
+        finished["stats"]["entities_found"]["external"], 1,
+        "{finished}"
+    );
+
+    let traverse = call(
+        &state,
+        "traverse_graph",
+        json!({ "repo": "shop", "start_entities": ["shop/checkout.py::checkout"], "relations": ["invoke"] }),
+    )
+    .await;
+    let external = traverse["subgraph"]["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|node| node["id"] == "@billing/billing/invoice.py::send_invoice")
+        .unwrap_or_else(|| panic!("external callee missing: {traverse}"));
+    assert_eq!(external["file_path"], "billing/invoice.py");
+    assert_eq!(external["external"]["repo"], "billing");
+    assert_eq!(
+        external["external"]["entity_id"],
+        "billing/invoice.py::send_invoice"
+    );
+
+    let retrieve = call(
+        &state,
+        "retrieve_entity",
+        json!({ "repo": "shop", "entity_ids": ["@billing/billing/invoice.py::send_invoice"] }),
+    )
+    .await;
+    assert!(retrieve["entities"][0]["code"]
+        .as_str()
+        .unwrap()
+        .starts_with("def send_invoice"));
+
+    // External nodes stay out of the importing repository's search indices.
+    let search = respond(
+        &state,
+        "search_entities",
+        json!({ "repo": "shop", "query": "send_invoice" }),
+    )
+    .await;
+    assert_eq!(first_hit(&search), "shop/checkout.py::checkout", "{search}");
+    assert_eq!(search["result"]["total_count"], 1, "{search}");
+}
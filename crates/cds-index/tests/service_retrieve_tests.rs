@@ -0,0 +1,116 @@
+//! Service tests for `retrieve_entity`: snippet modes and signature metadata.
+
+mod common;
+
+use common::{assert_matches_schema, call, indexed_state, write_file};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const APP: &str = "import functools\n\n\nclass Base:\n    pass\n\n\nclass App(Base, metaclass=type):\n    \"\"\"Application entry.\"\"\"\n\n    @functools.lru_cache(maxsize=2)\n    @staticmethod\n    def run(x: int, *args, retries=3, **kwargs) -> str:\n        \"\"\"Run it.\n\n        Retries on failure.\n        \"\"\"\n        first = str(x)\n        second = first * 2\n        third = second * 2\n        return third\n\n    async def stop(self):\n        pass\n";
+
+fn app_repo() -> TempDir {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "app.py", APP);
+    repo
+}
+
+#[tokio::test]
+async fn metadata_describes_signatures_decorators_and_bases() {
+    let repo = app_repo();
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+
+    let result = call(
+        &state,
+        "retrieve_entity",
+        json!({
+            "entity_ids": ["app.py::App::run", "app.py::App::stop", "app.py::App", "app.py"],
+            "include_metadata": true,
+        }),
+    )
+    .await;
+    assert_matches_schema("retrieve_entity", &result);
+    let entities = result["entities"].as_array().unwrap();
+    assert_eq!(
+        entities[0]["metadata"],
+        json!({
+            "parameters": ["x: int", "*args", "retries=3", "**kwargs"],
+            "return_type": "str",
+            "docstring": "Run it.\n\nRetries on failure.",
+            "decorators": ["functools.lru_cache(maxsize=2)", "staticmethod"],
+            "parent_class": "app.py::App",
+        })
+    );
+    assert_eq!(
+        entities[1]["metadata"],
+        json!({ "parameters": ["self"], "parent_class": "app.py::App", "is_async": true })
+    );
+    assert_eq!(
+        entities[2]["metadata"],
+        json!({ "docstring": "Application entry.", "bases": ["Base", "metaclass=type"] })
+    );
+    assert!(entities[3].get("metadata").is_none());
+
+    let plain = call(
+        &state,
+        "retrieve_entity",
+        json!({ "entity_ids": ["app.py::App::run"] }),
+    )
+    .await;
+    assert!(plain["entities"][0].get("metadata").is_none());
+}
+
+#[tokio::test]
+async fn snippet_modes_shorten_the_code_but_not_the_range() {
+    let repo = app_repo();
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+    let retrieve = |mode: Value| {
+        let state = Arc::clone(&state);
+        async move {
+            let result = call(
+                &state,
+                "retrieve_entity",
+                json!({
+                    "entity_ids": ["app.py::App::run"],
+                    "include_context": 2,
+                    "snippet_mode": mode,
+                    "session_id": "agent",
+                }),
+            )
+            .await;
+            result["entities"][0].clone()
+        }
+    };
+
+    let full = retrieve(Value::Null).await;
+    assert_eq!(full["line_range"], json!([13, 21]));
+    assert_eq!(full["code"].as_str().unwrap().lines().count(), 9);
+    assert_eq!(full["context_after"], "\n    async def stop(self):");
+
+    let fold = retrieve(json!("fold")).await;
+    assert_eq!(fold["line_range"], full["line_range"]);
+    assert_eq!(
+        fold["code"],
+        "    def run(x: int, *args, retries=3, **kwargs) -> str:"
+    );
+    assert_eq!(fold["context_before"], full["context_before"]);
+    assert!(fold.get("context_after").is_none());
+
+    let preview = retrieve(json!("preview")).await;
+    assert_eq!(preview["code"].as_str().unwrap().lines().count(), 5);
+    assert!(preview.get("context_after").is_none());
+    assert_eq!(retrieve(json!("full")).await, full);
+
+    // The session remembers a retrieval at the detail it returned.
+    let fresh = call(
+        &state,
+        "retrieve_entity",
+        json!({ "entity_ids": ["app.py::App"], "snippet_mode": "fold", "session_id": "fresh" }),
+    )
+    .await;
+    assert_eq!(fresh["session"]["seen"], 1);
+    let summary = call(&state, "session_summary", json!({ "session_id": "fresh" })).await;
+    assert_eq!(summary["explored"][0]["level"], "fold");
+}
@@ -0,0 +1,227 @@
+//! Service tests for per-session tracking of the entities shown to an agent.
+
+mod common;
+
+use common::{call, indexed_state, respond, write_file};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+const ORDERS: &str = "def place_order(cart):\n    return charge(cart)\n\n\ndef charge(cart):\n    return log_charge(cart)\n\n\ndef log_charge(cart):\n    return cart\n";
+
+fn ids(items: &Value) -> Vec<&str> {
+    items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["id"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn seen_entities_are_excluded_at_the_same_or_less_detail() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "orders.py", ORDERS);
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+    let search = json!({ "query": "charge", "use_bm25": false });
+
+    // Warm the cache without a session; session filtering applies to hits too.
+    let plain = call(&state, "search_entities", search.clone()).await;
+    assert!(plain.get("session").is_none());
+    let charge = ["orders.py::charge"];
+    assert_eq!(ids(&plain["entities"]), charge);
+
+    let mut tracked = search.clone();
+    tracked["session_id"] = json!("agent-1");
+    tracked["exclude_seen"] = json!(true);
+    let first = call(&state, "search_entities", tracked.clone()).await;
+    assert_eq!(first["query_metadata"]["cache_hit"], true);
+    assert_eq!(ids(&first["entities"]), charge);
+    assert_eq!(
+        first["session"],
+        json!({ "session_id": "agent-1", "excluded": 0, "seen": 1 })
+    );
+
+    let again = call(&state, "search_entities", tracked.clone()).await;
+    assert!(ids(&again["entities"]).is_empty());
+    assert_eq!(again["session"]["excluded"], 1);
+
+    // A preview does not count as having read the code.
+    let retrieved = call(
+        &state,
+        "retrieve_entity",
+        json!({ "entity_ids": charge, "session_id": "agent-1", "exclude_seen": true }),
+    )
+    .await;
+    assert_eq!(ids(&retrieved["entities"]), charge);
+    let repeated = call(
+        &state,
+        "retrieve_entity",
+        json!({ "entity_ids": charge, "session_id": "agent-1", "exclude_seen": true }),
+    )
+    .await;
+    assert!(ids(&repeated["entities"]).is_empty());
+
+    // Full code covers the more detailed snippet modes, too.
+    tracked["snippet_mode"] = json!("full");
+    let full = call(&state, "search_entities", tracked).await;
+    assert!(ids(&full["entities"]).is_empty());
+
+    // Other sessions are unaffected.
+    let other = call(
+        &state,
+        "search_entities",
+        json!({ "query": "charge", "use_bm25": false, "session_id": "agent-2", "exclude_seen": true }),
+    )
+    .await;
+    assert_eq!(ids(&other["entities"]), charge);
+}
+
+#[tokio::test]
+async fn traversals_drop_seen_nodes_but_keep_edges_to_them() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "orders.py", ORDERS);
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+
+    call(
+        &state,
+        "retrieve_entity",
+        json!({ "entity_ids": ["orders.py::place_order"], "session_id": "agent" }),
+    )
+    .await;
+    let traversal = call(
+        &state,
+        "traverse_graph",
+        json!({
+            "start_entities": ["orders.py::place_order"],
+            "relations": ["invoke"],
+            "depth": 2,
+            "session_id": "agent",
+            "exclude_seen": true,
+        }),
+    )
+    .await;
+    assert_eq!(
+        ids(&traversal["subgraph"]["nodes"]),
+        ["orders.py::charge", "orders.py::log_charge"]
+    );
+    let edges: Vec<(&str, &str)> = traversal["subgraph"]["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| {
+            (
+                edge["source"].as_str().unwrap(),
+                edge["target"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert!(edges.contains(&("orders.py::place_order", "orders.py::charge")));
+    assert_eq!(traversal["session"]["excluded"], 1);
+    assert_eq!(traversal["session"]["seen"], 3);
+
+    // Listing a node in a traversal does not hide it from a search.
+    let search = call(
+        &state,
+        "search_entities",
+        json!({ "query": "log_charge", "use_bm25": false, "snippet_mode": "fold",
+                "session_id": "agent", "exclude_seen": true }),
+    )
+    .await;
+    assert_eq!(ids(&search["entities"]), ["orders.py::log_charge"]);
+}
+
+#[tokio::test]
+async fn mark_seen_false_queries_without_recording() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "orders.py", ORDERS);
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+    let params = json!({ "query": "charge", "use_bm25": false, "session_id": "agent",
+                         "exclude_seen": true, "mark_seen": false });
+
+    for _ in 0..2 {
+        let result = call(&state, "search_entities", params.clone()).await;
+        assert_eq!(ids(&result["entities"]), ["orders.py::charge"]);
+        assert_eq!(result["session"]["seen"], 0);
+    }
+
+    let response = respond(
+        &state,
+        "search_entities",
+        json!({ "query": "charge", "exclude_seen": true }),
+    )
+    .await;
+    assert_eq!(response["error"]["code"], -32602);
+    let response = respond(
+        &state,
+        "retrieve_entity",
+        json!({ "entity_ids": ["orders.py::charge"], "session_id": "" }),
+    )
+    .await;
+    assert_eq!(response["error"]["code"], -32602);
+}
+
+#[tokio::test]
+async fn session_summary_lists_explored_entities_and_their_frontier() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "orders.py", ORDERS);
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+
+    let empty = call(&state, "session_summary", json!({ "session_id": "agent" })).await;
+    assert_eq!(empty["calls"], 0);
+    assert!(empty.get("created_at").is_none());
+    assert_eq!(empty["explored"], json!([]));
+    assert_eq!(empty["frontier_total"], 0);
+
+    call(
+        &state,
+        "search_entities",
+        json!({ "query": "place_order", "use_bm25": false, "snippet_mode": "fold", "session_id": "agent" }),
+    )
+    .await;
+    call(
+        &state,
+        "retrieve_entity",
+        json!({ "entity_ids": ["orders.py::place_order"], "session_id": "agent" }),
+    )
+    .await;
+
+    let summary = call(&state, "session_summary", json!({ "session_id": "agent" })).await;
+    assert_eq!(summary["repo"], "default");
+    assert_eq!(summary["calls"], 2);
+    assert!(summary["created_at"].is_string());
+    assert_eq!(
+        summary["explored"],
+        json!([{
+            "id": "orders.py::place_order",
+            "level": "full",
+            "times_returned": 2,
+            "entity_type": "function",
+            "file_path": "orders.py",
+        }])
+    );
+    assert_eq!(summary["frontier_total"], 1);
+    let frontier = &summary["frontier"][0];
+    assert_eq!(frontier["id"], "orders.py::charge");
+    assert_eq!(frontier["relations"], json!(["invoke"]));
+    assert_eq!(frontier["linked_from"], json!(["orders.py::place_order"]));
+
+    let limited = call(
+        &state,
+        "session_summary",
+        json!({ "session_id": "agent", "frontier_limit": 0 }),
+    )
+    .await;
+    assert_eq!(limited["frontier"], json!([]));
+    assert_eq!(limited["frontier_total"], 1);
+    let response = respond(
+        &state,
+        "session_summary",
+        json!({ "session_id": "agent", "frontier_limit": 500 }),
+    )
+    .await;
+    assert_eq!(response["error"]["code"], -32602);
+}
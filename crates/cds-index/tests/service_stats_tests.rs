@@ -0,0 +1,87 @@
+//! Service tests for `get_index_stats`: composition, hotspots and build diagnostics.
+
+mod common;
+
+use common::{assert_matches_schema, call, indexed_state, respond, service_state, write_file};
+use serde_json::json;
+use tempfile::TempDir;
+
+const MODELS: &str = "from shop.signals import notify\n\n\nclass Model:\n    pass\n\n\nclass Order(Model):\n    def save(self):\n        notify()\n\n\nclass RushOrder(Order):\n    def save(self):\n        notify()\n";
+
+const SIGNALS: &str = "import os\nfrom shop.models import Order\nfrom missing_pkg import thing\n\n\ndef notify():\n    return os.getpid()\n\n\ndef audit():\n    notify()\n";
+
+const LEGACY: &str = "def legacy():\n    print \"python 2\"\n";
+
+fn shop_repo() -> TempDir {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "shop/__init__.py", "");
+    write_file(repo.path(), "shop/models.py", MODELS);
+    write_file(repo.path(), "shop/signals.py", SIGNALS);
+    write_file(repo.path(), "shop/legacy.py", LEGACY);
+    repo
+}
+
+#[tokio::test]
+async fn stats_report_composition_hotspots_and_build_diagnostics() {
+    let repo = shop_repo();
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+
+    let stats = call(&state, "get_index_stats", json!({})).await;
+    assert_matches_schema("get_index_stats", &stats);
+    assert_eq!(stats["repo"], "default");
+    assert_eq!(stats["generation"], 1);
+    assert_eq!(stats["entities"]["files"], 4);
+    assert_eq!(stats["entities"]["classes"], 3);
+    assert_eq!(stats["edges"]["invoke"], 3);
+
+    assert_eq!(
+        stats["largest_files"][0],
+        json!({ "file_path": "shop/models.py", "entities": 5 })
+    );
+    assert_eq!(
+        stats["most_invoked"][0],
+        json!({ "id": "shop/signals.py::notify", "entity_type": "function", "callers": 3 })
+    );
+    assert_eq!(
+        stats["inheritance_chains"],
+        json!([[
+            "shop/models.py::RushOrder",
+            "shop/models.py::Order",
+            "shop/models.py::Model"
+        ]])
+    );
+    assert_eq!(
+        stats["import_cycles"],
+        json!([["shop/models.py", "shop/signals.py"]])
+    );
+    assert_eq!(stats["import_cycles_total"], 1);
+    // `os` and `missing_pkg.thing`: neither is part of the repository.
+    assert_eq!(stats["unresolved_imports"], 2);
+    assert_eq!(stats["parse_failures"], json!(["shop/legacy.py"]));
+
+    let top = call(&state, "get_index_stats", json!({ "top": 1 })).await;
+    assert_eq!(top["largest_files"].as_array().unwrap().len(), 1);
+    assert_eq!(top["most_invoked"].as_array().unwrap().len(), 1);
+
+    // The diagnostics are persisted with the index, not recomputed.
+    let reloaded = service_state(index_root.path());
+    let persisted = call(&reloaded, "get_index_stats", json!({})).await;
+    assert_eq!(persisted["unresolved_imports"], 2);
+    assert_eq!(persisted["parse_failures"], stats["parse_failures"]);
+}
+
+#[tokio::test]
+async fn stats_validate_top_and_require_an_index() {
+    let repo = shop_repo();
+    let index_root = TempDir::new().unwrap();
+    let state = indexed_state(index_root.path(), repo.path()).await;
+    for top in [0, 101] {
+        let response = respond(&state, "get_index_stats", json!({ "top": top })).await;
+        assert_eq!(response["error"]["code"], -32602, "{response}");
+    }
+
+    let empty = TempDir::new().unwrap();
+    let response = respond(&service_state(empty.path()), "get_index_stats", json!({})).await;
+    assert_eq!(response["error"]["code"], -32001, "{response}");
+}
@@ -0,0 +1,198 @@
+//! Service tests for the stdio and Unix domain socket transports.
+
+mod common;
+
+use cds_index::service::stdio::serve_lines;
+use common::{service_state, write_file};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+#[tokio::test]
+async fn stdio_transport_answers_each_line() {
+    let index_root = TempDir::new().unwrap();
+    let state = service_state(index_root.path());
+    let (client, server) = tokio::io::duplex(64 * 1024);
+    let (server_read, server_write) = tokio::io::split(server);
+    let serving = tokio::spawn(serve_lines(state, server_read, server_write));
+
+    let (client_read, mut client_write) = tokio::io::split(client);
+    client_write
+        .write_all(
+            concat!(
+                r#"{"jsonrpc":"2.0","id":1,"method":"health_check"}"#,
+                "\n\n",
+                r#"{"jsonrpc":"2.0","id":2,"method":"search_entities","params":{"query":"x"}}"#,
+                "\n",
+                "{not json\n",
+            )
+            .as_bytes(),
+        )
+        .await
+        .unwrap();
+    client_write.shutdown().await.unwrap();
+
+    let mut lines = tokio::io::BufReader::new(client_read).lines();
+    let mut responses = Vec::new();
+    while let Some(line) = lines.next_line().await.unwrap() {
+        responses.push(serde_json::from_str::<Value>(&line).unwrap());
+    }
+    serving.await.unwrap().unwrap();
+
+    assert_eq!(responses.len(), 3, "{responses:?}");
+    let by_id = |id: Value| {
+        responses
+            .iter()
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("no response for {id}"))
+    };
+    assert_eq!(by_id(json!(1))["result"]["status"], "ok");
+    assert_eq!(by_id(json!(2))["error"]["code"], -32001);
+    assert_eq!(by_id(Value::Null)["error"]["code"], -32700);
+}
+
+#[test]
+fn service_binary_speaks_json_rpc_on_stdout_only() {
+    let index_root = TempDir::new().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cds-index-service"))
+        .env("INDEX_SERVICE_TRANSPORT", "stdio")
+        .env("GRAPH_INDEX_DIR", index_root.path().join("graph"))
+        .env("BM25_INDEX_DIR", index_root.path().join("bm25"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("spawn cds-index-service");
+
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(
+        stdin,
+        r#"{{"jsonrpc":"2.0","id":"a","method":"list_repos"}}"#
+    )
+    .unwrap();
+    drop(stdin);
+
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let lines: Vec<String> = stdout.lines().map(Result::unwrap).collect();
+    assert!(child.wait().unwrap().success());
+    assert_eq!(lines.len(), 1, "{lines:?}");
+    let response: Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(response["id"], "a");
+    assert_eq!(response["result"]["repos"][0]["name"], "default");
+}
+
+/// Reads the next stdout line, which must be a JSON-RPC response.
+fn next_response(stdout: &mut impl Iterator<Item = std::io::Result<String>>) -> Value {
+    let line = stdout.next().expect("response line").unwrap();
+    serde_json::from_str(&line).unwrap_or_else(|err| panic!("{err}: {line:?}"))
+}
+
+#[test]
+fn rebuilding_unparsable_sources_keeps_stdout_json_only() {
+    let repo = TempDir::new().unwrap();
+    write_file(repo.path(), "ok.py", "def ok():\n    return 1\n");
+    write_file(
+        repo.path(),
+        "legacy.py",
+        "def legacy():\n    print \"python 2\"\n",
+    );
+    let index_root = TempDir::new().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cds-index-service"))
+        .env("INDEX_SERVICE_TRANSPORT", "stdio")
+        .env("GRAPH_INDEX_DIR", index_root.path().join("graph"))
+        .env("BM25_INDEX_DIR", index_root.path().join("bm25"))
+        .env("PARITY_DEBUG", "1")
+        .env("RUST_LOG", "debug")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("spawn cds-index-service");
+
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "rebuild_index",
+        "params": { "repo_path": repo.path().display().to_string() },
+    });
+    writeln!(stdin, "{request}").unwrap();
+    let started = next_response(&mut stdout);
+    let job_id = started["result"]["job_id"].clone();
+    let status = loop {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "get_job_status",
+            "params": { "job_id": job_id },
+        });
+        writeln!(stdin, "{request}").unwrap();
+        let status = next_response(&mut stdout);
+        match status["result"]["status"].as_str() {
+            Some("queued") | Some("running") => {
+                std::thread::sleep(std::time::Duration::from_millis(50))
+            }
+            _ => break status,
+        }
+    };
+    assert_eq!(status["result"]["status"], "completed", "{status}");
+    assert_eq!(status["result"]["stats"]["files_indexed"], 2);
+
+    drop(stdin);
+    let rest: Vec<String> = stdout.map(Result::unwrap).collect();
+    assert!(child.wait().unwrap().success());
+    assert!(rest.is_empty(), "unexpected output: {rest:?}");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_socket_transport_serves_the_http_routes() {
+    use cds_index::service::server::{bind_unix, serve_unix};
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::UnixStream;
+
+    let index_root = TempDir::new().unwrap();
+    let socket = index_root.path().join("run/cds-index.sock");
+    let state = service_state(index_root.path());
+    let server = tokio::spawn({
+        let socket = socket.clone();
+        async move { serve_unix(state, &socket, 0o600).await }
+    });
+    for _ in 0..100 {
+        if UnixStream::connect(&socket).await.is_ok() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let body = r#"{"jsonrpc":"2.0","id":7,"method":"health_check"}"#;
+    let mut stream = UnixStream::connect(&socket).await.unwrap();
+    stream
+        .write_all(
+            format!(
+                "POST /rpc HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .as_bytes(),
+        )
+        .await
+        .unwrap();
+    let mut raw = String::new();
+    stream.read_to_string(&mut raw).await.unwrap();
+    assert!(raw.starts_with("HTTP/1.1 200"), "{raw}");
+    let payload: Value = serde_json::from_str(raw.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+    assert_eq!(payload["id"], 7);
+    assert_eq!(payload["result"]["status"], "ok");
+
+    // A live socket is never taken over; a stale one is replaced.
+    assert!(bind_unix(&socket, 0o600).is_err());
+    server.abort();
+    let _ = server.await;
+    assert!(bind_unix(&socket, 0o600).is_ok());
+}
@@ -0,0 +1,39 @@
+//! Embedded mode: answer requests from a persisted index in-process
+//!
+//! The index directory (`<dir>/graph`, `<dir>/bm25`, as written for
+//! `repo/.cds-index`) is served by the same [`handle_request`] the service
+//! uses, so results are identical to the service path without running it.
+
+use cds_index::persistence::IndexLayout;
+use cds_index::service::{handle_request, JsonRpcRequest, ServiceState};
+use cds_index::IndexServiceConfig;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Index directory looked up from the working directory upwards.
+pub const INDEX_DIR_NAME: &str = ".cds-index";
+
+/// Service state over the index in `index_dir`; nothing is loaded until
+/// the first query. Repositories registered in `<index_dir>/repos` are
+/// available through `--repo`.
+pub fn open(index_dir: &Path) -> anyhow::Result<Arc<ServiceState>> {
+    let layout = IndexLayout::under(index_dir);
+    let state = ServiceState::new(IndexServiceConfig::new(layout.graph_dir, layout.bm25_dir));
+    state.repos().load_registered()?;
+    Ok(Arc::new(state))
+}
+
+/// Handles one serialized JSON-RPC request and returns the serialized response.
+pub async fn call(state: &Arc<ServiceState>, body: &str) -> anyhow::Result<String> {
+    let request: JsonRpcRequest = serde_json::from_str(body)?;
+    let response = handle_request(state, request).await;
+    Ok(serde_json::to_string(&response)?)
+}
+
+/// The nearest `.cds-index` holding a persisted index, from `start` upwards.
+pub fn discover(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(INDEX_DIR_NAME))
+        .find(|dir| IndexLayout::under(dir).exists())
+}
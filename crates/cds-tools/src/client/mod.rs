@@ -0,0 +1,365 @@
+//! CDS-Index Service client
+//!
+//! Sends JSON-RPC requests over whichever transport the configuration
+//! selects: HTTP, HTTP on a Unix domain socket, the stdin/stdout of a
+//! spawned `cds-index-service`, or an index opened in-process (see
+//! [`embedded`]). All of them speak the same protocol, so callers only see
+//! [`IndexClient::call`].
+
+pub mod embedded;
+
+use crate::config::{CliConfig, Transport, DEFAULT_SERVICE_COMMAND, DEFAULT_SERVICE_URL};
+use cds_index::service::ServiceState;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+
+/// Where to reach the service, resolved from flags, env and config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Http(String),
+    Unix(PathBuf),
+    Stdio {
+        command: PathBuf,
+        args: Vec<String>,
+    },
+    /// A persisted index directory queried in-process.
+    Embedded(PathBuf),
+}
+
+impl Endpoint {
+    /// Picks the transport (flag/env, then `[service].transport`, then HTTP)
+    /// and fills in its address the same way.
+    ///
+    /// An index directory (`--index-dir` or `[service].index_dir`) selects
+    /// embedded mode. So does a `.cds-index` in the working directory or one
+    /// of its parents, unless a transport or address was configured.
+    pub fn resolve(
+        config: &CliConfig,
+        transport: Option<Transport>,
+        url: Option<String>,
+        socket: Option<PathBuf>,
+        index_dir: Option<PathBuf>,
+    ) -> Result<Self, ClientError> {
+        let service = &config.service;
+        if let Some(dir) = index_dir.or_else(|| service.index_dir.clone()) {
+            return Ok(Endpoint::Embedded(dir));
+        }
+        let configured = transport.or(service.transport).is_some()
+            || url.is_some()
+            || socket.is_some()
+            || service.url.is_some()
+            || service.socket.is_some();
+        if !configured {
+            let discovered = std::env::current_dir()
+                .ok()
+                .and_then(|cwd| embedded::discover(&cwd));
+            if let Some(dir) = discovered {
+                return Ok(Endpoint::Embedded(dir));
+            }
+        }
+        match transport.or(service.transport).unwrap_or_default() {
+            Transport::Http => Ok(Endpoint::Http(
+                url.or_else(|| service.url.clone())
+                    .unwrap_or_else(|| DEFAULT_SERVICE_URL.to_string()),
+            )),
+            Transport::Unix => socket
+                .or_else(|| service.socket.clone())
+                .map(Endpoint::Unix)
+                .ok_or_else(|| {
+                    ClientError::Config(
+                        "the unix transport needs --socket, CDS_SOCKET or [service].socket"
+                            .to_string(),
+                    )
+                }),
+            Transport::Stdio => Ok(Endpoint::Stdio {
+                command: service
+                    .command
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(DEFAULT_SERVICE_COMMAND)),
+                args: service.args.clone(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Http(url) => f.write_str(url),
+            Endpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+            Endpoint::Stdio { command, .. } => write!(f, "stdio:{}", command.display()),
+            Endpoint::Embedded(dir) => write!(f, "embedded:{}", dir.display()),
+        }
+    }
+}
+
+/// A JSON-RPC error returned by the service.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+    #[serde(default)]
+    pub data: Option<Value>,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)?;
+        let detail = self.data.as_ref().and_then(|data| data.get("detail"));
+        if let Some(detail) = detail.and_then(Value::as_str) {
+            write!(f, ": {detail}")?;
+        }
+        let suggestion = self.data.as_ref().and_then(|data| data.get("suggestion"));
+        if let Some(suggestion) = suggestion.and_then(Value::as_str) {
+            write!(f, "; {suggestion}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("invalid client configuration: {0}")]
+    Config(String),
+    #[error("cannot reach the index service at {endpoint}: {detail}")]
+    Transport { endpoint: String, detail: String },
+    #[error("invalid response from the index service: {0}")]
+    InvalidResponse(String),
+    #[error("{0}")]
+    Rpc(RpcError),
+}
+
+struct StdioService {
+    // Kept so the child is killed when the client is dropped.
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+enum Connection {
+    Http(reqwest::Client),
+    #[cfg(unix)]
+    Unix,
+    Stdio(Box<StdioService>),
+    Embedded(Arc<ServiceState>),
+}
+
+/// JSON-RPC client for one endpoint.
+pub struct IndexClient {
+    endpoint: Endpoint,
+    connection: Connection,
+    token: Option<String>,
+    next_id: u64,
+}
+
+impl IndexClient {
+    /// Connects to `endpoint`; for the stdio transport this spawns the service.
+    ///
+    /// Embedded mode opens the index directory without loading it yet.
+    pub fn connect(endpoint: Endpoint) -> Result<Self, ClientError> {
+        let connection = match &endpoint {
+            Endpoint::Http(_) => Connection::Http(reqwest::Client::new()),
+            #[cfg(unix)]
+            Endpoint::Unix(_) => Connection::Unix,
+            #[cfg(not(unix))]
+            Endpoint::Unix(_) => {
+                return Err(ClientError::Config(
+                    "the unix transport is not supported on this platform".to_string(),
+                ))
+            }
+            Endpoint::Stdio { command, args } => {
+                let mut child = Command::new(command)
+                    .args(args)
+                    .env("INDEX_SERVICE_TRANSPORT", "stdio")
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(|err| transport_error(&endpoint, err))?;
+                let stdin = child.stdin.take().expect("stdin is piped");
+                let stdout = child.stdout.take().expect("stdout is piped");
+                Connection::Stdio(Box::new(StdioService {
+                    _child: child,
+                    stdin,
+                    stdout: BufReader::new(stdout).lines(),
+                }))
+            }
+            Endpoint::Embedded(dir) => Connection::Embedded(
+                embedded::open(dir).map_err(|err| transport_error(&endpoint, err))?,
+            ),
+        };
+        Ok(Self {
+            endpoint,
+            connection,
+            token: None,
+            next_id: 1,
+        })
+    }
+
+    /// Sends `token` as `Authorization: Bearer` on the HTTP transports
+    /// (embedded mode has no authentication).
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// Calls `method` and returns its `result`, or the service's error as [`ClientError::Rpc`].
+    pub async fn call(&mut self, method: &str, params: Value) -> Result<Value, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let body = serde_json::to_string(&request)
+            .map_err(|err| ClientError::InvalidResponse(err.to_string()))?;
+
+        let raw = match &mut self.connection {
+            Connection::Http(client) => {
+                let Endpoint::Http(url) = &self.endpoint else {
+                    unreachable!("http connection for a non-http endpoint")
+                };
+                send_http(client, url, self.token.as_deref(), body)
+                    .await
+                    .map_err(|err| transport_error(&self.endpoint, err))?
+            }
+            #[cfg(unix)]
+            Connection::Unix => {
+                let Endpoint::Unix(path) = &self.endpoint else {
+                    unreachable!("unix connection for a non-unix endpoint")
+                };
+                send_unix(path, self.token.as_deref(), body)
+                    .await
+                    .map_err(|err| transport_error(&self.endpoint, err))?
+            }
+            Connection::Stdio(service) => send_stdio(service, body, id)
+                .await
+                .map_err(|err| transport_error(&self.endpoint, err))?,
+            Connection::Embedded(state) => embedded::call(state, &body)
+                .await
+                .map_err(|err| ClientError::InvalidResponse(err.to_string()))?,
+        };
+        parse_response(&raw)
+    }
+
+    /// Calls a paginated method and follows `next_cursor` to the last page.
+    ///
+    /// The arrays at `items` (JSON pointers such as `/entities`) are
+    /// concatenated across pages; everything else comes from the last page.
+    pub async fn call_all(
+        &mut self,
+        method: &str,
+        mut params: Value,
+        items: &[&str],
+    ) -> Result<Value, ClientError> {
+        let mut result = self.call(method, params.clone()).await?;
+        while let Some(cursor) = result.get("next_cursor").and_then(Value::as_str) {
+            params["cursor"] = json!(cursor);
+            let mut page = self.call(method, params.clone()).await?;
+            for pointer in items {
+                let (Some(Value::Array(seen)), Some(Value::Array(more))) =
+                    (result.pointer_mut(pointer), page.pointer_mut(pointer))
+                else {
+                    return Err(ClientError::InvalidResponse(format!(
+                        "{method} page has no {pointer} array"
+                    )));
+                };
+                seen.append(more);
+                std::mem::swap(seen, more);
+            }
+            result = page;
+        }
+        Ok(result)
+    }
+}
+
+fn transport_error(endpoint: &Endpoint, err: impl fmt::Display) -> ClientError {
+    ClientError::Transport {
+        endpoint: endpoint.to_string(),
+        detail: err.to_string(),
+    }
+}
+
+fn parse_response(raw: &str) -> Result<Value, ClientError> {
+    #[derive(Deserialize)]
+    struct Response {
+        #[serde(default)]
+        result: Option<Value>,
+        #[serde(default)]
+        error: Option<RpcError>,
+    }
+    let response: Response =
+        serde_json::from_str(raw).map_err(|err| ClientError::InvalidResponse(err.to_string()))?;
+    match (response.result, response.error) {
+        (_, Some(error)) => Err(ClientError::Rpc(error)),
+        (Some(result), None) => Ok(result),
+        (None, None) => Err(ClientError::InvalidResponse(
+            "response has neither result nor error".to_string(),
+        )),
+    }
+}
+
+async fn send_http(
+    client: &reqwest::Client,
+    url: &str,
+    token: Option<&str>,
+    body: String,
+) -> reqwest::Result<String> {
+    // Error responses (e.g. 400 for malformed requests, 401/403 for auth
+    // failures) still carry a JSON-RPC body.
+    let mut request = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json");
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request.body(body).send().await?.text().await
+}
+
+#[cfg(unix)]
+async fn send_unix(
+    path: &std::path::Path,
+    token: Option<&str>,
+    body: String,
+) -> anyhow::Result<String> {
+    use http_body_util::{BodyExt, Full};
+    use hyper::body::Bytes;
+    use hyper::header::{AUTHORIZATION, CONTENT_TYPE, HOST};
+    use hyper_util::rt::TokioIo;
+
+    let stream = tokio::net::UnixStream::connect(path).await?;
+    let (mut sender, connection) =
+        hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+    let mut request = hyper::Request::post("/rpc")
+        .header(HOST, "localhost")
+        .header(CONTENT_TYPE, "application/json");
+    if let Some(token) = token {
+        request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let request = request.body(Full::new(Bytes::from(body)))?;
+    let response = sender.send_request(request).await?;
+    let bytes = response.into_body().collect().await?.to_bytes();
+    Ok(String::from_utf8(bytes.to_vec())?)
+}
+
+async fn send_stdio(service: &mut StdioService, body: String, id: u64) -> anyhow::Result<String> {
+    service.stdin.write_all(body.as_bytes()).await?;
+    service.stdin.write_all(b"\n").await?;
+    service.stdin.flush().await?;
+    // Requests are sent one at a time, but skip anything that is not our answer.
+    while let Some(line) = service.stdout.next_line().await? {
+        let matches = serde_json::from_str::<Value>(&line)
+            .map(|value| value.get("id") == Some(&json!(id)))
+            .unwrap_or(false);
+        if matches {
+            return Ok(line);
+        }
+    }
+    anyhow::bail!("the service exited before answering")
+}
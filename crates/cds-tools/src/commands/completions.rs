@@ -0,0 +1,154 @@
+//! `cds completions`: shell tab completion, entity ids included
+//!
+//! The script printed for a shell calls back into `cds` (with `COMPLETE` set)
+//! on every <Tab>, so completions always match the installed binary. Entity-id
+//! arguments are completed from the name index of whichever index the command
+//! line or the environment selects (`--index-dir`, `--url`, ...), the service
+//! and embedded mode alike; without a reachable index they complete nothing.
+
+use super::{CliError, Outcome, QueryScope};
+use crate::client::{ClientError, IndexClient};
+use crate::config::Transport;
+use clap::{Args, ValueEnum};
+use clap_complete::env::Shells;
+use clap_complete::CompletionCandidate;
+use serde_json::{json, Value};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use tokio::runtime::Handle;
+
+/// Environment variable the completion script sets when calling back.
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+/// Entity ids offered for one completion.
+const COMPLETION_LIMIT: usize = 50;
+
+#[derive(Debug, Args)]
+pub struct CompletionsArgs {
+    /// Shell to print the completion script for
+    #[arg(value_parser = ["bash", "zsh", "fish", "elvish", "powershell"])]
+    pub shell: String,
+}
+
+/// Prints the registration script for `args.shell`, e.g.
+/// `source <(cds completions bash)` in `~/.bashrc`.
+pub fn run(args: CompletionsArgs) -> Result<Outcome, CliError> {
+    let shells = Shells::builtins();
+    let shell = shells
+        .completer(&args.shell)
+        .ok_or_else(|| CliError::InvalidArgs(format!("unsupported shell '{}'", args.shell)))?;
+    let mut script = Vec::new();
+    shell.write_registration(COMPLETE_VAR, "cds", "cds", &completer_path(), &mut script)?;
+    print!("{}", String::from_utf8_lossy(&script));
+    Ok(Outcome {
+        result: Value::Null,
+        found: true,
+    })
+}
+
+/// How the script invokes `cds`: as it was invoked now, made absolute when
+/// it was run by a relative path.
+fn completer_path() -> String {
+    let invoked = PathBuf::from(std::env::args_os().next().unwrap_or_else(|| "cds".into()));
+    let path = if invoked.components().count() > 1 {
+        std::env::current_dir()
+            .map(|dir| dir.join(&invoked))
+            .unwrap_or(invoked)
+    } else {
+        invoked
+    };
+    path.to_string_lossy().into_owned()
+}
+
+/// [`clap_complete::ArgValueCompleter`] for entity-id arguments.
+///
+/// Runs while `cds` answers a completion request, before the command line is
+/// parsed, so the global flags are read from the words typed so far and fall
+/// back to their environment variables.
+pub fn complete_entity_id(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Some(word) = current.to_str().filter(|word| !word.is_empty()) else {
+        return Vec::new();
+    };
+    let words: Vec<String> = std::env::args()
+        .skip_while(|arg| arg != "--")
+        .skip(1)
+        .collect();
+    let flag = |name: &str, env: &str| flag_value(&words, name).or_else(|| std::env::var(env).ok());
+    let transport = flag("--transport", "CDS_TRANSPORT")
+        .and_then(|value| Transport::from_str(&value, true).ok());
+    let client = crate::connect(
+        transport,
+        flag("--url", "CDS_INDEX_SERVICE_URL"),
+        flag("--socket", "CDS_SOCKET").map(PathBuf::from),
+        flag("--index-dir", "CDS_INDEX_DIR").map(PathBuf::from),
+        flag("--token", "CDS_TOKEN"),
+    );
+    let Ok(mut client) = client else {
+        return Vec::new();
+    };
+    let scope = QueryScope {
+        repo: flag("--repo", "CDS_REPO"),
+        session: None,
+    };
+    tokio::task::block_in_place(|| {
+        Handle::current().block_on(matching_entity_ids(&mut client, &scope, word))
+    })
+    .unwrap_or_default()
+    .into_iter()
+    .map(CompletionCandidate::new)
+    .collect()
+}
+
+/// The value of `--name value` or `--name=value` in `words`, the last one winning.
+fn flag_value(words: &[String], name: &str) -> Option<String> {
+    let mut value = None;
+    for (i, word) in words.iter().enumerate() {
+        if word == name {
+            value = words.get(i + 1).cloned();
+        } else if let Some(rest) = word
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            value = Some(rest.to_string());
+        }
+    }
+    value
+}
+
+/// Entity ids extending `word`; a bare name completes to the ids of the
+/// entities it names.
+pub(crate) async fn matching_entity_ids(
+    client: &mut IndexClient,
+    scope: &QueryScope,
+    word: &str,
+) -> Result<Vec<String>, ClientError> {
+    let name = short_name(word);
+    if name.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut params = json!({
+        "query": name,
+        "limit": COMPLETION_LIMIT,
+        "use_bm25": false,
+        "snippet_mode": "fold",
+    });
+    scope.apply(&mut params);
+    let result = client.call("search_entities", params).await?;
+    let qualified = word.contains("::") || word.contains('/');
+    Ok(result["entities"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entity| entity["id"].as_str())
+        .filter(|id| !qualified || id.starts_with(word))
+        .map(str::to_string)
+        .collect())
+}
+
+/// The last segment of an entity id (`pkg/app.py::App::run` -> `run`).
+pub(crate) fn short_name(id: &str) -> &str {
+    id.rsplit("::")
+        .next()
+        .and_then(|last| last.rsplit('/').next())
+        .unwrap_or(id)
+}
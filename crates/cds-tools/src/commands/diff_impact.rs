@@ -0,0 +1,81 @@
+//! `cds diff-impact`: entities a change touches and their dependents
+//! (`diff_impact`)
+//!
+//! The diff is read from stdin, or taken from `git diff <rev>` in the
+//! current directory's checkout with `--rev`; paths in it must be relative
+//! to the indexed repository root, as git prints them.
+
+use super::{has_items, CliError, Outcome, QueryScope};
+use crate::client::IndexClient;
+use clap::Args;
+use serde_json::json;
+use std::io::{IsTerminal, Read};
+use std::process::Command;
+
+#[derive(Debug, Args)]
+pub struct DiffImpactArgs {
+    /// Diff these revisions of the local checkout instead of reading stdin,
+    /// e.g. `main..HEAD` or `HEAD~1`
+    #[arg(long)]
+    pub rev: Option<String>,
+    /// Hops of callers, importers and subclasses to follow
+    #[arg(long, default_value = "2")]
+    pub depth: usize,
+    /// Only follow these relations (import, invoke, inherit, contain),
+    /// repeated or comma-separated
+    #[arg(short, long, value_delimiter = ',')]
+    pub edge_type: Vec<String>,
+}
+
+/// Maps the diff; a diff touching no indexed entity counts as no results.
+pub async fn run(
+    client: &mut IndexClient,
+    scope: &QueryScope,
+    args: DiffImpactArgs,
+) -> Result<Outcome, CliError> {
+    let diff = match &args.rev {
+        Some(rev) => git_diff(rev)?,
+        None => read_stdin()?,
+    };
+    let mut params = json!({ "diff": diff, "depth": args.depth });
+    if !args.edge_type.is_empty() {
+        params["relations"] = json!(args.edge_type);
+    }
+    scope.apply(&mut params);
+    let result = client.call("diff_impact", params).await?;
+    let found = has_items(&result, "/changed");
+    Ok(Outcome { result, found })
+}
+
+/// `git diff <rev>` without context lines, which the mapping ignores anyway.
+fn git_diff(rev: &str) -> Result<String, CliError> {
+    let output = Command::new("git")
+        .args([
+            "diff",
+            "--no-color",
+            "--no-ext-diff",
+            "--unified=0",
+            rev,
+            "--",
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Err(CliError::InvalidArgs(format!(
+            "git diff {rev} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn read_stdin() -> Result<String, CliError> {
+    let mut stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return Err(CliError::InvalidArgs(
+            "pipe a unified diff to stdin or pass --rev".to_string(),
+        ));
+    }
+    let mut diff = String::new();
+    stdin.read_to_string(&mut diff)?;
+    Ok(diff)
+}
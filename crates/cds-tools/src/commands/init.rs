@@ -0,0 +1,194 @@
+//! `cds init`: build and persist the index of a repository
+//!
+//! Runs the graph builder and the BM25 indexer in-process and writes the
+//! result to `<repo>/.cds-index` (or `--index-dir`), where embedded mode and
+//! `cds-index-service` pick it up. Each build phase gets a progress bar on
+//! stderr; the result carries the entity and edge counts for the summary.
+
+use super::{CliError, Outcome};
+use crate::client::embedded::INDEX_DIR_NAME;
+use cds_index::graph::{BuildPhase, BuildProgress, GraphBuilderConfig};
+use cds_index::index::Bm25Params;
+use cds_index::persistence::{self, IndexLayout};
+use cds_index::service::LoadedIndex;
+use clap::Args;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::json;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Args)]
+pub struct InitArgs {
+    /// Repository to index
+    #[arg(value_name = "REPO", default_value = ".")]
+    pub path: PathBuf,
+    /// Follow symbolic links while walking the repository
+    #[arg(long)]
+    pub follow_symlinks: bool,
+    /// Stop after this many Python files
+    #[arg(long)]
+    pub max_files: Option<usize>,
+    /// Only index Python files at or under this repository-relative path (repeatable)
+    #[arg(long, value_name = "PATH")]
+    pub include: Vec<PathBuf>,
+    /// Skip directories with this name, in addition to `.git`, virtualenvs and
+    /// build output (repeatable)
+    #[arg(long, value_name = "DIR")]
+    pub exclude: Vec<String>,
+}
+
+/// Builds the index of `args.path` into `index_dir` (default `<repo>/.cds-index`).
+pub async fn run(args: InitArgs, index_dir: Option<PathBuf>) -> Result<Outcome, CliError> {
+    let repo = args.path.canonicalize().map_err(|err| {
+        CliError::InvalidArgs(format!("cannot index {}: {err}", args.path.display()))
+    })?;
+    if !repo.is_dir() {
+        return Err(CliError::InvalidArgs(format!(
+            "cannot index {}: not a directory",
+            repo.display()
+        )));
+    }
+    let index_dir = index_dir.unwrap_or_else(|| repo.join(INDEX_DIR_NAME));
+    let config = builder_config(&repo, &args)?;
+
+    let layout = IndexLayout::under(&index_dir);
+    let generation =
+        persistence::load_manifest(&layout.graph_dir).map_or(1, |manifest| manifest.generation + 1);
+    let build_root = repo.clone();
+    let (index, stats) = tokio::task::spawn_blocking(move || {
+        let progress = PhaseBars::default();
+        let built = LoadedIndex::build(
+            &build_root,
+            config,
+            &Bm25Params::default(),
+            &layout,
+            generation,
+            &progress,
+        );
+        progress.finish();
+        built
+    })
+    .await
+    .map_err(std::io::Error::other)?
+    .map_err(|err| std::io::Error::other(format!("cannot build the index: {err}")))?;
+
+    let result = json!({
+        "repo_path": repo,
+        "index_dir": index_dir,
+        "generation": index.generation,
+        "files_indexed": stats.files_indexed,
+        "entities_found": stats.entities_found,
+        "edges_created": stats.edges_created,
+        "build_time_ms": stats.build_time_ms,
+    });
+    Ok(Outcome {
+        result,
+        found: true,
+    })
+}
+
+/// Maps the flags onto [`GraphBuilderConfig`]; `--include` paths become the
+/// allow-list of Python files.
+fn builder_config(repo: &Path, args: &InitArgs) -> Result<GraphBuilderConfig, CliError> {
+    let mut allowed = HashSet::new();
+    for include in &args.include {
+        let path = repo.join(include);
+        if !path.exists() {
+            return Err(CliError::InvalidArgs(format!(
+                "--include {}: no such file or directory in {}",
+                include.display(),
+                repo.display()
+            )));
+        }
+        collect_python_files(repo, &path, args.follow_symlinks, &mut allowed)?;
+    }
+    Ok(GraphBuilderConfig {
+        follow_symlinks: args.follow_symlinks,
+        max_python_files: args.max_files,
+        allowed_python_files: (!args.include.is_empty()).then_some(allowed),
+        skip_dirs: args.exclude.clone(),
+        ..GraphBuilderConfig::default()
+    })
+}
+
+/// Adds the repository-relative paths (with `/` separators, as the builder
+/// compares them) of the Python files at or under `path`.
+fn collect_python_files(
+    repo: &Path,
+    path: &Path,
+    follow_symlinks: bool,
+    files: &mut HashSet<String>,
+) -> std::io::Result<()> {
+    let metadata = if follow_symlinks {
+        std::fs::metadata(path)?
+    } else {
+        std::fs::symlink_metadata(path)?
+    };
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            collect_python_files(repo, &entry?.path(), follow_symlinks, files)?;
+        }
+    } else if path.extension().is_some_and(|ext| ext == "py") {
+        let relative = path.strip_prefix(repo).unwrap_or(path);
+        files.insert(relative.to_string_lossy().replace('\\', "/"));
+    }
+    Ok(())
+}
+
+/// One progress bar per build phase: a bar when the phase's size is known
+/// (files to parse, documents to index), a spinner otherwise. indicatif
+/// hides them when stderr is not a terminal.
+#[derive(Default)]
+struct PhaseBars {
+    current: Mutex<Option<ProgressBar>>,
+}
+
+impl PhaseBars {
+    fn finish(&self) {
+        if let Some(bar) = self.lock().take() {
+            complete(&bar);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<ProgressBar>> {
+        self.current.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+impl BuildProgress for PhaseBars {
+    fn phase_started(&self, phase: BuildPhase, total: Option<usize>) {
+        let bar = match total {
+            Some(total) => ProgressBar::new(total as u64).with_style(
+                ProgressStyle::with_template("{prefix:>9} [{bar:30}] {pos}/{len} {wide_msg}")
+                    .expect("valid template")
+                    .progress_chars("=> "),
+            ),
+            None => ProgressBar::new_spinner().with_style(
+                ProgressStyle::with_template("{prefix:>9} {spinner} {elapsed}")
+                    .expect("valid template"),
+            ),
+        }
+        .with_prefix(phase.as_str());
+        bar.enable_steady_tick(Duration::from_millis(100));
+        if let Some(previous) = self.lock().replace(bar) {
+            complete(&previous);
+        }
+    }
+
+    fn file_processed(&self, rel_path: &Path) {
+        if let Some(bar) = self.lock().as_ref() {
+            bar.set_message(rel_path.display().to_string());
+            bar.inc(1);
+        }
+    }
+}
+
+/// Finishes a phase's bar at 100%: the BM25 phase reports no per-document steps.
+fn complete(bar: &ProgressBar) {
+    if let Some(len) = bar.length() {
+        bar.set_position(len);
+    }
+    bar.finish_with_message("");
+}
@@ -0,0 +1,46 @@
+//! `cds man`: manual pages generated from the command-line definitions
+
+use super::{CliError, Outcome};
+use clap::{Args, Command};
+use clap_mangen::Man;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Args)]
+pub struct ManArgs {
+    /// Write `cds.1` and one page per subcommand (`cds-search.1`, ...) into
+    /// this directory instead of printing `cds.1`
+    #[arg(long, value_name = "DIR")]
+    pub out_dir: Option<PathBuf>,
+}
+
+/// Prints the page of `cli`, or writes the pages of `cli` and its
+/// subcommands to `args.out_dir` and reports their paths.
+pub fn run(cli: Command, args: ManArgs) -> Result<Outcome, CliError> {
+    let mut cli = cli.disable_help_subcommand(true);
+    cli.build();
+    let Some(out_dir) = args.out_dir else {
+        let mut page = Vec::new();
+        Man::new(cli).render(&mut page)?;
+        print!("{}", String::from_utf8_lossy(&page));
+        return Ok(Outcome {
+            result: Value::Null,
+            found: true,
+        });
+    };
+    std::fs::create_dir_all(&out_dir)?;
+    let mut pages = Vec::new();
+    write_pages(&cli, &out_dir, &mut pages)?;
+    Ok(Outcome {
+        result: json!({ "out_dir": out_dir, "pages": pages }),
+        found: true,
+    })
+}
+
+fn write_pages(command: &Command, out_dir: &Path, pages: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    pages.push(Man::new(command.clone()).generate_to(out_dir)?);
+    for subcommand in command.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        write_pages(subcommand, out_dir, pages)?;
+    }
+    Ok(())
+}
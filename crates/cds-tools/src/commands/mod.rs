@@ -1,5 +1,131 @@
 //! CLI command implementations
+//!
+//! Each command calls the index service and returns the result to print.
+//! Failures carry the exit code documented in `docs/api/error-codes.md`, so
+//! scripts and agents can tell "nothing found" from "no index" from "service
+//! down" without parsing stderr.
 
+pub mod completions;
+pub mod diff_impact;
+pub mod init;
+pub mod man;
+pub mod path;
+pub mod retrieve;
 pub mod search;
+pub mod shell;
+pub mod stats;
 pub mod traverse;
-pub mod retrieve;
+
+use crate::client::{ClientError, IndexClient};
+use serde_json::Value;
+use std::process::ExitCode;
+use thiserror::Error;
+
+/// The query ran but matched nothing.
+pub const EXIT_NO_RESULTS: u8 = 1;
+/// Missing or invalid arguments (also used by clap for usage errors).
+pub const EXIT_INVALID_ARGS: u8 = 2;
+/// No index (or no such repository) to query.
+pub const EXIT_INDEX_NOT_FOUND: u8 = 3;
+/// The service is unreachable or failed.
+pub const EXIT_SERVICE_ERROR: u8 = 4;
+/// Reading or writing a local file failed.
+pub const EXIT_IO_ERROR: u8 = 5;
+
+/// JSON-RPC error codes that decide the exit code.
+const INVALID_REQUEST: i32 = -32600;
+const INVALID_PARAMS: i32 = -32602;
+const INDEX_NOT_FOUND: i32 = -32001;
+const ENTITY_NOT_FOUND: i32 = -32002;
+const REPO_NOT_FOUND: i32 = -32007;
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("{0}")]
+    InvalidArgs(String),
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CliError::InvalidArgs(_) => EXIT_INVALID_ARGS,
+            CliError::Io(_) => EXIT_IO_ERROR,
+            CliError::Client(ClientError::Config(_)) => EXIT_INVALID_ARGS,
+            CliError::Client(ClientError::Rpc(error)) => match error.code {
+                ENTITY_NOT_FOUND => EXIT_NO_RESULTS,
+                INVALID_REQUEST | INVALID_PARAMS => EXIT_INVALID_ARGS,
+                INDEX_NOT_FOUND | REPO_NOT_FOUND => EXIT_INDEX_NOT_FOUND,
+                _ => EXIT_SERVICE_ERROR,
+            },
+            CliError::Client(_) => EXIT_SERVICE_ERROR,
+        }
+    }
+}
+
+/// Options shared by the query commands.
+#[derive(Debug, Clone, Default)]
+pub struct QueryScope {
+    /// Registered repository to query (the default one when absent).
+    pub repo: Option<String>,
+    /// Agent session the returned entities are recorded under.
+    pub session: Option<String>,
+}
+
+impl QueryScope {
+    /// Adds `repo` and `session_id` to a method's params.
+    fn apply(&self, params: &mut Value) {
+        if let Some(repo) = &self.repo {
+            params["repo"] = Value::from(repo.as_str());
+        }
+        if let Some(session) = &self.session {
+            params["session_id"] = Value::from(session.as_str());
+        }
+    }
+}
+
+/// What a command returns: the result to print and whether it found anything.
+///
+/// A `Null` result means the command printed its own output (the shell).
+#[derive(Debug)]
+pub struct Outcome {
+    pub result: Value,
+    pub found: bool,
+}
+
+impl Outcome {
+    /// Exit status for a successful call.
+    pub fn exit_code(&self) -> ExitCode {
+        if self.found {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::from(EXIT_NO_RESULTS)
+        }
+    }
+}
+
+/// Calls `method`, or every page of it with `all`, concatenating `items`.
+async fn call_pages(
+    client: &mut IndexClient,
+    method: &str,
+    params: Value,
+    all: bool,
+    items: &[&str],
+) -> Result<Value, CliError> {
+    Ok(if all {
+        client.call_all(method, params, items).await?
+    } else {
+        client.call(method, params).await?
+    })
+}
+
+/// Whether the array at `pointer` in `result` has any element.
+fn has_items(result: &Value, pointer: &str) -> bool {
+    result
+        .pointer(pointer)
+        .and_then(Value::as_array)
+        .is_some_and(|items| !items.is_empty())
+}
@@ -0,0 +1,54 @@
+//! `cds path`: how one entity ends up depending on another (`find_paths`)
+
+use super::completions::complete_entity_id;
+use super::traverse::direction_param;
+use super::{has_items, CliError, Outcome, QueryScope};
+use crate::client::IndexClient;
+use clap::Args;
+use clap_complete::ArgValueCompleter;
+use serde_json::json;
+
+#[derive(Debug, Args)]
+pub struct PathArgs {
+    /// Entity id the paths start at, e.g. `src/api.py::handle`
+    #[arg(add = ArgValueCompleter::new(complete_entity_id))]
+    pub from: String,
+    /// Entity id the paths end at
+    #[arg(add = ArgValueCompleter::new(complete_entity_id))]
+    pub to: String,
+    /// outgoing, incoming or both
+    #[arg(short, long, default_value = "outgoing")]
+    pub direction: String,
+    /// Only follow these relations (contain, import, invoke, inherit),
+    /// repeated or comma-separated
+    #[arg(short, long, value_delimiter = ',')]
+    pub edge_type: Vec<String>,
+    /// Longest path considered, in hops
+    #[arg(long, default_value = "10")]
+    pub max_depth: usize,
+    /// Number of paths: the k shortest
+    #[arg(short, default_value = "1")]
+    pub k: usize,
+}
+
+/// Finds the paths; unconnected entities count as no results.
+pub async fn run(
+    client: &mut IndexClient,
+    scope: &QueryScope,
+    args: PathArgs,
+) -> Result<Outcome, CliError> {
+    let mut params = json!({
+        "from": args.from,
+        "to": args.to,
+        "direction": direction_param(&args.direction)?,
+        "max_depth": args.max_depth,
+        "k": args.k,
+    });
+    if !args.edge_type.is_empty() {
+        params["relations"] = json!(args.edge_type);
+    }
+    scope.apply(&mut params);
+    let result = client.call("find_paths", params).await?;
+    let found = has_items(&result, "/paths");
+    Ok(Outcome { result, found })
+}
@@ -0,0 +1,68 @@
+//! `cds retrieve`: source code of entities (`retrieve_entity`)
+//!
+//! The text output numbers every line and marks the entities' own lines;
+//! entities of the same file are merged into one listing, overlapping
+//! context printed once.
+
+use super::completions::complete_entity_id;
+use super::{has_items, CliError, Outcome, QueryScope};
+use crate::client::IndexClient;
+use clap::Args;
+use clap_complete::ArgValueCompleter;
+use serde_json::json;
+
+#[derive(Debug, Args)]
+pub struct RetrieveArgs {
+    /// Entity ids, e.g. `src/app.py::App::run`
+    #[arg(required = true, add = ArgValueCompleter::new(complete_entity_id))]
+    pub entity_ids: Vec<String>,
+    /// Lines of surrounding code to include before and after each entity
+    #[arg(short, long, default_value = "5")]
+    pub context: usize,
+    /// Include the entity's metadata: parameters, decorators, bases, ...
+    #[arg(long)]
+    pub include_metadata: bool,
+    /// Show only the first line of each entity
+    #[arg(long, group = "snippet")]
+    pub fold: bool,
+    /// Show only the first lines of each entity
+    #[arg(long, group = "snippet")]
+    pub preview: bool,
+    /// Show each entity in full (the default)
+    #[arg(long, group = "snippet")]
+    pub full: bool,
+}
+
+impl RetrieveArgs {
+    fn snippet_mode(&self) -> Option<&'static str> {
+        if self.fold {
+            Some("fold")
+        } else if self.preview {
+            Some("preview")
+        } else if self.full {
+            Some("full")
+        } else {
+            None
+        }
+    }
+}
+
+/// Fetches the entities; an unknown id fails with "no results".
+pub async fn run(
+    client: &mut IndexClient,
+    scope: &QueryScope,
+    args: RetrieveArgs,
+) -> Result<Outcome, CliError> {
+    let mut params = json!({
+        "entity_ids": args.entity_ids,
+        "include_context": args.context,
+        "include_metadata": args.include_metadata,
+    });
+    if let Some(mode) = args.snippet_mode() {
+        params["snippet_mode"] = json!(mode);
+    }
+    scope.apply(&mut params);
+    let result = client.call("retrieve_entity", params).await?;
+    let found = has_items(&result, "/entities");
+    Ok(Outcome { result, found })
+}
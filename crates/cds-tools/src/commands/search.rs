@@ -0,0 +1,41 @@
+//! `cds search`: hierarchical name and BM25 search (`search_entities`)
+
+use super::{call_pages, has_items, CliError, Outcome, QueryScope};
+use crate::client::IndexClient;
+use clap::Args;
+use serde_json::json;
+
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    /// Entity name, id prefix or keywords
+    pub query: String,
+    /// Only return this entity type (directory, file, class, function)
+    #[arg(short, long)]
+    pub entity_type: Option<String>,
+    /// Results per page
+    #[arg(short, long, default_value = "10")]
+    pub limit: usize,
+    /// Follow `next_cursor` and print every page as one result
+    #[arg(long)]
+    pub all: bool,
+}
+
+pub async fn run(
+    client: &mut IndexClient,
+    scope: &QueryScope,
+    args: SearchArgs,
+) -> Result<Outcome, CliError> {
+    if args.query.trim().is_empty() {
+        return Err(CliError::InvalidArgs(
+            "the query must not be empty".to_string(),
+        ));
+    }
+    let mut params = json!({ "query": args.query, "limit": args.limit });
+    if let Some(entity_type) = args.entity_type {
+        params["entity_types"] = json!([entity_type]);
+    }
+    scope.apply(&mut params);
+    let result = call_pages(client, "search_entities", params, args.all, &["/entities"]).await?;
+    let found = has_items(&result, "/entities");
+    Ok(Outcome { result, found })
+}
@@ -0,0 +1,328 @@
+//! `cds shell`: interactive navigation of the code graph
+//!
+//! Runs over whatever client the global flags select, so it works against
+//! the service and in embedded mode alike. The session keeps a breadcrumb of
+//! opened entities (shown in the prompt, popped by `back`) and numbers every
+//! listing so `open 2` opens its second entry. Entity ids tab-complete from
+//! the name index; history is kept in the platform data directory.
+
+use super::completions::{matching_entity_ids, short_name};
+use super::{CliError, Outcome, QueryScope};
+use crate::client::IndexClient;
+use crate::formatters::{self, text, Format};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use tokio::runtime::Handle;
+
+const HELP: &str = "\
+search <query>   find entities by name, id prefix or keywords
+open <id|n>      show an entity (n: entry of the last listing) and make it current
+callers          functions that invoke the current entity
+callees          functions the current entity invokes
+parents          the directory, file or class containing the current entity
+back             return to the previous entity
+where            print the breadcrumb
+help             this list
+quit             leave the shell (also Ctrl-D)";
+
+const COMMANDS: &[&str] = &[
+    "search", "open", "callers", "callees", "parents", "back", "where", "help", "quit",
+];
+
+type SharedClient = Rc<RefCell<IndexClient>>;
+
+/// Runs the shell until `quit` or end of input. Blocks the current worker
+/// thread; requests run on the ambient runtime.
+pub fn run(client: IndexClient, scope: QueryScope, format: Format) -> Result<Outcome, CliError> {
+    tokio::task::block_in_place(|| {
+        let color = formatters::to_terminal(false);
+        if !color {
+            colored::control::set_override(false);
+        }
+        let client = Rc::new(RefCell::new(client));
+        let mut editor: Editor<ShellHelper, DefaultHistory> =
+            Editor::new().map_err(readline_error)?;
+        editor.set_helper(Some(ShellHelper {
+            client: Rc::clone(&client),
+            scope: scope.clone(),
+            handle: Handle::current(),
+        }));
+        let history = history_path();
+        if let Some(path) = &history {
+            let _ = editor.load_history(path);
+        }
+
+        let mut shell = Shell {
+            client,
+            scope,
+            format,
+            color,
+            handle: Handle::current(),
+            trail: Vec::new(),
+            listing: Vec::new(),
+        };
+        loop {
+            let line = match editor.readline(&shell.prompt()) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(err) => return Err(readline_error(err)),
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let _ = editor.add_history_entry(line);
+            let (command, argument) = line
+                .split_once(char::is_whitespace)
+                .map_or((line, ""), |(command, rest)| (command, rest.trim()));
+            if matches!(command, "quit" | "exit") {
+                break;
+            }
+            if let Err(err) = shell.execute(command, argument) {
+                eprintln!("error: {err}");
+            }
+        }
+
+        if let Some(path) = &history {
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            let _ = editor.save_history(path);
+        }
+        Ok(Outcome {
+            result: Value::Null,
+            found: true,
+        })
+    })
+}
+
+/// `<data dir>/cds/shell_history`.
+fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("cds").join("shell_history"))
+}
+
+fn readline_error(err: ReadlineError) -> CliError {
+    CliError::Io(std::io::Error::other(err))
+}
+
+struct Shell {
+    client: SharedClient,
+    scope: QueryScope,
+    format: Format,
+    color: bool,
+    handle: Handle,
+    /// Opened entities, the current one last.
+    trail: Vec<String>,
+    /// Entity ids of the last numbered listing.
+    listing: Vec<String>,
+}
+
+impl Shell {
+    /// `cds> ` or `cds App > run> `: the last names of the breadcrumb.
+    fn prompt(&self) -> String {
+        let names: Vec<&str> = self
+            .trail
+            .iter()
+            .rev()
+            .take(3)
+            .rev()
+            .map(|id| short_name(id))
+            .collect();
+        if names.is_empty() {
+            "cds> ".to_string()
+        } else {
+            format!("cds {}> ", names.join(" > "))
+        }
+    }
+
+    fn execute(&mut self, command: &str, argument: &str) -> Result<(), CliError> {
+        match command {
+            "help" | "?" => println!("{HELP}"),
+            "search" => {
+                if argument.is_empty() {
+                    return Err(CliError::InvalidArgs("usage: search <query>".to_string()));
+                }
+                let result = self.call(
+                    "search_entities",
+                    json!({ "query": argument, "limit": 20, "snippet_mode": "fold" }),
+                )?;
+                let entities = result["entities"].as_array().cloned().unwrap_or_default();
+                self.list(&entities);
+            }
+            "open" => {
+                let id = self.resolve(argument)?;
+                let result = self.call(
+                    "retrieve_entity",
+                    json!({ "entity_ids": [id], "include_context": 0 }),
+                )?;
+                println!("{}", formatters::render(self.format, &result, self.color));
+                if self.trail.last() != Some(&id) {
+                    self.trail.push(id);
+                }
+            }
+            "callers" => self.neighbours("backward", "invoke")?,
+            "callees" => self.neighbours("forward", "invoke")?,
+            "parents" => self.neighbours("backward", "contain")?,
+            "back" => {
+                if self.trail.pop().is_none() {
+                    return Err(CliError::InvalidArgs("nothing to go back to".to_string()));
+                }
+                self.where_am_i();
+            }
+            "where" => self.where_am_i(),
+            other => {
+                return Err(CliError::InvalidArgs(format!(
+                    "unknown command '{other}' (try help)"
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists the entities one `relation` edge away from the current one.
+    fn neighbours(&mut self, direction: &str, relation: &str) -> Result<(), CliError> {
+        let current = self.current()?.to_string();
+        let result = self.call(
+            "traverse_graph",
+            json!({
+                "start_entities": [current],
+                "depth": 1,
+                "direction": direction,
+                "relations": [relation],
+            }),
+        )?;
+        let nodes: Vec<Value> = result["subgraph"]["nodes"]
+            .as_array()
+            .map(|nodes| {
+                nodes
+                    .iter()
+                    .filter(|node| node["depth"].as_u64() != Some(0))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.list(&nodes);
+        Ok(())
+    }
+
+    /// Prints `entities` numbered and remembers them for `open <n>`.
+    fn list(&mut self, entities: &[Value]) {
+        if entities.is_empty() {
+            println!("no results");
+        }
+        self.listing = entities
+            .iter()
+            .map(|entity| text::str_field(entity, "id").to_string())
+            .collect();
+        for (n, entity) in entities.iter().enumerate() {
+            println!("{:>3}  {}", n + 1, text::heading(entity));
+        }
+    }
+
+    fn where_am_i(&self) {
+        if self.trail.is_empty() {
+            println!("no entity open");
+        } else {
+            println!("{}", self.trail.join(" > "));
+        }
+    }
+
+    fn current(&self) -> Result<&str, CliError> {
+        self.trail
+            .last()
+            .map(String::as_str)
+            .ok_or_else(|| CliError::InvalidArgs("open an entity first".to_string()))
+    }
+
+    /// An entity id, or the `n`th entry of the last listing.
+    fn resolve(&self, argument: &str) -> Result<String, CliError> {
+        if argument.is_empty() {
+            return Err(CliError::InvalidArgs("usage: open <id|n>".to_string()));
+        }
+        match argument.parse::<usize>() {
+            Ok(n) => n
+                .checked_sub(1)
+                .and_then(|i| self.listing.get(i))
+                .cloned()
+                .ok_or_else(|| CliError::InvalidArgs(format!("no entry {n} in the last listing"))),
+            Err(_) => Ok(argument.to_string()),
+        }
+    }
+
+    fn call(&self, method: &str, mut params: Value) -> Result<Value, CliError> {
+        self.scope.apply(&mut params);
+        let mut client = self.client.borrow_mut();
+        Ok(self.handle.block_on(client.call(method, params))?)
+    }
+}
+
+struct ShellHelper {
+    client: SharedClient,
+    scope: QueryScope,
+    handle: Handle,
+}
+
+impl ShellHelper {
+    fn entity_ids(&self, word: &str) -> Vec<String> {
+        let Ok(mut client) = self.client.try_borrow_mut() else {
+            return Vec::new();
+        };
+        self.handle
+            .block_on(matching_entity_ids(&mut client, &self.scope, word))
+            .unwrap_or_default()
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &before[start..];
+        let candidates: Vec<String> = if start == 0 {
+            COMMANDS
+                .iter()
+                .filter(|command| command.starts_with(word))
+                .map(|command| format!("{command} "))
+                .collect()
+        } else if before.starts_with("open ") && !word.is_empty() {
+            self.entity_ids(word)
+        } else {
+            Vec::new()
+        };
+        let pairs = candidates
+            .into_iter()
+            .map(|candidate| Pair {
+                display: candidate.trim_end().to_string(),
+                replacement: candidate,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
@@ -0,0 +1,27 @@
+//! `cds stats`: composition and hotspots of an index (`get_index_stats`)
+
+use super::{CliError, Outcome, QueryScope};
+use crate::client::IndexClient;
+use clap::Args;
+use serde_json::json;
+
+#[derive(Debug, Args)]
+pub struct StatsArgs {
+    /// Entries to show in each hotspot list (largest files, most invoked, ...)
+    #[arg(long, default_value = "10")]
+    pub top: usize,
+}
+
+pub async fn run(
+    client: &mut IndexClient,
+    scope: &QueryScope,
+    args: StatsArgs,
+) -> Result<Outcome, CliError> {
+    let mut params = json!({ "top": args.top });
+    scope.apply(&mut params);
+    let result = client.call("get_index_stats", params).await?;
+    Ok(Outcome {
+        result,
+        found: true,
+    })
+}
@@ -0,0 +1,79 @@
+//! `cds traverse`: dependency graph walk from an entity (`traverse_graph`)
+
+use super::completions::complete_entity_id;
+use super::{call_pages, has_items, CliError, Outcome, QueryScope};
+use crate::client::IndexClient;
+use crate::formatters::Format;
+use clap::Args;
+use clap_complete::ArgValueCompleter;
+use serde_json::json;
+
+#[derive(Debug, Args)]
+pub struct TraverseArgs {
+    /// Entity id to start from, e.g. `src/app.py::App`
+    #[arg(add = ArgValueCompleter::new(complete_entity_id))]
+    pub entity_id: String,
+    /// outgoing, incoming or both
+    #[arg(short, long, default_value = "outgoing")]
+    pub direction: String,
+    /// Only follow this relation (contain, import, invoke, inherit)
+    #[arg(short, long)]
+    pub edge_type: Option<String>,
+    #[arg(long, default_value = "1")]
+    pub depth: usize,
+    /// Nodes per page (default: the whole subgraph)
+    #[arg(short, long)]
+    pub limit: Option<usize>,
+    /// Follow `next_cursor` and print every page as one result
+    #[arg(long)]
+    pub all: bool,
+}
+
+/// Walks the graph; a start entity without matching edges counts as no results.
+///
+/// The tree format asks the service for the discovering edges only, so each
+/// node appears once in the printed tree.
+pub async fn run(
+    client: &mut IndexClient,
+    scope: &QueryScope,
+    args: TraverseArgs,
+    format: Format,
+) -> Result<Outcome, CliError> {
+    let mut params = json!({
+        "start_entities": [args.entity_id],
+        "depth": args.depth,
+        "direction": direction_param(&args.direction)?,
+    });
+    if let Some(edge_type) = args.edge_type {
+        params["relations"] = json!([edge_type]);
+    }
+    if format == Format::Tree {
+        params["format"] = json!("tree");
+    }
+    if let Some(limit) = args.limit {
+        params["limit"] = json!(limit);
+    }
+    scope.apply(&mut params);
+    let result = call_pages(
+        client,
+        "traverse_graph",
+        params,
+        args.all,
+        &["/subgraph/nodes", "/subgraph/edges"],
+    )
+    .await?;
+    let found = has_items(&result, "/subgraph/edges");
+    Ok(Outcome { result, found })
+}
+
+/// The service's name for a `--direction` value.
+pub(crate) fn direction_param(direction: &str) -> Result<&'static str, CliError> {
+    match direction {
+        "outgoing" | "forward" => Ok("forward"),
+        "incoming" | "backward" => Ok("backward"),
+        "both" | "bidirectional" => Ok("bidirectional"),
+        other => Err(CliError::InvalidArgs(format!(
+            "unknown direction '{other}' (expected outgoing, incoming or both)"
+        ))),
+    }
+}
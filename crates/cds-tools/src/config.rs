@@ -0,0 +1,81 @@
+//! CLI configuration (`~/.config/cds/config.toml`)
+//!
+//! Only the `[service]` section is read so far; it selects how `cds` reaches
+//! the index service. Flags and their environment variables take precedence
+//! over the file.
+//!
+//! ```toml
+//! [service]
+//! transport = "unix"              # http | unix | stdio
+//! url = "http://127.0.0.1:3030/rpc"
+//! socket = "/home/me/.cache/cds/cds-index.sock"
+//! token = "..."                   # bearer token, if the service requires one
+//! command = "cds-index-service"   # spawned for the stdio transport
+//! args = []
+//! index_dir = "./.cds-index"      # query this index in-process instead
+//! ```
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_SERVICE_URL: &str = "http://127.0.0.1:3030/rpc";
+pub const DEFAULT_SERVICE_COMMAND: &str = "cds-index-service";
+
+/// How `cds` talks to the index service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// JSON-RPC over HTTP (`url`).
+    #[default]
+    Http,
+    /// JSON-RPC over HTTP on a Unix domain socket (`socket`).
+    Unix,
+    /// Spawn the service (`command`) and talk line-delimited JSON-RPC over its stdin/stdout.
+    Stdio,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ServiceSection {
+    pub transport: Option<Transport>,
+    pub url: Option<String>,
+    pub socket: Option<PathBuf>,
+    pub token: Option<String>,
+    pub command: Option<PathBuf>,
+    pub args: Vec<String>,
+    pub index_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CliConfig {
+    pub service: ServiceSection,
+}
+
+impl CliConfig {
+    /// Reads `$CDS_CONFIG`, else `~/.config/cds/config.toml`; a missing file
+    /// yields the defaults.
+    pub fn load() -> Result<Self> {
+        match std::env::var_os("CDS_CONFIG") {
+            Some(path) => Self::from_file(Path::new(&path)),
+            None => match default_config_path() {
+                Some(path) if path.is_file() => Self::from_file(&path),
+                _ => Ok(Self::default()),
+            },
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+}
+
+/// `~/.config/cds/config.toml` (platform config directory).
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("cds").join("config.toml"))
+}
@@ -0,0 +1,7 @@
+//! JSON output: the service result as returned, pretty-printed
+
+use serde_json::Value;
+
+pub fn render(result: &Value) -> String {
+    serde_json::to_string_pretty(result).expect("JSON values serialize")
+}
@@ -0,0 +1,55 @@
+//! Output formatters for command results
+//!
+//! Commands return the service's JSON result unchanged; the formatter picks
+//! how it is printed. JSON is the default when stdout is not a terminal, so
+//! pipes and `-o` files stay machine-readable.
+
+pub mod json;
+pub mod text;
+pub mod tree;
+
+use clap::ValueEnum;
+use serde_json::Value;
+use std::io::IsTerminal;
+
+/// `--format` of the query commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Pretty-printed JSON-RPC result.
+    Json,
+    /// Colored listing of entities with a folded snippet.
+    Text,
+    /// Indented dependency tree of a traversal.
+    Tree,
+}
+
+impl Format {
+    /// The requested format, else text on a terminal and JSON otherwise.
+    pub fn resolve(requested: Option<Format>, to_terminal: bool) -> Format {
+        requested.unwrap_or(if to_terminal {
+            Format::Text
+        } else {
+            Format::Json
+        })
+    }
+}
+
+/// Whether output goes to an interactive terminal: stdout is a TTY and no
+/// `-o` file was given.
+pub fn to_terminal(has_output_file: bool) -> bool {
+    !has_output_file && std::io::stdout().is_terminal()
+}
+
+/// Renders `result` in `format`, without a trailing newline.
+///
+/// Colors are emitted only when `color` is set (and `NO_COLOR` is unset).
+pub fn render(format: Format, result: &Value, color: bool) -> String {
+    if !color {
+        colored::control::set_override(false);
+    }
+    match format {
+        Format::Json => json::render(result),
+        Format::Text => text::render(result),
+        Format::Tree => tree::render(result),
+    }
+}
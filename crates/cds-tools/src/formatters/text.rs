@@ -0,0 +1,522 @@
+//! Human-readable output
+//!
+//! One line per entity (id, kind, `path:line`) followed by its folded
+//! snippet. Retrieved entities are grouped by file: their headings (and
+//! metadata) come first, then one numbered listing of their code and
+//! context, the entities' own lines marked with `>`. Paths alternate their
+//! entities with the relation of each hop, and diff impact lists the changed
+//! entities, then their dependents with the edge that reached each.
+//! Traversals list their nodes and then their edges, build statistics
+//! (`cds init`) print as a table of entity and edge counts, and index
+//! statistics (`cds stats`) add a table or list per hotspot; other results
+//! print as `key: value` lines.
+
+use colored::Colorize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub fn render(result: &Value) -> String {
+    let mut lines = Vec::new();
+    if let Some(entities) = result.get("entities").and_then(Value::as_array) {
+        if entities.iter().any(|entity| entity.get("code").is_some()) {
+            retrieved_lines(entities, &mut lines);
+        } else {
+            entity_lines(entities, &mut lines);
+        }
+        summary_line(result, entities.len(), &mut lines);
+    } else if result.get("impacted").is_some() {
+        impact_lines(result, &mut lines);
+    } else if let Some(paths) = result.get("paths").and_then(Value::as_array) {
+        path_lines(paths, &mut lines);
+    } else if let Some(subgraph) = result.get("subgraph") {
+        subgraph_lines(subgraph, &mut lines);
+    } else if result.get("entities_found").is_some() {
+        build_lines(result, &mut lines);
+    } else if result.get("import_cycles").is_some() {
+        stats_lines(result, &mut lines);
+    } else {
+        field_lines(result, &mut lines);
+    }
+    lines.join("\n")
+}
+
+/// `id  kind  path:line` with colors.
+pub(crate) fn heading(entity: &Value) -> String {
+    let id = str_field(entity, "id");
+    let kind = str_field(entity, "entity_type");
+    let mut line = format!(
+        "{}  {}  {}",
+        id.bold(),
+        kind.cyan(),
+        location(entity).green()
+    );
+    if let Some(external) = entity.get("external") {
+        let repo = str_field(external, "repo");
+        line.push_str(&format!("  {}", format!("[{repo}]").magenta()));
+    }
+    line
+}
+
+/// `file_path:start_line`, or just the path for entities without lines.
+pub(crate) fn location(entity: &Value) -> String {
+    let path = str_field(entity, "file_path");
+    match entity.pointer("/line_range/0").and_then(Value::as_u64) {
+        Some(line) if line > 0 => format!("{path}:{line}"),
+        _ => path.to_string(),
+    }
+}
+
+pub(crate) fn str_field<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(Value::as_str).unwrap_or("")
+}
+
+fn entity_lines(entities: &[Value], lines: &mut Vec<String>) {
+    for (i, entity) in entities.iter().enumerate() {
+        if i > 0 {
+            lines.push(String::new());
+        }
+        let mut heading = heading(entity);
+        if let Some(score) = entity.get("score").and_then(Value::as_f64) {
+            heading.push_str(&format!("  {}", format!("{score:.2}").yellow()));
+        }
+        lines.push(heading);
+        let body = entity
+            .pointer("/snippet/fold")
+            .or_else(|| entity.get("code"))
+            .and_then(Value::as_str)
+            .unwrap_or("");
+        for line in body.lines() {
+            lines.push(format!("    {}", line.dimmed()));
+        }
+    }
+}
+
+/// `retrieve_entity` results, one block per file in order of first appearance.
+fn retrieved_lines(entities: &[Value], lines: &mut Vec<String>) {
+    let mut files: Vec<(String, Vec<&Value>)> = Vec::new();
+    for entity in entities {
+        let repo = entity.pointer("/external/repo").and_then(Value::as_str);
+        let key = format!("{}\0{}", repo.unwrap_or(""), str_field(entity, "file_path"));
+        match files.iter_mut().find(|(file, _)| *file == key) {
+            Some((_, group)) => group.push(entity),
+            None => files.push((key, vec![entity])),
+        }
+    }
+    for (i, (_, group)) in files.iter().enumerate() {
+        if i > 0 {
+            lines.push(String::new());
+        }
+        for entity in group {
+            lines.push(heading(entity));
+            metadata_lines(entity, lines);
+            if entity.pointer("/line_range/0").is_none() {
+                for line in str_field(entity, "code").lines() {
+                    lines.push(format!("    {}", line.dimmed()));
+                }
+            }
+        }
+        listing_lines(group, lines);
+    }
+}
+
+/// The `metadata` of a retrieved entity, one `key: value` line per field.
+fn metadata_lines(entity: &Value, lines: &mut Vec<String>) {
+    let Some(metadata) = entity.get("metadata") else {
+        return;
+    };
+    let joined = |key: &str, prefix: &str| {
+        let values: Vec<String> = items(metadata, key)
+            .iter()
+            .filter_map(Value::as_str)
+            .map(|value| format!("{prefix}{value}"))
+            .collect();
+        (!values.is_empty()).then(|| values.join(", "))
+    };
+    let docstring = metadata
+        .get("docstring")
+        .and_then(Value::as_str)
+        .and_then(|doc| doc.lines().next())
+        .map(str::to_string);
+    let fields = [
+        ("parameters", joined("parameters", "")),
+        (
+            "returns",
+            metadata
+                .get("return_type")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        ),
+        ("decorators", joined("decorators", "@")),
+        ("bases", joined("bases", "")),
+        (
+            "parent",
+            metadata
+                .get("parent_class")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        ),
+        (
+            "async",
+            (metadata.get("is_async") == Some(&Value::Bool(true))).then(|| "yes".to_string()),
+        ),
+        ("doc", docstring),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            lines.push(format!("    {} {value}", format!("{key}:").dimmed()));
+        }
+    }
+}
+
+/// The code and context of entities of one file as a single numbered
+/// listing; a line shared by two entities or their context appears once and
+/// skipped lines show as `⋮`.
+fn listing_lines(group: &[&Value], lines: &mut Vec<String>) {
+    // Line number -> (text, part of an entity).
+    let mut listing: BTreeMap<u64, (&str, bool)> = BTreeMap::new();
+    for entity in group {
+        let mut add = |number: u64, text, own: bool| {
+            let entry = listing.entry(number).or_insert((text, own));
+            entry.1 |= own;
+        };
+        let code = str_field(entity, "code");
+        let Some(start) = entity.pointer("/line_range/0").and_then(Value::as_u64) else {
+            continue;
+        };
+        let end = entity
+            .pointer("/line_range/1")
+            .and_then(Value::as_u64)
+            .unwrap_or(start);
+        // Context is joined with '\n' and may be made of blank lines only.
+        let context = |key| {
+            entity
+                .get(key)
+                .and_then(Value::as_str)
+                .map(|text| text.split('\n'))
+        };
+        let before: Vec<&str> = context("context_before").into_iter().flatten().collect();
+        let first = start.saturating_sub(before.len() as u64);
+        for (number, text) in (first..).zip(before) {
+            add(number, text, false);
+        }
+        for (number, text) in (start..).zip(code.lines()) {
+            add(number, text, true);
+        }
+        for (number, text) in (end + 1..).zip(context("context_after").into_iter().flatten()) {
+            add(number, text, false);
+        }
+    }
+    let width = listing
+        .keys()
+        .next_back()
+        .map_or(1, |last| last.to_string().len());
+    let mut previous = None;
+    for (number, (text, own)) in listing {
+        if previous.is_some_and(|previous| number > previous + 1) {
+            lines.push(format!("  {:>width$} │", "⋮").dimmed().to_string());
+        }
+        previous = Some(number);
+        let line = format!("{number:>width$} │ {text}");
+        let line = line.trim_end();
+        lines.push(if own {
+            format!("{} {line}", ">".green())
+        } else {
+            format!("  {}", line.dimmed())
+        });
+    }
+}
+
+/// "N of M results", and how to get the rest when the result is paged.
+fn summary_line(result: &Value, shown: usize, lines: &mut Vec<String>) {
+    if shown == 0 {
+        lines.push("no results".dimmed().to_string());
+        return;
+    }
+    let Some(total) = result.get("total_count").and_then(Value::as_u64) else {
+        return;
+    };
+    let mut summary = format!("{shown} of {total} results");
+    if result.get("next_cursor").is_some() {
+        summary.push_str(" (--all for every page)");
+    }
+    lines.push(String::new());
+    lines.push(summary.dimmed().to_string());
+}
+
+/// Each path as its entities, one per line, with the hop between two of them
+/// in between; `<──` marks an edge followed backwards.
+fn path_lines(paths: &[Value], lines: &mut Vec<String>) {
+    if paths.is_empty() {
+        lines.push("no results".dimmed().to_string());
+        return;
+    }
+    for (i, path) in paths.iter().enumerate() {
+        if i > 0 {
+            lines.push(String::new());
+        }
+        let length = path.get("length").and_then(Value::as_u64).unwrap_or(0);
+        let hops = if length == 1 { "hop" } else { "hops" };
+        lines.push(
+            format!("path {} of {}, {length} {hops}", i + 1, paths.len())
+                .dimmed()
+                .to_string(),
+        );
+        let nodes = items(path, "nodes");
+        for (n, node) in nodes.iter().enumerate() {
+            lines.push(format!("  {}", heading(node)));
+            let Some(hop) = items(path, "hops").get(n) else {
+                continue;
+            };
+            let relation = str_field(hop, "relation");
+            let label = match hop.get("alias").and_then(Value::as_str) {
+                Some(alias) => format!("{relation} as {alias}"),
+                None => relation.to_string(),
+            };
+            let arrow = if str_field(hop, "source") == str_field(node, "id") {
+                format!("{label} ──>")
+            } else {
+                format!("<── {label} ──")
+            };
+            lines.push(format!("  │ {}", arrow.yellow()));
+        }
+    }
+}
+
+/// Changed entities, impacted ones (each with the relation to the entity it
+/// depends on) and the files the index does not know.
+fn impact_lines(result: &Value, lines: &mut Vec<String>) {
+    let changed = items(result, "changed");
+    let impacted = items(result, "impacted");
+    let unindexed = items(result, "unindexed_files");
+    list_section(
+        format!("changed ({})", changed.len()),
+        changed.iter().map(heading).collect(),
+        lines,
+    );
+    // The first section needs no separating blank line.
+    lines.remove(0);
+    list_section(
+        format!("impacted ({})", impacted.len()),
+        impacted
+            .iter()
+            .map(|entity| {
+                let via = &entity["via"];
+                let edge = format!(
+                    "{} ──> {}",
+                    str_field(via, "relation"),
+                    str_field(via, "target")
+                );
+                format!("{}  {}", heading(entity), edge.dimmed())
+            })
+            .collect(),
+        lines,
+    );
+    if !unindexed.is_empty() {
+        list_section(
+            format!("not indexed ({})", unindexed.len()),
+            unindexed
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            lines,
+        );
+    }
+}
+
+fn subgraph_lines(subgraph: &Value, lines: &mut Vec<String>) {
+    let nodes = subgraph
+        .get("nodes")
+        .and_then(Value::as_array)
+        .map_or(&[][..], Vec::as_slice);
+    let edges = subgraph
+        .get("edges")
+        .and_then(Value::as_array)
+        .map_or(&[][..], Vec::as_slice);
+    for node in nodes {
+        let depth = node.get("depth").and_then(Value::as_u64).unwrap_or(0);
+        lines.push(format!("{}{}", "  ".repeat(depth as usize), heading(node)));
+    }
+    if edges.is_empty() {
+        lines.push("no edges".dimmed().to_string());
+        return;
+    }
+    lines.push(String::new());
+    for edge in edges {
+        lines.push(format!(
+            "{} {} {}",
+            str_field(edge, "source"),
+            format!("-{}->", str_field(edge, "relation")).blue(),
+            str_field(edge, "target")
+        ));
+    }
+}
+
+/// Where the index went, then entity counts per kind and edge counts per `EdgeKind`.
+fn build_lines(result: &Value, lines: &mut Vec<String>) {
+    let seconds = result["build_time_ms"].as_f64().unwrap_or(0.0) / 1000.0;
+    lines.push(format!(
+        "Indexed {} files into {} in {seconds:.1}s (generation {})",
+        result["files_indexed"],
+        str_field(result, "index_dir").bold(),
+        result["generation"]
+    ));
+    count_tables(&result["entities_found"], &result["edges_created"], lines);
+}
+
+/// Entity counts per kind and edge counts per `EdgeKind`.
+fn count_tables(entities: &Value, edges: &Value, lines: &mut Vec<String>) {
+    for (title, counts, kinds) in [
+        (
+            "entity",
+            entities,
+            &["directories", "files", "classes", "functions", "external"][..],
+        ),
+        (
+            "edge",
+            edges,
+            &["contain", "import", "invoke", "inherit"][..],
+        ),
+    ] {
+        lines.push(String::new());
+        lines.push(format!("{title:<12} {:>8}", "count").bold().to_string());
+        for kind in kinds {
+            if let Some(count) = counts.get(*kind).and_then(Value::as_u64) {
+                lines.push(format!("{kind:<12} {count:>8}"));
+            }
+        }
+    }
+}
+
+/// The counts of [`build_lines`], then each hotspot list and the build's
+/// unresolved imports and parse failures.
+fn stats_lines(result: &Value, lines: &mut Vec<String>) {
+    lines.push(format!(
+        "Index of {} (generation {}, built {})",
+        str_field(result, "repo_root").bold(),
+        result["generation"],
+        str_field(result, "built_at")
+    ));
+    count_tables(&result["entities"], &result["edges"], lines);
+
+    let rows = |key: &str, name: &str, count: &str| -> Vec<(String, u64)> {
+        items(result, key)
+            .iter()
+            .map(|row| {
+                (
+                    str_field(row, name).to_string(),
+                    row[count].as_u64().unwrap_or(0),
+                )
+            })
+            .collect()
+    };
+    ranked_table(
+        "largest files",
+        "entities",
+        rows("largest_files", "file_path", "entities"),
+        lines,
+    );
+    ranked_table(
+        "most invoked",
+        "callers",
+        rows("most_invoked", "id", "callers"),
+        lines,
+    );
+    let joined = |key: &str, separator: &str| -> Vec<String> {
+        items(result, key)
+            .iter()
+            .map(|group| {
+                group
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(separator)
+            })
+            .collect()
+    };
+    list_section(
+        "inheritance chains".to_string(),
+        joined("inheritance_chains", " -> "),
+        lines,
+    );
+    list_section(
+        format!("import cycles ({})", result["import_cycles_total"]),
+        joined("import_cycles", " <-> "),
+        lines,
+    );
+
+    lines.push(String::new());
+    lines.push(format!(
+        "{} {}",
+        "unresolved imports".bold(),
+        result["unresolved_imports"]
+    ));
+    let failures = items(result, "parse_failures");
+    list_section(
+        format!("parse failures ({})", failures.len()),
+        failures
+            .iter()
+            .filter_map(Value::as_str)
+            .map(|path| path.red().to_string())
+            .collect(),
+        lines,
+    );
+}
+
+/// The array at `key`, empty when absent.
+fn items<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .map_or(&[][..], Vec::as_slice)
+}
+
+/// `name  count` rows under a bold heading, names padded to the longest.
+fn ranked_table(title: &str, count_title: &str, rows: Vec<(String, u64)>, lines: &mut Vec<String>) {
+    lines.push(String::new());
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain([title.len()])
+        .max()
+        .unwrap_or(0);
+    lines.push(
+        format!("{title:<width$} {count_title:>8}")
+            .bold()
+            .to_string(),
+    );
+    if rows.is_empty() {
+        lines.push("none".dimmed().to_string());
+    }
+    for (name, count) in rows {
+        lines.push(format!("{name:<width$} {count:>8}"));
+    }
+}
+
+fn list_section(title: String, entries: Vec<String>, lines: &mut Vec<String>) {
+    lines.push(String::new());
+    lines.push(title.bold().to_string());
+    if entries.is_empty() {
+        lines.push("none".dimmed().to_string());
+    }
+    for entry in entries {
+        lines.push(format!("  {entry}"));
+    }
+}
+
+fn field_lines(result: &Value, lines: &mut Vec<String>) {
+    match result.as_object() {
+        Some(fields) => {
+            for (key, value) in fields {
+                let value = match value {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                lines.push(format!("{}: {value}", key.bold()));
+            }
+        }
+        None => lines.push(result.to_string()),
+    }
+}
@@ -0,0 +1,93 @@
+//! Dependency tree output for traversals, LocAgent style
+//!
+//! ```text
+//! src/app.py::App  class  src/app.py:3
+//! ├── invoke ──> src/app.py::App.run  function  src/app.py:7
+//! │   └── import ──> src/util.py  file  src/util.py
+//! └── <── inherit ── src/admin.py::Admin  class  src/admin.py:1
+//! ```
+//!
+//! Each node hangs under the node one level shallower that discovered it;
+//! `<──` marks an edge followed backwards. Results without a subgraph fall
+//! back to the text format.
+
+use super::text::{heading, str_field};
+use colored::Colorize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+pub fn render(result: &Value) -> String {
+    let Some(subgraph) = result.get("subgraph") else {
+        return super::text::render(result);
+    };
+    let nodes = array(subgraph, "nodes");
+    let by_id: HashMap<&str, &Value> = nodes.iter().map(|n| (str_field(n, "id"), n)).collect();
+    let depth = |id: &str| by_id.get(id).map(|node| node_depth(node));
+
+    let mut children: HashMap<&str, Vec<(&str, String)>> = HashMap::new();
+    let mut attached = HashSet::new();
+    for edge in array(subgraph, "edges") {
+        let (source, target) = (str_field(edge, "source"), str_field(edge, "target"));
+        let relation = str_field(edge, "relation");
+        let (Some(source_depth), Some(target_depth)) = (depth(source), depth(target)) else {
+            continue;
+        };
+        let (parent, child, label) = if target_depth == source_depth + 1 {
+            (source, target, format!("{relation} ──>"))
+        } else if source_depth == target_depth + 1 {
+            (target, source, format!("<── {relation} ──"))
+        } else {
+            continue;
+        };
+        if attached.insert(child) {
+            children.entry(parent).or_default().push((child, label));
+        }
+    }
+
+    let mut lines = Vec::new();
+    for node in nodes {
+        let id = str_field(node, "id");
+        if !attached.contains(id) {
+            lines.push(heading(node));
+            subtree(id, "", &by_id, &children, &mut lines);
+        }
+    }
+    lines.join("\n")
+}
+
+fn subtree(
+    id: &str,
+    prefix: &str,
+    nodes: &HashMap<&str, &Value>,
+    children: &HashMap<&str, Vec<(&str, String)>>,
+    lines: &mut Vec<String>,
+) {
+    let Some(kids) = children.get(id) else {
+        return;
+    };
+    for (i, (child, label)) in kids.iter().enumerate() {
+        let last = i + 1 == kids.len();
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        lines.push(format!(
+            "{prefix}{branch}{} {}",
+            label.blue(),
+            heading(nodes[child])
+        ));
+        subtree(child, &format!("{prefix}{indent}"), nodes, children, lines);
+    }
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .map_or(&[], Vec::as_slice)
+}
+
+fn node_depth(node: &Value) -> u64 {
+    node.get("depth").and_then(Value::as_u64).unwrap_or(0)
+}
@@ -1,71 +1,191 @@
 //! CDS CLI - Code search and navigation tool
+//!
+//! `search`, `traverse`, `retrieve` and `path` print the service's result
+//! (`--format` json, text or tree; text on a terminal, JSON otherwise) and
+//! exit with the codes in `docs/api/error-codes.md`: 0 found, 1 no results,
+//! 2 invalid arguments, 3 index not found, 4 service error, 5 IO error.
+//! `init` builds the index those commands query; `shell` explores it
+//! interactively and `stats` summarizes it. `diff-impact` maps a diff to the
+//! entities it changes and their dependents. `completions` and `man` generate
+//! shell completion scripts and manual pages from these definitions.
 
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+mod client;
+mod commands;
+mod config;
+mod formatters;
+
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::CompleteEnv;
+use client::{ClientError, Endpoint, IndexClient};
+use commands::completions::{CompletionsArgs, COMPLETE_VAR};
+use commands::diff_impact::DiffImpactArgs;
+use commands::init::InitArgs;
+use commands::man::ManArgs;
+use commands::path::PathArgs;
+use commands::retrieve::RetrieveArgs;
+use commands::search::SearchArgs;
+use commands::stats::StatsArgs;
+use commands::traverse::TraverseArgs;
+use commands::{CliError, Outcome, QueryScope};
+use config::{CliConfig, Transport};
+use formatters::Format;
+use serde_json::json;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 #[derive(Parser)]
 #[command(name = "cds")]
 #[command(about = "Code Dependency Search - Navigate codebases with graph-based search")]
 struct Cli {
+    /// How to reach the index service (default: `[service].transport` in the config, else http)
+    #[arg(long, global = true, value_enum, env = "CDS_TRANSPORT")]
+    transport: Option<Transport>,
+    /// JSON-RPC endpoint for the http transport
+    #[arg(long, global = true, env = "CDS_INDEX_SERVICE_URL")]
+    url: Option<String>,
+    /// Socket path for the unix transport
+    #[arg(long, global = true, env = "CDS_SOCKET")]
+    socket: Option<PathBuf>,
+    /// Query the persisted index in this directory in-process, without the
+    /// service (default: the nearest `.cds-index` when no service is configured)
+    #[arg(long, global = true, env = "CDS_INDEX_DIR")]
+    index_dir: Option<PathBuf>,
+    /// Bearer token for services that require authentication
+    #[arg(long, global = true, env = "CDS_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+    /// Registered repository to query (default: the service's default index)
+    #[arg(long, global = true, env = "CDS_REPO")]
+    repo: Option<String>,
+    /// Record returned entities under this agent session
+    #[arg(long, global = true, env = "CDS_SESSION")]
+    session: Option<String>,
+    /// Write the result to this file instead of stdout
+    #[arg(short, long, global = true)]
+    output: Option<PathBuf>,
+    /// Output format (default: text on a terminal, JSON when piped or written to a file)
+    #[arg(short, long, global = true, value_enum, env = "CDS_FORMAT")]
+    format: Option<Format>,
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Build the index of a repository into `<repo>/.cds-index`
+    Init(InitArgs),
     /// Search for entities by name or keyword
-    Search {
-        query: String,
-        #[arg(short, long)]
-        entity_type: Option<String>,
-        #[arg(short, long, default_value = "10")]
-        limit: usize,
-    },
+    Search(SearchArgs),
     /// Traverse dependency graph
-    Traverse {
-        entity_id: String,
-        #[arg(short, long, default_value = "outgoing")]
-        direction: String,
-        #[arg(short, long)]
-        edge_type: Option<String>,
-    },
+    Traverse(TraverseArgs),
     /// Retrieve code content
-    Retrieve {
-        entity_id: String,
-        #[arg(short, long, default_value = "5")]
-        context: usize,
-    },
+    Retrieve(RetrieveArgs),
+    /// Find the shortest dependency paths from one entity to another
+    Path(PathArgs),
+    /// List the entities a diff (stdin or --rev) changes and their callers,
+    /// importers and subclasses
+    DiffImpact(DiffImpactArgs),
+    /// Summarize an index: counts per kind, largest files, most invoked
+    /// entities, inheritance chains, import cycles and build problems
+    Stats(StatsArgs),
+    /// Explore the graph interactively (search, open, callers, callees, parents, back)
+    Shell,
+    /// Check that the index service is reachable over the configured transport
+    Health,
+    /// Print the tab-completion script for a shell, e.g.
+    /// `source <(cds completions bash)`
+    Completions(CompletionsArgs),
+    /// Print the manual page, or write one per subcommand with --out-dir
+    Man(ManArgs),
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    // Answers the callbacks of the `cds completions` scripts and exits.
+    CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE_VAR)
+        .complete();
     let cli = Cli::parse();
+    let output = cli.output.clone();
+    let to_terminal = formatters::to_terminal(output.is_some());
+    let format = match cli.command {
+        // The shell is read by a person even when its input is scripted.
+        Commands::Shell => cli.format.unwrap_or(Format::Text),
+        _ => Format::resolve(cli.format, to_terminal),
+    };
+    match run(cli, format).await.and_then(|outcome| {
+        if !outcome.result.is_null() {
+            let text = formatters::render(format, &outcome.result, to_terminal);
+            write_result(output.as_deref(), &text)?;
+        }
+        Ok(outcome)
+    }) {
+        Ok(outcome) => outcome.exit_code(),
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
 
+async fn run(cli: Cli, format: Format) -> Result<Outcome, CliError> {
     match cli.command {
-        Commands::Search {
-            query,
-            entity_type,
-            limit,
-        } => {
-            println!(
-                "TODO: Search for '{}' (type: {:?}, limit: {})",
-                query, entity_type, limit
-            );
+        Commands::Init(args) => return commands::init::run(args, cli.index_dir).await,
+        Commands::Completions(args) => return commands::completions::run(args),
+        Commands::Man(args) => return commands::man::run(Cli::command(), args),
+        _ => {}
+    }
+    let mut client = connect(cli.transport, cli.url, cli.socket, cli.index_dir, cli.token)?;
+    let scope = QueryScope {
+        repo: cli.repo,
+        session: cli.session,
+    };
+    match cli.command {
+        Commands::Search(args) => commands::search::run(&mut client, &scope, args).await,
+        Commands::Traverse(args) => {
+            commands::traverse::run(&mut client, &scope, args, format).await
         }
-        Commands::Traverse {
-            entity_id,
-            direction,
-            edge_type,
-        } => {
-            println!(
-                "TODO: Traverse '{}' (direction: {}, edge_type: {:?})",
-                entity_id, direction, edge_type
-            );
+        Commands::Retrieve(args) => commands::retrieve::run(&mut client, &scope, args).await,
+        Commands::Path(args) => commands::path::run(&mut client, &scope, args).await,
+        Commands::DiffImpact(args) => commands::diff_impact::run(&mut client, &scope, args).await,
+        Commands::Stats(args) => commands::stats::run(&mut client, &scope, args).await,
+        Commands::Init(_) | Commands::Completions(_) | Commands::Man(_) => {
+            unreachable!("runs without a client")
         }
-        Commands::Retrieve { entity_id, context } => {
-            println!("TODO: Retrieve '{}' (context: {})", entity_id, context);
+        Commands::Shell => commands::shell::run(client, scope, format),
+        Commands::Health => {
+            let result = client.call("health_check", json!({})).await?;
+            Ok(Outcome {
+                result,
+                found: true,
+            })
         }
     }
+}
 
+/// Prints the rendered result to `output`, or stdout.
+fn write_result(output: Option<&std::path::Path>, text: &str) -> Result<(), CliError> {
+    match output {
+        Some(path) => std::fs::write(path, format!("{text}\n")).map_err(|err| {
+            std::io::Error::new(
+                err.kind(),
+                format!("cannot write {}: {err}", path.display()),
+            )
+        })?,
+        None => println!("{text}"),
+    }
     Ok(())
 }
+
+/// Connects to the service chosen by the global flags and the config file.
+fn connect(
+    transport: Option<Transport>,
+    url: Option<String>,
+    socket: Option<PathBuf>,
+    index_dir: Option<PathBuf>,
+    token: Option<String>,
+) -> Result<IndexClient, ClientError> {
+    let config = CliConfig::load().map_err(|err| ClientError::Config(format!("{err:#}")))?;
+    let endpoint = Endpoint::resolve(&config, transport, url, socket, index_dir)?;
+    let token = token.or(config.service.token.clone());
+    Ok(IndexClient::connect(endpoint)?.with_token(token))
+}
@@ -0,0 +1,341 @@
+//! End-to-end tests of the `cds` binary against a stub JSON-RPC service.
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Handler = dyn Fn(&str, &Value) -> Value + Send + Sync;
+
+/// Serves HTTP JSON-RPC on a free port, answering each request's method and
+/// params with `handler`'s `result` or `error` object. Returns the `/rpc` URL
+/// and every request received.
+fn stub_service(handler: Box<Handler>) -> (String, Arc<Mutex<Vec<Value>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/rpc", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&requests);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let request = read_request(&stream);
+            seen.lock().unwrap().push(request.clone());
+            let reply = handler(request["method"].as_str().unwrap_or(""), &request["params"]);
+            let mut body = json!({ "jsonrpc": "2.0", "id": request["id"] });
+            if reply.get("code").is_some() {
+                body["error"] = reply;
+            } else {
+                body["result"] = reply;
+            }
+            write_response(stream, &body.to_string());
+        }
+    });
+    (url, requests)
+}
+
+fn read_request(stream: &TcpStream) -> Value {
+    let mut reader = BufReader::new(stream);
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn write_response(mut stream: TcpStream, body: &str) {
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).unwrap();
+}
+
+fn cds(url: &str) -> Command {
+    let mut command = Command::cargo_bin("cds").unwrap();
+    // An empty config file keeps the user's `~/.config/cds/config.toml` out.
+    let config = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/empty-config.toml"
+    );
+    command
+        .env("CDS_INDEX_SERVICE_URL", url)
+        .env("CDS_CONFIG", config)
+        .env_remove("CDS_TRANSPORT")
+        .env_remove("CDS_TOKEN")
+        .env_remove("CDS_REPO")
+        .env_remove("CDS_SESSION");
+    command
+}
+
+fn entity(id: &str) -> Value {
+    json!({ "id": id, "name": id, "entity_type": "function", "file_path": "app.py" })
+}
+
+#[test]
+fn search_prints_results_and_passes_scope() {
+    let (url, requests) = stub_service(Box::new(|method, params| match method {
+        "search_entities" if params["query"] == "run" => json!({
+            "entities": [entity("app.py::run")],
+            "total_count": 1,
+            "query_metadata": {},
+        }),
+        _ => json!({ "entities": [], "total_count": 0, "query_metadata": {} }),
+    }));
+
+    cds(&url)
+        .args([
+            "search",
+            "run",
+            "--repo",
+            "billing",
+            "--session",
+            "agent-1",
+            "-e",
+            "function",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("app.py::run"));
+    let params = requests.lock().unwrap()[0]["params"].clone();
+    assert_eq!(params["repo"], "billing");
+    assert_eq!(params["session_id"], "agent-1");
+    assert_eq!(params["entity_types"], json!(["function"]));
+
+    // Nothing found: the (empty) result is still printed, with exit code 1.
+    cds(&url)
+        .args(["search", "missing"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("\"total_count\": 0"));
+}
+
+#[test]
+fn traverse_and_retrieve_map_results_and_errors_to_exit_codes() {
+    let (url, _) = stub_service(Box::new(|method, params| match method {
+        "traverse_graph" if params["start_entities"][0] == "app.py::run" => json!({
+            "subgraph": {
+                "nodes": [entity("app.py::run"), entity("app.py::helper")],
+                "edges": [{ "source": "app.py::run", "target": "app.py::helper", "relation": "invoke" }],
+            },
+            "metadata": {},
+        }),
+        "traverse_graph" => json!({
+            "subgraph": { "nodes": [entity("app.py::leaf")], "edges": [] },
+            "metadata": {},
+        }),
+        "retrieve_entity" if params["repo"] == "gone" => json!({
+            "code": -32007,
+            "message": "Repository not found",
+            "data": { "suggestion": "Call list_repos, or add_repo to register it" },
+        }),
+        "retrieve_entity" if params["entity_ids"][0] == "app.py::run" => {
+            assert_eq!(params["include_context"], 2);
+            json!({ "entities": [entity("app.py::run")] })
+        }
+        "retrieve_entity" => json!({ "code": -32002, "message": "Entity not found" }),
+        "health_check" => json!({ "code": -32603, "message": "Internal error" }),
+        _ => json!({ "code": -32001, "message": "Index not found" }),
+    }));
+
+    cds(&url)
+        .args(["traverse", "app.py::run", "--direction", "both"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("app.py::helper"));
+    cds(&url)
+        .args(["traverse", "app.py::leaf"])
+        .assert()
+        .code(1);
+    cds(&url)
+        .args(["traverse", "app.py::run", "--direction", "sideways"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unknown direction"));
+
+    cds(&url)
+        .args(["retrieve", "app.py::run", "--context", "2"])
+        .assert()
+        .success();
+    cds(&url)
+        .args(["retrieve", "app.py::nope"])
+        .assert()
+        .code(1);
+    cds(&url)
+        .args(["retrieve", "app.py::run", "--repo", "gone"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("add_repo"));
+    cds(&url).args(["health"]).assert().code(4);
+    cds(&url)
+        .args(["--repo", "fresh", "search", "run"])
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn usage_service_and_io_errors_have_their_own_exit_codes() {
+    cds("http://127.0.0.1:9/rpc")
+        .args(["search"])
+        .assert()
+        .code(2);
+    cds("http://127.0.0.1:9/rpc")
+        .args(["retrieve"])
+        .assert()
+        .code(2);
+
+    // Nothing listens on the discard port.
+    cds("http://127.0.0.1:9/rpc")
+        .args(["search", "run"])
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("cannot reach the index service"));
+
+    let (url, _) = stub_service(Box::new(
+        |_, _| json!({ "entities": [entity("app.py::run")], "total_count": 1, "query_metadata": {} }),
+    ));
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("result.json");
+    cds(&url)
+        .args(["search", "run", "-o"])
+        .arg(&output)
+        .assert()
+        .success()
+        .stdout("");
+    let written: Value = serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+    assert_eq!(written["total_count"], 1);
+
+    cds(&url)
+        .args(["search", "run", "-o"])
+        .arg(dir.path().join("no/such/dir/result.json"))
+        .assert()
+        .code(5);
+}
+
+#[test]
+fn text_and_tree_formats_render_results_without_colors_when_piped() {
+    let (url, requests) = stub_service(Box::new(|method, _| match method {
+        "search_entities" => json!({
+            "entities": [{
+                "id": "app.py::run",
+                "name": "run",
+                "entity_type": "function",
+                "file_path": "app.py",
+                "line_range": [7, 9],
+                "score": 0.9,
+                "snippet": { "fold": "def run(self):", "preview": "def run(self):\n    helper()" },
+            }],
+            "total_count": 3,
+            "query_metadata": {},
+            "next_cursor": "abc",
+        }),
+        _ => json!({
+            "subgraph": {
+                "nodes": [
+                    { "id": "app.py::App", "name": "App", "entity_type": "class", "file_path": "app.py", "line_range": [3, 9], "depth": 0 },
+                    { "id": "app.py::App.run", "name": "run", "entity_type": "function", "file_path": "app.py", "line_range": [7, 9], "depth": 1 },
+                    { "id": "util.py::helper", "name": "helper", "entity_type": "function", "file_path": "util.py", "line_range": [1, 2], "depth": 2 },
+                    { "id": "admin.py::Admin", "name": "Admin", "entity_type": "class", "file_path": "admin.py", "line_range": [1, 4], "depth": 1 },
+                ],
+                "edges": [
+                    { "source": "app.py::App", "target": "app.py::App.run", "relation": "contain" },
+                    { "source": "app.py::App.run", "target": "util.py::helper", "relation": "invoke" },
+                    { "source": "admin.py::Admin", "target": "app.py::App", "relation": "inherit" },
+                ],
+            },
+            "metadata": {},
+        }),
+    }));
+
+    let output = cds(&url)
+        .args(["search", "run", "--format", "text"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let text = String::from_utf8(output).unwrap();
+    assert!(
+        !text.contains('\u{1b}'),
+        "no ANSI escapes when piped: {text:?}"
+    );
+    assert!(text.contains("app.py::run  function  app.py:7  0.90"));
+    assert!(text.contains("    def run(self):"));
+    assert!(!text.contains("helper()"), "only the fold is shown");
+    assert!(text.contains("1 of 3 results (--all for every page)"));
+
+    cds(&url)
+        .args(["traverse", "app.py::App", "-d", "both", "--format", "tree"])
+        .assert()
+        .success()
+        .stdout(
+            "app.py::App  class  app.py:3\n\
+             ├── contain ──> app.py::App.run  function  app.py:7\n\
+             │   └── invoke ──> util.py::helper  function  util.py:1\n\
+             └── <── inherit ── admin.py::Admin  class  admin.py:1\n",
+        );
+    assert_eq!(requests.lock().unwrap()[1]["params"]["format"], "tree");
+
+    // Piped output without --format stays JSON.
+    let output = cds(&url)
+        .args(["traverse", "app.py::App"])
+        .output()
+        .unwrap();
+    let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["subgraph"]["edges"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn shell_runs_over_the_rpc_client() {
+    let (url, requests) = stub_service(Box::new(|method, _| match method {
+        "search_entities" => {
+            json!({ "entities": [entity("app.py::run")], "total_count": 1, "query_metadata": {} })
+        }
+        "retrieve_entity" => {
+            let mut run = entity("app.py::run");
+            run["code"] = json!("def run():\n    pass");
+            json!({ "entities": [run] })
+        }
+        _ => {
+            let (mut run, mut main) = (entity("app.py::run"), entity("app.py::main"));
+            run["depth"] = json!(0);
+            main["depth"] = json!(1);
+            json!({ "subgraph": { "nodes": [run, main], "edges": [] }, "metadata": {} })
+        }
+    }));
+    let output = cds(&url)
+        .env("XDG_DATA_HOME", tempfile::tempdir().unwrap().path())
+        .args(["--repo", "billing", "shell"])
+        .write_stdin("search run\nopen 1\ncallers\n")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("    def run():"), "{stdout}");
+    assert!(
+        stdout.contains("  1  app.py::main  function  app.py"),
+        "{stdout}"
+    );
+
+    let requests = requests.lock().unwrap();
+    let callers = &requests[2]["params"];
+    assert_eq!(callers["start_entities"], json!(["app.py::run"]));
+    assert_eq!(callers["direction"], "backward");
+    assert_eq!(callers["relations"], json!(["invoke"]));
+    assert!(requests
+        .iter()
+        .all(|request| request["params"]["repo"] == "billing"));
+}
@@ -0,0 +1,549 @@
+//! `cds` in embedded mode: a persisted index queried without the service.
+
+use assert_cmd::Command;
+use cds_index::graph::{GraphBuilderConfig, NoopProgress};
+use cds_index::index::Bm25Params;
+use cds_index::persistence::IndexLayout;
+use cds_index::service::{handle_request, JsonRpcRequest, LoadedIndex, ServiceState};
+use cds_index::IndexServiceConfig;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// A small repository with its index built into `.cds-index`.
+fn indexed_repo() -> TempDir {
+    let repo = TempDir::new().unwrap();
+    fs::create_dir_all(repo.path().join("pkg")).unwrap();
+    fs::write(
+        repo.path().join("pkg/app.py"),
+        "from pkg.util import helper\n\n\nclass App:\n    def run(self):\n        return helper()\n",
+    )
+    .unwrap();
+    fs::write(
+        repo.path().join("pkg/util.py"),
+        "def helper():\n    \"\"\"Shared helper.\"\"\"\n    return 1\n",
+    )
+    .unwrap();
+    LoadedIndex::build(
+        repo.path(),
+        GraphBuilderConfig::default(),
+        &Bm25Params::default(),
+        &IndexLayout::under(&repo.path().join(".cds-index")),
+        1,
+        &NoopProgress,
+    )
+    .expect("build index");
+    repo
+}
+
+fn cds(dir: &Path) -> Command {
+    let mut command = Command::cargo_bin("cds").unwrap();
+    command
+        .current_dir(dir)
+        .env(
+            "CDS_CONFIG",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/empty-config.toml"
+            ),
+        )
+        .env_remove("CDS_TRANSPORT")
+        .env_remove("CDS_INDEX_SERVICE_URL")
+        .env_remove("CDS_SOCKET")
+        .env_remove("CDS_INDEX_DIR")
+        .env_remove("CDS_REPO")
+        .env_remove("CDS_SESSION");
+    command
+}
+
+/// The service's answer to `method` for the index in `index_dir`, read back
+/// from the wire format as a client would.
+async fn service_result(index_dir: &Path, method: &str, params: Value) -> Value {
+    let layout = IndexLayout::under(index_dir);
+    let state = Arc::new(ServiceState::new(IndexServiceConfig::new(
+        layout.graph_dir,
+        layout.bm25_dir,
+    )));
+    let response = handle_request(&state, JsonRpcRequest::new(method, params, json!(1))).await;
+    let wire = serde_json::to_string(&response).unwrap();
+    serde_json::from_str::<Value>(&wire).unwrap()["result"].clone()
+}
+
+/// Drops the timings, which differ between any two calls.
+fn without_timings(mut result: Value) -> Value {
+    for metadata in ["", "/query_metadata", "/metadata"] {
+        if let Some(Value::Object(fields)) = result.pointer_mut(metadata) {
+            fields.remove("execution_time_ms");
+        }
+    }
+    result
+}
+
+fn stdout_json(command: &mut Command) -> Value {
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[tokio::test]
+async fn index_dir_answers_like_the_service() {
+    let repo = indexed_repo();
+    let index_dir = repo.path().join(".cds-index");
+    let elsewhere = TempDir::new().unwrap();
+
+    let cases = [
+        (
+            vec!["search", "helper"],
+            "search_entities",
+            json!({ "query": "helper", "limit": 10 }),
+        ),
+        (
+            vec![
+                "traverse",
+                "pkg/app.py::App::run",
+                "-d",
+                "both",
+                "--depth",
+                "2",
+            ],
+            "traverse_graph",
+            json!({ "start_entities": ["pkg/app.py::App::run"], "depth": 2, "direction": "bidirectional" }),
+        ),
+        (
+            vec!["retrieve", "pkg/util.py::helper"],
+            "retrieve_entity",
+            json!({ "entity_ids": ["pkg/util.py::helper"], "include_context": 5, "include_metadata": false }),
+        ),
+        (
+            vec!["path", "pkg/app.py::App", "pkg/util.py::helper", "-k", "2"],
+            "find_paths",
+            json!({ "from": "pkg/app.py::App", "to": "pkg/util.py::helper", "direction": "forward", "max_depth": 10, "k": 2 }),
+        ),
+    ];
+    for (args, method, params) in cases {
+        let embedded = stdout_json(
+            cds(elsewhere.path())
+                .arg("--index-dir")
+                .arg(&index_dir)
+                .args(&args),
+        );
+        let served = service_result(&index_dir, method, params).await;
+        assert!(served.is_object(), "{method} failed");
+        assert_eq!(
+            without_timings(embedded),
+            without_timings(served),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn nearest_cds_index_is_discovered_unless_a_service_is_configured() {
+    let repo = indexed_repo();
+    let nested = repo.path().join("pkg");
+
+    let result = stdout_json(cds(&nested).args(["search", "App"]));
+    let ids: Vec<_> = result["entities"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entity| entity["id"].as_str().unwrap())
+        .collect();
+    assert!(ids.contains(&"pkg/app.py::App"), "{ids:?}");
+    cds(&nested)
+        .args(["retrieve", "pkg/app.py::Missing"])
+        .assert()
+        .code(1);
+
+    // An explicit service address wins over the discovered index.
+    cds(&nested)
+        .env("CDS_INDEX_SERVICE_URL", "http://127.0.0.1:9/rpc")
+        .args(["search", "App"])
+        .assert()
+        .code(4);
+
+    // An index directory without an index reports "index not found".
+    let empty = TempDir::new().unwrap();
+    cds(empty.path())
+        .arg("--index-dir")
+        .arg(empty.path())
+        .args(["search", "App"])
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn init_builds_the_index_that_embedded_mode_queries() {
+    let repo = TempDir::new().unwrap();
+    for (path, code) in [
+        (
+            "pkg/app.py",
+            "class App:\n    def run(self):\n        pass\n",
+        ),
+        ("pkg/extra.py", "def extra():\n    pass\n"),
+        ("vendor/lib.py", "def vendored():\n    pass\n"),
+    ] {
+        let path = repo.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, code).unwrap();
+    }
+
+    let stats = stdout_json(cds(repo.path()).args(["init", "--exclude", "vendor"]));
+    assert_eq!(stats["generation"], 1);
+    assert_eq!(stats["files_indexed"], 2);
+    assert_eq!(stats["entities_found"]["classes"], 1);
+    assert_eq!(stats["entities_found"]["functions"], 2);
+    assert!(IndexLayout::under(&repo.path().join(".cds-index")).exists());
+    cds(repo.path())
+        .args(["search", "vendored"])
+        .assert()
+        .code(1);
+
+    // Rebuilding bumps the generation; --include narrows the indexed files.
+    let elsewhere = TempDir::new().unwrap();
+    let output = cds(elsewhere.path())
+        .arg("init")
+        .arg(repo.path())
+        .args(["--include", "pkg/app.py", "--format", "text"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let table = String::from_utf8(output.stdout).unwrap();
+    assert!(table.contains("(generation 2)"), "{table}");
+    assert!(table.contains("functions           1"), "{table}");
+    assert!(table.contains("contain      "), "{table}");
+    cds(repo.path()).args(["search", "extra"]).assert().code(1);
+
+    cds(repo.path())
+        .args(["init", "--include", "missing.py"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn shell_navigates_the_embedded_index() {
+    let repo = indexed_repo();
+    let data = TempDir::new().unwrap();
+    let script =
+        "search helper\nopen 1\ncallers\nopen 1\nwhere\nparents\nback\nback\nback\nopen 7\nquit\n";
+    let output = cds(repo.path())
+        .env("XDG_DATA_HOME", data.path())
+        .arg("shell")
+        .write_stdin(script)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(
+        stdout.contains("  1  pkg/util.py::helper  function  pkg/util.py:1"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("    \"\"\"Shared helper.\"\"\""),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("  1  pkg/app.py::App::run  function  pkg/app.py:5"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("pkg/util.py::helper > pkg/app.py::App::run\n"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("  1  pkg/app.py::App  class  pkg/app.py:4"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("no entity open"), "{stdout}");
+    assert!(stderr.contains("error: nothing to go back to"), "{stderr}");
+    assert!(
+        stderr.contains("error: no entry 7 in the last listing"),
+        "{stderr}"
+    );
+    let history = fs::read_to_string(data.path().join("cds/shell_history")).unwrap();
+    assert!(history.contains("open 1"), "{history}");
+}
+
+#[test]
+fn stats_summarize_the_embedded_index() {
+    let repo = indexed_repo();
+
+    let stats = stdout_json(cds(repo.path()).args(["stats", "--top", "1"]));
+    assert_eq!(stats["entities"]["functions"], 2);
+    assert_eq!(
+        stats["most_invoked"],
+        json!([{ "id": "pkg/util.py::helper", "entity_type": "function", "callers": 1 }])
+    );
+    assert_eq!(stats["largest_files"].as_array().unwrap().len(), 1);
+    assert_eq!(stats["import_cycles"], json!([]));
+
+    let output = cds(repo.path())
+        .args(["stats", "--format", "text"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.contains("(generation 1,"), "{report}");
+    assert!(report.contains("pkg/util.py::helper        1"), "{report}");
+    assert!(report.contains("import cycles (0)\nnone"), "{report}");
+    assert!(report.contains("unresolved imports 0"), "{report}");
+
+    cds(repo.path())
+        .args(["stats", "--top", "0"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn init_and_stats_keep_stdout_json_when_a_file_fails_to_parse() {
+    let repo = TempDir::new().unwrap();
+    fs::write(repo.path().join("app.py"), "def main():\n    return 1\n").unwrap();
+    fs::write(
+        repo.path().join("legacy.py"),
+        "def legacy():\n    print \"python 2\"\n",
+    )
+    .unwrap();
+
+    let built = stdout_json(cds(repo.path()).arg("init").env("PARITY_DEBUG", "1"));
+    assert_eq!(built["files_indexed"], 2);
+    let stats = stdout_json(
+        cds(repo.path())
+            .args(["stats", "--format", "json"])
+            .env("PARITY_DEBUG", "1"),
+    );
+    assert_eq!(stats["parse_failures"], json!(["legacy.py"]));
+}
+
+#[test]
+fn retrieve_merges_entities_of_a_file_into_one_numbered_listing() {
+    let repo = indexed_repo();
+    let retrieve = |args: &[&str]| {
+        let output = cds(repo.path())
+            .args(["--format", "text", "retrieve"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let listing = retrieve(&[
+        "pkg/app.py::App::run",
+        "pkg/util.py::helper",
+        "pkg/app.py::App",
+        "-c",
+        "1",
+    ]);
+    assert_eq!(
+        listing,
+        "pkg/app.py::App::run  function  pkg/app.py:5\n\
+         pkg/app.py::App  class  pkg/app.py:4\n  \
+         3 │\n\
+         > 4 │ class App:\n\
+         > 5 │     def run(self):\n\
+         > 6 │         return helper()\n\
+         \n\
+         pkg/util.py::helper  function  pkg/util.py:1\n\
+         > 1 │ def helper():\n\
+         > 2 │     \"\"\"Shared helper.\"\"\"\n\
+         > 3 │     return 1\n"
+    );
+
+    let folded = retrieve(&["pkg/util.py::helper", "--fold", "--include-metadata"]);
+    assert!(folded.contains("    doc: Shared helper.\n"), "{folded}");
+    assert!(folded.ends_with("> 1 │ def helper():\n"), "{folded}");
+
+    cds(repo.path())
+        .args(["retrieve", "pkg/util.py::helper", "--fold", "--preview"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn path_shows_each_hop_of_the_embedded_index() {
+    let repo = indexed_repo();
+    let output = cds(repo.path())
+        .args([
+            "--format",
+            "text",
+            "path",
+            "pkg/app.py::App",
+            "pkg/util.py::helper",
+        ])
+        .args(["-e", "contain,invoke"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "path 1 of 1, 2 hops\n  \
+         pkg/app.py::App  class  pkg/app.py:4\n  \
+         │ contain ──>\n  \
+         pkg/app.py::App::run  function  pkg/app.py:5\n  \
+         │ invoke ──>\n  \
+         pkg/util.py::helper  function  pkg/util.py:1\n"
+    );
+
+    cds(repo.path())
+        .args([
+            "path",
+            "pkg/util.py::helper",
+            "pkg/app.py::App",
+            "-e",
+            "invoke",
+        ])
+        .assert()
+        .code(1);
+    cds(repo.path())
+        .args(["path", "pkg/app.py::App", "pkg/util.py::helper", "-d", "up"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn diff_impact_reads_stdin_or_a_git_revision_range() {
+    let repo = indexed_repo();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .current_dir(repo.path())
+            .args(["-c", "user.name=cds", "-c", "user.email=cds@example.com"])
+            .args(args)
+            .status()
+            .expect("run git");
+        assert!(status.success(), "git {args:?}");
+    };
+    git(&["init", "-q"]);
+    git(&["add", "pkg"]);
+    git(&["commit", "-q", "-m", "initial"]);
+    fs::write(
+        repo.path().join("pkg/util.py"),
+        "def helper():\n    \"\"\"Shared helper.\"\"\"\n    return 2\n",
+    )
+    .unwrap();
+    git(&["commit", "-q", "-am", "change helper"]);
+
+    let result = stdout_json(cds(repo.path()).args(["diff-impact", "--rev", "HEAD~1..HEAD"]));
+    assert_eq!(result["changed"][0]["id"], "pkg/util.py::helper");
+    let impacted: Vec<_> = result["impacted"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entity| entity["id"].as_str().unwrap())
+        .collect();
+    assert!(impacted.contains(&"pkg/app.py::App::run"), "{result}");
+
+    let output = cds(repo.path())
+        .args([
+            "--format",
+            "text",
+            "diff-impact",
+            "--depth",
+            "1",
+            "-e",
+            "invoke",
+        ])
+        .write_stdin(
+            "--- a/pkg/util.py\n+++ b/pkg/util.py\n@@ -3 +3 @@\n-    return 1\n+    return 2\n",
+        )
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "changed (1)\n  \
+         pkg/util.py::helper  function  pkg/util.py:1\n\
+         \n\
+         impacted (1)\n  \
+         pkg/app.py::App::run  function  pkg/app.py:5  invoke ──> pkg/util.py::helper\n"
+    );
+
+    cds(repo.path())
+        .args(["diff-impact", "--rev", "no-such-rev"])
+        .assert()
+        .code(2);
+    cds(repo.path())
+        .args(["diff-impact"])
+        .write_stdin("--- a/docs.md\n+++ b/docs.md\n@@ -1 +1 @@\n-a\n+b\n")
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn completions_offer_entity_ids_from_the_index() {
+    let repo = indexed_repo();
+    let script = cds(repo.path())
+        .args(["completions", "bash"])
+        .output()
+        .unwrap();
+    assert!(script.status.success(), "{script:?}");
+    let script = String::from_utf8(script.stdout).unwrap();
+    assert!(script.contains("COMPLETE=\"bash\""), "{script}");
+    assert!(script.contains("-F _clap_complete_cds cds"), "{script}");
+    cds(repo.path())
+        .args(["completions", "tcsh"])
+        .assert()
+        .code(2);
+
+    // The callback the script makes on <Tab>.
+    let complete = |dir: &Path, words: &[&str]| {
+        let output = cds(dir)
+            .env("COMPLETE", "fish")
+            .arg("--")
+            .args(words)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(
+        complete(repo.path(), &["cds", "retrieve", "help"]),
+        "pkg/util.py::helper\n"
+    );
+    assert_eq!(
+        complete(repo.path(), &["cds", "traverse", "pkg/app.py::App::r"]),
+        "pkg/app.py::App::run\n"
+    );
+    let elsewhere = TempDir::new().unwrap();
+    let index_dir = repo.path().join(".cds-index");
+    assert_eq!(
+        complete(
+            elsewhere.path(),
+            &[
+                "cds",
+                "--index-dir",
+                index_dir.to_str().unwrap(),
+                "retrieve",
+                "pkg/util.py::helper",
+                "pkg/app.py::A",
+            ],
+        ),
+        "pkg/app.py::App\n"
+    );
+    // No index to ask: nothing to offer, and no error.
+    assert_eq!(complete(elsewhere.path(), &["cds", "retrieve", "help"]), "");
+    assert!(complete(repo.path(), &["cds", "st"]).starts_with("stats\t"));
+}
+
+#[test]
+fn man_pages_are_generated_for_every_command() {
+    let dir = TempDir::new().unwrap();
+    let output = cds(dir.path()).arg("man").output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let page = String::from_utf8(output.stdout).unwrap();
+    assert!(page.starts_with(".ie \\n(.g .ds Aq"), "{page}");
+    assert!(page.contains(".TH cds 1"), "{page}");
+    assert!(page.contains("cds\\-search(1)"), "{page}");
+
+    let pages = dir.path().join("man1");
+    let result = stdout_json(cds(dir.path()).args(["man", "--out-dir"]).arg(&pages));
+    assert_eq!(result["pages"].as_array().unwrap().len(), 12);
+    for name in ["cds.1", "cds-search.1", "cds-completions.1", "cds-man.1"] {
+        assert!(pages.join(name).is_file(), "{name} missing");
+    }
+    let search = fs::read_to_string(pages.join("cds-search.1")).unwrap();
+    assert!(search.contains(".TH cds-search 1"), "{search}");
+}